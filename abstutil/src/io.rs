@@ -81,6 +81,12 @@ pub fn serialized_size_bytes<T: Serialize>(obj: &T) -> usize {
     bincode::serialized_size(obj).unwrap() as usize
 }
 
+// Copy something that can't derive Clone by round-tripping through the binary format. Anything
+// skipped during serialization is lost.
+pub fn clone_via_binary<T: Serialize + DeserializeOwned>(obj: &T) -> T {
+    bincode::deserialize(&bincode::serialize(obj).unwrap()).unwrap()
+}
+
 pub fn write_binary<T: Serialize>(path: String, obj: &T) {
     if let Err(err) = maybe_write_binary(&path, obj) {
         panic!("Can't write_binary({}): {}", path, err);
@@ -3,11 +3,14 @@ mod neighborhoods;
 mod osm_reader;
 mod split_ways;
 
+pub use crate::split_ways::split_up_roads;
+
 use abstutil::Timer;
 use geom::{Distance, FindClosest, Line, PolyLine, Pt2D};
 use kml::ExtraShapes;
 use map_model::raw::{OriginalBuilding, OriginalRoad, RawMap};
 use map_model::{osm, LaneID, OffstreetParking, Position, LANE_THICKNESS};
+use std::collections::HashMap;
 
 pub struct Flags {
     pub osm: String,
@@ -16,6 +19,8 @@ pub struct Flags {
     pub sidewalks: Option<String>,
     pub gtfs: Option<String>,
     pub neighborhoods: Option<String>,
+    // A CSV file with an osm_node_id and elevation (in meters) column
+    pub elevation: Option<String>,
     pub clip: Option<String>,
     pub output: String,
 }
@@ -40,6 +45,9 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     if let Some(ref path) = flags.sidewalks {
         use_sidewalk_hints(&mut map, path.clone(), timer);
     }
+    if let Some(ref path) = flags.elevation {
+        use_elevation(&mut map, path, timer);
+    }
     if let Some(ref path) = flags.gtfs {
         timer.start("load GTFS");
         map.bus_routes = gtfs::load(path);
@@ -148,6 +156,32 @@ fn use_parking_hints(map: &mut RawMap, path: String, timer: &mut Timer) {
     timer.stop("apply parking hints");
 }
 
+pub fn use_elevation(map: &mut RawMap, path: &str, timer: &mut Timer) {
+    timer.start("apply elevation data");
+    let mut elevation: HashMap<i64, f64> = HashMap::new();
+    let mut reader = csv::Reader::from_path(path).expect("loading elevation failed");
+    for rec in reader.deserialize() {
+        let (id, meters): (i64, f64) = rec.expect("bad row in elevation data");
+        elevation.insert(id, meters);
+    }
+
+    let mut missing = 0;
+    for (id, i) in map.intersections.iter_mut() {
+        if let Some(meters) = elevation.get(&id.osm_node_id) {
+            i.elevation = Some(Distance::meters(*meters));
+        } else {
+            missing += 1;
+        }
+    }
+    if missing > 0 {
+        timer.warn(format!(
+            "{} intersections have no elevation data; lanes touching them will be flat",
+            missing
+        ));
+    }
+    timer.stop("apply elevation data");
+}
+
 fn use_offstreet_parking(map: &mut RawMap, path: &str, timer: &mut Timer) {
     timer.start("match offstreet parking points");
     let shapes = kml::load(path, &map.gps_bounds, timer).expect("loading offstreet_parking failed");
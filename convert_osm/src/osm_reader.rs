@@ -17,6 +17,8 @@ pub fn extract_osm(
     Vec<(i64, RawRoad)>,
     // Traffic signals
     HashSet<HashablePt2D>,
+    // Crosswalks, possibly in the middle of a road
+    HashSet<HashablePt2D>,
     // OSM Node IDs
     HashMap<HashablePt2D, i64>,
     // Turn restrictions: (restriction type, from way ID, via node ID, to way ID)
@@ -46,6 +48,7 @@ pub fn extract_osm(
     let mut id_to_way: HashMap<i64, Vec<Pt2D>> = HashMap::new();
     let mut roads: Vec<(i64, RawRoad)> = Vec::new();
     let mut traffic_signals: HashSet<HashablePt2D> = HashSet::new();
+    let mut crossings: HashSet<HashablePt2D> = HashSet::new();
     let mut osm_node_ids = HashMap::new();
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -59,6 +62,12 @@ pub fn extract_osm(
         if tags.get(osm::HIGHWAY) == Some(&"traffic_signals".to_string()) {
             traffic_signals.insert(pt);
         }
+        if tags.get(osm::HIGHWAY) == Some(&"crossing".to_string()) {
+            crossings.insert(pt);
+            if tags.get(osm::CROSSING) == Some(&"traffic_signals".to_string()) {
+                traffic_signals.insert(pt);
+            }
+        }
     }
 
     let mut coastline_groups: Vec<Vec<Pt2D>> = Vec::new();
@@ -86,7 +95,17 @@ pub fn extract_osm(
         let mut tags = tags_to_map(&way.tags);
         tags.insert(osm::OSM_WAY_ID.to_string(), way.id.to_string());
 
-        if is_road(&tags) {
+        if is_light_rail(&tags) {
+            // Tracks have no parking or sidewalks; lane_specs handles them specially.
+            roads.push((
+                way.id,
+                RawRoad {
+                    center_points: pts,
+                    osm_tags: tags,
+                    turn_restrictions: Vec::new(),
+                },
+            ));
+        } else if is_road(&tags) {
             // If there's no parking data in OSM already, then assume no parking and mark that it's
             // inferred.
             if !tags.contains_key(osm::PARKING_LEFT)
@@ -253,7 +272,14 @@ pub fn extract_osm(
         });
     }
 
-    (map, roads, traffic_signals, osm_node_ids, turn_restrictions)
+    (
+        map,
+        roads,
+        traffic_signals,
+        crossings,
+        osm_node_ids,
+        turn_restrictions,
+    )
 }
 
 fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
@@ -309,6 +335,14 @@ fn is_road(tags: &BTreeMap<String, String>) -> bool {
     true
 }
 
+fn is_light_rail(tags: &BTreeMap<String, String>) -> bool {
+    // Some tram tracks run down the middle of a street and share its way. Just treat those as
+    // streets for now.
+    !tags.contains_key(osm::HIGHWAY)
+        && (tags.get(osm::RAILWAY) == Some(&"light_rail".to_string())
+            || tags.get(osm::RAILWAY) == Some(&"tram".to_string()))
+}
+
 fn is_bldg(tags: &BTreeMap<String, String>) -> bool {
     tags.contains_key("building")
 }
@@ -1,16 +1,21 @@
 use abstutil::{Counter, Timer};
-use geom::{HashablePt2D, Pt2D};
+use geom::{Distance, HashablePt2D, Pt2D};
 use map_model::raw::{
     OriginalIntersection, OriginalRoad, RawIntersection, RawMap, RawRoad, RestrictionType,
 };
 use map_model::{osm, IntersectionType};
 use std::collections::{HashMap, HashSet};
 
+// Crosswalks closer than this to an intersection are really part of it. Crosswalks this close to
+// each other only get one intersection.
+const MIN_CROSSING_DIST_FROM_INTERSECTION: Distance = Distance::const_meters(20.0);
+
 pub fn split_up_roads(
-    (mut map, roads, traffic_signals, osm_node_ids, turn_restrictions): (
+    (mut map, roads, traffic_signals, crossings, osm_node_ids, turn_restrictions): (
         RawMap,
         Vec<(i64, RawRoad)>,
         HashSet<HashablePt2D>,
+        HashSet<HashablePt2D>,
         HashMap<HashablePt2D, i64>,
         Vec<(RestrictionType, i64, i64, i64)>,
     ),
@@ -37,6 +42,46 @@ pub fn split_up_roads(
         }
     }
 
+    // Also split roads at mid-block crosswalks. The degenerate intersection there gets crosswalk
+    // turns like any other.
+    let mut mid_block_crossings = 0;
+    for (_, r) in &roads {
+        let mut dists = vec![Distance::ZERO];
+        for pair in r.center_points.windows(2) {
+            dists.push(*dists.last().unwrap() + pair[0].dist_to(pair[1]));
+        }
+        let mut intersection_dists: Vec<Distance> = r
+            .center_points
+            .iter()
+            .zip(&dists)
+            .filter(|(pt, _)| pt_to_intersection.contains_key(&pt.to_hashable()))
+            .map(|(_, dist)| *dist)
+            .collect();
+        for (raw_pt, dist) in r.center_points.iter().zip(&dists) {
+            let pt = raw_pt.to_hashable();
+            if !crossings.contains(&pt) || pt_to_intersection.contains_key(&pt) {
+                continue;
+            }
+            if intersection_dists
+                .iter()
+                .all(|d| (*d - *dist).abs() >= MIN_CROSSING_DIST_FROM_INTERSECTION)
+            {
+                pt_to_intersection.insert(
+                    pt,
+                    OriginalIntersection {
+                        osm_node_id: osm_node_ids[&pt],
+                    },
+                );
+                intersection_dists.push(*dist);
+                mid_block_crossings += 1;
+            }
+        }
+    }
+    timer.note(format!(
+        "Split roads at {} mid-block crossings",
+        mid_block_crossings
+    ));
+
     for (pt, id) in &pt_to_intersection {
         map.intersections.insert(
             *id,
@@ -47,6 +92,8 @@ pub fn split_up_roads(
                 } else {
                     IntersectionType::StopSign
                 },
+                // Filled out later, if we have data
+                elevation: None,
             },
         );
     }
@@ -134,12 +134,9 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                     format!("{} with {}", test.test_name, test.edits1_name);
                 ui.primary.current_flags.sim_flags.opts.savestate_every = None;
 
-                apply_map_edits(
-                    &mut ui.primary,
-                    &ui.cs,
-                    ctx,
-                    MapEdits::load(&test.map_name, &test.edits1_name, &mut timer),
-                );
+                let (edits, _) =
+                    MapEdits::load(&ui.primary.map, &test.edits1_name, &mut timer).unwrap();
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
                 ui.primary.map.mark_edits_fresh();
                 ui.primary
                     .map
@@ -163,6 +160,7 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                             load: abstutil::path_map(&test.map_name),
                             use_map_fixes: current_flags.sim_flags.use_map_fixes,
                             rng_seed: current_flags.sim_flags.rng_seed,
+                            travel_times: current_flags.sim_flags.travel_times.clone(),
                             opts: SimOptions {
                                 run_name: format!("{} with {}", test.test_name, test.edits2_name),
                                 savestate_every: None,
@@ -178,6 +176,9 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                                     .sim_flags
                                     .opts
                                     .recalc_lanechanging,
+                                event_log: None,
+                                trajectory_log: None,
+                                reroute_compliance: current_flags.sim_flags.opts.reroute_compliance,
                             },
                         },
                         ..current_flags.clone()
@@ -186,12 +187,9 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                     ctx,
                     &mut timer,
                 );
-                apply_map_edits(
-                    &mut secondary,
-                    &ui.cs,
-                    ctx,
-                    MapEdits::load(&test.map_name, &test.edits2_name, &mut timer),
-                );
+                let (edits, _) =
+                    MapEdits::load(&secondary.map, &test.edits2_name, &mut timer).unwrap();
+                apply_map_edits(&mut secondary, &ui.cs, ctx, edits);
                 secondary.map.mark_edits_fresh();
                 secondary
                     .map
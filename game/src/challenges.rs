@@ -1,5 +1,5 @@
 use crate::edit::apply_map_edits;
-use crate::game::{State, Transition, WizardState};
+use crate::game::{msg, State, Transition, WizardState};
 use crate::managed::{ManagedGUIState, WrappedComposite};
 use crate::sandbox::{GameplayMode, SandboxMode};
 use crate::ui::UI;
@@ -9,6 +9,7 @@ use ezgui::{
     ManagedWidget, ModalMenu, Text, VerticalAlignment,
 };
 use geom::{Duration, Time};
+use map_model::MapEdits;
 use sim::{Sim, SimFlags, SimOptions, TripMode};
 use std::collections::{BTreeMap, HashSet};
 
@@ -221,29 +222,45 @@ impl State for ChallengeSplash {
             let gameplay = self.challenge.gameplay.clone();
             return Transition::Push(WizardState::new(Box::new(move |wiz, ctx, ui| {
                 let mut wizard = wiz.wrap(ctx);
-                let (_, new_edits) = wizard.choose("Load which map edits?", || {
-                    Choice::from(
-                        abstutil::load_all_objects(abstutil::path_all_edits(&abstutil::basename(
-                            &map_path,
-                        )))
-                        .into_iter()
-                        .filter(|(_, edits)| gameplay.allows(edits))
-                        .collect(),
-                    )
+                let edits_name = wizard.choose_string("Load which map edits?", || {
+                    abstutil::list_all_objects(abstutil::path_all_edits(&abstutil::basename(
+                        &map_path,
+                    )))
                 })?;
                 if &abstutil::basename(&map_path) != ui.primary.map.get_name() {
                     ui.switch_map(ctx, map_path.clone());
                 }
+                // Edits are loaded relative to the map, so this has to happen after switching.
+                let (new_edits, skipped) = match MapEdits::load(
+                    &ui.primary.map,
+                    &edits_name,
+                    &mut Timer::new("load map edits"),
+                ) {
+                    Ok(pair) => pair,
+                    Err(err) => {
+                        return Some(Transition::Replace(msg("Error", vec![err])));
+                    }
+                };
+                if !gameplay.allows(&new_edits) {
+                    return Some(Transition::Replace(msg(
+                        "Error",
+                        vec![format!("{} can't be used in this challenge", edits_name)],
+                    )));
+                }
                 apply_map_edits(&mut ui.primary, &ui.cs, ctx, new_edits);
                 ui.primary.map.mark_edits_fresh();
                 ui.primary
                     .map
                     .recalculate_pathfinding_after_edits(&mut Timer::new("finalize loaded edits"));
-                Some(Transition::PopThenReplace(Box::new(SandboxMode::new(
-                    ctx,
-                    ui,
-                    gameplay.clone(),
-                ))))
+                let sandbox = Box::new(SandboxMode::new(ctx, ui, gameplay.clone()));
+                if skipped.is_empty() {
+                    Some(Transition::PopThenReplace(sandbox))
+                } else {
+                    Some(Transition::PopThenReplaceThenPush(
+                        sandbox,
+                        msg("Some edits no longer apply", skipped),
+                    ))
+                }
             })));
         }
         if self.menu.action("start challenge fresh") {
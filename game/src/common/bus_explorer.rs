@@ -3,7 +3,7 @@ use crate::game::{State, Transition, WizardState};
 use crate::ui::UI;
 use ezgui::{Choice, Color, EventCtx, GeomBatch, GfxCtx, Line, Text};
 use geom::{Circle, Distance, Pt2D};
-use map_model::{BusRouteID, PathConstraints, PathRequest, PathStep};
+use map_model::{BusRouteID, PathRequest, PathStep};
 
 pub struct ShowBusRoute {
     pub colorer: Colorer,
@@ -40,7 +40,8 @@ impl ShowBusRoute {
                 .pathfind(PathRequest {
                     start: bs1.driving_pos,
                     end: bs2.driving_pos,
-                    constraints: PathConstraints::Bus,
+                    constraints: route.route_type,
+                    departure_time: None,
                 })
                 .unwrap()
                 .get_steps()
@@ -241,6 +241,13 @@ fn info_for(id: ID, ui: &UI) -> Text {
                 txt.add(Line(format!("Parent is {}", r.id)));
             }
             txt.add(Line(format!("Lane is {} long", l.length())));
+            if l.grade != 0.0 {
+                txt.add(Line(format!(
+                    "{:.1}% {}",
+                    l.grade.abs() * 100.0,
+                    if l.grade > 0.0 { "uphill" } else { "downhill" }
+                )));
+            }
 
             txt.add(Line(""));
             styled_kv(&mut txt, &r.osm_tags);
@@ -11,8 +11,8 @@ use ezgui::{
     Text, VerticalAlignment,
 };
 use geom::{Circle, Distance, Duration, PolyLine, Polygon, Pt2D, Statistic, Time};
-use map_model::{BusRouteID, IntersectionID};
-use sim::ParkingSpot;
+use map_model::{BusRouteID, IntersectionID, RoadID};
+use sim::{Emissions, ParkingSpot};
 use std::collections::HashSet;
 
 pub enum Overlays {
@@ -20,6 +20,7 @@ pub enum Overlays {
     ParkingAvailability(Time, Colorer),
     IntersectionDelay(Time, Colorer),
     CumulativeThroughput(Time, Colorer),
+    Emissions(Time, Colorer),
     BikeNetwork(Colorer),
     BusNetwork(Colorer),
 
@@ -50,6 +51,11 @@ impl Overlays {
                     ui.overlay = Overlays::cumulative_throughput(ctx, ui);
                 }
             }
+            Overlays::Emissions(t, _) => {
+                if now != t {
+                    ui.overlay = Overlays::emissions(ctx, ui);
+                }
+            }
             Overlays::IntersectionDemand(t, i, _, _) => {
                 if now != t {
                     ui.overlay = Overlays::intersection_demand(i, ctx, ui);
@@ -88,7 +94,8 @@ impl Overlays {
             | Overlays::BikeNetwork(ref mut heatmap)
             | Overlays::BusNetwork(ref mut heatmap)
             | Overlays::IntersectionDelay(_, ref mut heatmap)
-            | Overlays::CumulativeThroughput(_, ref mut heatmap) => {
+            | Overlays::CumulativeThroughput(_, ref mut heatmap)
+            | Overlays::Emissions(_, ref mut heatmap) => {
                 if heatmap.event(ctx) {
                     ui.overlay = Overlays::Inactive;
                 } else {
@@ -162,7 +169,8 @@ impl Overlays {
             | Overlays::BikeNetwork(ref heatmap)
             | Overlays::BusNetwork(ref heatmap)
             | Overlays::IntersectionDelay(_, ref heatmap)
-            | Overlays::CumulativeThroughput(_, ref heatmap) => {
+            | Overlays::CumulativeThroughput(_, ref heatmap)
+            | Overlays::Emissions(_, ref heatmap) => {
                 heatmap.draw(g);
             }
             Overlays::FinishedTripsHistogram(_, ref composite)
@@ -188,7 +196,8 @@ impl Overlays {
             | Overlays::BikeNetwork(ref heatmap)
             | Overlays::BusNetwork(ref heatmap)
             | Overlays::IntersectionDelay(_, ref heatmap)
-            | Overlays::CumulativeThroughput(_, ref heatmap) => Some(heatmap),
+            | Overlays::CumulativeThroughput(_, ref heatmap)
+            | Overlays::Emissions(_, ref heatmap) => Some(heatmap),
             Overlays::BusRoute(_, _, ref s) => Some(&s.colorer),
             _ => None,
         }
@@ -232,6 +241,7 @@ impl Overlays {
                             RewriteColor::ChangeAll(Color::ORANGE),
                             ctx,
                         )),
+                        WrappedComposite::text_button(ctx, "emissions", hotkey(Key::E)),
                         ManagedWidget::btn(Button::rectangle_svg(
                             "assets/layers/bike_network.svg",
                             "bike network",
@@ -283,6 +293,13 @@ impl Overlays {
                 Some(Transition::Pop)
             }),
         )
+        .cb(
+            "emissions",
+            Box::new(|ctx, ui| {
+                ui.overlay = Overlays::emissions(ctx, ui);
+                Some(Transition::Pop)
+            }),
+        )
         .cb(
             "bike network",
             Box::new(|ctx, ui| {
@@ -467,6 +484,76 @@ impl Overlays {
         Overlays::CumulativeThroughput(ui.primary.sim.time(), colorer.build(ctx, ui))
     }
 
+    fn emissions(ctx: &mut EventCtx, ui: &UI) -> Overlays {
+        let light = Color::hex("#7FFA4D");
+        let medium = Color::hex("#F4DA22");
+        let heavy = Color::hex("#EB5757");
+
+        let (per_road, per_intersection) = ui
+            .primary
+            .sim
+            .get_analytics()
+            .emissions_per_location(ui.primary.sim.time(), &ui.primary.map);
+        let mut total = Emissions::ZERO;
+        for amount in per_road.values().chain(per_intersection.values()) {
+            total += *amount;
+        }
+        let mut txt = Text::from(Line("emissions so far"));
+        txt.add(Line(format!("{:.1} kg of CO2", total.co2 / 1000.0)));
+        txt.add(Line(format!("{:.1} g of NOx", total.nox)));
+        txt.add(Line(format!("{:.1} L of fuel burned", total.fuel)));
+        let mut colorer = ColorerBuilder::new(
+            txt,
+            vec![
+                ("< 50%ile", light),
+                ("< 90%ile", medium),
+                (">= 90%ile", heavy),
+            ],
+        );
+
+        // Longer roads shouldn't look worse just because they're long, so use CO2 per meter.
+        let mut roads: Vec<(RoadID, f64)> = per_road
+            .into_iter()
+            .map(|(r, amount)| {
+                (
+                    r,
+                    amount.co2 / ui.primary.map.get_r(r).center_pts.length().inner_meters(),
+                )
+            })
+            .collect();
+        roads.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+        let p50_idx = ((roads.len() as f64) * 0.5) as usize;
+        let p90_idx = ((roads.len() as f64) * 0.9) as usize;
+        for (idx, (r, _)) in roads.into_iter().enumerate() {
+            let color = if idx < p50_idx {
+                light
+            } else if idx < p90_idx {
+                medium
+            } else {
+                heavy
+            };
+            colorer.add_r(r, color, &ui.primary.map);
+        }
+
+        let mut intersections: Vec<(IntersectionID, Emissions)> =
+            per_intersection.into_iter().collect();
+        intersections.sort_by(|(_, a), (_, b)| a.co2.partial_cmp(&b.co2).unwrap());
+        let p50_idx = ((intersections.len() as f64) * 0.5) as usize;
+        let p90_idx = ((intersections.len() as f64) * 0.9) as usize;
+        for (idx, (i, _)) in intersections.into_iter().enumerate() {
+            let color = if idx < p50_idx {
+                light
+            } else if idx < p90_idx {
+                medium
+            } else {
+                heavy
+            };
+            colorer.add_i(i, color);
+        }
+
+        Overlays::Emissions(ui.primary.sim.time(), colorer.build(ctx, ui))
+    }
+
     fn bike_network(ctx: &mut EventCtx, ui: &UI) -> Overlays {
         let color = Color::hex("#7FFA4D");
         let mut colorer = ColorerBuilder::new(
@@ -2,7 +2,9 @@ use crate::game::{State, Transition};
 use crate::helpers::plain_list_names;
 use crate::helpers::ID;
 use crate::options::TrafficSignalStyle;
-use crate::render::{dashed_lines, draw_signal_phase, DrawOptions, DrawTurn};
+use crate::render::{
+    dashed_lines, describe_phase_duration, draw_signal_phase, DrawOptions, DrawTurn,
+};
 use crate::ui::{ShowEverything, UI};
 use ezgui::{
     hotkey, Button, Color, Composite, DrawBoth, Drawable, EventCtx, GeomBatch, GfxCtx,
@@ -45,14 +47,16 @@ impl TurnCyclerState {
                 }
             }
             Some(ID::Intersection(i)) => {
-                if let Some(ref signal) = ui.primary.map.maybe_get_traffic_signal(i) {
+                if ui.primary.map.maybe_get_traffic_signal(i).is_some() {
                     if ui
                         .per_obj
                         .action(ctx, Key::F, "show full traffic signal diagram")
                     {
                         ui.primary.current_selection = None;
-                        let (idx, _, _) =
-                            signal.current_phase_and_remaining_time(ui.primary.sim.time());
+                        let (idx, _) = ui
+                            .primary
+                            .sim
+                            .current_phase_and_remaining_time(i, &ui.primary.map);
                         return Some(Transition::Push(Box::new(ShowTrafficSignal {
                             menu: ModalMenu::new(
                                 "Traffic Signal Diagram",
@@ -284,7 +288,7 @@ fn make_diagram(i: IntersectionID, selected: usize, ui: &UI, ctx: &mut EventCtx)
         col.push(
             ManagedWidget::row(vec![
                 ManagedWidget::draw_text(ctx, Text::from(Line(format!("#{}", idx + 1)))),
-                ManagedWidget::draw_text(ctx, Text::from(Line(phase.duration.to_string()))),
+                ManagedWidget::draw_text(ctx, Text::from(Line(describe_phase_duration(phase)))),
             ])
             .margin(5)
             .evenly_spaced(),
@@ -0,0 +1,77 @@
+use crate::edit::{apply_map_edits, saved_edits, EditMode};
+use crate::game::{msg, State, Transition, WizardState};
+use crate::sandbox::GameplayMode;
+use abstutil::Timer;
+use ezgui::Choice;
+use map_model::MapEdits;
+
+// Highlight what differs from some other saved edits, instead of what differs from the original
+// map.
+pub fn compare_edits() -> Box<dyn State> {
+    WizardState::new(Box::new(|wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let (_, other) = wizard.choose("Compare the current edits with what?", || {
+            let current = ui.primary.map.get_edits().edits_name.clone();
+            let mut list = vec![Choice::new(
+                "the original map",
+                MapEdits::new(ui.primary.map.get_name().to_string()),
+            )];
+            for saved in saved_edits(ui, &mut Timer::new("load map edits")) {
+                if saved.name != current {
+                    list.push(Choice::new(saved.name, saved.edits));
+                }
+            }
+            list
+        })?;
+        // No edits at all is the same as the original map.
+        let compare_to = if other.commands.is_empty() {
+            None
+        } else {
+            Some(other)
+        };
+        Some(Transition::PopWithData(Box::new(move |state, _, _| {
+            state.downcast_mut::<EditMode>().unwrap().compare_to = compare_to;
+        })))
+    }))
+}
+
+// Combine someone else's proposal with the current edits.
+pub fn merge_edits(mode: GameplayMode) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let (name, other) = wizard.choose("Merge which edits into the current ones?", || {
+            let current = ui.primary.map.get_edits().edits_name.clone();
+            saved_edits(ui, &mut Timer::new("load map edits"))
+                .into_iter()
+                .filter(|saved| saved.name != current)
+                .map(|saved| Choice::new(saved.name.clone(), saved))
+                .collect()
+        })?;
+
+        let before = ui.primary.map.get_edits().commands.len();
+        match ui
+            .primary
+            .map
+            .get_edits()
+            .merge(&other.edits, &ui.primary.map)
+        {
+            Ok(merged) => {
+                if !mode.allows(&merged) {
+                    return Some(Transition::Replace(msg(
+                        "Can't merge edits",
+                        vec![format!("{} has edits that this mode doesn't allow", name)],
+                    )));
+                }
+                let added = merged.commands.len() - before;
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, merged);
+                let mut lines = vec![format!("Added {} changes from {}", added, name)];
+                if !other.skipped.is_empty() {
+                    lines.push(format!("These changes from {} no longer apply:", name));
+                    lines.extend(other.skipped);
+                }
+                Some(Transition::Replace(msg("Merged edits", lines)))
+            }
+            Err(err) => Some(Transition::Replace(msg("Can't merge edits", vec![err]))),
+        }
+    }))
+}
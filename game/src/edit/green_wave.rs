@@ -0,0 +1,248 @@
+use crate::edit::apply_map_edits;
+use crate::game::{msg, State, Transition};
+use crate::helpers::ID;
+use crate::managed::WrappedComposite;
+use crate::render::DrawOptions;
+use crate::ui::{ShowEverything, UI};
+use ezgui::{
+    hotkey, Color, Composite, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line,
+    ManagedWidget, ModalMenu, Outcome, Text, VerticalAlignment,
+};
+use geom::{Distance, Duration, Polygon, Pt2D};
+use map_model::{ControlTrafficSignal, Corridor, EditCmd, IntersectionID};
+
+// Pick a sequence of traffic signals, then coordinate them so that traffic along the corridor hits
+// a green wave.
+pub struct GreenWaveTool {
+    menu: ModalMenu,
+    signals: Vec<IntersectionID>,
+}
+
+impl GreenWaveTool {
+    pub fn new(ctx: &EventCtx, ui: &mut UI) -> GreenWaveTool {
+        ui.primary.current_selection = None;
+        GreenWaveTool {
+            menu: ModalMenu::new(
+                "Green Wave",
+                vec![
+                    (hotkey(Key::Escape), "quit"),
+                    (hotkey(Key::Backspace), "remove last signal"),
+                    (hotkey(Key::Num1), "make one-way green wave"),
+                    (hotkey(Key::Num2), "make two-way green wave"),
+                    (hotkey(Key::T), "show time-space diagram"),
+                ],
+                ctx,
+            ),
+            signals: Vec::new(),
+        }
+    }
+
+    fn corridor(&self, ui: &UI) -> Result<Corridor, String> {
+        Corridor::new(&ui.primary.map, self.signals.clone())
+    }
+}
+
+impl State for GreenWaveTool {
+    fn event(&mut self, ctx: &mut EventCtx, ui: &mut UI) -> Transition {
+        {
+            let mut txt = Text::new();
+            txt.add(Line(format!(
+                "{} signals in the corridor",
+                self.signals.len()
+            )));
+            self.menu.set_info(ctx, txt);
+        }
+        self.menu.event(ctx);
+        ctx.canvas_movement();
+        if ctx.redo_mouseover() {
+            ui.recalculate_current_selection(ctx);
+        }
+
+        if let Some(ID::Intersection(i)) = ui.primary.current_selection {
+            if ui.primary.map.maybe_get_traffic_signal(i).is_some()
+                && self.signals.last() != Some(&i)
+                && ui.per_obj.action(ctx, Key::Space, "add to the corridor")
+            {
+                self.signals.push(i);
+            }
+        }
+
+        if self.menu.action("quit") {
+            return Transition::Pop;
+        }
+        if !self.signals.is_empty() && self.menu.action("remove last signal") {
+            self.signals.pop();
+        }
+        if self.menu.action("show time-space diagram") {
+            return match self.corridor(ui) {
+                Ok(corridor) => {
+                    Transition::Push(Box::new(TimeSpaceDiagram::new(ctx, ui, corridor)))
+                }
+                Err(err) => Transition::Push(msg("Can't show diagram", vec![err])),
+            };
+        }
+        for (action, bidirectional) in vec![
+            ("make one-way green wave", false),
+            ("make two-way green wave", true),
+        ] {
+            if self.menu.action(action) {
+                let result = self.corridor(ui).and_then(|corridor| {
+                    let signals = corridor.green_wave(&ui.primary.map, bidirectional)?;
+                    Ok((corridor, signals))
+                });
+                return match result {
+                    Ok((corridor, signals)) => {
+                        let mut edits = ui.primary.map.get_edits().clone();
+                        for signal in signals {
+                            edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+                        }
+                        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+                        Transition::Push(Box::new(TimeSpaceDiagram::new(ctx, ui, corridor)))
+                    }
+                    Err(err) => Transition::Push(msg("Can't make a green wave", vec![err])),
+                };
+            }
+        }
+
+        Transition::Keep
+    }
+
+    fn draw_default_ui(&self) -> bool {
+        false
+    }
+
+    fn draw(&self, g: &mut GfxCtx, ui: &UI) {
+        ui.draw(
+            g,
+            DrawOptions::new(),
+            &ui.primary.sim,
+            &ShowEverything::new(),
+        );
+        let color = ui.cs.get_def("signal in corridor", Color::GREEN.alpha(0.8));
+        for i in &self.signals {
+            g.draw_polygon(color, &ui.primary.map.get_i(*i).polygon);
+        }
+        self.menu.draw(g);
+    }
+}
+
+// Time goes to the right, distance along the corridor goes up. Each signal is a horizontal bar,
+// green when the corridor movement is allowed. A vehicle going the speed limit makes a straight
+// line; the green wave works if those lines only cross bars while they're green.
+struct TimeSpaceDiagram {
+    composite: Composite,
+}
+
+impl TimeSpaceDiagram {
+    fn new(ctx: &mut EventCtx, ui: &UI, corridor: Corridor) -> TimeSpaceDiagram {
+        let map = &ui.primary.map;
+        let signals: Vec<&ControlTrafficSignal> = corridor
+            .signals
+            .iter()
+            .map(|i| map.get_traffic_signal(*i))
+            .collect();
+        let cycle = signals.iter().map(|s| s.cycle_length()).max().unwrap();
+        let total_time = *corridor.time_along.last().unwrap();
+        let total_dist = *corridor.dist_along.last().unwrap();
+        let until = (cycle * 3.0).max(total_time + cycle);
+
+        let width = 0.5 * ctx.canvas.window_width;
+        let height = 0.4 * ctx.canvas.window_height;
+        let to_pt = |t: Duration, d: Distance| {
+            Pt2D::new(width * (t / until), height * (1.0 - d / total_dist))
+        };
+        let bar = 6.0;
+
+        let mut batch = GeomBatch::new();
+        batch.push(Color::grey(0.2), Polygon::rectangle(width, height));
+        for (k, signal) in signals.iter().enumerate() {
+            let y = to_pt(Duration::ZERO, corridor.dist_along[k]).y();
+            batch.push(
+                Color::RED,
+                Polygon::rectangle(width, bar).translate(0.0, y - bar / 2.0),
+            );
+            for (forwards, offset) in vec![(true, -bar / 4.0), (false, bar / 4.0)] {
+                let color = if forwards {
+                    Color::GREEN
+                } else {
+                    Color::hex("#7FFF7F")
+                };
+                for (start, end) in corridor.green_times(signal, k, forwards, until) {
+                    let x1 = to_pt(start, Distance::ZERO).x();
+                    let x2 = to_pt(end, Distance::ZERO).x();
+                    if x2 > x1 {
+                        batch.push(
+                            color,
+                            Polygon::rectangle(x2 - x1, bar / 2.0)
+                                .translate(x1, y + offset - bar / 4.0),
+                        );
+                    }
+                }
+            }
+        }
+
+        // Start a vehicle at the beginning of every green at each end of the corridor.
+        let last = signals.len() - 1;
+        for (forwards, k) in vec![(true, 0), (false, last)] {
+            for (start, _) in corridor.green_times(signals[k], k, forwards, until) {
+                let pts: Vec<Pt2D> = (0..signals.len())
+                    .map(|j| {
+                        let dt = if forwards {
+                            corridor.time_along[j]
+                        } else {
+                            total_time - corridor.time_along[j]
+                        };
+                        to_pt(start + dt, corridor.dist_along[j])
+                    })
+                    .collect();
+                for pair in pts.windows(2) {
+                    if pair[0].x() > width || pair[1].x() > width {
+                        continue;
+                    }
+                    batch.push(
+                        Color::WHITE,
+                        geom::Line::new(pair[0], pair[1]).make_polygons(Distance::meters(1.5)),
+                    );
+                }
+            }
+        }
+
+        let composite = Composite::new(
+            ManagedWidget::col(vec![
+                ManagedWidget::draw_text(ctx, {
+                    let mut txt = Text::from(Line("Time-space diagram"));
+                    txt.add(Line(format!(
+                        "{} signals over {}, {} at the speed limit",
+                        signals.len(),
+                        total_dist,
+                        total_time
+                    )));
+                    txt.add(Line(format!("Showing the first {} of the day", until)));
+                    txt
+                }),
+                ManagedWidget::draw_batch(ctx, batch),
+                WrappedComposite::text_button(ctx, "X", hotkey(Key::Escape)),
+            ])
+            .bg(Color::hex("#545454")),
+        )
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Center)
+        .build(ctx);
+        TimeSpaceDiagram { composite }
+    }
+}
+
+impl State for TimeSpaceDiagram {
+    fn event(&mut self, ctx: &mut EventCtx, _: &mut UI) -> Transition {
+        match self.composite.event(ctx) {
+            Some(Outcome::Clicked(x)) => match x.as_ref() {
+                "X" => Transition::Pop,
+                _ => unreachable!(),
+            },
+            None => Transition::Keep,
+        }
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &UI) {
+        self.composite.draw(g);
+    }
+}
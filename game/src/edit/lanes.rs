@@ -1,4 +1,5 @@
 use crate::edit::apply_map_edits;
+use crate::edit::speed_limits::edit_speed_limit;
 use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::ui::UI;
@@ -6,8 +7,10 @@ use ezgui::{
     hotkey, Button, Choice, Color, Composite, EventCtx, GfxCtx, HorizontalAlignment, Key,
     ManagedWidget, Outcome, VerticalAlignment,
 };
+use geom::Distance;
 use map_model::{
-    connectivity, EditCmd, IntersectionType, LaneID, LaneType, Map, PathConstraints, RoadID,
+    connectivity, EditCmd, IntersectionType, LaneID, LaneType, Map, PathConstraints, Road, RoadID,
+    LANE_THICKNESS,
 };
 use std::collections::BTreeSet;
 
@@ -99,6 +102,35 @@ impl LaneEditor {
                 }
             }
 
+            // The new lane goes between this one and the center of the road.
+            if ui.per_obj.action(ctx, Key::A, "add a lane here") {
+                return Some(Transition::Push(make_add_lane(l)));
+            }
+            if ui.per_obj.action(ctx, Key::X, "delete this lane") {
+                match try_delete_lane(l, &ui.primary.map) {
+                    Ok(cmd) => {
+                        let mut edits = ui.primary.map.get_edits().clone();
+                        edits.commands.push(cmd);
+                        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+                        ui.primary.current_selection = None;
+                        return None;
+                    }
+                    Err(err) => {
+                        return Some(Transition::Push(msg("Error", vec![err])));
+                    }
+                }
+            }
+
+            if ui.per_obj.action(ctx, Key::W, "change the lane width") {
+                return Some(Transition::Push(make_change_width(l)));
+            }
+
+            if ui.per_obj.action(ctx, Key::M, "change the speed limit") {
+                return Some(Transition::Push(edit_speed_limit(
+                    ui.primary.map.get_l(l).parent,
+                )));
+            }
+
             if ui
                 .per_obj
                 .action(ctx, Key::U, "bulk edit lanes on this road")
@@ -226,14 +258,19 @@ fn make_brush_panel(ctx: &mut EventCtx, brush: Brush) -> Composite {
     .build(ctx)
 }
 
-fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String> {
-    let r = map.get_parent(l);
-    let (fwds, idx) = r.dir_and_offset(l);
-    let (mut proposed_lts, other_side) = if fwds {
+// Returns the lane types on the same side of the road as the lane, then the other side
+fn lane_types(r: &Road, fwds: bool) -> (Vec<LaneType>, Vec<LaneType>) {
+    if fwds {
         (r.get_lane_types().0, r.get_lane_types().1)
     } else {
         (r.get_lane_types().1, r.get_lane_types().0)
-    };
+    }
+}
+
+fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String> {
+    let r = map.get_parent(l);
+    let (fwds, idx) = r.dir_and_offset(l);
+    let (mut proposed_lts, other_side) = lane_types(r, fwds);
     proposed_lts[idx] = new_lt;
 
     // No-op change
@@ -241,6 +278,20 @@ fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String
         return None;
     }
 
+    if map.get_l(l).is_light_rail() || new_lt == LaneType::LightRail {
+        return Some(format!("You can't lay or tear up light rail tracks"));
+    }
+
+    check_lane_types(r, map, &proposed_lts, other_side)
+}
+
+// Checks the lane types proposed for one side of a road.
+fn check_lane_types(
+    r: &Road,
+    map: &Map,
+    proposed_lts: &Vec<LaneType>,
+    other_side: Vec<LaneType>,
+) -> Option<String> {
     // Only one parking lane per side.
     if proposed_lts
         .iter()
@@ -255,8 +306,10 @@ fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String
         ));
     }
 
-    // Don't let players orphan a bus stop.
-    if !r.all_bus_stops(map).is_empty()
+    // Don't let players orphan a bus stop. Light rail stations don't care about the road.
+    if r.all_bus_stops(map)
+        .into_iter()
+        .any(|bs| !map.get_bs(bs).is_train_stop)
         && !proposed_lts
             .iter()
             .any(|lt| *lt == LaneType::Driving || *lt == LaneType::Bus)
@@ -279,6 +332,93 @@ fn can_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Option<String
     None
 }
 
+fn try_delete_lane(l: LaneID, map: &Map) -> Result<EditCmd, String> {
+    let lane = map.get_l(l);
+    if lane.is_sidewalk() || lane.is_light_rail() || lane.lane_type == LaneType::SharedLeftTurn {
+        return Err(format!("You can't delete a {:?} lane", lane.lane_type));
+    }
+    if map
+        .all_buildings()
+        .iter()
+        .any(|b| b.parking.as_ref().map(|p| p.driving_pos.lane()) == Some(l))
+    {
+        return Err(format!("A parking garage needs this lane"));
+    }
+
+    let r = map.get_parent(l);
+    let (fwds, idx) = r.dir_and_offset(l);
+    let (mut proposed_lts, other_side) = lane_types(r, fwds);
+    proposed_lts.remove(idx);
+    if proposed_lts.is_empty() {
+        return Err(format!(
+            "You can't delete the last lane on this side of the road"
+        ));
+    }
+    if let Some(err) = check_lane_types(r, map, &proposed_lts, other_side) {
+        return Err(err);
+    }
+    Ok(EditCmd::DeleteLane { id: l, fwds, idx })
+}
+
+fn make_add_lane(l: LaneID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let (_, lt) = wizard.choose("Add what kind of lane?", || {
+            vec![
+                Choice::new("driving", LaneType::Driving),
+                Choice::new("parking", LaneType::Parking),
+                Choice::new("biking", LaneType::Biking),
+                Choice::new("bus", LaneType::Bus),
+            ]
+        })?;
+
+        let map = &ui.primary.map;
+        let r = map.get_parent(l);
+        let (fwds, idx) = r.dir_and_offset(l);
+        let (mut proposed_lts, other_side) = lane_types(r, fwds);
+        proposed_lts.insert(idx, lt);
+        if let Some(err) = check_lane_types(r, map, &proposed_lts, other_side) {
+            return Some(Transition::Replace(msg("Error", vec![err])));
+        }
+
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::AddLane {
+            road: r.id,
+            fwds,
+            idx,
+            lt,
+        });
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        ui.primary.current_selection = None;
+        Some(Transition::Pop)
+    }))
+}
+
+fn make_change_width(l: LaneID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let (_, width) = wizard.choose("How wide should this lane be?", || {
+            vec![
+                Choice::new("narrow (2m)", Distance::meters(2.0)),
+                Choice::new("standard (2.5m)", LANE_THICKNESS),
+                Choice::new("wide (3.5m)", Distance::meters(3.5)),
+            ]
+        })?;
+
+        let old = ui.primary.map.get_l(l).width;
+        if width != old {
+            let mut edits = ui.primary.map.get_edits().clone();
+            edits.commands.push(EditCmd::ChangeLaneWidth {
+                id: l,
+                new: width,
+                old,
+            });
+            apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        }
+        Some(Transition::Pop)
+    }))
+}
+
 fn try_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Result<Option<EditCmd>, String> {
     if let Some(err) = can_change_lane_type(l, new_lt, map) {
         return Err(err);
@@ -323,7 +463,7 @@ fn make_bulk_edit_lanes(road: RoadID) -> Box<dyn State> {
         let road_name = ui.primary.map.get_r(road).get_name();
         let mut success = 0;
         let mut failure = 0;
-        let lane_ids: Vec<LaneID> = ui.primary.map.all_lanes().iter().map(|l| l.id).collect();
+        let lane_ids: Vec<LaneID> = ui.primary.map.all_lanes().map(|l| l.id).collect();
         for l in lane_ids {
             let orig_lt = ui.primary.map.get_l(l).lane_type;
             if orig_lt != from || ui.primary.map.get_parent(l).get_name() != road_name {
@@ -367,7 +507,7 @@ fn apply_brush(brush: Brush, map: &Map, l: LaneID) -> Result<Option<EditCmd>, St
         Brush::Construction => try_change_lane_type(l, LaneType::Construction, map),
         Brush::Reverse => {
             let lane = map.get_l(l);
-            if !lane.lane_type.is_for_moving_vehicles() {
+            if !lane.lane_type.is_for_moving_vehicles() || lane.is_light_rail() {
                 return Err(format!("You can't reverse a {:?} lane", lane.lane_type));
             }
             if map.get_r(lane.parent).dir_and_offset(l).1 != 0 {
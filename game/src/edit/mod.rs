@@ -1,13 +1,19 @@
+mod compare;
+mod green_wave;
 mod lanes;
+mod speed_limits;
 mod stop_signs;
 mod traffic_signals;
 
+use self::compare::{compare_edits, merge_edits};
+use self::green_wave::GreenWaveTool;
 use self::lanes::{Brush, LaneEditor};
+use self::speed_limits::bulk_edit_speed_limits;
 pub use self::stop_signs::StopSignEditor;
 pub use self::traffic_signals::TrafficSignalEditor;
 use crate::common::{tool_panel, CommonState, Warping};
 use crate::debug::DebugMode;
-use crate::game::{State, Transition, WizardState};
+use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::{ColorScheme, ID};
 use crate::managed::{WrappedComposite, WrappedOutcome};
 use crate::render::{
@@ -19,7 +25,9 @@ use abstutil::Timer;
 use ezgui::{
     hotkey, lctrl, Choice, Color, EventCtx, GfxCtx, Key, Line, ModalMenu, Text, WrappedWizard,
 };
-use map_model::{ControlStopSign, ControlTrafficSignal, EditCmd, LaneID, MapEdits};
+use map_model::{
+    ControlStopSign, ControlTrafficSignal, EditCmd, IntersectionID, LaneID, MapEdits, TurnBan,
+};
 use sim::Sim;
 use std::collections::BTreeSet;
 
@@ -33,6 +41,8 @@ pub struct EditMode {
     pub suspended_sim: Sim,
 
     lane_editor: LaneEditor,
+    // Show differences from these instead of from the original map
+    compare_to: Option<MapEdits>,
 }
 
 impl EditMode {
@@ -46,15 +56,19 @@ impl EditMode {
                 vec![
                     (hotkey(Key::S), "save edits"),
                     (hotkey(Key::L), "load different edits"),
-                    // TODO Support redo. Bit harder here to reset the redo_stack when the edits
-                    // change, because nested other places modify it too.
                     (lctrl(Key::Z), "undo"),
+                    (lctrl(Key::Y), "redo"),
+                    (hotkey(Key::O), "compare with other edits"),
+                    (hotkey(Key::I), "merge in other edits"),
+                    (hotkey(Key::G), "coordinate traffic signals"),
+                    (hotkey(Key::H), "set speed limits in a neighborhood"),
                 ],
                 ctx,
             ),
             mode,
             suspended_sim,
             lane_editor: LaneEditor::new(ctx),
+            compare_to: None,
         }
     }
 }
@@ -80,6 +94,29 @@ impl State for EditMode {
                 "{} intersections changed",
                 edits.changed_intersections.len()
             )));
+            txt.add(Line(format!("{} turn bans", edits.banned_turns.len())));
+            txt.add(Line(format!(
+                "{} roads restriped",
+                edits.restriped_roads.len()
+            )));
+            txt.add(Line(format!(
+                "{} lane widths changed",
+                edits.original_lane_widths.len()
+            )));
+            txt.add(Line(format!(
+                "{} speed limits changed",
+                edits.original_speed_limits.len()
+            )));
+            if let Some(ref other) = self.compare_to {
+                let diff = edits.diff(other, &ui.primary.map);
+                txt.add(Line(format!(
+                    "Compared to {}: {} lanes, {} roads, {} intersections differ",
+                    other.edits_name,
+                    diff.lanes.len(),
+                    diff.roads.len(),
+                    diff.intersections.len()
+                )));
+            }
             self.menu.set_info(ctx, txt);
         }
 
@@ -119,6 +156,16 @@ impl State for EditMode {
             })));
         } else if self.menu.action("load different edits") {
             return Transition::Push(make_load_edits(self.mode.clone()));
+        } else if self.menu.action("compare with other edits") {
+            return Transition::Push(compare_edits());
+        } else if self.menu.action("merge in other edits") {
+            return Transition::Push(merge_edits(self.mode.clone()));
+        } else if self.menu.action("coordinate traffic signals") {
+            return Transition::Push(Box::new(GreenWaveTool::new(ctx, ui)));
+        } else if self.mode.can_edit_lanes()
+            && self.menu.action("set speed limits in a neighborhood")
+        {
+            return Transition::Push(bulk_edit_speed_limits());
         }
 
         if let Some(ID::Intersection(id)) = ui.primary.current_selection {
@@ -177,6 +224,11 @@ impl State for EditMode {
                     apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
                 }
             }
+            if !ui.primary.map.get_i(id).banned_turns.is_empty()
+                && ui.per_obj.action(ctx, Key::U, "allow banned turns")
+            {
+                return Transition::Push(make_allow_turns(id));
+            }
             if ui.primary.map.get_i(id).is_closed() && ui.per_obj.action(ctx, Key::R, "revert") {
                 let mut edits = ui.primary.map.get_edits().clone();
                 edits
@@ -186,15 +238,27 @@ impl State for EditMode {
             }
         }
 
-        if !ui.primary.map.get_edits().commands.is_empty() && self.menu.action("undo") {
+        let mut undo_redo = None;
+        if ui.primary.map.get_edits().can_undo() && self.menu.action("undo") {
             let mut edits = ui.primary.map.get_edits().clone();
-            let id = match edits.commands.pop().unwrap() {
+            undo_redo = Some((edits.undo().unwrap(), edits));
+        } else if ui.primary.map.get_edits().can_redo() && self.menu.action("redo") {
+            let mut edits = ui.primary.map.get_edits().clone();
+            undo_redo = Some((edits.redo().unwrap(), edits));
+        }
+        if let Some((cmd, edits)) = undo_redo {
+            let id = match cmd {
                 EditCmd::ChangeLaneType { id, .. } => ID::Lane(id),
                 EditCmd::ReverseLane { l, .. } => ID::Lane(l),
                 EditCmd::ChangeStopSign(ss) => ID::Intersection(ss.id),
                 EditCmd::ChangeTrafficSignal(ss) => ID::Intersection(ss.id),
                 EditCmd::CloseIntersection { id, .. } => ID::Intersection(id),
                 EditCmd::UncloseIntersection(id, _) => ID::Intersection(id),
+                EditCmd::BanTurns(ban) | EditCmd::AllowTurns(ban) => ID::Intersection(ban.parent()),
+                EditCmd::AddLane { road, .. } => ID::Road(road),
+                EditCmd::DeleteLane { id, .. } => ID::Lane(id),
+                EditCmd::ChangeLaneWidth { id, .. } => ID::Lane(id),
+                EditCmd::ChangeSpeedLimit { road, .. } => ID::Road(road),
             };
             apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
             return Transition::Push(Warping::new(
@@ -239,9 +303,46 @@ impl State for EditMode {
             &ShowEverything::new(),
         );
 
-        // More generally we might want to show the diff between two edits, but for now,
-        // just show diff relative to basemap.
         let edits = ui.primary.map.get_edits();
+        let (changed_lanes, changed_intersections): (BTreeSet<LaneID>, BTreeSet<IntersectionID>) =
+            if let Some(ref other) = self.compare_to {
+                let diff = edits.diff(other, &ui.primary.map);
+                (
+                    diff.lanes
+                        .into_iter()
+                        // The other edits might've added lanes that don't exist here.
+                        .filter(|l| l.0 < ui.primary.map.all_lanes_including_deleted().len())
+                        .chain(
+                            diff.roads
+                                .iter()
+                                .flat_map(|r| ui.primary.map.get_r(*r).all_lanes()),
+                        )
+                        .collect(),
+                    diff.intersections,
+                )
+            } else {
+                (
+                    edits
+                        .original_lts
+                        .keys()
+                        .chain(&edits.reversed_lanes)
+                        .cloned()
+                        .chain(
+                            edits
+                                .restriped_roads
+                                .iter()
+                                .chain(edits.original_speed_limits.keys())
+                                .flat_map(|r| ui.primary.map.get_r(*r).all_lanes()),
+                        )
+                        .collect(),
+                    edits
+                        .changed_intersections
+                        .iter()
+                        .cloned()
+                        .chain(edits.banned_turns.iter().map(|ban| ban.parent()))
+                        .collect(),
+                )
+            };
 
         let ctx = ui.draw_ctx();
         let mut opts = DrawOptions::new();
@@ -250,12 +351,12 @@ impl State for EditMode {
         // supply a set of things to highlight and have something else take care of drawing
         // with detail or not.
         if g.canvas.cam_zoom >= MIN_ZOOM_FOR_DETAIL {
-            for l in edits.original_lts.keys().chain(&edits.reversed_lanes) {
+            for l in &changed_lanes {
                 opts.override_colors
                     .insert(ID::Lane(*l), Color::HatchingStyle1);
                 ctx.draw_map.get_l(*l).draw(g, &opts, &ctx);
             }
-            for i in &edits.changed_intersections {
+            for i in &changed_intersections {
                 opts.override_colors
                     .insert(ID::Intersection(*i), Color::HatchingStyle1);
                 ctx.draw_map.get_i(*i).draw(g, &opts, &ctx);
@@ -279,11 +380,14 @@ impl State for EditMode {
             }
         } else {
             let color = ui.cs.get_def("unzoomed map diffs", Color::RED);
-            for l in edits.original_lts.keys().chain(&edits.reversed_lanes) {
-                g.draw_polygon(color, &ctx.map.get_parent(*l).get_thick_polygon().unwrap());
+            for l in &changed_lanes {
+                g.draw_polygon(
+                    color,
+                    &ctx.map.get_parent(*l).get_thick_polygon(&ctx.map).unwrap(),
+                );
             }
 
-            for i in &edits.changed_intersections {
+            for i in &changed_intersections {
                 opts.override_colors.insert(ID::Intersection(*i), color);
                 ctx.draw_map.get_i(*i).draw(g, &opts, &ctx);
             }
@@ -354,23 +458,104 @@ fn make_load_edits(mode: GameplayMode) -> Box<dyn State> {
         }
 
         // TODO Exclude current
-        let map_name = ui.primary.map.get_name().to_string();
-        let (_, new_edits) = wizard.choose("Load which map edits?", || {
-            let mut list = Choice::from(
-                abstutil::load_all_objects(abstutil::path_all_edits(&map_name))
+        let (_, saved) = wizard.choose("Load which map edits?", || {
+            let mut list: Vec<Choice<SavedEdits>> =
+                saved_edits(ui, &mut Timer::new("load map edits"))
                     .into_iter()
-                    .filter(|(_, edits)| mode.allows(edits))
-                    .collect(),
-            );
-            list.push(Choice::new("no_edits", MapEdits::new(map_name.clone())));
+                    .filter(|saved| mode.allows(&saved.edits))
+                    .map(|saved| Choice::new(saved.name.clone(), saved))
+                    .collect();
+            list.push(Choice::new(
+                "no_edits",
+                SavedEdits {
+                    name: "no_edits".to_string(),
+                    edits: MapEdits::new(ui.primary.map.get_name().to_string()),
+                    skipped: Vec::new(),
+                },
+            ));
             list
         })?;
-        apply_map_edits(&mut ui.primary, &ui.cs, ctx, new_edits);
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, saved.edits);
         ui.primary.map.mark_edits_fresh();
+        if saved.skipped.is_empty() {
+            Some(Transition::Pop)
+        } else {
+            Some(Transition::Replace(msg(
+                "Some edits no longer apply",
+                saved.skipped,
+            )))
+        }
+    }))
+}
+
+fn make_allow_turns(i: IntersectionID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let banned: Vec<TurnBan> = ui
+            .primary
+            .map
+            .get_i(i)
+            .banned_turns
+            .iter()
+            .cloned()
+            .collect();
+        // Pick by index; one past the end means all of them.
+        let (_, idx) = wizard.choose("Allow which banned turns?", || {
+            let mut list: Vec<Choice<usize>> = banned
+                .iter()
+                .enumerate()
+                .map(|(idx, ban)| Choice::new(ban.describe(), idx))
+                .collect();
+            if banned.len() > 1 {
+                list.push(Choice::new("all of them", banned.len()));
+            }
+            list
+        })?;
+
+        let mut edits = ui.primary.map.get_edits().clone();
+        let chosen = if idx == banned.len() {
+            banned
+        } else {
+            vec![banned[idx]]
+        };
+        for ban in chosen {
+            edits.commands.push(EditCmd::AllowTurns(ban));
+        }
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
         Some(Transition::Pop)
     }))
 }
 
+#[derive(Clone)]
+pub struct SavedEdits {
+    pub name: String,
+    pub edits: MapEdits,
+    // Descriptions of the commands that no longer apply to the map
+    pub skipped: Vec<String>,
+}
+
+impl abstutil::Cloneable for SavedEdits {}
+
+// Everything saved for the current map that still loads
+fn saved_edits(ui: &UI, timer: &mut Timer) -> Vec<SavedEdits> {
+    let mut list = Vec::new();
+    for name in abstutil::list_all_objects(abstutil::path_all_edits(ui.primary.map.get_name())) {
+        match MapEdits::load(&ui.primary.map, &name, timer) {
+            Ok((edits, skipped)) => {
+                list.push(SavedEdits {
+                    name,
+                    edits,
+                    skipped,
+                });
+            }
+            Err(err) => {
+                timer.warn(err);
+            }
+        }
+    }
+    list
+}
+
 pub fn apply_map_edits(
     bundle: &mut PerMapUI,
     cs: &ColorScheme,
@@ -380,11 +565,17 @@ pub fn apply_map_edits(
     edits.dirty = true;
     let mut timer = Timer::new("apply map edits");
 
+    let was_restriped = !bundle.map.get_edits().restriped_roads.is_empty();
     let (lanes_changed, roads_changed, turns_deleted, turns_added, mut modified_intersections) =
         bundle.map.apply_edits(edits, &mut timer);
 
+    // Lanes might've been added or removed. New lanes are always in lanes_changed, in order.
+    bundle
+        .draw_map
+        .lanes
+        .truncate(bundle.map.all_lanes_including_deleted().len());
     for l in lanes_changed {
-        bundle.draw_map.lanes[l.0] = DrawLane::new(
+        let draw = DrawLane::new(
             bundle.map.get_l(l),
             &bundle.map,
             bundle.current_flags.draw_lane_markings,
@@ -392,6 +583,11 @@ pub fn apply_map_edits(
             &mut timer,
         )
         .finish(ctx.prerender);
+        if l.0 == bundle.draw_map.lanes.len() {
+            bundle.draw_map.lanes.push(draw);
+        } else {
+            bundle.draw_map.lanes[l.0] = draw;
+        }
     }
     for r in roads_changed {
         bundle.draw_map.roads[r.0] =
@@ -417,4 +613,8 @@ pub fn apply_map_edits(
             &mut timer,
         );
     }
+
+    if was_restriped || !bundle.map.get_edits().restriped_roads.is_empty() {
+        bundle.draw_map.recalculate_quadtree(&bundle.map);
+    }
 }
@@ -0,0 +1,76 @@
+use crate::edit::apply_map_edits;
+use crate::game::{msg, State, Transition, WizardState};
+use ezgui::{Choice, WrappedWizard};
+use geom::Speed;
+use map_model::{osm, EditCmd, FullNeighborhoodInfo, Neighborhood, RoadID};
+
+pub fn edit_speed_limit(road: RoadID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let old = ui.primary.map.get_r(road).speed_limit;
+        let new = choose_speed_limit(
+            &mut wizard,
+            &format!("Change speed limit of {} (currently {})", road, old),
+        )?;
+
+        let mut edits = ui.primary.map.get_edits().clone();
+        edits
+            .commands
+            .push(EditCmd::ChangeSpeedLimit { road, new, old });
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        Some(Transition::Pop)
+    }))
+}
+
+// For things like "20 is plenty" campaigns, set the speed limit on every road in a neighborhood.
+pub fn bulk_edit_speed_limits() -> Box<dyn State> {
+    WizardState::new(Box::new(|wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let name = wizard.choose_string("Change speed limits in which neighborhood?", || {
+            Neighborhood::load_all(ui.primary.map.get_name(), ui.primary.map.get_gps_bounds())
+                .into_iter()
+                .map(|(n, _)| n)
+                .collect()
+        })?;
+        let new = choose_speed_limit(&mut wizard, "Change all speed limits to...")?;
+
+        let roads = FullNeighborhoodInfo::load_all(&ui.primary.map)
+            .remove(&name)
+            .unwrap()
+            .roads;
+        let mut edits = ui.primary.map.get_edits().clone();
+        let mut changed = 0;
+        for r in roads {
+            let road = ui.primary.map.get_r(r);
+            // Light rail tracks aren't streets.
+            if road.osm_tags.contains_key(osm::RAILWAY) || road.speed_limit == new {
+                continue;
+            }
+            edits.commands.push(EditCmd::ChangeSpeedLimit {
+                road: r,
+                new,
+                old: road.speed_limit,
+            });
+            changed += 1;
+        }
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        Some(Transition::Replace(msg(
+            "Bulk speed limit edit",
+            vec![format!(
+                "Changed the speed limit of {} roads in {} to {}",
+                changed, name, new
+            )],
+        )))
+    }))
+}
+
+fn choose_speed_limit(wizard: &mut WrappedWizard, query: &str) -> Option<Speed> {
+    wizard
+        .choose(query, || {
+            vec![10.0, 15.0, 20.0, 25.0, 30.0, 35.0, 40.0, 45.0, 50.0]
+                .into_iter()
+                .map(|mph| Choice::new(format!("{} mph", mph), mph))
+                .collect()
+        })
+        .map(|(_, mph)| Speed::miles_per_hour(mph))
+}
@@ -1,21 +1,24 @@
 use crate::common::CommonState;
 use crate::edit::apply_map_edits;
-use crate::game::{State, Transition};
-use crate::render::{DrawIntersection, DrawOptions};
+use crate::game::{msg, State, Transition};
+use crate::render::{DrawIntersection, DrawOptions, BIG_ARROW_THICKNESS};
 use crate::ui::{ShowEverything, UI};
 use ezgui::{hotkey, Color, EventCtx, GeomBatch, GfxCtx, Key, Line, ModalMenu, Text};
 use geom::Polygon;
-use map_model::{ControlStopSign, EditCmd, IntersectionID, RoadID};
+use map_model::{
+    ControlStopSign, EditCmd, IntersectionID, Map, RoadID, TurnBan, TurnID, LANE_THICKNESS,
+};
 use std::collections::HashMap;
 
-// TODO For now, individual turns can't be manipulated. Banning turns could be useful, but I'm not
-// sure what to do about the player orphaning a section of the map.
+// Individual turns can be banned, as long as that doesn't orphan any lanes.
 pub struct StopSignEditor {
     menu: ModalMenu,
     id: IntersectionID,
     // (octagon, pole)
     geom: HashMap<RoadID, (Polygon, Polygon)>,
     selected_sign: Option<RoadID>,
+    turns: Vec<(TurnID, Polygon)>,
+    selected_turn: Option<TurnID>,
 }
 
 impl StopSignEditor {
@@ -45,10 +48,22 @@ impl StopSignEditor {
             id,
             geom,
             selected_sign: None,
+            turns: turn_geom(id, &ui.primary.map),
+            selected_turn: None,
         }
     }
 }
 
+fn turn_geom(id: IntersectionID, map: &Map) -> Vec<(TurnID, Polygon)> {
+    map.get_i(id)
+        .turns
+        .iter()
+        .map(|t| map.get_t(*t))
+        .filter(|t| !t.between_sidewalks())
+        .map(|t| (t.id, t.geom.make_polygons(LANE_THICKNESS / 2.0)))
+        .collect()
+}
+
 impl State for StopSignEditor {
     fn event(&mut self, ctx: &mut EventCtx, ui: &mut UI) -> Transition {
         self.menu.event(ctx);
@@ -56,6 +71,7 @@ impl State for StopSignEditor {
 
         if ctx.redo_mouseover() {
             self.selected_sign = None;
+            self.selected_turn = None;
             if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
                 for (r, (octagon, _)) in &self.geom {
                     if octagon.contains_pt(pt) {
@@ -63,6 +79,14 @@ impl State for StopSignEditor {
                         break;
                     }
                 }
+                if self.selected_sign.is_none() {
+                    for (t, poly) in &self.turns {
+                        if poly.contains_pt(pt) {
+                            self.selected_turn = Some(*t);
+                            break;
+                        }
+                    }
+                }
             }
         }
 
@@ -80,6 +104,18 @@ impl State for StopSignEditor {
                 edits.commands.push(EditCmd::ChangeStopSign(sign));
                 apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
             }
+        } else if let Some(t) = self.selected_turn {
+            if ui.per_obj.action(ctx, Key::X, "ban this turn") {
+                let ban = TurnBan::Turn(t);
+                if let Err(err) = ban.check(&ui.primary.map) {
+                    return Transition::Push(msg("Can't ban this turn", vec![err]));
+                }
+                let mut edits = ui.primary.map.get_edits().clone();
+                edits.commands.push(EditCmd::BanTurns(ban));
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+                self.turns = turn_geom(self.id, &ui.primary.map);
+                self.selected_turn = None;
+            }
         } else if self.menu.action("quit") {
             return Transition::Pop;
         } else if self.menu.action("reset to default") {
@@ -126,6 +162,13 @@ impl State for StopSignEditor {
             }
         }
 
+        if let Some(t) = self.selected_turn {
+            batch.push(
+                ui.cs.get("selected stop sign"),
+                map.get_t(t).geom.make_arrow(BIG_ARROW_THICKNESS).unwrap(),
+            );
+        }
+
         batch.draw(g);
 
         self.menu.draw(g);
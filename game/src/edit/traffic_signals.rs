@@ -4,7 +4,9 @@ use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::plain_list_names;
 use crate::managed::{WrappedComposite, WrappedOutcome};
 use crate::options::TrafficSignalStyle;
-use crate::render::{draw_signal_phase, DrawOptions, DrawTurnGroup, BIG_ARROW_THICKNESS};
+use crate::render::{
+    describe_phase_duration, draw_signal_phase, DrawOptions, DrawTurnGroup, BIG_ARROW_THICKNESS,
+};
 use crate::sandbox::{spawn_agents_around, SpeedControls, TimePanel};
 use crate::ui::{ShowEverything, UI};
 use abstutil::Timer;
@@ -14,7 +16,10 @@ use ezgui::{
     VerticalAlignment,
 };
 use geom::{Duration, Polygon};
-use map_model::{ControlTrafficSignal, EditCmd, IntersectionID, Phase, TurnGroupID, TurnPriority};
+use map_model::{
+    ActuatedTiming, ControlTrafficSignal, EditCmd, IntersectionID, Phase, TurnBan, TurnGroupID,
+    TurnPriority,
+};
 use sim::Sim;
 use std::collections::BTreeSet;
 
@@ -112,10 +117,29 @@ impl State for TrafficSignalEditor {
                 }
                 x if x.starts_with("change duration of #") => {
                     let idx = x["change duration of #".len()..].parse::<usize>().unwrap() - 1;
-                    return Transition::Push(change_phase_duration(
-                        idx,
-                        orig_signal.phases[idx].duration,
-                    ));
+                    return Transition::Push(
+                        if let Some(timing) = orig_signal.phases[idx].actuated {
+                            change_actuated_timing(idx, timing)
+                        } else {
+                            change_phase_duration(idx, orig_signal.phases[idx].duration)
+                        },
+                    );
+                }
+                x if x.starts_with("toggle actuated #") => {
+                    let idx = x["toggle actuated #".len()..].parse::<usize>().unwrap() - 1;
+                    let mut new_signal = orig_signal.clone();
+                    let phase = &mut new_signal.phases[idx];
+                    phase.actuated = if phase.actuated.is_some() {
+                        None
+                    } else {
+                        Some(ActuatedTiming::new(phase.duration))
+                    };
+                    self.command_stack.push(orig_signal.clone());
+                    self.redo_stack.clear();
+                    self.top_panel = make_top_panel(true, false, ctx);
+                    change_traffic_signal(new_signal, ui, ctx);
+                    self.change_phase(idx, ui, ctx);
+                    return Transition::Keep;
                 }
                 x if x.starts_with("delete phase #") => {
                     let idx = x["delete phase #".len()..].parse::<usize>().unwrap() - 1;
@@ -230,6 +254,26 @@ impl State for TrafficSignalEditor {
                     return Transition::Keep;
                 }
             }
+
+            if ui.per_obj.action(ctx, Key::X, "ban this movement") {
+                let ban = TurnBan::Group(self.i, id);
+                if let Err(err) = ban.check(&ui.primary.map) {
+                    return Transition::Push(msg("Can't ban this movement", vec![err]));
+                }
+                let mut edits = ui.primary.map.get_edits().clone();
+                edits.commands.push(EditCmd::BanTurns(ban));
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+                // The signal gets reset to the default for the remaining turns, so the undo
+                // history doesn't apply anymore.
+                self.groups = DrawTurnGroup::for_i(self.i, &ui.primary.map);
+                self.group_selected = None;
+                self.command_stack.clear();
+                self.redo_stack.clear();
+                self.top_panel = make_top_panel(false, false, ctx);
+                self.current_phase = 0;
+                self.composite = make_diagram(self.i, 0, ui, ctx);
+                return Transition::Keep;
+            }
         }
 
         match self.top_panel.event(ctx) {
@@ -431,7 +475,7 @@ fn make_diagram(i: IntersectionID, selected: usize, ui: &UI, ctx: &mut EventCtx)
     for (idx, phase) in signal.phases.iter().enumerate() {
         let mut row = vec![
             ManagedWidget::draw_text(ctx, Text::from(Line(format!("#{}", idx + 1)))),
-            ManagedWidget::draw_text(ctx, Text::from(Line(phase.duration.to_string()))),
+            ManagedWidget::draw_text(ctx, Text::from(Line(describe_phase_duration(phase)))),
             WrappedComposite::svg_button(
                 ctx,
                 "assets/tools/edit.svg",
@@ -443,6 +487,15 @@ fn make_diagram(i: IntersectionID, selected: usize, ui: &UI, ctx: &mut EventCtx)
                 },
             ),
         ];
+        row.push(WrappedComposite::text_button(
+            ctx,
+            &format!("toggle actuated #{}", idx + 1),
+            if selected == idx {
+                hotkey(Key::A)
+            } else {
+                None
+            },
+        ));
         if signal.phases.len() > 1 {
             // TODO Trash can icon
             row.push(WrappedComposite::text_button(
@@ -581,6 +634,52 @@ fn change_phase_duration(idx: usize, current_duration: Duration) -> Box<dyn Stat
     }))
 }
 
+fn change_actuated_timing(idx: usize, current: ActuatedTiming) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, _| {
+        let mut wizard = wiz.wrap(ctx);
+        let min_green = wizard.input_usize_prefilled(
+            "What's the shortest this phase should last (seconds)?",
+            format!("{}", current.min_green.inner_seconds() as usize),
+        )?;
+        let extension = wizard.input_something(
+            "Extend the phase while somebody arrives within how many seconds?",
+            Some(format!("{}", current.extension.inner_seconds() as usize)),
+            Box::new(|line| {
+                line.parse::<usize>()
+                    .ok()
+                    .and_then(|n| if n != 0 { Some(n) } else { None })
+            }),
+        )?;
+        let max_green = wizard.input_something(
+            "What's the longest this phase should last (seconds)?",
+            Some(format!("{}", current.max_green.inner_seconds() as usize)),
+            Box::new(move |line| {
+                line.parse::<usize>().ok().and_then(|n| {
+                    if n >= min_green && n != 0 {
+                        Some(n)
+                    } else {
+                        None
+                    }
+                })
+            }),
+        )?;
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            let mut signal = ui.primary.map.get_traffic_signal(editor.i).clone();
+            editor.command_stack.push(signal.clone());
+            editor.redo_stack.clear();
+            editor.top_panel = make_top_panel(true, false, ctx);
+            signal.phases[idx].actuated = Some(ActuatedTiming {
+                min_green: Duration::seconds(min_green as f64),
+                extension: Duration::seconds(extension as f64),
+                max_green: Duration::seconds(max_green as f64),
+            });
+            change_traffic_signal(signal, ui, ctx);
+            editor.change_phase(idx, ui, ctx);
+        })))
+    }))
+}
+
 fn change_offset(current_duration: Duration) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, _| {
         let new_duration = wiz.wrap(ctx).input_usize_prefilled(
@@ -96,6 +96,14 @@ impl GUI for Game {
                 self.states.push(s2);
                 return EventLoopMode::InputOnly;
             }
+            Transition::PopThenReplaceThenPush(s1, s2) => {
+                self.states.pop().unwrap().on_destroy(ctx, &mut self.ui);
+                assert!(!self.states.is_empty());
+                self.states.pop().unwrap().on_destroy(ctx, &mut self.ui);
+                self.states.push(s1);
+                self.states.push(s2);
+                return EventLoopMode::InputOnly;
+            }
         };
         self.ui.per_obj.assert_chosen_used();
         // Let the new state initialize with a fake event. Usually these just return
@@ -183,6 +191,8 @@ pub enum Transition {
     Clear(Box<dyn State>),
     ApplyObjectAction(String),
     PushTwice(Box<dyn State>, Box<dyn State>),
+    // Like PopThenReplace, then push the second state on top
+    PopThenReplaceThenPush(Box<dyn State>, Box<dyn State>),
 }
 
 pub struct WizardState {
@@ -13,8 +13,8 @@ use ezgui::{
 use geom::{Distance, Duration, PolyLine, Time};
 use map_model::{BuildingID, IntersectionID, Map, Neighborhood};
 use sim::{
-    BorderSpawnOverTime, DrivingGoal, OriginDestination, Scenario, SeedParkedCars, SidewalkPOI,
-    SidewalkSpot, SpawnOverTime, SpawnTrip,
+    BorderSpawnOverTime, DrivingGoal, LogitParams, ModeChoice, OriginDestination, Scenario,
+    SeedParkedCars, SidewalkPOI, SidewalkSpot, SpawnOverTime, SpawnTrip,
 };
 use std::collections::BTreeSet;
 
@@ -24,7 +24,7 @@ pub struct ScenarioManager {
     tool_panel: WrappedComposite,
     scenario: Scenario,
 
-    // The usizes are indices into scenario.individ_trips
+    // The usizes are indices into scenario.all_trips()
     trips_from_bldg: MultiMap<BuildingID, usize>,
     trips_to_bldg: MultiMap<BuildingID, usize>,
     trips_from_border: MultiMap<IntersectionID, usize>,
@@ -42,7 +42,7 @@ impl ScenarioManager {
         let mut trips_to_bldg = MultiMap::new();
         let mut trips_from_border = MultiMap::new();
         let mut trips_to_border = MultiMap::new();
-        for (idx, trip) in scenario.individ_trips.iter().enumerate() {
+        for (idx, trip) in scenario.all_trips().into_iter().enumerate() {
             // trips_from_bldg and trips_from_border
             match trip {
                 // TODO CarAppearing might be from a border
@@ -148,7 +148,7 @@ impl State for ScenarioManager {
             txt.add(Line(&self.scenario.scenario_name));
             txt.add(Line(format!(
                 "{} total trips",
-                prettyprint_usize(self.scenario.individ_trips.len())
+                prettyprint_usize(self.scenario.all_trips().len())
             )));
             txt.add(Line(format!(
                 "seed {} parked cars",
@@ -301,7 +301,7 @@ impl State for ScenarioEditor {
                 let mut manager = state.downcast_mut::<ScenarioManager>().unwrap();
                 manager.scenario = scenario;
                 // Don't need to update trips_from_bldg or trips_to_bldg, since edit_scenario
-                // doesn't touch individ_trips.
+                // doesn't touch individ_trips or people.
             }));
         } else if self.wizard.aborted() {
             return Transition::Pop;
@@ -322,9 +322,10 @@ fn edit_scenario(map: &Map, scenario: &mut Scenario, mut wizard: WrappedWizard)
     let spawn = "Spawn agents";
     let spawn_border = "Spawn agents from a border";
     let randomize = "Randomly spawn stuff from/to every neighborhood";
+    let mode_choice = "Change how agents pick a mode";
     match wizard
         .choose_string("What kind of edit?", || {
-            vec![seed_parked, spawn, spawn_border, randomize]
+            vec![seed_parked, spawn, spawn_border, randomize, mode_choice]
         })?
         .as_str()
     {
@@ -398,6 +399,25 @@ fn edit_scenario(map: &Map, scenario: &mut Scenario, mut wizard: WrappedWizard)
                 }
             }
         }
+        x if x == mode_choice => {
+            scenario.mode_choice = wizard
+                .choose(
+                    "How should agents from \"Spawn agents\" pick a mode?",
+                    || {
+                        vec![
+                            Choice::new(
+                                "fixed percentages (set per spawn)",
+                                ModeChoice::FixedPercentages,
+                            ),
+                            Choice::new(
+                                "multinomial logit over estimated time and cost",
+                                ModeChoice::Logit(LogitParams::default()),
+                            ),
+                        ]
+                    },
+                )?
+                .1;
+        }
         _ => unreachable!(),
     };
     Some(())
@@ -462,10 +482,11 @@ fn make_trip_picker(
             .wrap(ctx)
             .choose(&format!("Trips from/to this {}", noun), || {
                 // TODO Panics if there are two duplicate trips (b1124 in montlake)
+                let trips = scenario.all_trips();
                 indices
                     .iter()
                     .map(|idx| {
-                        let trip = &scenario.individ_trips[*idx];
+                        let trip = trips[*idx];
                         Choice::new(
                             describe(trip, home),
                             other_endpt(trip, home, &ui.primary.map),
@@ -614,21 +635,14 @@ fn show_demand(
     ui: &UI,
     ctx: &EventCtx,
 ) -> Drawable {
+    let trips = scenario.all_trips();
     let mut from_ids = Counter::new();
     for idx in from {
-        from_ids.inc(other_endpt(
-            &scenario.individ_trips[*idx],
-            home,
-            &ui.primary.map,
-        ));
+        from_ids.inc(other_endpt(trips[*idx], home, &ui.primary.map));
     }
     let mut to_ids = Counter::new();
     for idx in to {
-        to_ids.inc(other_endpt(
-            &scenario.individ_trips[*idx],
-            home,
-            &ui.primary.map,
-        ));
+        to_ids.inc(other_endpt(trips[*idx], home, &ui.primary.map));
     }
     let from_count = from_ids.consume();
     let mut to_count = to_ids.consume();
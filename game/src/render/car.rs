@@ -170,6 +170,8 @@ fn thick_line_from_angle(
 fn zoomed_color_car(input: &DrawCarInput, cs: &ColorScheme) -> Color {
     if input.id.1 == VehicleType::Bus {
         cs.get_def("bus", Color::rgb(50, 133, 117))
+    } else if input.id.1 == VehicleType::Train {
+        cs.get_def("train", Color::rgb(42, 96, 154))
     } else {
         match input.status {
             CarStatus::Moving => rotating_color_agents(input.id.0),
@@ -137,10 +137,10 @@ impl Renderable for DrawIntersection {
                     .map(|(t, _, _, _)| *t != ctx.sim.time())
                     .unwrap_or(true);
                 if recalc {
-                    let (idx, phase, t) = signal.current_phase_and_remaining_time(ctx.sim.time());
+                    let (idx, t) = ctx.sim.current_phase_and_remaining_time(self.id, ctx.map);
                     let mut batch = GeomBatch::new();
                     draw_signal_phase(
-                        phase,
+                        &signal.phases[idx],
                         self.id,
                         Some(t),
                         &mut batch,
@@ -42,7 +42,15 @@ impl DrawLane {
         timer: &mut Timer,
     ) -> AlmostDrawLane {
         let road = map.get_r(lane.parent);
-        let polygon = lane.lane_center_pts.make_polygons(LANE_THICKNESS);
+        let polygon = lane.lane_center_pts.make_polygons(lane.width);
+        if lane.deleted {
+            return AlmostDrawLane {
+                id: lane.id,
+                polygon,
+                zorder: road.get_zorder(),
+                draw_default: GeomBatch::new(),
+            };
+        }
 
         let mut draw = GeomBatch::new();
         draw.push(
@@ -56,6 +64,7 @@ impl DrawLane {
                 LaneType::Construction => {
                     cs.get_def("construction background", Color::rgb(255, 109, 0))
                 }
+                LaneType::LightRail => cs.get_def("light rail track", Color::rgb(145, 138, 132)),
             },
             polygon.clone(),
         );
@@ -126,14 +135,14 @@ impl DrawLane {
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_right(LANE_THICKNESS / 2.0)
+                            .shift_right(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_left(LANE_THICKNESS / 2.0)
+                            .shift_left(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
@@ -144,6 +153,23 @@ impl DrawLane {
                         polygon.clone(),
                     );
                 }
+                LaneType::LightRail => {
+                    // The two rails
+                    draw.push(
+                        cs.get_def("light rail rails", Color::grey(0.3)),
+                        lane.lane_center_pts
+                            .shift_right(LANE_THICKNESS / 4.0)
+                            .get(timer)
+                            .make_polygons(Distance::meters(0.25)),
+                    );
+                    draw.push(
+                        cs.get("light rail rails"),
+                        lane.lane_center_pts
+                            .shift_left(LANE_THICKNESS / 4.0)
+                            .get(timer)
+                            .make_polygons(Distance::meters(0.25)),
+                    );
+                }
             };
         }
 
@@ -170,9 +196,9 @@ impl Renderable for DrawLane {
     }
 
     fn get_outline(&self, map: &Map) -> Polygon {
-        map.get_l(self.id)
-            .lane_center_pts
-            .to_thick_boundary(LANE_THICKNESS, OUTLINE_THICKNESS)
+        let lane = map.get_l(self.id);
+        lane.lane_center_pts
+            .to_thick_boundary(lane.width, OUTLINE_THICKNESS)
             .unwrap_or_else(|| self.polygon.clone())
     }
 
@@ -64,7 +64,7 @@ impl DrawMap {
         for r in road_refs {
             all_roads.push(
                 osm_rank_to_color(cs, r.get_rank()),
-                r.get_thick_polygon().get(timer),
+                r.get_thick_polygon(map).get(timer),
             );
             if false {
                 all_roads.push(
@@ -76,8 +76,11 @@ impl DrawMap {
         let draw_all_thick_roads = all_roads.upload(ctx);
         timer.stop("generate thick roads");
 
-        let almost_lanes =
-            timer.parallelize("prepare DrawLanes", map.all_lanes().iter().collect(), |l| {
+        // Lanes are looked up by LaneID later, so draw the deleted ones too.
+        let almost_lanes = timer.parallelize(
+            "prepare DrawLanes",
+            map.all_lanes_including_deleted().iter().collect(),
+            |l| {
                 DrawLane::new(
                     l,
                     map,
@@ -87,7 +90,8 @@ impl DrawMap {
                     // least take notes.
                     &mut Timer::throwaway(),
                 )
-            });
+            },
+        );
         timer.start_iter("finalize DrawLanes", almost_lanes.len());
         let mut lanes: Vec<DrawLane> = Vec::new();
         for almost in almost_lanes {
@@ -199,36 +203,12 @@ impl DrawMap {
             map.get_boundary_polygon(),
         )]);
 
-        timer.start("create quadtree");
-        let mut quadtree = QuadTree::default(map.get_bounds().as_bbox());
-        // TODO use iter chain if everything was boxed as a renderable...
-        for obj in &roads {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &lanes {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &intersections {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &buildings {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        for obj in &extra_shapes {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        // Don't put BusStops in the quadtree
-        for obj in &areas {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
-        }
-        timer.stop("create quadtree");
-
         timer.note(format!(
             "static DrawMap consumes {} MB on the GPU",
             abstutil::prettyprint_usize(ctx.prerender.get_total_bytes_uploaded() / 1024 / 1024)
         ));
 
-        DrawMap {
+        let mut draw_map = DrawMap {
             roads,
             lanes,
             intersections,
@@ -248,8 +228,41 @@ impl DrawMap {
                 unzoomed: None,
             }),
 
-            quadtree,
+            quadtree: QuadTree::default(map.get_bounds().as_bbox()),
+        };
+        timer.start("create quadtree");
+        draw_map.recalculate_quadtree(map);
+        timer.stop("create quadtree");
+        draw_map
+    }
+
+    // Map edits that add or remove lanes shift the rest of the road over.
+    pub fn recalculate_quadtree(&mut self, map: &Map) {
+        let mut quadtree = QuadTree::default(map.get_bounds().as_bbox());
+        // TODO use iter chain if everything was boxed as a renderable...
+        for obj in &self.roads {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.lanes {
+            if map.get_l(obj.id).deleted {
+                continue;
+            }
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.intersections {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.buildings {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        for obj in &self.extra_shapes {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
         }
+        // Don't put BusStops in the quadtree
+        for obj in &self.areas {
+            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+        }
+        self.quadtree = quadtree;
     }
 
     // The alt to these is implementing std::ops::Index, but that's way more verbose!
@@ -390,6 +403,10 @@ impl InnerAgentColorScheme {
                     ("car", cs.get_def("unzoomed car", Color::RED.alpha(0.5))),
                     ("bike", cs.get_def("unzoomed bike", Color::GREEN.alpha(0.5))),
                     ("bus", cs.get_def("unzoomed bus", Color::BLUE.alpha(0.5))),
+                    (
+                        "train",
+                        cs.get_def("unzoomed train", Color::PURPLE.alpha(0.5)),
+                    ),
                     (
                         "pedestrian",
                         cs.get_def("unzoomed pedestrian", Color::ORANGE.alpha(0.5)),
@@ -436,6 +453,7 @@ impl InnerAgentColorScheme {
                 Some(VehicleType::Car) => "car".to_string(),
                 Some(VehicleType::Bike) => "bike".to_string(),
                 Some(VehicleType::Bus) => "bus".to_string(),
+                Some(VehicleType::Train) => "train".to_string(),
                 None => "pedestrian".to_string(),
             },
             InnerAgentColorScheme::Delay => classify_delay(agent.metadata.time_spent_blocked),
@@ -23,7 +23,7 @@ pub use crate::render::lane::DrawLane;
 pub use crate::render::map::{AgentCache, AgentColorScheme, DrawMap, InnerAgentColorScheme};
 pub use crate::render::pedestrian::{DrawPedCrowd, DrawPedestrian};
 pub use crate::render::road::DrawRoad;
-pub use crate::render::traffic_signal::draw_signal_phase;
+pub use crate::render::traffic_signal::{describe_phase_duration, draw_signal_phase};
 pub use crate::render::turn::{DrawTurn, DrawTurnGroup};
 use ezgui::{Color, GfxCtx, Prerender};
 use geom::{Distance, PolyLine, Polygon, Pt2D, EPSILON_DIST};
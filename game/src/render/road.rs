@@ -69,14 +69,14 @@ impl Renderable for DrawRoad {
     }
 
     fn get_outline(&self, map: &Map) -> Polygon {
-        let (pl, width) = map.get_r(self.id).get_thick_polyline().unwrap();
+        let (pl, width) = map.get_r(self.id).get_thick_polyline(map).unwrap();
         pl.to_thick_boundary(width, OUTLINE_THICKNESS)
-            .unwrap_or_else(|| map.get_r(self.id).get_thick_polygon().unwrap())
+            .unwrap_or_else(|| map.get_r(self.id).get_thick_polygon(map).unwrap())
     }
 
     fn contains_pt(&self, pt: Pt2D, map: &Map) -> bool {
         map.get_r(self.id)
-            .get_thick_polygon()
+            .get_thick_polygon(map)
             .unwrap()
             .contains_pt(pt)
     }
@@ -152,7 +152,7 @@ pub fn draw_signal_phase(
 
     let radius = Distance::meters(2.0);
     let center = ctx.map.get_i(i).polygon.center();
-    let percent = time_left.unwrap() / phase.duration;
+    let percent = time_left.unwrap() / phase.max_duration();
     // TODO Tune colors.
     batch.push(
         ctx.cs.get_def("traffic signal box", Color::grey(0.5)),
@@ -181,3 +181,13 @@ fn crosswalk_icon(geom: &PolyLine) -> (Pt2D, Angle) {
             .invert_y(),
     )
 }
+
+pub fn describe_phase_duration(phase: &Phase) -> String {
+    match phase.actuated {
+        Some(timing) => format!(
+            "actuated: {} to {}, extend by {}",
+            timing.min_green, timing.max_green, timing.extension
+        ),
+        None => phase.duration.to_string(),
+    }
+}
@@ -106,7 +106,14 @@ impl GameplayMode {
     pub fn allows(&self, edits: &MapEdits) -> bool {
         for cmd in &edits.commands {
             match cmd {
-                EditCmd::ChangeLaneType { .. } | EditCmd::ReverseLane { .. } => {
+                EditCmd::ChangeLaneType { .. }
+                | EditCmd::ReverseLane { .. }
+                | EditCmd::BanTurns(_)
+                | EditCmd::AllowTurns(_)
+                | EditCmd::AddLane { .. }
+                | EditCmd::DeleteLane { .. }
+                | EditCmd::ChangeLaneWidth { .. }
+                | EditCmd::ChangeSpeedLimit { .. } => {
                     if !self.can_edit_lanes() {
                         return false;
                     }
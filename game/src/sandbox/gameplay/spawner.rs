@@ -238,6 +238,7 @@ impl State for AgentSpawner {
                     start,
                     end,
                     constraints,
+                    departure_time: None,
                 }) {
                     self.maybe_goal = Some((new_goal, path.trace(map, start.dist_along(), None)));
                 } else {
@@ -577,6 +578,7 @@ impl State for SpawnManyAgents {
                 start: Position::new(self.from, Distance::ZERO),
                 end: Position::new(new_goal, map.get_l(new_goal).length()),
                 constraints: PathConstraints::Car,
+                departure_time: None,
             }) {
                 self.maybe_goal = Some((new_goal, path.trace(map, Distance::ZERO, None)));
             } else {
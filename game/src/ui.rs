@@ -10,7 +10,7 @@ use abstutil::{MeasureMemory, Timer};
 use ezgui::{Color, EventCtx, GfxCtx, Prerender, TextureType};
 use geom::{Bounds, Circle, Distance, Pt2D};
 use map_model::{Map, Traversable};
-use rand::seq::SliceRandom;
+use rand::seq::{IteratorRandom, SliceRandom};
 use sim::{Analytics, GetDrawAgents, Sim, SimFlags};
 
 pub struct UI {
@@ -37,5 +37,6 @@ pub fn trim_f64(x: f64) -> f64 {
     (x * 10_000.0).round() / 10_000.0
 }
 
+impl abstutil::Cloneable for Distance {}
 impl abstutil::Cloneable for Time {}
 impl abstutil::Cloneable for Statistic {}
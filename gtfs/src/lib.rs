@@ -1,24 +1,39 @@
-use geom::LonLat;
+use geom::{Duration, LonLat, Time};
 use itertools::Itertools;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use transitfeed::GTFSIterator;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Route {
     pub name: String,
+    // Light rail or a streetcar, instead of a bus
+    pub is_train: bool,
     pub stops: Vec<LonLat>,
+    // When does each scheduled run depart from the first stop? Sorted. Empty if the feed doesn't
+    // describe any trips running on a weekday.
+    pub departures: Vec<Time>,
+    // For each run, when should the bus arrive at each of the stops? None where the timetable
+    // doesn't say.
+    pub scheduled_arrivals: Vec<Vec<Option<Time>>>,
 }
 
 pub fn load(dir_path: &str) -> Vec<Route> {
     println!("Loading GTFS from {}", dir_path);
 
     let mut route_id_to_name: HashMap<String, String> = HashMap::new();
+    let mut train_route_ids: HashSet<String> = HashSet::new();
     for rec in GTFSIterator::<_, transitfeed::Route>::from_path(&format!("{}/routes.txt", dir_path))
         .unwrap()
     {
         let rec = rec.unwrap();
         route_id_to_name.insert(rec.route_id.clone(), rec.route_short_name.clone());
+        match rec.route_type {
+            transitfeed::RouteType::LightRail | transitfeed::RouteType::Tram => {
+                train_route_ids.insert(rec.route_id.clone());
+            }
+            _ => {}
+        }
     }
 
     let mut stop_id_to_pt: HashMap<String, LonLat> = HashMap::new();
@@ -29,11 +44,32 @@ pub fn load(dir_path: &str) -> Vec<Route> {
         stop_id_to_pt.insert(rec.stop_id.clone(), LonLat::new(rec.stop_lon, rec.stop_lat));
     }
 
+    // We only simulate one representative weekday, so just keep the services running on Monday.
+    // calendar.txt is optional when a feed only uses calendar_dates.txt; keep everything then.
+    let mut weekday_services: Option<HashSet<String>> = None;
+    if let Ok(iter) =
+        GTFSIterator::<_, transitfeed::Calendar>::from_path(&format!("{}/calendar.txt", dir_path))
+    {
+        let mut services = HashSet::new();
+        for rec in iter {
+            let rec = rec.unwrap();
+            if rec.monday {
+                services.insert(rec.service_id.clone());
+            }
+        }
+        weekday_services = Some(services);
+    }
+
     let mut trip_id_to_route_id_and_direction: HashMap<String, (String, bool)> = HashMap::new();
     for rec in
         GTFSIterator::<_, transitfeed::Trip>::from_path(&format!("{}/trips.txt", dir_path)).unwrap()
     {
         let rec = rec.unwrap();
+        if let Some(ref services) = weekday_services {
+            if !services.contains(&rec.service_id) {
+                continue;
+            }
+        }
         trip_id_to_route_id_and_direction.insert(
             rec.trip_id.clone(),
             (
@@ -43,10 +79,30 @@ pub fn load(dir_path: &str) -> Vec<Route> {
         );
     }
 
-    // Each (directed) route has many trips. Ignore all but the first and assume the list of stops
-    // is the same. Also assume that records with the same trip are contiguous and that
-    // stop_sequence is monotonic.
-    let mut directed_routes: HashMap<(String, bool), Vec<LonLat>> = HashMap::new();
+    // Frequency-based trips describe a template run, repeated every headway between the start and
+    // end time. frequencies.txt is optional.
+    let mut trip_id_to_frequencies: HashMap<String, Vec<(Time, Time, Duration)>> = HashMap::new();
+    if let Ok(iter) = GTFSIterator::<_, transitfeed::Frequency>::from_path(&format!(
+        "{}/frequencies.txt",
+        dir_path
+    )) {
+        for rec in iter {
+            let rec = rec.unwrap();
+            trip_id_to_frequencies
+                .entry(rec.trip_id.clone())
+                .or_insert_with(Vec::new)
+                .push((
+                    to_time(&rec.start_time),
+                    to_time(&rec.end_time),
+                    Duration::seconds(rec.headway_secs as f64),
+                ));
+        }
+    }
+
+    // Each (directed) route has many trips. Use the first to define the list of stops. Also
+    // assume that records with the same trip are contiguous and that stop_sequence is monotonic.
+    let mut directed_routes: HashMap<(String, bool), Vec<String>> = HashMap::new();
+    let mut directed_runs: HashMap<(String, bool), Vec<Run>> = HashMap::new();
     for (key, group) in
         GTFSIterator::<_, transitfeed::StopTime>::from_path(&format!("{}/stop_times.txt", dir_path))
             .unwrap()
@@ -54,14 +110,47 @@ pub fn load(dir_path: &str) -> Vec<Route> {
             .group_by(|rec| rec.trip_id.clone())
             .into_iter()
     {
-        let (route_id, forwards) = trip_id_to_route_id_and_direction[&key].clone();
-        if directed_routes.contains_key(&(route_id.clone(), forwards)) {
+        let (route_id, forwards) = if let Some(pair) = trip_id_to_route_id_and_direction.get(&key) {
+            pair.clone()
+        } else {
+            // Not running on a weekday
             continue;
+        };
+        let stop_times: Vec<transitfeed::StopTime> = group.collect();
+        let stop_ids: Vec<String> = stop_times.iter().map(|rec| rec.stop_id.clone()).collect();
+
+        let directed_key = (route_id, forwards);
+        if !directed_routes.contains_key(&directed_key) {
+            directed_routes.insert(directed_key.clone(), stop_ids.clone());
+        }
+        // Trips skipping some stops (or serving extra ones) can't be lined up with the others, so
+        // only keep when they depart.
+        let first_departure = to_time(&stop_times[0].departure_time);
+        let template = Run {
+            departure: first_departure,
+            arrivals: if directed_routes[&directed_key] == stop_ids {
+                stop_times
+                    .iter()
+                    .map(|rec| Some(to_time(&rec.arrival_time)))
+                    .collect()
+            } else {
+                vec![None; directed_routes[&directed_key].len()]
+            },
+        };
+
+        let runs = directed_runs.entry(directed_key).or_insert_with(Vec::new);
+        if let Some(frequencies) = trip_id_to_frequencies.get(&key) {
+            // The times in stop_times.txt are just relative to the first departure.
+            for (start, end, headway) in frequencies {
+                let mut t = *start;
+                while t < *end && *headway > Duration::ZERO {
+                    runs.push(template.shift(t));
+                    t = t + *headway;
+                }
+            }
+        } else {
+            runs.push(template);
         }
-        directed_routes.insert(
-            (route_id, forwards),
-            group.map(|rec| stop_id_to_pt[&rec.stop_id]).collect(),
-        );
     }
 
     // Group together the pairs of directed routes
@@ -71,19 +160,94 @@ pub fn load(dir_path: &str) -> Vec<Route> {
         .collect();
     let mut results = Vec::new();
     for route_id in route_ids {
-        let mut stops = directed_routes
+        let mut forwards_runs = directed_runs
             .remove(&(route_id.clone(), true))
             .unwrap_or_else(Vec::new);
-        if let Some(more_stops) = directed_routes.remove(&(route_id.clone(), false)) {
-            stops.extend(more_stops);
-        }
+        let mut backwards_runs = directed_runs
+            .remove(&(route_id.clone(), false))
+            .unwrap_or_else(Vec::new);
+        forwards_runs.sort_by_key(|r| r.departure);
+        backwards_runs.sort_by_key(|r| r.departure);
+
+        let forwards_stops = directed_routes
+            .remove(&(route_id.clone(), true))
+            .unwrap_or_else(Vec::new);
+        let backwards_stops = directed_routes
+            .remove(&(route_id.clone(), false))
+            .unwrap_or_else(Vec::new);
+        let mut stops: Vec<LonLat> = forwards_stops.iter().map(|id| stop_id_to_pt[id]).collect();
+        stops.extend(backwards_stops.iter().map(|id| stop_id_to_pt[id]));
         assert!(!stops.is_empty());
+
+        // One bus serves the forwards stops, then the backwards stops. So dispatch a run whenever
+        // a trip departs from the very first stop, and after finishing, pair it up with the next
+        // unused trip in the opposite direction.
+        let mut departures = Vec::new();
+        let mut scheduled_arrivals = Vec::new();
+        if forwards_stops.is_empty() {
+            for run in backwards_runs {
+                departures.push(run.departure);
+                scheduled_arrivals.push(run.arrivals);
+            }
+        } else {
+            let mut backwards_runs: VecDeque<Run> = backwards_runs.into_iter().collect();
+            for run in forwards_runs {
+                departures.push(run.departure);
+                let finished = run.arrivals.iter().rev().find_map(|t| *t);
+                let mut arrivals = run.arrivals;
+                if !backwards_stops.is_empty() {
+                    let finished = finished.unwrap_or(run.departure);
+                    while backwards_runs
+                        .front()
+                        .map(|r| r.departure < finished)
+                        .unwrap_or(false)
+                    {
+                        backwards_runs.pop_front();
+                    }
+                    if let Some(next) = backwards_runs.pop_front() {
+                        arrivals.extend(next.arrivals);
+                    } else {
+                        arrivals.extend(backwards_stops.iter().map(|_| None));
+                    }
+                }
+                scheduled_arrivals.push(arrivals);
+            }
+        }
+
         results.push(Route {
             name: route_id_to_name[&route_id].to_string(),
+            is_train: train_route_ids.contains(&route_id),
             stops,
+            departures,
+            scheduled_arrivals,
         });
     }
     assert!(directed_routes.is_empty());
 
     results
 }
+
+struct Run {
+    departure: Time,
+    // Lined up with the stops of the directed route
+    arrivals: Vec<Option<Time>>,
+}
+
+impl Run {
+    // Copy this run, but departing at a different time.
+    fn shift(&self, departure: Time) -> Run {
+        Run {
+            departure,
+            arrivals: self
+                .arrivals
+                .iter()
+                .map(|t| t.map(|t| departure + (t - self.departure)))
+                .collect(),
+        }
+    }
+}
+
+// GTFS times can exceed 24 hours, for trips running past midnight of the service day.
+fn to_time(t: &transitfeed::TimeOfDay) -> Time {
+    Time::START_OF_DAY + Duration::seconds(f64::from(t.hour * 3600 + t.minute * 60 + t.second))
+}
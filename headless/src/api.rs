@@ -0,0 +1,368 @@
+// A tiny HTTP server to control the simulation from other tools. Requests and responses are JSON.
+// Times and durations are in seconds.
+//
+// POST /sim/load            {"map": "montlake", "scenario": "weekday" or null,
+//                            "num_agents": 1000 or null, "rng_seed": 42 or null}
+// POST /map/edits           {"name": "some edits"}, then restarts the scenario
+// POST /sim/step            {"seconds": 60.0}
+// POST /sim/savestate       returns {"path": ...}
+// POST /sim/load-savestate  {"path": ...}, which must match the current map and edits
+// GET  /sim/status          returns {"time": ..., "is_done": ...}
+// GET  /sim/agents          returns the position of every agent
+// GET  /sim/trips           returns (finish time, trip, mode, duration) of every finished trip
+// GET  /sim/trip/<id>       returns what's happening with one trip
+// GET  /sim/analytics       returns everything Analytics has recorded so far. Maps keyed by
+//                           IDs become lists of pairs.
+
+use abstutil::Timer;
+use geom::{Duration, LonLat, Pt2D, Time};
+use map_model::{
+    BusRouteID, BusStopID, IntersectionID, LaneID, Map, MapEdits, PathRequest, RoadID, Traversable,
+    TurnGroupID,
+};
+use serde_derive::{Deserialize, Serialize};
+use sim::{
+    AgentID, Analytics, CarID, Emissions, Scenario, Sim, SimFlags, TripID, TripMode, TripResult,
+};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+pub struct Session {
+    map: Map,
+    sim: Sim,
+    // Restarted after applying edits
+    scenario: Scenario,
+    flags: SimFlags,
+}
+
+#[derive(Deserialize)]
+struct LoadRequest {
+    map: String,
+    scenario: Option<String>,
+    num_agents: Option<usize>,
+    rng_seed: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct EditsRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct StepRequest {
+    seconds: f64,
+}
+
+#[derive(Deserialize)]
+struct SavestateRequest {
+    path: String,
+}
+
+#[derive(Serialize)]
+struct Status {
+    time: Time,
+    is_done: bool,
+}
+
+#[derive(Serialize)]
+struct AgentPosition {
+    id: AgentID,
+    trip: Option<TripID>,
+    pos: Pt2D,
+    gps: LonLat,
+}
+
+// JSON object keys must be strings, so Analytics can't be serialized directly.
+#[derive(Serialize)]
+struct AnalyticsResponse<'a> {
+    bus_arrivals: &'a Vec<(Time, CarID, BusRouteID, BusStopID)>,
+    bus_schedule: &'a Vec<(Time, CarID, BusRouteID, usize, BusStopID, Time)>,
+    bus_passengers_waiting: &'a Vec<(Time, BusStopID, BusRouteID)>,
+    bus_boardings_denied: &'a Vec<(Time, BusStopID, BusRouteID)>,
+    finished_trips: &'a Vec<(Time, TripID, Option<TripMode>, Duration)>,
+    trip_log: &'a Vec<(Time, TripID, Option<PathRequest>, String)>,
+    intersection_delays: Vec<(IntersectionID, &'a Vec<(Time, Duration)>)>,
+    emissions: &'a Vec<(Time, TripID, Traversable, Emissions)>,
+    lane_travel_times: &'a Vec<(Time, LaneID, Duration)>,
+    reroutes: &'a Vec<(Time, TripID)>,
+    turn_group_demand: Vec<(TurnGroupID, usize)>,
+    // How many agents have entered each road and intersection
+    throughput_per_road: Vec<(RoadID, usize)>,
+    throughput_per_intersection: Vec<(IntersectionID, usize)>,
+    // (time, mode, road or intersection) for each of those
+    raw_throughput_per_road: &'a Vec<(Time, TripMode, RoadID)>,
+    raw_throughput_per_intersection: &'a Vec<(Time, TripMode, IntersectionID)>,
+}
+
+impl<'a> AnalyticsResponse<'a> {
+    fn new(a: &'a Analytics) -> AnalyticsResponse<'a> {
+        AnalyticsResponse {
+            bus_arrivals: &a.bus_arrivals,
+            bus_schedule: &a.bus_schedule,
+            bus_passengers_waiting: &a.bus_passengers_waiting,
+            bus_boardings_denied: &a.bus_boardings_denied,
+            finished_trips: &a.finished_trips,
+            trip_log: &a.trip_log,
+            intersection_delays: a
+                .intersection_delays
+                .iter()
+                .map(|(i, delays)| (*i, delays))
+                .collect(),
+            emissions: &a.emissions,
+            lane_travel_times: &a.lane_travel_times,
+            reroutes: &a.reroutes,
+            turn_group_demand: a
+                .thruput_stats
+                .demand
+                .iter()
+                .map(|(id, cnt)| (*id, *cnt))
+                .collect(),
+            throughput_per_road: a
+                .thruput_stats
+                .count_per_road
+                .clone()
+                .consume()
+                .into_iter()
+                .collect(),
+            throughput_per_intersection: a
+                .thruput_stats
+                .count_per_intersection
+                .clone()
+                .consume()
+                .into_iter()
+                .collect(),
+            raw_throughput_per_road: &a.thruput_stats.raw_per_road,
+            raw_throughput_per_intersection: &a.thruput_stats.raw_per_intersection,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TripStatus {
+    // "ongoing", "mode_change", "done", or "doesnt_exist"
+    status: String,
+    // Only while the trip is ongoing
+    agent: Option<AgentID>,
+}
+
+pub fn serve(port: u16, flags: SimFlags) {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .unwrap_or_else(|err| panic!("Can't listen on port {}: {}", port, err));
+    println!("Listening for commands on http://127.0.0.1:{}", port);
+
+    let mut session: Option<Session> = None;
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = handle_connection(stream, &mut session, &flags) {
+                    println!("WARNING: Couldn't handle request: {}", err);
+                }
+            }
+            Err(err) => {
+                println!("WARNING: Bad connection: {}", err);
+            }
+        }
+    }
+}
+
+// Only errors talking to the client bubble up here.
+fn handle_connection(
+    stream: TcpStream,
+    session: &mut Option<Session>,
+    flags: &SimFlags,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    if parts.len() < 2 {
+        return respond(stream, Err(format!("Bad request line {}", request_line)));
+    }
+    let method = parts[0].to_string();
+    let path = parts[1].to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        let lower = line.to_lowercase();
+        if lower.starts_with("content-length:") {
+            content_length = lower["content-length:".len()..].trim().parse().unwrap_or(0);
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    let result = handle_request(&method, &path, &body, session, flags);
+    respond(stream, result)
+}
+
+fn respond(mut stream: TcpStream, result: Result<String, String>) -> std::io::Result<()> {
+    let (status, body) = match result {
+        Ok(json) => ("200 OK", json),
+        Err(err) => (
+            "400 Bad Request",
+            serde_json::json!({ "error": err }).to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: \
+         close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// Returns JSON on success
+pub fn handle_request(
+    method: &str,
+    path: &str,
+    body: &str,
+    session: &mut Option<Session>,
+    flags: &SimFlags,
+) -> Result<String, String> {
+    if method == "POST" && path == "/sim/load" {
+        let req: LoadRequest = parse(body)?;
+        *session = Some(load(req, flags)?);
+        return to_json(&status(session.as_ref().unwrap()));
+    }
+
+    let session = session
+        .as_mut()
+        .ok_or_else(|| "Nothing loaded yet; POST /sim/load first".to_string())?;
+    match (method, path) {
+        ("POST", "/map/edits") => {
+            let req: EditsRequest = parse(body)?;
+            let mut timer = Timer::new(format!("apply edits {}", req.name));
+            let (edits, _) = MapEdits::load(&session.map, &req.name, &mut timer)?;
+            session.map.apply_edits(edits, &mut timer);
+            session.map.mark_edits_fresh();
+            session.map.recalculate_pathfinding_after_edits(&mut timer);
+            restart(session, &mut timer);
+            to_json(&status(session))
+        }
+        ("POST", "/sim/step") => {
+            let req: StepRequest = parse(body)?;
+            if req.seconds <= 0.0 {
+                return Err(format!("Can't step by {} seconds", req.seconds));
+            }
+            session
+                .sim
+                .step(&session.map, Duration::seconds(req.seconds));
+            to_json(&status(session))
+        }
+        ("POST", "/sim/savestate") => {
+            let path = session.sim.save();
+            to_json(&serde_json::json!({ "path": path }))
+        }
+        ("POST", "/sim/load-savestate") => {
+            let req: SavestateRequest = parse(body)?;
+            let mut timer = Timer::new(format!("load savestate {}", req.path));
+            session.sim = Sim::load_savestate(req.path.clone(), &session.map, &mut timer)
+                .map_err(|err| format!("Can't load {}: {}", req.path, err))?;
+            to_json(&status(session))
+        }
+        ("GET", "/sim/status") => to_json(&status(session)),
+        ("GET", "/sim/agents") => {
+            let gps_bounds = session.map.get_gps_bounds();
+            let agents: Vec<AgentPosition> = session
+                .sim
+                .active_agents()
+                .into_iter()
+                .filter_map(|id| {
+                    let pos = session.sim.canonical_pt_for_agent(id, &session.map)?;
+                    Some(AgentPosition {
+                        id,
+                        trip: session.sim.agent_to_trip(id),
+                        pos,
+                        gps: pos.forcibly_to_gps(gps_bounds),
+                    })
+                })
+                .collect();
+            to_json(&agents)
+        }
+        ("GET", "/sim/trips") => to_json(&session.sim.get_analytics().finished_trips),
+        ("GET", "/sim/analytics") => to_json(&AnalyticsResponse::new(session.sim.get_analytics())),
+        ("GET", _) if path.starts_with("/sim/trip/") => {
+            let id = path["/sim/trip/".len()..]
+                .parse::<usize>()
+                .map_err(|_| format!("Bad trip ID in {}", path))?;
+            let (status, agent) = match session.sim.trip_to_agent(TripID(id)) {
+                TripResult::Ok(a) => ("ongoing", Some(a)),
+                TripResult::ModeChange => ("mode_change", None),
+                TripResult::TripDone => ("done", None),
+                TripResult::TripDoesntExist => ("doesnt_exist", None),
+            };
+            to_json(&TripStatus {
+                status: status.to_string(),
+                agent,
+            })
+        }
+        _ => Err(format!("Unknown request {} {}", method, path)),
+    }
+}
+
+fn load(req: LoadRequest, flags: &SimFlags) -> Result<Session, String> {
+    let mut timer = Timer::new(format!("load {}", req.map));
+    let mut flags = flags.clone();
+    if req.rng_seed.is_some() {
+        flags.rng_seed = req.rng_seed;
+    }
+
+    let map_path = abstutil::path_map(&req.map);
+    if !std::path::Path::new(&map_path).exists() {
+        return Err(format!("{} doesn't exist", map_path));
+    }
+    let map = Map::new(map_path, false, &mut timer);
+    let scenario = if let Some(ref name) = req.scenario {
+        abstutil::maybe_read_binary(abstutil::path_scenario(&req.map, name), &mut timer)
+            .map_err(|err| format!("Can't load scenario {}: {}", name, err))?
+    } else if let Some(n) = req.num_agents {
+        Scenario::scaled_run(&map, n)
+    } else {
+        Scenario::small_run(&map)
+    };
+
+    let sim = start_scenario(&map, &scenario, &flags, &mut timer);
+    Ok(Session {
+        map,
+        sim,
+        scenario,
+        flags,
+    })
+}
+
+fn restart(session: &mut Session, timer: &mut Timer) {
+    session.sim = start_scenario(&session.map, &session.scenario, &session.flags, timer);
+}
+
+fn start_scenario(map: &Map, scenario: &Scenario, flags: &SimFlags, timer: &mut Timer) -> Sim {
+    let mut sim = Sim::new(map, flags.opts.clone(), timer);
+    let mut rng = flags.make_rng();
+    scenario.instantiate(&mut sim, map, &mut rng, timer);
+    sim
+}
+
+fn status(session: &Session) -> Status {
+    Status {
+        time: session.sim.time(),
+        is_done: session.sim.is_done(),
+    }
+}
+
+fn parse<'a, T: serde::Deserialize<'a>>(body: &'a str) -> Result<T, String> {
+    serde_json::from_str(body).map_err(|err| format!("Bad request body: {}", err))
+}
+
+fn to_json<T: serde::Serialize>(obj: &T) -> Result<String, String> {
+    serde_json::to_string(obj).map_err(|err| err.to_string())
+}
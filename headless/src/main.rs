@@ -1,6 +1,10 @@
 use abstutil::{CmdArgs, Timer};
 use geom::Time;
-use sim::{GetDrawAgents, Scenario, SimFlags};
+use map_model::IntersectionID;
+use sim::{
+    calibrate_scenario, optimize_signals, CalibrationOptions, GetDrawAgents, ObservedCount,
+    Scenario, SignalOptimizerOptions, SimFlags,
+};
 
 fn main() {
     let mut args = CmdArgs::new();
@@ -10,10 +14,108 @@ fn main() {
     let enable_profiler = args.enabled("--enable_profiler");
     // Every 0.1s, pretend to draw everything to make sure there are no bugs.
     let paranoia = args.enabled("--paranoia");
+    // Instead of running anything, wait for another tool to drive the simulation over HTTP.
+    let api_port = args.optional_parse("--port", |s| s.parse::<u16>());
+    // After the run, save the observed travel times.
+    let save_travel_times = args.optional("--save_travel_times");
+    // Instead of running anything, search for better timing at these traffic signals
+    // (comma-separated IDs) and save the result as map edits.
+    let optimize = args.optional_parse("--optimize_signals", |s| {
+        s.split(',')
+            .map(|x| x.parse::<usize>().map(IntersectionID))
+            .collect::<Result<Vec<_>, _>>()
+    });
+    let optimizer_rounds = args
+        .optional_parse("--optimizer_rounds", |s| s.parse::<usize>())
+        .unwrap_or(10);
+    let optimizer_batch = args
+        .optional_parse("--optimizer_batch", |s| s.parse::<usize>())
+        .unwrap_or(4);
+    let optimizer_end = args
+        .optional_parse("--optimizer_end", Time::parse)
+        .unwrap_or(Time::START_OF_DAY + geom::Duration::hours(1));
+    let optimized_edits_name = args
+        .optional("--optimized_edits_name")
+        .unwrap_or_else(|| "optimized_signals".to_string());
+    // Instead of running anything, scale the scenario's demand to match traffic counts from this
+    // CSV file and save the result as a new scenario.
+    let calibrate = args.optional("--calibrate");
+    let calibration_rounds = args
+        .optional_parse("--calibration_rounds", |s| s.parse::<usize>())
+        .unwrap_or(5);
+    let calibrated_name = args.optional("--calibrated_name");
     args.done();
 
+    if let Some(port) = api_port {
+        headless::api::serve(port, sim_flags);
+        return;
+    }
+
     let mut timer = Timer::new("setup headless");
-    let (map, mut sim, mut rng) = sim_flags.load(&mut timer);
+    let (mut map, mut sim, mut rng) = sim_flags.load(&mut timer);
+
+    if let Some(intersections) = optimize {
+        if !sim_flags.load.starts_with("../data/system/scenarios/") {
+            panic!("--optimize_signals needs a scenario to simulate");
+        }
+        let scenario: Scenario = abstutil::read_binary(sim_flags.load.clone(), &mut timer);
+        let mut edits = match optimize_signals(
+            &map,
+            &scenario,
+            intersections,
+            SignalOptimizerOptions {
+                end_time: optimizer_end,
+                rounds: optimizer_rounds,
+                batch_size: optimizer_batch,
+                rng_seed: sim_flags.rng_seed.unwrap_or(42),
+                sim_options: sim_flags.opts.clone(),
+            },
+            &mut timer,
+        ) {
+            Ok(edits) => edits,
+            Err(err) => panic!("{}", err),
+        };
+        edits.edits_name = optimized_edits_name;
+        edits.dirty = true;
+        map.apply_edits(edits, &mut timer);
+        map.save_edits();
+        timer.done();
+        println!(
+            "Saved optimized signal timing to {}",
+            abstutil::path_edits(map.get_name(), &map.get_edits().edits_name)
+        );
+        return;
+    }
+
+    if let Some(path) = calibrate {
+        if !sim_flags.load.starts_with("../data/system/scenarios/") {
+            panic!("--calibrate needs a scenario to simulate");
+        }
+        let counts = match ObservedCount::load(&path) {
+            Ok(counts) => counts,
+            Err(err) => panic!("{}", err),
+        };
+        let scenario: Scenario = abstutil::read_binary(sim_flags.load.clone(), &mut timer);
+        let mut calibrated = calibrate_scenario(
+            &map,
+            &scenario,
+            &counts,
+            CalibrationOptions {
+                rounds: calibration_rounds,
+                rng_seed: sim_flags.rng_seed.unwrap_or(42),
+            },
+            &mut timer,
+        );
+        calibrated.scenario_name =
+            calibrated_name.unwrap_or_else(|| format!("{}_calibrated", scenario.scenario_name));
+        calibrated.save();
+        timer.done();
+        println!(
+            "Saved calibrated scenario to {}",
+            abstutil::path_scenario(map.get_name(), &calibrated.scenario_name)
+        );
+        return;
+    }
 
     // TODO not the ideal way to distinguish what thing we loaded
     if sim_flags.load.starts_with(&abstutil::path_all_raw_maps())
@@ -61,6 +163,9 @@ fn main() {
     );
     timer.done();
     println!("Done at {}", sim.time());
+    if let Some(path) = save_travel_times {
+        abstutil::write_binary(path, &sim.get_analytics().travel_times());
+    }
     if enable_profiler && save_at.is_none() {
         #[cfg(feature = "profiler")]
         {
@@ -245,6 +245,7 @@ impl Model {
             RawIntersection {
                 point,
                 intersection_type: IntersectionType::StopSign,
+                elevation: None,
             },
         );
         self.intersection_added(id, prerender);
@@ -615,6 +616,7 @@ impl Model {
             LaneType::Biking => Color::rgb(15, 125, 75),
             LaneType::SharedLeftTurn => Color::YELLOW,
             LaneType::Construction => Color::rgb(255, 109, 0),
+            LaneType::LightRail => Color::rgb(145, 138, 132),
         };
         if unset {
             match color {
@@ -1,4 +1,5 @@
-use crate::{LaneID, Position};
+use crate::{LaneID, PathConstraints, Position};
+use geom::Time;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 
@@ -32,6 +33,9 @@ pub struct BusStop {
     // anywhere.
     pub driving_pos: Position,
     pub sidewalk_pos: Position,
+    // A light rail station. The driving_pos is on the tracks, which might not be part of the same
+    // road as the sidewalk.
+    pub is_train_stop: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,4 +43,31 @@ pub struct BusRoute {
     pub id: BusRouteID,
     pub name: String,
     pub stops: Vec<BusStopID>,
+    // Either Bus or Train
+    pub route_type: PathConstraints,
+    // When a bus should start each run from the first stop, from the GTFS timetable. If this is
+    // empty, buses just loop around the route forever.
+    // TODO If the original first stop was outside the map, these times are a bit early.
+    pub departures: Vec<Time>,
+    // For each run in departures, when the bus should arrive at each stop. None where the
+    // timetable doesn't say.
+    pub scheduled_arrivals: Vec<Vec<Option<Time>>>,
+    // How many passengers each vehicle serving this route fits. Once the seats fill up,
+    // passengers stand, until nobody else fits.
+    pub seated_capacity: usize,
+    pub standing_capacity: usize,
+}
+
+impl BusRoute {
+    // Can somebody ride from one stop to another? Buses on a timetable only run once from the
+    // first stop to the last, so they can't wrap around.
+    pub fn can_ride(&self, stop1: BusStopID, stop2: BusStopID) -> bool {
+        match (
+            self.stops.iter().position(|s| *s == stop1),
+            self.stops.iter().position(|s| *s == stop2),
+        ) {
+            (Some(idx1), Some(idx2)) => idx1 != idx2 && (self.departures.is_empty() || idx1 < idx2),
+            _ => false,
+        }
+    }
 }
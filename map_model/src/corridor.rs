@@ -0,0 +1,263 @@
+use crate::{
+    ControlTrafficSignal, IntersectionID, Map, Path, PathConstraints, PathRequest, PathStep,
+    Position, RoadID,
+};
+use geom::{Distance, Duration};
+
+// A sequence of traffic signals along some route, to coordinate into a green wave.
+pub struct Corridor {
+    pub signals: Vec<IntersectionID>,
+    // From the first signal to each signal
+    pub dist_along: Vec<Distance>,
+    // How long it takes to drive from the first signal to each signal at the speed limit
+    pub time_along: Vec<Duration>,
+    // The roads between signals[k] and signals[k + 1], in the order driven
+    pub roads: Vec<Vec<RoadID>>,
+}
+
+impl Corridor {
+    pub fn new(map: &Map, signals: Vec<IntersectionID>) -> Result<Corridor, String> {
+        if signals.len() < 2 {
+            return Err("A corridor needs at least two traffic signals".to_string());
+        }
+        for i in &signals {
+            if map.maybe_get_traffic_signal(*i).is_none() {
+                return Err(format!("{} isn't a traffic signal", i));
+            }
+        }
+
+        // Signals don't have to be adjacent; find the shortest route a car could actually drive
+        // between each pair, respecting one-way roads and turn restrictions.
+        let mut dist_along = vec![Distance::ZERO];
+        let mut time_along = vec![Duration::ZERO];
+        let mut roads = Vec::new();
+        for pair in signals.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(format!("{} appears twice in a row", pair[0]));
+            }
+            let mut best: Option<Path> = None;
+            for src in &map.get_i(pair[0]).outgoing_lanes {
+                for dst in &map.get_i(pair[1]).incoming_lanes {
+                    if !map.get_l(*src).is_driving() || !map.get_l(*dst).is_driving() {
+                        continue;
+                    }
+                    if let Some(path) = map.pathfind(PathRequest {
+                        start: Position::new(*src, Distance::ZERO),
+                        end: Position::new(*dst, map.get_l(*dst).length()),
+                        constraints: PathConstraints::Car,
+                        departure_time: None,
+                    }) {
+                        if best
+                            .as_ref()
+                            .map(|p| path.total_length() < p.total_length())
+                            .unwrap_or(true)
+                        {
+                            best = Some(path);
+                        }
+                    }
+                }
+            }
+            let path = best.ok_or_else(|| format!("No route from {} to {}", pair[0], pair[1]))?;
+
+            let mut dist = *dist_along.last().unwrap();
+            let mut time = *time_along.last().unwrap();
+            let mut between = Vec::new();
+            for step in path.get_steps() {
+                if let PathStep::Lane(l) = step {
+                    let r = map.get_parent(*l);
+                    dist += r.center_pts.length();
+                    time += r.center_pts.length() / r.get_speed_limit();
+                    between.push(r.id);
+                }
+            }
+            dist_along.push(dist);
+            time_along.push(time);
+            roads.push(between);
+        }
+
+        Ok(Corridor {
+            signals,
+            dist_along,
+            time_along,
+            roads,
+        })
+    }
+
+    // The phase at signals[k] that lets traffic through along the corridor. The first and last
+    // signals just need some movement onto or off of the corridor.
+    pub fn through_phase(
+        &self,
+        signal: &ControlTrafficSignal,
+        k: usize,
+        forwards: bool,
+    ) -> Option<usize> {
+        let before = if k > 0 {
+            Some(*self.roads[k - 1].last().unwrap())
+        } else {
+            None
+        };
+        let after = if k < self.roads.len() {
+            Some(self.roads[k][0])
+        } else {
+            None
+        };
+        let (from, to) = if forwards {
+            (before, after)
+        } else {
+            (after, before)
+        };
+        signal.phases.iter().position(|p| {
+            p.protected_groups.iter().any(|g| {
+                g.crosswalk.is_none()
+                    && from.map(|r| g.from == r).unwrap_or(true)
+                    && to.map(|r| g.to == r).unwrap_or(true)
+            })
+        })
+    }
+
+    // When the corridor movement at signals[k] is green, from midnight until some time later.
+    pub fn green_times(
+        &self,
+        signal: &ControlTrafficSignal,
+        k: usize,
+        forwards: bool,
+        until: Duration,
+    ) -> Vec<(Duration, Duration)> {
+        let idx = match self.through_phase(signal, k, forwards) {
+            Some(idx) => idx,
+            None => {
+                return Vec::new();
+            }
+        };
+        let cycle = signal.cycle_length().inner_seconds();
+        let length = signal.phases[idx].duration.inner_seconds();
+        let until = until.inner_seconds();
+
+        let mut results = Vec::new();
+        // Start a cycle early, in case the phase wraps around midnight.
+        let mut start = modulo(
+            phase_start(signal, idx) - signal.offset.inner_seconds(),
+            cycle,
+        ) - cycle;
+        while start < until {
+            let end = (start + length).min(until);
+            if end > 0.0 {
+                results.push((Duration::seconds(start.max(0.0)), Duration::seconds(end)));
+            }
+            start += cycle;
+        }
+        results
+    }
+
+    // Give every signal the same cycle length, then pick offsets so that somebody driving at the
+    // speed limit along the corridor reaches each signal right as it turns green. If
+    // bidirectional, compromise between both directions. Returns the changed signals; the caller
+    // turns them into map edits.
+    pub fn green_wave(
+        &self,
+        map: &Map,
+        bidirectional: bool,
+    ) -> Result<Vec<ControlTrafficSignal>, String> {
+        let mut signals: Vec<ControlTrafficSignal> = self
+            .signals
+            .iter()
+            .map(|i| map.get_traffic_signal(*i).clone())
+            .collect();
+        for signal in &signals {
+            if signal.is_actuated() {
+                return Err(format!(
+                    "{} has actuated phases, so its offset doesn't matter",
+                    signal.id
+                ));
+            }
+        }
+
+        // Stretch every signal to the longest cycle, keeping the proportion of each phase.
+        let cycle = signals.iter().map(|s| s.cycle_length()).max().unwrap();
+        for signal in signals.iter_mut() {
+            let factor = cycle / signal.cycle_length();
+            for phase in signal.phases.iter_mut() {
+                phase.duration = phase.duration * factor;
+            }
+        }
+        let cycle = cycle.inner_seconds();
+
+        // The offset at each signal that'd be perfect for one direction. A platoon leaves the first
+        // signal (or the last one, going backwards) at time 0.
+        let total_time = self.time_along.last().unwrap().inner_seconds();
+        let mut forwards = Vec::new();
+        let mut backwards = Vec::new();
+        for (k, signal) in signals.iter().enumerate() {
+            let arrival = self.time_along[k].inner_seconds();
+            let phase = self
+                .through_phase(signal, k, true)
+                .ok_or_else(|| no_phase(signal, true))?;
+            forwards.push(modulo(phase_start(signal, phase) - arrival, cycle));
+
+            if bidirectional {
+                let phase = self
+                    .through_phase(signal, k, false)
+                    .ok_or_else(|| no_phase(signal, false))?;
+                backwards.push(modulo(
+                    phase_start(signal, phase) - (total_time - arrival),
+                    cycle,
+                ));
+            }
+        }
+
+        let offsets = if bidirectional {
+            // The backwards platoon can leave whenever, so try shifting it to best line up with
+            // the forwards direction, then split the difference at each signal.
+            let mut best: Option<(f64, f64)> = None;
+            for shift in 0..(cycle.ceil() as usize) {
+                let shift = shift as f64;
+                let cost: f64 = forwards
+                    .iter()
+                    .zip(backwards.iter())
+                    .map(|(a, b)| circular_diff(*a, b + shift, cycle).abs())
+                    .sum();
+                if best.map(|(c, _)| cost < c).unwrap_or(true) {
+                    best = Some((cost, shift));
+                }
+            }
+            let shift = best.unwrap().1;
+            forwards
+                .iter()
+                .zip(backwards.iter())
+                .map(|(a, b)| modulo(a + circular_diff(*a, b + shift, cycle) / 2.0, cycle))
+                .collect()
+        } else {
+            forwards
+        };
+
+        for (signal, offset) in signals.iter_mut().zip(offsets) {
+            signal.offset = Duration::seconds(offset);
+        }
+        Ok(signals)
+    }
+}
+
+// Relative to the start of the cycle, ignoring the offset
+fn phase_start(signal: &ControlTrafficSignal, idx: usize) -> f64 {
+    signal.phases[0..idx]
+        .iter()
+        .map(|p| p.duration.inner_seconds())
+        .sum()
+}
+
+fn no_phase(signal: &ControlTrafficSignal, forwards: bool) -> String {
+    format!(
+        "No phase at {} protects movement along the corridor going {}",
+        signal.id,
+        if forwards { "forwards" } else { "backwards" }
+    )
+}
+
+fn modulo(x: f64, m: f64) -> f64 {
+    ((x % m) + m) % m
+}
+
+// b - a, wrapped around to the shortest way around the cycle
+fn circular_diff(a: f64, b: f64, m: f64) -> f64 {
+    modulo(b - a + m / 2.0, m) - m / 2.0
+}
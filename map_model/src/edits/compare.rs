@@ -0,0 +1,265 @@
+use crate::{
+    ControlStopSign, ControlTrafficSignal, EditCmd, IntersectionID, LaneID, LaneType, Map,
+    MapEdits, RoadID, TurnBan,
+};
+use abstutil::retain_btreemap;
+use geom::{Distance, Speed};
+use std::collections::{BTreeMap, BTreeSet};
+
+// What differs between two sets of edits for the same map
+pub struct EditsDiff {
+    pub lanes: BTreeSet<LaneID>,
+    pub roads: BTreeSet<RoadID>,
+    pub intersections: BTreeSet<IntersectionID>,
+}
+
+// One independently editable thing. Adding, deleting, or reversing any lane shifts the others on
+// the road, so all of those count as one thing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    LaneType(LaneID),
+    LaneWidth(LaneID),
+    Restripe(RoadID),
+    SpeedLimit(RoadID),
+    Control(IntersectionID),
+    TurnBans(IntersectionID),
+}
+
+// The final state of one Key after all of the commands
+#[derive(PartialEq)]
+enum Value {
+    LaneType(LaneType),
+    LaneWidth(Distance),
+    // The AddLane, DeleteLane, and ReverseLane commands, in order
+    Restriped(Vec<EditCmd>),
+    SpeedLimit(Speed),
+    StopSign(ControlStopSign),
+    TrafficSignal(ControlTrafficSignal),
+    Closed,
+    TurnBans(BTreeSet<TurnBan>),
+}
+
+impl MapEdits {
+    // The map is only used to find the road each lane belongs to.
+    pub fn diff(&self, other: &MapEdits, map: &Map) -> EditsDiff {
+        let mine = summarize(self, map);
+        let theirs = summarize(other, map);
+
+        let mut diff = EditsDiff {
+            lanes: BTreeSet::new(),
+            roads: BTreeSet::new(),
+            intersections: BTreeSet::new(),
+        };
+        for key in mine.keys().chain(theirs.keys()) {
+            if mine.get(key) == theirs.get(key) {
+                continue;
+            }
+            match key {
+                Key::LaneType(l) | Key::LaneWidth(l) => {
+                    diff.lanes.insert(*l);
+                }
+                Key::Restripe(r) | Key::SpeedLimit(r) => {
+                    diff.roads.insert(*r);
+                }
+                Key::Control(i) | Key::TurnBans(i) => {
+                    diff.intersections.insert(*i);
+                }
+            }
+        }
+        diff
+    }
+
+    // Combine two proposals for the same map. Fails if they change the same thing in different
+    // ways. Things changed by both in the same way are only applied once.
+    // TODO Lane edits from other reset traffic signals that self changed at the same
+    // intersections.
+    pub fn merge(&self, other: &MapEdits, map: &Map) -> Result<MapEdits, String> {
+        if self.map_name != other.map_name {
+            return Err(format!(
+                "{} is for {}, but {} is for {}",
+                self.edits_name, self.map_name, other.edits_name, other.map_name
+            ));
+        }
+        // Both would hand out the same new LaneIDs.
+        if adds_lanes(self) && adds_lanes(other) {
+            return Err(format!(
+                "{} and {} both add lanes; merging those isn't supported yet",
+                self.edits_name, other.edits_name
+            ));
+        }
+
+        let mine = summarize(self, map);
+        let theirs = summarize(other, map);
+        let conflicts: Vec<String> = mine
+            .iter()
+            .filter(|(key, value)| theirs.get(*key).map(|v| v != *value).unwrap_or(false))
+            .map(|(key, _)| key.describe())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(format!(
+                "{} and {} conflict: {}",
+                self.edits_name,
+                other.edits_name,
+                conflicts.join(", ")
+            ));
+        }
+
+        let mut merged = self.clone();
+        merged.edits_name = if self.edits_name == "no_edits" {
+            other.edits_name.clone()
+        } else {
+            format!("{}_and_{}", self.edits_name, other.edits_name)
+        };
+        for (cmd, key) in other.commands.iter().zip(keys(other, map)) {
+            if !mine.contains_key(&key) {
+                merged.commands.push(cmd.clone());
+            }
+        }
+        merged.redo_stack.clear();
+        merged.dirty = true;
+        Ok(merged)
+    }
+}
+
+fn adds_lanes(edits: &MapEdits) -> bool {
+    edits.commands.iter().any(|cmd| match cmd {
+        EditCmd::AddLane { .. } => true,
+        _ => false,
+    })
+}
+
+// The Key of each command. Lanes from AddLane might not exist in the map, but they get LaneIDs
+// in order after the lanes of the unedited map.
+fn keys(edits: &MapEdits, map: &Map) -> Vec<Key> {
+    let num_base_lanes = map.all_lanes_including_deleted().len()
+        - map
+            .get_edits()
+            .commands
+            .iter()
+            .filter(|cmd| match cmd {
+                EditCmd::AddLane { .. } => true,
+                _ => false,
+            })
+            .count();
+    let mut added_lanes: Vec<RoadID> = Vec::new();
+    let parent = |l: LaneID, added_lanes: &Vec<RoadID>| {
+        if l.0 >= num_base_lanes {
+            added_lanes[l.0 - num_base_lanes]
+        } else {
+            map.get_l(l).parent
+        }
+    };
+
+    let mut keys = Vec::new();
+    for cmd in &edits.commands {
+        keys.push(match cmd {
+            EditCmd::ChangeLaneType { id, .. } => Key::LaneType(*id),
+            EditCmd::ReverseLane { l, .. } => Key::Restripe(parent(*l, &added_lanes)),
+            EditCmd::ChangeStopSign(ss) => Key::Control(ss.id),
+            EditCmd::ChangeTrafficSignal(ts) => Key::Control(ts.id),
+            EditCmd::CloseIntersection { id, .. } => Key::Control(*id),
+            EditCmd::UncloseIntersection(id, _) => Key::Control(*id),
+            EditCmd::BanTurns(ban) | EditCmd::AllowTurns(ban) => Key::TurnBans(ban.parent()),
+            EditCmd::AddLane { road, .. } => {
+                added_lanes.push(*road);
+                Key::Restripe(*road)
+            }
+            EditCmd::DeleteLane { id, .. } => Key::Restripe(parent(*id, &added_lanes)),
+            EditCmd::ChangeLaneWidth { id, .. } => Key::LaneWidth(*id),
+            EditCmd::ChangeSpeedLimit { road, .. } => Key::SpeedLimit(*road),
+        });
+    }
+    keys
+}
+
+// Keys whose final state matches the unedited map are left out.
+fn summarize(edits: &MapEdits, map: &Map) -> BTreeMap<Key, Value> {
+    let mut summary = BTreeMap::new();
+    let mut orig_lts = BTreeMap::new();
+    let mut orig_widths = BTreeMap::new();
+    let mut orig_speed_limits = BTreeMap::new();
+    for (cmd, key) in edits.commands.iter().zip(keys(edits, map)) {
+        match cmd {
+            EditCmd::ChangeLaneType { id, lt, orig_lt } => {
+                orig_lts.entry(*id).or_insert(*orig_lt);
+                summary.insert(key, Value::LaneType(*lt));
+            }
+            EditCmd::AddLane { .. } | EditCmd::DeleteLane { .. } | EditCmd::ReverseLane { .. } => {
+                let mut cmds = match summary.remove(&key) {
+                    Some(Value::Restriped(cmds)) => cmds,
+                    _ => Vec::new(),
+                };
+                // Reversing the same lane twice undoes it.
+                match (cmds.last(), cmd) {
+                    (
+                        Some(EditCmd::ReverseLane { l: l1, .. }),
+                        EditCmd::ReverseLane { l: l2, .. },
+                    ) if l1 == l2 => {
+                        cmds.pop();
+                    }
+                    _ => {
+                        cmds.push(cmd.clone());
+                    }
+                }
+                if !cmds.is_empty() {
+                    summary.insert(key, Value::Restriped(cmds));
+                }
+            }
+            EditCmd::ChangeStopSign(ss) => {
+                summary.insert(key, Value::StopSign(ss.clone()));
+            }
+            EditCmd::ChangeTrafficSignal(ts) => {
+                summary.insert(key, Value::TrafficSignal(ts.clone()));
+            }
+            EditCmd::CloseIntersection { .. } => {
+                summary.insert(key, Value::Closed);
+            }
+            EditCmd::UncloseIntersection(_, _) => {
+                summary.remove(&key);
+            }
+            EditCmd::BanTurns(ban) | EditCmd::AllowTurns(ban) => {
+                let mut bans = match summary.remove(&key) {
+                    Some(Value::TurnBans(bans)) => bans,
+                    _ => BTreeSet::new(),
+                };
+                if let EditCmd::BanTurns(_) = cmd {
+                    bans.insert(*ban);
+                } else {
+                    bans.remove(ban);
+                }
+                if !bans.is_empty() {
+                    summary.insert(key, Value::TurnBans(bans));
+                }
+            }
+            EditCmd::ChangeLaneWidth { id, new, old } => {
+                orig_widths.entry(*id).or_insert(*old);
+                summary.insert(key, Value::LaneWidth(*new));
+            }
+            EditCmd::ChangeSpeedLimit { road, new, old } => {
+                orig_speed_limits.entry(*road).or_insert(*old);
+                summary.insert(key, Value::SpeedLimit(*new));
+            }
+        }
+    }
+
+    retain_btreemap(&mut summary, |key, value| match (key, value) {
+        (Key::LaneType(l), Value::LaneType(lt)) => orig_lts[l] != *lt,
+        (Key::LaneWidth(l), Value::LaneWidth(width)) => orig_widths[l] != *width,
+        (Key::SpeedLimit(r), Value::SpeedLimit(speed)) => orig_speed_limits[r] != *speed,
+        _ => true,
+    });
+    summary
+}
+
+impl Key {
+    fn describe(&self) -> String {
+        match self {
+            Key::LaneType(l) => format!("lane type of {}", l),
+            Key::LaneWidth(l) => format!("width of {}", l),
+            Key::Restripe(r) => format!("lanes of {}", r),
+            Key::SpeedLimit(r) => format!("speed limit of {}", r),
+            Key::Control(i) => format!("control of {}", i),
+            Key::TurnBans(i) => format!("turn bans at {}", i),
+        }
+    }
+}
@@ -0,0 +1,517 @@
+mod compare;
+mod perma;
+
+pub use self::compare::EditsDiff;
+use self::perma::{PermanentMapEdits, SavedMapEdits};
+use crate::{
+    ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, Lane, LaneID,
+    LaneType, Map, RoadID, Turn, TurnGroupID, TurnID,
+};
+use abstutil::{retain_btreemap, retain_btreeset, Timer};
+use geom::{Distance, Speed};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MapEdits {
+    pub(crate) map_name: String,
+    pub edits_name: String,
+    pub commands: Vec<EditCmd>,
+
+    // Derived from commands, kept up to date by update_derived
+    pub original_lts: BTreeMap<LaneID, LaneType>,
+    pub reversed_lanes: BTreeSet<LaneID>,
+    pub changed_intersections: BTreeSet<IntersectionID>,
+    pub banned_turns: BTreeSet<TurnBan>,
+    pub restriped_roads: BTreeSet<RoadID>,
+    pub original_lane_widths: BTreeMap<LaneID, Distance>,
+    pub original_speed_limits: BTreeMap<RoadID, Speed>,
+
+    // Undone commands, most recent last. Map::apply_edits clears this when the commands diverge
+    // from it.
+    #[serde(skip_serializing, skip_deserializing)]
+    redo_stack: Vec<EditCmd>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub dirty: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum EditCmd {
+    ChangeLaneType {
+        id: LaneID,
+        lt: LaneType,
+        orig_lt: LaneType,
+    },
+    ReverseLane {
+        l: LaneID,
+        // New intended dst_i
+        dst_i: IntersectionID,
+    },
+    ChangeStopSign(ControlStopSign),
+    ChangeTrafficSignal(ControlTrafficSignal),
+    CloseIntersection {
+        id: IntersectionID,
+        orig_it: IntersectionType,
+    },
+    UncloseIntersection(IntersectionID, IntersectionType),
+    BanTurns(TurnBan),
+    AllowTurns(TurnBan),
+    // Counting from the center of the road. The new lane gets the next unused LaneID.
+    AddLane {
+        road: RoadID,
+        fwds: bool,
+        idx: usize,
+        lt: LaneType,
+    },
+    // The LaneID stays reserved. fwds and idx say where to put the lane back.
+    DeleteLane {
+        id: LaneID,
+        fwds: bool,
+        idx: usize,
+    },
+    ChangeLaneWidth {
+        id: LaneID,
+        new: Distance,
+        old: Distance,
+    },
+    ChangeSpeedLimit {
+        road: RoadID,
+        new: Speed,
+        old: Speed,
+    },
+}
+
+// Turns that don't exist at all, no matter what the stop sign or traffic signal says. These work
+// like turn restrictions from OSM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TurnBan {
+    Turn(TurnID),
+    // Every turn in the group, even if lane edits later change the members.
+    Group(IntersectionID, TurnGroupID),
+}
+
+pub struct EditEffects {
+    pub changed_lanes: BTreeSet<LaneID>,
+    pub changed_roads: BTreeSet<RoadID>,
+    pub changed_intersections: BTreeSet<IntersectionID>,
+    pub added_turns: BTreeSet<TurnID>,
+    pub deleted_turns: BTreeSet<TurnID>,
+}
+
+impl MapEdits {
+    pub fn new(map_name: String) -> MapEdits {
+        MapEdits {
+            map_name,
+            // Something has to fill this out later
+            edits_name: "no_edits".to_string(),
+            commands: Vec::new(),
+
+            original_lts: BTreeMap::new(),
+            reversed_lanes: BTreeSet::new(),
+            changed_intersections: BTreeSet::new(),
+            banned_turns: BTreeSet::new(),
+            restriped_roads: BTreeSet::new(),
+            original_lane_widths: BTreeMap::new(),
+            original_speed_limits: BTreeMap::new(),
+            redo_stack: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits_name == "no_edits" && self.commands.is_empty()
+    }
+
+    // Edits are saved in terms of OSM IDs, so they still work after the map is rebuilt. Commands
+    // that don't apply to this map anymore are skipped, and described in the second result. The
+    // edits the map has right now don't matter.
+    pub fn load(
+        map: &Map,
+        edits_name: &str,
+        timer: &mut Timer,
+    ) -> Result<(MapEdits, Vec<String>), String> {
+        if edits_name == "no_edits" {
+            return Ok((MapEdits::new(map.get_name().to_string()), Vec::new()));
+        }
+        let path = abstutil::path_edits(map.get_name(), edits_name);
+        match abstutil::maybe_read_json(path.clone(), timer) {
+            Ok(SavedMapEdits::Permanent(perma)) => perma.to_edits(map, timer),
+            Ok(SavedMapEdits::Legacy(legacy)) => {
+                timer.warn(format!(
+                    "{} is from before edits were versioned. Save it again to upgrade it.",
+                    path
+                ));
+                PermanentMapEdits::from_legacy(legacy, map, timer).to_edits(map, timer)
+            }
+            Err(err) => Err(format!("Can't load {}: {}", path, err)),
+        }
+    }
+
+    pub(crate) fn save(&mut self, map: &Map) {
+        self.compress(map);
+
+        assert!(self.dirty);
+        assert_ne!(self.edits_name, "no_edits");
+        abstutil::write_json(
+            abstutil::path_edits(&self.map_name, &self.edits_name),
+            &PermanentMapEdits::new(self, map),
+        );
+        self.dirty = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.commands.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    // Returns the command that was undone. The caller still has to apply the edits.
+    pub fn undo(&mut self) -> Option<EditCmd> {
+        let cmd = self.commands.pop()?;
+        self.redo_stack.push(cmd.clone());
+        Some(cmd)
+    }
+
+    pub fn redo(&mut self) -> Option<EditCmd> {
+        let cmd = self.redo_stack.pop()?;
+        self.commands.push(cmd.clone());
+        Some(cmd)
+    }
+
+    // Lots of places edit a clone of the current commands directly, so figure out here if the
+    // redo stack still makes sense: the new commands have to be somewhere along the old history.
+    pub(crate) fn update_history(&mut self, old: &MapEdits) {
+        let history: Vec<&EditCmd> = old
+            .commands
+            .iter()
+            .chain(old.redo_stack.iter().rev())
+            .collect();
+        let len = self.commands.len();
+        if self.edits_name == old.edits_name
+            && len <= history.len()
+            && self.commands.iter().zip(&history).all(|(a, b)| a == *b)
+        {
+            self.redo_stack = history[len..]
+                .iter()
+                .rev()
+                .map(|cmd| (*cmd).clone())
+                .collect();
+        } else {
+            self.redo_stack.clear();
+        }
+    }
+
+    pub fn original_it(&self, i: IntersectionID) -> IntersectionType {
+        for cmd in &self.commands {
+            if let EditCmd::CloseIntersection { id, orig_it } = cmd {
+                if *id == i {
+                    return *orig_it;
+                }
+            }
+        }
+        panic!("{} isn't closed", i);
+    }
+
+    // Original lane types, widths, and speed limits, reversed lanes, banned turns, restriped roads,
+    // and all changed intersections
+    pub(crate) fn update_derived(&mut self, map: &Map, timer: &mut Timer) {
+        let mut orig_lts = BTreeMap::new();
+        let mut reversed_lanes = BTreeSet::new();
+        let mut banned_turns = BTreeSet::new();
+        let mut restriped_roads = BTreeSet::new();
+        let mut orig_widths = BTreeMap::new();
+        let mut orig_speed_limits = BTreeMap::new();
+        let mut changed_stop_signs = BTreeSet::new();
+        let mut changed_traffic_signals = BTreeSet::new();
+        let mut closed_intersections = BTreeSet::new();
+
+        for cmd in &self.commands {
+            match cmd {
+                EditCmd::ChangeLaneType { id, orig_lt, .. } => {
+                    if !orig_lts.contains_key(id) {
+                        orig_lts.insert(*id, *orig_lt);
+                    }
+                }
+                EditCmd::ReverseLane { l, .. } => {
+                    if reversed_lanes.contains(l) {
+                        reversed_lanes.remove(l);
+                    } else {
+                        reversed_lanes.insert(*l);
+                    }
+                }
+                EditCmd::ChangeStopSign(ss) => {
+                    changed_stop_signs.insert(ss.id);
+                }
+                EditCmd::ChangeTrafficSignal(ts) => {
+                    changed_traffic_signals.insert(ts.id);
+                }
+                EditCmd::CloseIntersection { id, .. } => {
+                    closed_intersections.insert(*id);
+                }
+                EditCmd::UncloseIntersection(id, _) => {
+                    closed_intersections.remove(id);
+                }
+                EditCmd::BanTurns(ban) => {
+                    banned_turns.insert(*ban);
+                }
+                EditCmd::AllowTurns(ban) => {
+                    banned_turns.remove(ban);
+                }
+                EditCmd::AddLane { road, .. } => {
+                    restriped_roads.insert(*road);
+                }
+                EditCmd::DeleteLane { id, .. } => {
+                    restriped_roads.insert(map.get_l(*id).parent);
+                }
+                EditCmd::ChangeLaneWidth { id, old, .. } => {
+                    if !orig_widths.contains_key(id) {
+                        orig_widths.insert(*id, *old);
+                    }
+                }
+                EditCmd::ChangeSpeedLimit { road, old, .. } => {
+                    if !orig_speed_limits.contains_key(road) {
+                        orig_speed_limits.insert(*road, *old);
+                    }
+                }
+            }
+        }
+
+        retain_btreemap(&mut orig_lts, |l, lt| {
+            !map.get_l(*l).deleted && map.get_l(*l).lane_type != *lt
+        });
+        retain_btreeset(&mut reversed_lanes, |l| !map.get_l(*l).deleted);
+        retain_btreemap(&mut orig_widths, |l, width| {
+            !map.get_l(*l).deleted && map.get_l(*l).width != *width
+        });
+        restriped_roads.extend(orig_widths.keys().map(|l| map.get_l(*l).parent));
+        // Bans that failed their check when applied
+        retain_btreeset(&mut banned_turns, |ban| {
+            map.get_i(ban.parent()).banned_turns.contains(ban)
+        });
+        retain_btreemap(&mut orig_speed_limits, |r, speed| {
+            map.get_r(*r).speed_limit != *speed
+        });
+        for i in &closed_intersections {
+            changed_stop_signs.remove(i);
+            changed_traffic_signals.remove(i);
+        }
+        retain_btreeset(&mut changed_stop_signs, |i| {
+            &ControlStopSign::new(map, *i) != map.get_stop_sign(*i)
+        });
+        retain_btreeset(&mut changed_traffic_signals, |i| {
+            &ControlTrafficSignal::new(map, *i, timer) != map.get_traffic_signal(*i)
+        });
+
+        self.original_lts = orig_lts;
+        self.reversed_lanes = reversed_lanes;
+        self.banned_turns = banned_turns;
+        self.restriped_roads = restriped_roads;
+        self.original_lane_widths = orig_widths;
+        self.original_speed_limits = orig_speed_limits;
+        self.changed_intersections = closed_intersections;
+        self.changed_intersections.extend(changed_stop_signs);
+        self.changed_intersections.extend(changed_traffic_signals);
+    }
+
+    // Assumes update_derived has been called.
+    pub(crate) fn compress(&mut self, map: &Map) {
+        let orig_cmds: Vec<EditCmd> = self.commands.drain(..).collect();
+
+        // The map keeps the lanes from the original commands, so the compressed commands have to
+        // hand out the same LaneIDs and leave deleted lanes exactly as they were. Keep everything
+        // that adds, deletes, or moves lanes around on those roads in the original order, plus
+        // changes to lanes that wind up deleted.
+        let mut lanes_moved = BTreeSet::new();
+        for cmd in &orig_cmds {
+            match cmd {
+                EditCmd::AddLane { road, .. } => {
+                    lanes_moved.insert(*road);
+                }
+                EditCmd::DeleteLane { id, .. } => {
+                    lanes_moved.insert(map.get_l(*id).parent);
+                }
+                _ => {}
+            }
+        }
+        for cmd in &orig_cmds {
+            let keep = match cmd {
+                EditCmd::AddLane { .. } | EditCmd::DeleteLane { .. } => true,
+                EditCmd::ReverseLane { l, .. } => lanes_moved.contains(&map.get_l(*l).parent),
+                EditCmd::ChangeLaneType { id, .. } | EditCmd::ChangeLaneWidth { id, .. } => {
+                    map.get_l(*id).deleted
+                }
+                _ => false,
+            };
+            if keep {
+                self.commands.push(cmd.clone());
+            }
+        }
+
+        for (l, orig_lt) in &self.original_lts {
+            self.commands.push(EditCmd::ChangeLaneType {
+                id: *l,
+                lt: map.get_l(*l).lane_type,
+                orig_lt: *orig_lt,
+            });
+        }
+        for l in &self.reversed_lanes {
+            if lanes_moved.contains(&map.get_l(*l).parent) {
+                continue;
+            }
+            self.commands.push(EditCmd::ReverseLane {
+                l: *l,
+                dst_i: map.get_l(*l).dst_i,
+            });
+        }
+        for (l, old) in &self.original_lane_widths {
+            self.commands.push(EditCmd::ChangeLaneWidth {
+                id: *l,
+                new: map.get_l(*l).width,
+                old: *old,
+            });
+        }
+        for (r, old) in &self.original_speed_limits {
+            self.commands.push(EditCmd::ChangeSpeedLimit {
+                road: *r,
+                new: map.get_r(*r).speed_limit,
+                old: *old,
+            });
+        }
+        // Before the intersections, since banning turns resets traffic signals.
+        for ban in &self.banned_turns {
+            self.commands.push(EditCmd::BanTurns(*ban));
+        }
+        for i in &self.changed_intersections {
+            match map.get_i(*i).intersection_type {
+                IntersectionType::StopSign => {
+                    self.commands
+                        .push(EditCmd::ChangeStopSign(map.get_stop_sign(*i).clone()));
+                }
+                IntersectionType::TrafficSignal => {
+                    self.commands.push(EditCmd::ChangeTrafficSignal(
+                        map.get_traffic_signal(*i).clone(),
+                    ));
+                }
+                IntersectionType::Construction => {
+                    // We have to recover orig_it from the original list of commands. :\
+                    let mut found = false;
+                    for cmd in &orig_cmds {
+                        if let EditCmd::CloseIntersection { id, .. } = cmd {
+                            if *id == *i {
+                                self.commands.push(cmd.clone());
+                                found = true;
+                                break;
+                            }
+                        }
+                    }
+                    assert!(found);
+                }
+                IntersectionType::Border => unreachable!(),
+            }
+        }
+    }
+}
+
+impl EditEffects {
+    pub fn new() -> EditEffects {
+        EditEffects {
+            changed_lanes: BTreeSet::new(),
+            changed_roads: BTreeSet::new(),
+            changed_intersections: BTreeSet::new(),
+            added_turns: BTreeSet::new(),
+            deleted_turns: BTreeSet::new(),
+        }
+    }
+}
+
+impl EditCmd {
+    pub fn describe(&self) -> String {
+        match self {
+            EditCmd::ChangeLaneType { id, lt, .. } => format!("Change {} to {:?}", id, lt),
+            EditCmd::ReverseLane { l, .. } => format!("Reverse {}", l),
+            EditCmd::ChangeStopSign(ss) => format!("Edit stop sign {}", ss.id),
+            EditCmd::ChangeTrafficSignal(ts) => format!("Edit traffic signal {}", ts.id),
+            EditCmd::CloseIntersection { id, .. } => format!("Close {}", id),
+            EditCmd::UncloseIntersection(id, _) => format!("Restore {}", id),
+            EditCmd::BanTurns(ban) => format!("Ban {}", ban.describe()),
+            EditCmd::AllowTurns(ban) => format!("Allow {}", ban.describe()),
+            EditCmd::AddLane { road, lt, .. } => format!("Add a {:?} lane to {}", lt, road),
+            EditCmd::DeleteLane { id, .. } => format!("Delete {}", id),
+            EditCmd::ChangeLaneWidth { id, new, .. } => {
+                format!("Change width of {} to {}", id, new)
+            }
+            EditCmd::ChangeSpeedLimit { road, new, .. } => {
+                format!("Change speed limit of {} to {}", road, new)
+            }
+        }
+    }
+}
+
+impl TurnBan {
+    pub fn parent(&self) -> IntersectionID {
+        match self {
+            TurnBan::Turn(t) => t.parent,
+            TurnBan::Group(i, _) => *i,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            TurnBan::Turn(t) => format!("{}", t),
+            TurnBan::Group(i, g) => {
+                if let Some(t) = g.crosswalk {
+                    format!("{}", t)
+                } else {
+                    format!("turns from {} to {} at {}", g.from, g.to, i)
+                }
+            }
+        }
+    }
+
+    pub(crate) fn matches(&self, turn: &Turn, lanes: &Vec<Lane>) -> bool {
+        match self {
+            TurnBan::Turn(t) => turn.id == *t,
+            TurnBan::Group(i, g) => {
+                if let Some(t) = g.crosswalk {
+                    return turn.id == t;
+                }
+                turn.id.parent == *i
+                    && !turn.between_sidewalks()
+                    && lanes[turn.id.src.0].parent == g.from
+                    && lanes[turn.id.dst.0].parent == g.to
+            }
+        }
+    }
+
+    // Banning turns must leave every lane some way into and out of the intersection.
+    pub fn check(&self, map: &Map) -> Result<(), String> {
+        let (banned, others): (Vec<&Turn>, Vec<&Turn>) = map
+            .get_i(self.parent())
+            .turns
+            .iter()
+            .map(|t| map.get_t(*t))
+            .partition(|t| self.matches(t, map.all_lanes_including_deleted()));
+        if banned.is_empty() {
+            return Err(format!("{} doesn't match any turns", self.describe()));
+        }
+        for turn in banned {
+            if !others.iter().any(|t| t.id.src == turn.id.src) {
+                return Err(format!(
+                    "Banning {} would leave no way out of {}",
+                    self.describe(),
+                    turn.id.src
+                ));
+            }
+            if !others.iter().any(|t| t.id.dst == turn.id.dst) {
+                return Err(format!(
+                    "Banning {} would leave no way into {}",
+                    self.describe(),
+                    turn.id.dst
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,793 @@
+use crate::raw::{OriginalIntersection, OriginalRoad};
+use crate::{
+    ActuatedTiming, ControlStopSign, ControlTrafficSignal, EditCmd, IntersectionID,
+    IntersectionType, LaneID, LaneType, Map, MapEdits, Phase, RoadID, TurnBan, TurnGroup,
+    TurnGroupID, TurnID,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::{Distance, Duration, Speed};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Bump this when the format changes, and upgrade the old format in MapEdits::load. Version 0 was
+// MapEdits serialized directly, with IDs that change whenever the map is rebuilt.
+const VERSION: usize = 1;
+
+// What's actually saved to disk. Everything is referred to by OSM IDs, so the edits still load
+// after the map is rebuilt.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PermanentMapEdits {
+    map_name: String,
+    edits_name: String,
+    version: usize,
+    commands: Vec<PermanentEditCmd>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LegacyMapEdits {
+    pub map_name: String,
+    pub edits_name: String,
+    pub commands: Vec<EditCmd>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SavedMapEdits {
+    Permanent(PermanentMapEdits),
+    Legacy(LegacyMapEdits),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OriginalLane {
+    // Where the lane is on the road in the unedited map
+    Road {
+        parent: OriginalRoad,
+        fwds: bool,
+        idx: usize,
+    },
+    // The nth lane created by AddLane
+    Added(usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct OriginalTurn {
+    parent: OriginalIntersection,
+    src: OriginalLane,
+    dst: OriginalLane,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct OriginalTurnGroup {
+    from: OriginalRoad,
+    to: OriginalRoad,
+    crosswalk: Option<OriginalTurn>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum PermanentTurnBan {
+    Turn(OriginalTurn),
+    Group(OriginalIntersection, OriginalTurnGroup),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PermanentTrafficSignal {
+    id: OriginalIntersection,
+    phases: Vec<PermanentPhase>,
+    offset: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PermanentPhase {
+    protected_groups: BTreeSet<OriginalTurnGroup>,
+    yield_groups: BTreeSet<OriginalTurnGroup>,
+    duration: Duration,
+    actuated: Option<ActuatedTiming>,
+}
+
+// Mirrors EditCmd. The positions in AddLane and DeleteLane are relative to the road at the time of
+// the command, just like in EditCmd.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+enum PermanentEditCmd {
+    ChangeLaneType {
+        id: OriginalLane,
+        lt: LaneType,
+        orig_lt: LaneType,
+    },
+    ReverseLane {
+        l: OriginalLane,
+        dst_i: OriginalIntersection,
+    },
+    ChangeStopSign {
+        id: OriginalIntersection,
+        #[serde(
+            serialize_with = "serialize_btreemap",
+            deserialize_with = "deserialize_btreemap"
+        )]
+        must_stop: BTreeMap<OriginalRoad, bool>,
+    },
+    ChangeTrafficSignal(PermanentTrafficSignal),
+    CloseIntersection {
+        id: OriginalIntersection,
+        orig_it: IntersectionType,
+    },
+    UncloseIntersection(OriginalIntersection, IntersectionType),
+    BanTurns(PermanentTurnBan),
+    AllowTurns(PermanentTurnBan),
+    AddLane {
+        road: OriginalRoad,
+        fwds: bool,
+        idx: usize,
+        lt: LaneType,
+    },
+    DeleteLane {
+        id: OriginalLane,
+        fwds: bool,
+        idx: usize,
+    },
+    ChangeLaneWidth {
+        id: OriginalLane,
+        new: Distance,
+        old: Distance,
+    },
+    ChangeSpeedLimit {
+        road: OriginalRoad,
+        new: Speed,
+        old: Speed,
+    },
+}
+
+impl PermanentMapEdits {
+    // The map must have these edits applied.
+    pub(crate) fn new(edits: &MapEdits, map: &Map) -> PermanentMapEdits {
+        let ids = IDMapper::new(map, &edits.commands);
+        PermanentMapEdits {
+            map_name: edits.map_name.clone(),
+            edits_name: edits.edits_name.clone(),
+            version: VERSION,
+            commands: edits.commands.iter().map(|cmd| ids.to_perma(cmd)).collect(),
+        }
+    }
+
+    // Version 0 edits refer to IDs directly, which only mean something for the map they were made
+    // for. Commands naming things this map doesn't have are skipped. The rest are checked like any
+    // other saved edits when they're loaded.
+    pub(crate) fn from_legacy(
+        legacy: LegacyMapEdits,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> PermanentMapEdits {
+        let ids = IDMapper::new(map, &map.get_edits().commands);
+        let mut num_added = 0;
+        let mut commands = Vec::new();
+        for cmd in legacy.commands {
+            match check_legacy(&cmd, &ids, num_added) {
+                Ok(()) => {
+                    if let EditCmd::AddLane { .. } = cmd {
+                        num_added += 1;
+                    }
+                    commands.push(ids.to_perma(&cmd));
+                }
+                Err(err) => {
+                    timer.warn(format!(
+                        "Skipping edit to {} that doesn't match the map: {}",
+                        legacy.edits_name, err
+                    ));
+                }
+            }
+        }
+        PermanentMapEdits {
+            map_name: legacy.map_name,
+            edits_name: legacy.edits_name,
+            version: VERSION,
+            commands,
+        }
+    }
+
+    // Commands that don't make sense in this map anymore are skipped and returned, described. Each
+    // command is checked against the unedited map as changed by the commands before it. The map
+    // isn't touched, so its current edits don't matter.
+    pub(crate) fn to_edits(
+        self,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Result<(MapEdits, Vec<String>), String> {
+        if self.version != VERSION {
+            return Err(format!(
+                "{} is version {}, but only version {} is supported",
+                self.edits_name, self.version, VERSION
+            ));
+        }
+
+        let mut ids = IDMapper::for_loading(map);
+        let mut edits = MapEdits::new(self.map_name);
+        edits.edits_name = self.edits_name;
+        let mut skipped = Vec::new();
+        for cmd in self.commands {
+            match ids.from_perma(&cmd) {
+                Ok(cmd) => {
+                    edits.commands.push(cmd);
+                }
+                Err(err) => {
+                    let problem = format!("{} ({})", cmd.describe(), err);
+                    timer.warn(format!(
+                        "Skipping edit to {} that no longer applies: {}",
+                        edits.edits_name, problem
+                    ));
+                    skipped.push(problem);
+                    // Keep the numbering of added lanes straight.
+                    if let PermanentEditCmd::AddLane { .. } = cmd {
+                        ids.added_lanes.push(None);
+                    }
+                }
+            }
+        }
+        Ok((edits, skipped))
+    }
+}
+
+impl PermanentEditCmd {
+    fn describe(&self) -> String {
+        match self {
+            PermanentEditCmd::ChangeLaneType { id, lt, .. } => {
+                format!("Change {} to {:?}", id.describe(), lt)
+            }
+            PermanentEditCmd::ReverseLane { l, .. } => format!("Reverse {}", l.describe()),
+            PermanentEditCmd::ChangeStopSign { id, .. } => format!("Edit stop sign {}", id),
+            PermanentEditCmd::ChangeTrafficSignal(ts) => {
+                format!("Edit traffic signal {}", ts.id)
+            }
+            PermanentEditCmd::CloseIntersection { id, .. } => format!("Close {}", id),
+            PermanentEditCmd::UncloseIntersection(id, _) => format!("Restore {}", id),
+            PermanentEditCmd::BanTurns(ban) => format!("Ban turns at {}", ban.parent()),
+            PermanentEditCmd::AllowTurns(ban) => format!("Allow turns at {}", ban.parent()),
+            PermanentEditCmd::AddLane { road, lt, .. } => {
+                format!("Add a {:?} lane to {}", lt, road)
+            }
+            PermanentEditCmd::DeleteLane { id, .. } => format!("Delete {}", id.describe()),
+            PermanentEditCmd::ChangeLaneWidth { id, new, .. } => {
+                format!("Change width of {} to {}", id.describe(), new)
+            }
+            PermanentEditCmd::ChangeSpeedLimit { road, new, .. } => {
+                format!("Change speed limit of {} to {}", road, new)
+            }
+        }
+    }
+}
+
+impl OriginalLane {
+    fn describe(&self) -> String {
+        match self {
+            OriginalLane::Road { parent, fwds, idx } => format!(
+                "lane {} going {} on {}",
+                idx,
+                if *fwds { "forwards" } else { "backwards" },
+                parent
+            ),
+            OriginalLane::Added(n) => format!("added lane #{}", n),
+        }
+    }
+}
+
+impl PermanentTurnBan {
+    fn parent(&self) -> OriginalIntersection {
+        match self {
+            PermanentTurnBan::Turn(t) => t.parent,
+            PermanentTurnBan::Group(i, _) => *i,
+        }
+    }
+}
+
+// Legacy commands refer to IDs directly, which might not even exist in this map. Lanes past the
+// unedited ones have to come from an earlier AddLane.
+fn check_legacy(cmd: &EditCmd, ids: &IDMapper, num_added: usize) -> Result<(), String> {
+    let map = ids.map;
+    let lane = |l: LaneID| {
+        if l.0 < ids.num_base_lanes + num_added {
+            Ok(())
+        } else {
+            Err(format!("{} doesn't exist", l))
+        }
+    };
+    let intersection = |i: IntersectionID| {
+        map.maybe_get_i(i)
+            .map(|_| ())
+            .ok_or_else(|| format!("{} doesn't exist", i))
+    };
+    let road = |r: RoadID| {
+        map.maybe_get_r(r)
+            .map(|_| ())
+            .ok_or_else(|| format!("{} doesn't exist", r))
+    };
+    let turn = |t: TurnID| {
+        intersection(t.parent)?;
+        lane(t.src)?;
+        lane(t.dst)
+    };
+    let turn_group = |g: &TurnGroupID| -> Result<(), String> {
+        road(g.from)?;
+        road(g.to)?;
+        if let Some(t) = g.crosswalk {
+            turn(t)?;
+        }
+        Ok(())
+    };
+
+    match cmd {
+        EditCmd::ChangeLaneType { id, .. }
+        | EditCmd::DeleteLane { id, .. }
+        | EditCmd::ChangeLaneWidth { id, .. } => lane(*id),
+        EditCmd::ReverseLane { l, dst_i } => {
+            lane(*l)?;
+            intersection(*dst_i)
+        }
+        EditCmd::ChangeStopSign(ss) => {
+            intersection(ss.id)?;
+            for r in ss.roads.keys() {
+                road(*r)?;
+            }
+            Ok(())
+        }
+        EditCmd::ChangeTrafficSignal(ts) => {
+            intersection(ts.id)?;
+            for phase in &ts.phases {
+                for g in phase
+                    .protected_groups
+                    .iter()
+                    .chain(phase.yield_groups.iter())
+                {
+                    turn_group(g)?;
+                }
+            }
+            Ok(())
+        }
+        EditCmd::CloseIntersection { id, .. } | EditCmd::UncloseIntersection(id, _) => {
+            intersection(*id)
+        }
+        EditCmd::BanTurns(ban) | EditCmd::AllowTurns(ban) => match ban {
+            TurnBan::Turn(t) => turn(*t),
+            TurnBan::Group(i, g) => {
+                intersection(*i)?;
+                turn_group(g)
+            }
+        },
+        EditCmd::AddLane { road: r, .. } | EditCmd::ChangeSpeedLimit { road: r, .. } => road(*r),
+    }
+}
+
+// Translates between the IDs of one particular map and OSM-based IDs. Lanes are the tricky part;
+// positions on a road change as edits add, delete, and reverse lanes.
+struct IDMapper<'a> {
+    map: &'a Map,
+    roads: BTreeMap<OriginalRoad, RoadID>,
+    intersections: BTreeMap<OriginalIntersection, IntersectionID>,
+    // How many lanes the unedited map has. AddLane creates lanes past this.
+    num_base_lanes: usize,
+    // The (forwards, backwards) lanes in the unedited map, for roads where edits moved lanes
+    // around. Other roads are unchanged from the map.
+    base_lanes: BTreeMap<RoadID, (Vec<LaneID>, Vec<LaneID>)>,
+
+    // Only used while loading edits, to check they still apply. Where the lanes of each road will
+    // be after the commands so far, starting from the unedited map.
+    current_lanes: BTreeMap<RoadID, (Vec<LaneID>, Vec<LaneID>)>,
+    // The lane and its road for every AddLane so far, unless it was skipped
+    added_lanes: Vec<Option<(LaneID, RoadID)>>,
+}
+
+impl<'a> IDMapper<'a> {
+    // applied_cmds are the edits the map currently has. Work backwards from them to find the
+    // unedited lanes.
+    fn new(map: &'a Map, applied_cmds: &Vec<EditCmd>) -> IDMapper<'a> {
+        let mut num_base_lanes = map.all_lanes_including_deleted().len();
+        let mut base_lanes = BTreeMap::new();
+        for cmd in applied_cmds.iter().rev() {
+            match cmd {
+                EditCmd::AddLane {
+                    road, fwds, idx, ..
+                } => {
+                    num_base_lanes -= 1;
+                    side(&mut base_lanes, map, *road, *fwds).remove(*idx);
+                }
+                EditCmd::DeleteLane { id, fwds, idx } => {
+                    side(&mut base_lanes, map, map.get_l(*id).parent, *fwds).insert(*idx, *id);
+                }
+                EditCmd::ReverseLane { l, dst_i } => {
+                    let r = map.get_parent(*l);
+                    let now_fwds = *dst_i == r.dst_i;
+                    // It might've been a no-op.
+                    if side(&mut base_lanes, map, r.id, now_fwds).get(0) == Some(l) {
+                        side(&mut base_lanes, map, r.id, now_fwds).remove(0);
+                        side(&mut base_lanes, map, r.id, !now_fwds).insert(0, *l);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        IDMapper {
+            map,
+            roads: map.all_roads().iter().map(|r| (r.orig_id, r.id)).collect(),
+            intersections: map
+                .all_intersections()
+                .iter()
+                .map(|i| (i.orig_id, i.id))
+                .collect(),
+            num_base_lanes,
+            base_lanes,
+            current_lanes: BTreeMap::new(),
+            added_lanes: Vec::new(),
+        }
+    }
+
+    // The new edits will replace whatever the map has now, so they start from the unedited map.
+    fn for_loading(map: &'a Map) -> IDMapper<'a> {
+        let mut ids = IDMapper::new(map, &map.get_edits().commands);
+        ids.current_lanes = ids.base_lanes.clone();
+        ids
+    }
+
+    // The road of a lane in the unedited map, or of a lane added by the commands so far
+    fn parent(&self, l: LaneID) -> RoadID {
+        if l.0 < self.num_base_lanes {
+            return self.map.get_l(l).parent;
+        }
+        self.added_lanes
+            .iter()
+            .flatten()
+            .find(|(id, _)| *id == l)
+            .unwrap()
+            .1
+    }
+
+    fn to_perma(&self, cmd: &EditCmd) -> PermanentEditCmd {
+        match cmd {
+            EditCmd::ChangeLaneType { id, lt, orig_lt } => PermanentEditCmd::ChangeLaneType {
+                id: self.lane(*id),
+                lt: *lt,
+                orig_lt: *orig_lt,
+            },
+            EditCmd::ReverseLane { l, dst_i } => PermanentEditCmd::ReverseLane {
+                l: self.lane(*l),
+                dst_i: self.map.get_i(*dst_i).orig_id,
+            },
+            EditCmd::ChangeStopSign(ss) => PermanentEditCmd::ChangeStopSign {
+                id: self.map.get_i(ss.id).orig_id,
+                must_stop: ss
+                    .roads
+                    .iter()
+                    .map(|(r, sign)| (self.map.get_r(*r).orig_id, sign.must_stop))
+                    .collect(),
+            },
+            EditCmd::ChangeTrafficSignal(ts) => {
+                PermanentEditCmd::ChangeTrafficSignal(PermanentTrafficSignal {
+                    id: self.map.get_i(ts.id).orig_id,
+                    phases: ts
+                        .phases
+                        .iter()
+                        .map(|phase| PermanentPhase {
+                            protected_groups: phase
+                                .protected_groups
+                                .iter()
+                                .map(|g| self.turn_group(*g))
+                                .collect(),
+                            yield_groups: phase
+                                .yield_groups
+                                .iter()
+                                .map(|g| self.turn_group(*g))
+                                .collect(),
+                            duration: phase.duration,
+                            actuated: phase.actuated,
+                        })
+                        .collect(),
+                    offset: ts.offset,
+                })
+            }
+            EditCmd::CloseIntersection { id, orig_it } => PermanentEditCmd::CloseIntersection {
+                id: self.map.get_i(*id).orig_id,
+                orig_it: *orig_it,
+            },
+            EditCmd::UncloseIntersection(id, orig_it) => {
+                PermanentEditCmd::UncloseIntersection(self.map.get_i(*id).orig_id, *orig_it)
+            }
+            EditCmd::BanTurns(ban) => PermanentEditCmd::BanTurns(self.turn_ban(*ban)),
+            EditCmd::AllowTurns(ban) => PermanentEditCmd::AllowTurns(self.turn_ban(*ban)),
+            EditCmd::AddLane {
+                road,
+                fwds,
+                idx,
+                lt,
+            } => PermanentEditCmd::AddLane {
+                road: self.map.get_r(*road).orig_id,
+                fwds: *fwds,
+                idx: *idx,
+                lt: *lt,
+            },
+            EditCmd::DeleteLane { id, fwds, idx } => PermanentEditCmd::DeleteLane {
+                id: self.lane(*id),
+                fwds: *fwds,
+                idx: *idx,
+            },
+            EditCmd::ChangeLaneWidth { id, new, old } => PermanentEditCmd::ChangeLaneWidth {
+                id: self.lane(*id),
+                new: *new,
+                old: *old,
+            },
+            EditCmd::ChangeSpeedLimit { road, new, old } => PermanentEditCmd::ChangeSpeedLimit {
+                road: self.map.get_r(*road).orig_id,
+                new: *new,
+                old: *old,
+            },
+        }
+    }
+
+    fn lane(&self, l: LaneID) -> OriginalLane {
+        if l.0 >= self.num_base_lanes {
+            return OriginalLane::Added(l.0 - self.num_base_lanes);
+        }
+        let r = self.map.get_parent(l);
+        let (fwds, idx) = if let Some((fwd_lanes, back_lanes)) = self.base_lanes.get(&r.id) {
+            if let Some(idx) = fwd_lanes.iter().position(|x| *x == l) {
+                (true, idx)
+            } else {
+                (false, back_lanes.iter().position(|x| *x == l).unwrap())
+            }
+        } else {
+            r.dir_and_offset(l)
+        };
+        OriginalLane::Road {
+            parent: r.orig_id,
+            fwds,
+            idx,
+        }
+    }
+
+    fn turn(&self, t: TurnID) -> OriginalTurn {
+        OriginalTurn {
+            parent: self.map.get_i(t.parent).orig_id,
+            src: self.lane(t.src),
+            dst: self.lane(t.dst),
+        }
+    }
+
+    fn turn_group(&self, g: TurnGroupID) -> OriginalTurnGroup {
+        OriginalTurnGroup {
+            from: self.map.get_r(g.from).orig_id,
+            to: self.map.get_r(g.to).orig_id,
+            crosswalk: g.crosswalk.map(|t| self.turn(t)),
+        }
+    }
+
+    fn turn_ban(&self, ban: TurnBan) -> PermanentTurnBan {
+        match ban {
+            TurnBan::Turn(t) => PermanentTurnBan::Turn(self.turn(t)),
+            TurnBan::Group(i, g) => {
+                PermanentTurnBan::Group(self.map.get_i(i).orig_id, self.turn_group(g))
+            }
+        }
+    }
+
+    fn from_perma(&mut self, cmd: &PermanentEditCmd) -> Result<EditCmd, String> {
+        Ok(match cmd {
+            PermanentEditCmd::ChangeLaneType { id, lt, orig_lt } => EditCmd::ChangeLaneType {
+                id: self.get_lane(*id)?,
+                lt: *lt,
+                orig_lt: *orig_lt,
+            },
+            PermanentEditCmd::ReverseLane { l, dst_i } => {
+                let l = self.get_lane(*l)?;
+                let dst_i = self.get_i(*dst_i)?;
+                let r = self.map.get_r(self.parent(l));
+                let fwds = dst_i == r.dst_i;
+                if !fwds && dst_i != r.src_i {
+                    return Err(format!("{} doesn't lead to {}", l, dst_i));
+                }
+                let r = r.id;
+                let other_side = side(&mut self.current_lanes, self.map, r, !fwds);
+                if other_side.get(0) != Some(&l) {
+                    return Err(format!("{} isn't next to the center of its road", l));
+                }
+                other_side.remove(0);
+                side(&mut self.current_lanes, self.map, r, fwds).insert(0, l);
+                EditCmd::ReverseLane { l, dst_i }
+            }
+            PermanentEditCmd::ChangeStopSign { id, must_stop } => {
+                let i = self.get_i(*id)?;
+                let mut ss = ControlStopSign::new(self.map, i);
+                for (r, stop) in must_stop {
+                    let r = self.get_r(*r)?;
+                    if let Some(sign) = ss.roads.get_mut(&r) {
+                        sign.must_stop = *stop;
+                    } else {
+                        return Err(format!("{} doesn't touch {}", r, i));
+                    }
+                }
+                EditCmd::ChangeStopSign(ss)
+            }
+            PermanentEditCmd::ChangeTrafficSignal(ts) => {
+                let id = self.get_i(ts.id)?;
+                let mut phases = Vec::new();
+                for phase in &ts.phases {
+                    let mut protected_groups = BTreeSet::new();
+                    for g in &phase.protected_groups {
+                        protected_groups.insert(self.get_turn_group(*g)?);
+                    }
+                    let mut yield_groups = BTreeSet::new();
+                    for g in &phase.yield_groups {
+                        yield_groups.insert(self.get_turn_group(*g)?);
+                    }
+                    phases.push(Phase {
+                        protected_groups,
+                        yield_groups,
+                        duration: phase.duration,
+                        actuated: phase.actuated,
+                    });
+                }
+                // The turn groups depend on the lanes and turns, which earlier commands might
+                // change, so the signal is checked against them when it's applied.
+                EditCmd::ChangeTrafficSignal(ControlTrafficSignal {
+                    id,
+                    phases,
+                    offset: ts.offset,
+                    turn_groups: TurnGroup::for_i(id, self.map),
+                })
+            }
+            PermanentEditCmd::CloseIntersection { id, orig_it } => EditCmd::CloseIntersection {
+                id: self.get_i(*id)?,
+                orig_it: *orig_it,
+            },
+            PermanentEditCmd::UncloseIntersection(id, orig_it) => {
+                EditCmd::UncloseIntersection(self.get_i(*id)?, *orig_it)
+            }
+            PermanentEditCmd::BanTurns(ban) => EditCmd::BanTurns(self.get_turn_ban(ban)?),
+            PermanentEditCmd::AllowTurns(ban) => EditCmd::AllowTurns(self.get_turn_ban(ban)?),
+            PermanentEditCmd::AddLane {
+                road,
+                fwds,
+                idx,
+                lt,
+            } => {
+                let road = self.get_r(*road)?;
+                let lanes = side(&mut self.current_lanes, self.map, road, *fwds);
+                if *idx > lanes.len() {
+                    return Err(format!("{} doesn't have {} lanes", road, idx));
+                }
+                // Applying the edits starts from the unedited map, so added lanes come right after
+                // its lanes.
+                let id = LaneID(self.num_base_lanes + self.added_lanes.iter().flatten().count());
+                lanes.insert(*idx, id);
+                self.added_lanes.push(Some((id, road)));
+                EditCmd::AddLane {
+                    road,
+                    fwds: *fwds,
+                    idx: *idx,
+                    lt: *lt,
+                }
+            }
+            PermanentEditCmd::DeleteLane { id, fwds, idx } => {
+                let id = self.get_lane(*id)?;
+                let road = self.parent(id);
+                let lanes = side(&mut self.current_lanes, self.map, road, *fwds);
+                if lanes.get(*idx) != Some(&id) {
+                    return Err(format!("{} has moved", id));
+                }
+                lanes.remove(*idx);
+                EditCmd::DeleteLane {
+                    id,
+                    fwds: *fwds,
+                    idx: *idx,
+                }
+            }
+            PermanentEditCmd::ChangeLaneWidth { id, new, old } => EditCmd::ChangeLaneWidth {
+                id: self.get_lane(*id)?,
+                new: *new,
+                old: *old,
+            },
+            PermanentEditCmd::ChangeSpeedLimit { road, new, old } => EditCmd::ChangeSpeedLimit {
+                road: self.get_r(*road)?,
+                new: *new,
+                old: *old,
+            },
+        })
+    }
+
+    fn get_r(&self, r: OriginalRoad) -> Result<RoadID, String> {
+        self.roads
+            .get(&r)
+            .cloned()
+            .ok_or_else(|| format!("{} doesn't exist", r))
+    }
+
+    fn get_i(&self, i: OriginalIntersection) -> Result<IntersectionID, String> {
+        self.intersections
+            .get(&i)
+            .cloned()
+            .ok_or_else(|| format!("{} doesn't exist", i))
+    }
+
+    fn get_lane(&self, l: OriginalLane) -> Result<LaneID, String> {
+        match l {
+            OriginalLane::Road { parent, fwds, idx } => {
+                let r = self.get_r(parent)?;
+                let lanes = if let Some((fwd_lanes, back_lanes)) = self.base_lanes.get(&r) {
+                    if fwds {
+                        fwd_lanes.clone()
+                    } else {
+                        back_lanes.clone()
+                    }
+                } else {
+                    let road = self.map.get_r(r);
+                    if fwds {
+                        &road.children_forwards
+                    } else {
+                        &road.children_backwards
+                    }
+                    .iter()
+                    .map(|(l, _)| *l)
+                    .collect()
+                };
+                lanes.get(idx).cloned().ok_or_else(|| {
+                    format!(
+                        "{} doesn't have lane {} going {}",
+                        parent,
+                        idx,
+                        if fwds { "forwards" } else { "backwards" }
+                    )
+                })
+            }
+            OriginalLane::Added(n) => match self.added_lanes.get(n) {
+                Some(Some((l, _))) => Ok(*l),
+                Some(None) => Err(format!("added lane #{} was skipped", n)),
+                None => Err(format!("added lane #{} doesn't exist yet", n)),
+            },
+        }
+    }
+
+    fn get_turn(&self, t: OriginalTurn) -> Result<TurnID, String> {
+        Ok(TurnID {
+            parent: self.get_i(t.parent)?,
+            src: self.get_lane(t.src)?,
+            dst: self.get_lane(t.dst)?,
+        })
+    }
+
+    fn get_turn_group(&self, g: OriginalTurnGroup) -> Result<TurnGroupID, String> {
+        Ok(TurnGroupID {
+            from: self.get_r(g.from)?,
+            to: self.get_r(g.to)?,
+            crosswalk: if let Some(t) = g.crosswalk {
+                Some(self.get_turn(t)?)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn get_turn_ban(&self, ban: &PermanentTurnBan) -> Result<TurnBan, String> {
+        Ok(match ban {
+            PermanentTurnBan::Turn(t) => TurnBan::Turn(self.get_turn(*t)?),
+            PermanentTurnBan::Group(i, g) => {
+                TurnBan::Group(self.get_i(*i)?, self.get_turn_group(*g)?)
+            }
+        })
+    }
+}
+
+// The lanes on one side of a road, starting from the lanes in the map
+fn side<'a>(
+    lanes: &'a mut BTreeMap<RoadID, (Vec<LaneID>, Vec<LaneID>)>,
+    map: &Map,
+    r: RoadID,
+    fwds: bool,
+) -> &'a mut Vec<LaneID> {
+    let pair = lanes.entry(r).or_insert_with(|| {
+        let road = map.get_r(r);
+        (
+            road.children_forwards.iter().map(|(l, _)| *l).collect(),
+            road.children_backwards.iter().map(|(l, _)| *l).collect(),
+        )
+    });
+    if fwds {
+        &mut pair.0
+    } else {
+        &mut pair.1
+    }
+}
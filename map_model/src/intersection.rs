@@ -1,6 +1,6 @@
 use crate::raw::OriginalIntersection;
-use crate::{DirectedRoadID, LaneID, Map, PathConstraints, Road, RoadID, TurnID};
-use geom::Polygon;
+use crate::{DirectedRoadID, LaneID, Map, PathConstraints, Road, RoadID, TurnBan, TurnID};
+use geom::{Distance, Polygon};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::fmt;
@@ -32,6 +32,8 @@ pub struct Intersection {
 
     pub intersection_type: IntersectionType,
     pub orig_id: OriginalIntersection,
+    // Above sea level. None if there's no data.
+    pub elevation: Option<Distance>,
 
     // Note that a lane may belong to both incoming_lanes and outgoing_lanes.
     // TODO narrow down when and why. is it just sidewalks in weird cases?
@@ -39,6 +41,8 @@ pub struct Intersection {
     pub outgoing_lanes: Vec<LaneID>,
 
     pub roads: BTreeSet<RoadID>,
+    // From map edits
+    pub banned_turns: BTreeSet<TurnBan>,
 }
 
 impl Intersection {
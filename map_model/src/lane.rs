@@ -30,6 +30,7 @@ pub enum LaneType {
     Bus,
     SharedLeftTurn,
     Construction,
+    LightRail,
 }
 
 impl LaneType {
@@ -42,6 +43,7 @@ impl LaneType {
             LaneType::Sidewalk => false,
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
+            LaneType::LightRail => true,
         }
     }
 
@@ -54,6 +56,7 @@ impl LaneType {
             LaneType::Sidewalk => true,
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
+            LaneType::LightRail => true,
         }
     }
 
@@ -66,6 +69,7 @@ impl LaneType {
             LaneType::Sidewalk => "a sidewalk",
             LaneType::SharedLeftTurn => "a shared left-turn lane",
             LaneType::Construction => "a lane that's closed for construction",
+            LaneType::LightRail => "a light rail track",
         }
     }
 }
@@ -76,6 +80,8 @@ pub struct Lane {
     pub parent: RoadID,
     pub lane_type: LaneType,
     pub lane_center_pts: PolyLine,
+    // LANE_THICKNESS unless map edits changed it
+    pub width: Distance,
 
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
@@ -87,6 +93,13 @@ pub struct Lane {
     // If set, cars trying to park near here should actually start their search at this other lane.
     // Only populated for driving lanes inevitably leading to borders.
     pub parking_blackhole: Option<LaneID>,
+
+    // Rise over run from src_i to dst_i, so positive is uphill.
+    pub grade: f64,
+
+    // Deleted by map edits. LaneIDs index into a Vec, so the lane sticks around, but it doesn't
+    // belong to its road or any intersection anymore.
+    pub deleted: bool,
 }
 
 impl Lane {
@@ -143,6 +156,16 @@ impl Lane {
         self.lane_center_pts.length()
     }
 
+    // How much faster (> 1) or slower (< 1) than on flat ground somebody can bike along this lane.
+    // A 5% climb is about 40% slower. Going downhill is a bit faster, until braking kicks in.
+    pub fn bike_speed_factor(&self) -> f64 {
+        if self.grade > 0.0 {
+            1.0 / (1.0 + 12.0 * self.grade)
+        } else {
+            (1.0 - 3.0 * self.grade).min(1.3)
+        }
+    }
+
     pub fn intersections(&self) -> Vec<IntersectionID> {
         // TODO I think we're assuming there are no loop lanes
         vec![self.src_i, self.dst_i]
@@ -181,6 +204,10 @@ impl Lane {
         self.lane_type == LaneType::Parking
     }
 
+    pub fn is_light_rail(&self) -> bool {
+        self.lane_type == LaneType::LightRail
+    }
+
     // TODO Store this natively if this winds up being useful.
     pub fn get_directed_parent(&self, map: &Map) -> DirectedRoadID {
         let r = map.get_r(self.parent);
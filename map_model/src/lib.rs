@@ -2,6 +2,7 @@ mod area;
 mod building;
 mod bus_stop;
 pub mod connectivity;
+mod corridor;
 mod edits;
 mod intersection;
 mod lane;
@@ -20,16 +21,17 @@ mod turn;
 pub use crate::area::{Area, AreaID, AreaType};
 pub use crate::building::{Building, BuildingID, FrontPath, OffstreetParking};
 pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
-pub use crate::edits::{EditCmd, EditEffects, MapEdits};
+pub use crate::corridor::Corridor;
+pub use crate::edits::{EditCmd, EditEffects, EditsDiff, MapEdits, TurnBan};
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::lane::{Lane, LaneID, LaneType, PARKING_SPOT_LENGTH};
 pub use crate::make::RoadSpec;
 pub use crate::map::Map;
 pub use crate::neighborhood::{FullNeighborhoodInfo, Neighborhood, NeighborhoodBuilder};
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
-pub use crate::road::{DirectedRoadID, Road, RoadID};
+pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep, TravelTimes};
+pub use crate::road::{speed_limit_from_osm, DirectedRoadID, Road, RoadID};
 pub use crate::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::traffic_signals::{ControlTrafficSignal, Phase};
+pub use crate::traffic_signals::{ActuatedTiming, ControlTrafficSignal, Phase};
 pub use crate::traversable::{Position, Traversable};
 pub use crate::turn::{Turn, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
 use abstutil::Cloneable;
@@ -1,4 +1,4 @@
-use crate::make::sidewalk_finder::find_sidewalk_points;
+use crate::make::sidewalk_finder::{find_lane_points, find_sidewalk_points};
 use crate::{
     BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints, PathRequest,
     Position,
@@ -8,6 +8,13 @@ use geom::{Bounds, Distance, GPSBounds, HashablePt2D, Pt2D};
 use gtfs;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
+// GTFS doesn't say what vehicles serve a route. Assume a standard 40' bus, or two coupled light
+// rail cars.
+const BUS_SEATED_CAPACITY: usize = 40;
+const BUS_STANDING_CAPACITY: usize = 30;
+const TRAIN_SEATED_CAPACITY: usize = 2 * 74;
+const TRAIN_STANDING_CAPACITY: usize = 2 * 120;
+
 pub fn make_bus_stops(
     map: &Map,
     bus_routes: &Vec<gtfs::Route>,
@@ -17,25 +24,32 @@ pub fn make_bus_stops(
 ) -> (BTreeMap<BusStopID, BusStop>, Vec<BusRoute>) {
     timer.start("make bus stops");
     let mut bus_stop_pts: HashSet<HashablePt2D> = HashSet::new();
-    let mut route_lookups: HashMap<String, Vec<HashablePt2D>> = HashMap::new();
+    let mut train_stop_pts: HashSet<HashablePt2D> = HashSet::new();
+    // Remember the index into the original stops, to line up the schedule.
+    let mut route_lookups: HashMap<String, Vec<(usize, HashablePt2D)>> = HashMap::new();
     for route in bus_routes {
-        for gps in &route.stops {
+        for (idx, gps) in route.stops.iter().enumerate() {
             if let Some(pt) = Pt2D::from_gps(*gps, gps_bounds) {
                 let hash_pt = pt.to_hashable();
-                bus_stop_pts.insert(hash_pt);
+                if route.is_train {
+                    train_stop_pts.insert(hash_pt);
+                } else {
+                    bus_stop_pts.insert(hash_pt);
+                }
                 route_lookups
                     .entry(route.name.clone())
                     .or_insert_with(Vec::new)
-                    .push(hash_pt);
+                    .push((idx, hash_pt));
             }
         }
     }
 
+    // Nothing's been deleted by map edits yet while the map is being built.
     let mut stops_per_sidewalk: MultiMap<LaneID, (Distance, HashablePt2D)> = MultiMap::new();
     for (pt, pos) in find_sidewalk_points(
         bounds,
         bus_stop_pts,
-        map.all_lanes(),
+        map.all_lanes_including_deleted(),
         Distance::meters(10.0),
         timer,
     )
@@ -43,56 +57,114 @@ pub fn make_bus_stops(
     {
         stops_per_sidewalk.insert(pos.lane(), (pos.dist_along(), pt));
     }
+
+    // Light rail stations first snap to the tracks, then to the closest sidewalk, which might be
+    // further away than usual.
+    let mut train_driving_pos: HashMap<HashablePt2D, Position> = HashMap::new();
+    let mut track_pt_to_orig_pt: HashMap<HashablePt2D, HashablePt2D> = HashMap::new();
+    for (pt, pos) in find_lane_points(
+        bounds,
+        train_stop_pts,
+        map.all_lanes_including_deleted(),
+        LaneType::LightRail,
+        Distance::meters(10.0),
+        timer,
+    )
+    .into_iter()
+    {
+        track_pt_to_orig_pt.insert(pos.pt(map).to_hashable(), pt);
+        train_driving_pos.insert(pt, pos);
+    }
+    for (track_pt, pos) in find_sidewalk_points(
+        bounds,
+        track_pt_to_orig_pt.keys().cloned().collect(),
+        map.all_lanes_including_deleted(),
+        Distance::meters(30.0),
+        timer,
+    )
+    .into_iter()
+    {
+        stops_per_sidewalk.insert(
+            pos.lane(),
+            (pos.dist_along(), track_pt_to_orig_pt[&track_pt]),
+        );
+    }
     let mut point_to_stop_id: HashMap<HashablePt2D, BusStopID> = HashMap::new();
     let mut bus_stops: BTreeMap<BusStopID, BusStop> = BTreeMap::new();
 
     for (sidewalk_id, dists_set) in stops_per_sidewalk.consume().into_iter() {
         let road = map.get_parent(sidewalk_id);
-        if let Ok(driving_lane) =
-            road.find_closest_lane(sidewalk_id, vec![LaneType::Driving, LaneType::Bus])
-        {
-            let mut dists: Vec<(Distance, HashablePt2D)> = dists_set.into_iter().collect();
-            dists.sort_by_key(|(dist, _)| *dist);
-            for (idx, (dist_along, orig_pt)) in dists.into_iter().enumerate() {
-                let stop_id = BusStopID {
-                    sidewalk: sidewalk_id,
-                    idx,
-                };
-                point_to_stop_id.insert(orig_pt, stop_id);
-                let sidewalk_pos = Position::new(sidewalk_id, dist_along);
-                let driving_pos = sidewalk_pos.equiv_pos(driving_lane, Distance::ZERO, map);
-                bus_stops.insert(
-                    stop_id,
-                    BusStop {
-                        id: stop_id,
-                        sidewalk_pos,
-                        driving_pos,
-                    },
-                );
-            }
-        } else {
-            timer.warn(format!(
-                "Can't find driving lane next to {}: {:?} and {:?}",
-                sidewalk_id, road.children_forwards, road.children_backwards
-            ));
+        let driving_lane = road
+            .find_closest_lane(sidewalk_id, vec![LaneType::Driving, LaneType::Bus])
+            .ok();
+        let mut dists: Vec<(Distance, HashablePt2D)> = dists_set.into_iter().collect();
+        dists.sort_by_key(|(dist, _)| *dist);
+        for (idx, (dist_along, orig_pt)) in dists.into_iter().enumerate() {
+            let sidewalk_pos = Position::new(sidewalk_id, dist_along);
+            let (driving_pos, is_train_stop) = if let Some(pos) = train_driving_pos.get(&orig_pt) {
+                (*pos, true)
+            } else if let Some(l) = driving_lane {
+                (sidewalk_pos.equiv_pos(l, Distance::ZERO, map), false)
+            } else {
+                timer.warn(format!(
+                    "Can't find driving lane next to {}: {:?} and {:?}",
+                    sidewalk_id, road.children_forwards, road.children_backwards
+                ));
+                continue;
+            };
+            let stop_id = BusStopID {
+                sidewalk: sidewalk_id,
+                idx,
+            };
+            point_to_stop_id.insert(orig_pt, stop_id);
+            bus_stops.insert(
+                stop_id,
+                BusStop {
+                    id: stop_id,
+                    sidewalk_pos,
+                    driving_pos,
+                    is_train_stop,
+                },
+            );
         }
     }
 
     let mut routes: Vec<BusRoute> = Vec::new();
     for route in bus_routes {
         let route_name = route.name.to_string();
-        let stops: Vec<BusStopID> = route_lookups
+        let (orig_indices, stops): (Vec<usize>, Vec<BusStopID>) = route_lookups
             .remove(&route_name)
             .unwrap_or_else(Vec::new)
             .into_iter()
-            .filter_map(|pt| point_to_stop_id.get(&pt))
-            .cloned()
+            .filter_map(|(idx, pt)| point_to_stop_id.get(&pt).map(|id| (idx, *id)))
+            .unzip();
+        let scheduled_arrivals = route
+            .scheduled_arrivals
+            .iter()
+            .map(|times| orig_indices.iter().map(|idx| times[*idx]).collect())
             .collect();
         let id = BusRouteID(routes.len());
         routes.push(BusRoute {
             id,
             name: route_name.to_string(),
             stops,
+            route_type: if route.is_train {
+                PathConstraints::Train
+            } else {
+                PathConstraints::Bus
+            },
+            departures: route.departures.clone(),
+            scheduled_arrivals,
+            seated_capacity: if route.is_train {
+                TRAIN_SEATED_CAPACITY
+            } else {
+                BUS_SEATED_CAPACITY
+            },
+            standing_capacity: if route.is_train {
+                TRAIN_STANDING_CAPACITY
+            } else {
+                BUS_STANDING_CAPACITY
+            },
         });
     }
     timer.stop("make bus stops");
@@ -100,30 +172,39 @@ pub fn make_bus_stops(
 }
 
 pub fn fix_bus_route(map: &Map, r: &mut BusRoute) -> bool {
-    // Trim out stops if needed; map borders sometimes mean some paths don't work.
-    let mut stops = Vec::new();
-    for stop in r.stops.drain(..) {
+    // Trim out stops if needed; map borders sometimes mean some paths don't work. Remember which
+    // stops are kept, to trim the schedule too.
+    let mut stops: Vec<(usize, BusStopID)> = Vec::new();
+    for (idx, stop) in r.stops.drain(..).enumerate() {
         if stops.is_empty() {
-            stops.push(stop);
+            stops.push((idx, stop));
         } else {
-            if check_stops(*stops.last().unwrap(), stop, map) {
-                stops.push(stop);
+            if check_stops(r.route_type, stops.last().unwrap().1, stop, map) {
+                stops.push((idx, stop));
             }
         }
     }
     // Don't forget the last and first
     while stops.len() >= 2 {
-        if check_stops(*stops.last().unwrap(), stops[0], map) {
+        if check_stops(r.route_type, stops.last().unwrap().1, stops[0].1, map) {
             break;
         }
         // TODO Or the front one
         stops.pop();
     }
-    r.stops = stops;
+    for times in r.scheduled_arrivals.iter_mut() {
+        *times = stops.iter().map(|(idx, _)| times[*idx]).collect();
+    }
+    r.stops = stops.into_iter().map(|(_, stop)| stop).collect();
     r.stops.len() >= 2
 }
 
-fn check_stops(stop1: BusStopID, stop2: BusStopID, map: &Map) -> bool {
+fn check_stops(
+    constraints: PathConstraints,
+    stop1: BusStopID,
+    stop2: BusStopID,
+    map: &Map,
+) -> bool {
     let bs1 = map.get_bs(stop1);
     let bs2 = map.get_bs(stop2);
     // This is coming up because the dist_along's are in a bad order. But why should
@@ -133,7 +214,8 @@ fn check_stops(stop1: BusStopID, stop2: BusStopID, map: &Map) -> bool {
         .pathfind(PathRequest {
             start: bs1.driving_pos,
             end: bs2.driving_pos,
-            constraints: PathConstraints::Bus,
+            constraints,
+            departure_time: None,
         })
         .is_some();
     ok1 && ok2
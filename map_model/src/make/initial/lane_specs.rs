@@ -14,6 +14,17 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
     }
 
     // Easy special cases first.
+    if osm_tags.get(osm::RAILWAY) == Some(&"light_rail".to_string())
+        || osm_tags.get(osm::RAILWAY) == Some(&"tram".to_string())
+    {
+        // Single tracks are usually used in both directions.
+        if osm_tags.get("oneway") == Some(&"yes".to_string())
+            || osm_tags.get("railway:preferred_direction") == Some(&"forward".to_string())
+        {
+            return (vec![LaneType::LightRail], Vec::new());
+        }
+        return (vec![LaneType::LightRail], vec![LaneType::LightRail]);
+    }
     if osm_tags.get("junction") == Some(&"roundabout".to_string()) {
         return (vec![LaneType::Driving, LaneType::Sidewalk], Vec::new());
     }
@@ -196,6 +207,7 @@ impl RoadSpec {
             LaneType::Bus => 'u',
             LaneType::SharedLeftTurn => 'l',
             LaneType::Construction => 'c',
+            LaneType::LightRail => 'r',
         }
     }
 
@@ -208,6 +220,7 @@ impl RoadSpec {
             'u' => Some(LaneType::Bus),
             'l' => Some(LaneType::SharedLeftTurn),
             'c' => Some(LaneType::Construction),
+            'r' => Some(LaneType::LightRail),
             _ => None,
         }
     }
@@ -61,6 +61,7 @@ pub struct Intersection {
     pub polygon: Vec<Pt2D>,
     pub roads: BTreeSet<OriginalRoad>,
     pub intersection_type: IntersectionType,
+    pub elevation: Option<Distance>,
 }
 
 impl InitialMap {
@@ -80,6 +81,7 @@ impl InitialMap {
                     polygon: Vec::new(),
                     roads: BTreeSet::new(),
                     intersection_type: i.intersection_type,
+                    elevation: i.elevation,
                 },
             );
         }
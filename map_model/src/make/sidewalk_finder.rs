@@ -1,4 +1,4 @@
-use crate::{Lane, LaneID, Position};
+use crate::{Lane, LaneID, LaneType, Position};
 use abstutil::Timer;
 use geom::{Bounds, Distance, FindClosest, HashablePt2D};
 use std::collections::{HashMap, HashSet};
@@ -11,6 +11,18 @@ pub fn find_sidewalk_points(
     lanes: &Vec<Lane>,
     max_dist_away: Distance,
     timer: &mut Timer,
+) -> HashMap<HashablePt2D, Position> {
+    find_lane_points(bounds, pts, lanes, LaneType::Sidewalk, max_dist_away, timer)
+}
+
+// Same as find_sidewalk_points, but for any type of lane.
+pub fn find_lane_points(
+    bounds: &Bounds,
+    pts: HashSet<HashablePt2D>,
+    lanes: &Vec<Lane>,
+    lane_type: LaneType,
+    max_dist_away: Distance,
+    timer: &mut Timer,
 ) -> HashMap<HashablePt2D, Position> {
     if pts.is_empty() {
         return HashMap::new();
@@ -20,26 +32,24 @@ pub fn find_sidewalk_points(
     timer.start_iter("index lanes", lanes.len());
     for l in lanes {
         timer.next();
-        if l.is_sidewalk() {
+        if l.lane_type == lane_type {
             closest.add(l.id, l.lane_center_pts.points());
         }
     }
 
-    // For each point, find the closest point to any sidewalk, using the quadtree to prune the
-    // search.
+    // For each point, find the closest point to any lane, using the quadtree to prune the search.
     let mut results: HashMap<HashablePt2D, Position> = HashMap::new();
-    timer.start_iter("find closest sidewalk point", pts.len());
+    timer.start_iter("find closest lane point", pts.len());
     for query_pt in pts {
         timer.next();
-        if let Some((sidewalk, sidewalk_pt)) = closest.closest_pt(query_pt.to_pt2d(), max_dist_away)
-        {
-            if let Some(dist_along) = lanes[sidewalk.0].dist_along_of_point(sidewalk_pt) {
-                results.insert(query_pt, Position::new(sidewalk, dist_along));
+        if let Some((lane, lane_pt)) = closest.closest_pt(query_pt.to_pt2d(), max_dist_away) {
+            if let Some(dist_along) = lanes[lane.0].dist_along_of_point(lane_pt) {
+                results.insert(query_pt, Position::new(lane, dist_along));
             } else {
                 panic!(
                     "{} isn't on {} according to dist_along_of_point, even though closest_point \
                      thinks it is.\n{}",
-                    sidewalk_pt, sidewalk, lanes[sidewalk.0].lane_center_pts
+                    lane_pt, lane, lanes[lane.0].lane_center_pts
                 );
             }
         }
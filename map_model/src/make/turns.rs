@@ -30,7 +30,9 @@ pub fn make_all_turns(
             continue;
         }
 
-        if is_turn_allowed(&turn, roads, lanes) {
+        if is_turn_allowed(&turn, roads, lanes)
+            && !i.banned_turns.iter().any(|ban| ban.matches(&turn, lanes))
+        {
             final_turns.push(turn);
         } else {
             filtered_turns
@@ -51,7 +53,8 @@ pub fn make_all_turns(
     for t in &final_turns {
         incoming_missing.remove(&t.id.src);
     }
-    // Turn restrictions are buggy. If they orphan a lane, restore the filtered turns.
+    // Turn restrictions are buggy. If they orphan a lane, restore the filtered turns. Banned turns
+    // are checked before being edited in, so this shouldn't happen for them.
     for (l, turns) in filtered_turns {
         if incoming_missing.contains(&l) {
             timer.warn(format!(
@@ -620,6 +623,10 @@ fn get_sidewalk<'a>(lanes: &'a Vec<Lane>, children: &Vec<(LaneID, LaneType)>) ->
 }
 
 fn filter_vehicle_lanes(lanes: &Vec<(LaneID, LaneType)>, preferred: LaneType) -> Vec<LaneID> {
+    // Tracks only connect to other tracks.
+    if preferred == LaneType::LightRail {
+        return filter_lanes(lanes, preferred);
+    }
     let preferred = filter_lanes(lanes, preferred);
     if !preferred.is_empty() {
         return preferred;
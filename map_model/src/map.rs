@@ -1,13 +1,14 @@
 use crate::pathfind::Pathfinder;
 use crate::raw::{OriginalIntersection, OriginalRoad, RawMap};
 use crate::{
-    connectivity, make, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
+    connectivity, make, road, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
     BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
     IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits, Path, PathConstraints,
-    PathRequest, Position, Road, RoadID, Turn, TurnGroupID, TurnID, TurnType, LANE_THICKNESS,
+    PathRequest, Position, Road, RoadID, TravelTimes, Turn, TurnBan, TurnGroup, TurnGroupID,
+    TurnID, TurnType, LANE_THICKNESS,
 };
-use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer, Warn};
+use geom::{Bounds, Distance, Duration, GPSBounds, Line, PolyLine, Polygon, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
@@ -68,6 +69,12 @@ impl Map {
         Map::create_from_raw(raw, timer)
     }
 
+    // The pathfinder keeps per-thread scratch space that can't be cloned, so Map doesn't implement
+    // Clone. This makes a full copy, minus any observed travel times.
+    pub fn deep_clone(&self) -> Map {
+        abstutil::clone_via_binary(self)
+    }
+
     // Just for temporary std::mem::replace tricks.
     pub fn blank() -> Map {
         Map {
@@ -207,7 +214,14 @@ impl Map {
         &self.roads
     }
 
-    pub fn all_lanes(&self) -> &Vec<Lane> {
+    // Skips lanes deleted by map edits.
+    pub fn all_lanes(&self) -> impl Iterator<Item = &Lane> {
+        self.lanes.iter().filter(|l| !l.deleted)
+    }
+
+    // LaneIDs index into this, so lanes deleted by map edits are still here. Only use this when
+    // something needs to line up with every LaneID; otherwise use all_lanes.
+    pub fn all_lanes_including_deleted(&self) -> &Vec<Lane> {
         &self.lanes
     }
 
@@ -596,6 +610,27 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
+    // Like pathfind for cars, but also avoiding lanes with some extra delay right now.
+    pub fn pathfind_with_delays(
+        &self,
+        req: PathRequest,
+        delays: &BTreeMap<LaneID, Duration>,
+    ) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .as_ref()
+            .unwrap()
+            .pathfind_with_delays(req, self, delays)
+    }
+
+    // Cars asking for a departure_time will route around congestion observed in these.
+    pub fn set_travel_times(&mut self, travel_times: TravelTimes, timer: &mut Timer) {
+        assert!(!self.pathfinder_dirty);
+        let mut pathfinder = self.pathfinder.take().unwrap();
+        pathfinder.set_travel_times(self, travel_times, timer);
+        self.pathfinder = Some(pathfinder);
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -656,6 +691,7 @@ impl Map {
         // TODO More efficient ways to do this: given two sets of edits, produce a smaller diff.
         // Simplest strategy: Remove common prefix.
         let mut effects = EditEffects::new();
+        new_edits.update_history(&self.edits);
 
         // First undo all existing edits.
         let mut undo = std::mem::replace(&mut self.edits.commands, Vec::new());
@@ -685,6 +721,10 @@ impl Map {
         for id in &effects.changed_roads {
             let stops = self.get_r(*id).all_bus_stops(self);
             for s in stops {
+                // Tracks aren't editable, so light rail stations stay put.
+                if self.get_bs(s).is_train_stop {
+                    continue;
+                }
                 let sidewalk_pos = self.get_bs(s).sidewalk_pos;
                 // Must exist, because we aren't allowed to orphan a bus stop.
                 let driving_lane = self
@@ -700,7 +740,12 @@ impl Map {
         self.edits = new_edits;
         self.pathfinder_dirty = true;
         (
-            effects.changed_lanes,
+            // Some of these might've been added, then later removed.
+            effects
+                .changed_lanes
+                .into_iter()
+                .filter(|l| l.0 < self.lanes.len())
+                .collect(),
             // TODO We just care about contraflow roads here
             effects.changed_roads,
             effects.deleted_turns,
@@ -714,6 +759,13 @@ impl Map {
         )
     }
 
+    // For quickly trying out different signal timing. This doesn't record anything in MapEdits.
+    // Pathfinding doesn't care about signal timing, so nothing else needs to be recalculated.
+    pub fn incremental_edit_traffic_signal(&mut self, signal: ControlTrafficSignal) {
+        assert!(self.traffic_signals.contains_key(&signal.id));
+        self.traffic_signals.insert(signal.id, signal);
+    }
+
     pub fn recalculate_pathfinding_after_edits(&mut self, timer: &mut Timer) {
         if !self.pathfinder_dirty {
             return;
@@ -784,9 +836,11 @@ fn make_half_map(
             // Might change later
             intersection_type: i.intersection_type,
             orig_id: i.id,
+            elevation: i.elevation,
             incoming_lanes: Vec::new(),
             outgoing_lanes: Vec::new(),
             roads: i.roads.iter().map(|id| road_id_mapping[id]).collect(),
+            banned_turns: BTreeSet::new(),
         });
         intersection_id_mapping.insert(i.id, id);
     }
@@ -811,8 +865,10 @@ fn make_half_map(
             children_forwards: Vec::new(),
             children_backwards: Vec::new(),
             center_pts: r.trimmed_center_pts.clone(),
+            original_center_pts: r.original_center_pts.clone(),
             src_i: i1,
             dst_i: i2,
+            speed_limit: road::speed_limit_from_osm(&raw.roads[&r.id].osm_tags),
         };
 
         for lane in &r.lane_specs {
@@ -822,27 +878,30 @@ fn make_half_map(
             map.intersections[src_i.0].outgoing_lanes.push(id);
             map.intersections[dst_i.0].incoming_lanes.push(id);
 
-            let (unshifted_pts, offset) = if lane.reverse_pts {
+            let offset = if lane.reverse_pts {
                 road.children_backwards.push((id, lane.lane_type));
-                (
-                    road.center_pts.reversed(),
-                    road.children_backwards.len() - 1,
-                )
+                road.children_backwards.len() - 1
             } else {
                 road.children_forwards.push((id, lane.lane_type));
-                (road.center_pts.clone(), road.children_forwards.len() - 1)
+                road.children_forwards.len() - 1
             };
-            // TODO probably different behavior for oneways
-            // TODO need to factor in yellow center lines (but what's the right thing to even do?
-            // Reverse points for British-style driving on the left
-            let width = LANE_THICKNESS * (0.5 + (offset as f64));
-            let lane_center_pts = unshifted_pts
-                .shift_right(width)
-                .with_context(timer, format!("shift for {}", id));
+            let lane_center_pts = lane_center_pts(
+                &road,
+                !lane.reverse_pts,
+                LANE_THICKNESS * (offset as f64),
+                LANE_THICKNESS,
+            )
+            .with_context(timer, format!("shift for {}", id));
+            let grade = grade(
+                map.intersections[src_i.0].elevation,
+                map.intersections[dst_i.0].elevation,
+                lane_center_pts.length(),
+            );
 
             map.lanes.push(Lane {
                 id,
                 lane_center_pts,
+                width: LANE_THICKNESS,
                 src_i,
                 dst_i,
                 lane_type: lane.lane_type,
@@ -850,6 +909,8 @@ fn make_half_map(
                 building_paths: Vec::new(),
                 bus_stops: Vec::new(),
                 parking_blackhole: None,
+                grade,
+                deleted: false,
             });
         }
         if road.get_name() == "???" {
@@ -929,6 +990,25 @@ fn make_half_map(
     map
 }
 
+// The first lane in each direction is closest to the center of the road. The lanes closer to the
+// center than this one take up inner_width.
+// TODO probably different behavior for oneways
+// TODO need to factor in yellow center lines (but what's the right thing to even do?
+// Reverse points for British-style driving on the left
+fn lane_center_pts(
+    road: &Road,
+    fwds: bool,
+    inner_width: Distance,
+    width: Distance,
+) -> Warn<PolyLine> {
+    let unshifted_pts = if fwds {
+        road.center_pts.clone()
+    } else {
+        road.center_pts.reversed()
+    };
+    unshifted_pts.shift_right(inner_width + width / 2.0)
+}
+
 fn is_border(intersection: &Intersection, lanes: &Vec<Lane>) -> bool {
     // RawIntersection said it is.
     if intersection.is_border() {
@@ -1046,12 +1126,30 @@ impl EditCmd {
                 true
             }
             EditCmd::ChangeTrafficSignal(ref ts) => {
-                if &map.traffic_signals[&ts.id] == ts {
+                // Edits loaded from a file were made before earlier commands changed the turns, so
+                // check the signal against the turn groups the intersection has now.
+                if !map.traffic_signals.contains_key(&ts.id) {
+                    timer.warn(format!("Skipping edit to {}: no traffic signal", ts.id));
+                    return false;
+                }
+                let ts = match (ControlTrafficSignal {
+                    turn_groups: TurnGroup::for_i(ts.id, map),
+                    ..ts.clone()
+                })
+                .validate()
+                {
+                    Ok(ts) => ts,
+                    Err(err) => {
+                        timer.warn(format!("Skipping edit to {}: {}", ts.id, err));
+                        return false;
+                    }
+                };
+                if map.traffic_signals[&ts.id] == ts {
                     return false;
                 }
 
-                map.traffic_signals.insert(ts.id, ts.clone());
                 effects.changed_intersections.insert(ts.id);
+                map.traffic_signals.insert(ts.id, ts);
                 true
             }
             EditCmd::CloseIntersection { id, .. } => {
@@ -1088,6 +1186,100 @@ impl EditCmd {
                 effects.changed_intersections.insert(id);
                 true
             }
+            EditCmd::AddLane {
+                road,
+                fwds,
+                idx,
+                lt,
+            } => {
+                let id = LaneID(map.lanes.len());
+                let r = &mut map.roads[road.0];
+                let (src_i, dst_i) = if *fwds {
+                    r.children_forwards.insert(*idx, (id, *lt));
+                    (r.src_i, r.dst_i)
+                } else {
+                    r.children_backwards.insert(*idx, (id, *lt));
+                    (r.dst_i, r.src_i)
+                };
+                map.intersections[src_i.0].outgoing_lanes.push(id);
+                map.intersections[dst_i.0].incoming_lanes.push(id);
+                map.lanes.push(Lane {
+                    id,
+                    parent: *road,
+                    lane_type: *lt,
+                    // restripe fills out the real geometry
+                    lane_center_pts: map.roads[road.0].center_pts.clone(),
+                    width: LANE_THICKNESS,
+                    src_i,
+                    dst_i,
+                    building_paths: Vec::new(),
+                    bus_stops: Vec::new(),
+                    parking_blackhole: None,
+                    grade: 0.0,
+                    deleted: false,
+                });
+
+                effects.changed_lanes.insert(id);
+                restripe(*road, map, effects, timer);
+                true
+            }
+            EditCmd::DeleteLane { id, fwds, idx } => {
+                let lane = &mut map.lanes[id.0];
+                if lane.deleted {
+                    return false;
+                }
+                lane.deleted = true;
+
+                let r = &mut map.roads[lane.parent.0];
+                let children = if *fwds {
+                    &mut r.children_forwards
+                } else {
+                    &mut r.children_backwards
+                };
+                assert_eq!(children.remove(*idx).0, *id);
+                map.intersections[lane.src_i.0]
+                    .outgoing_lanes
+                    .retain(|l| l != id);
+                map.intersections[lane.dst_i.0]
+                    .incoming_lanes
+                    .retain(|l| l != id);
+
+                effects.changed_lanes.insert(*id);
+                let road = lane.parent;
+                restripe(road, map, effects, timer);
+                true
+            }
+            EditCmd::ChangeLaneWidth { id, new, .. } => {
+                let lane = &mut map.lanes[id.0];
+                if lane.deleted || lane.width == *new {
+                    return false;
+                }
+                lane.width = *new;
+                let road = lane.parent;
+                restripe(road, map, effects, timer);
+                true
+            }
+            EditCmd::ChangeSpeedLimit { road, new, .. } => {
+                if map.roads[road.0].speed_limit == *new {
+                    return false;
+                }
+                map.roads[road.0].speed_limit = *new;
+                effects.changed_roads.insert(*road);
+                true
+            }
+            EditCmd::BanTurns(ban) => {
+                if map.intersections[ban.parent().0].banned_turns.contains(ban) {
+                    return false;
+                }
+                // The UI checks this too, but edits loaded from a file might not make sense
+                // anymore.
+                if let Err(err) = ban.check(map) {
+                    timer.warn(format!("Skipping ban: {}", err));
+                    return false;
+                }
+                set_ban(*ban, true, effects, map, timer)
+            }
+            EditCmd::AllowTurns(ban) => set_ban(*ban, false, effects, map, timer),
         }
     }
 
@@ -1128,6 +1320,214 @@ impl EditCmd {
                 orig_it: *orig_it,
             }
             .apply(effects, map, timer),
+            EditCmd::AddLane {
+                road, fwds, idx, ..
+            } => {
+                let r = &mut map.roads[road.0];
+                let (id, _) = if *fwds {
+                    r.children_forwards.remove(*idx)
+                } else {
+                    r.children_backwards.remove(*idx)
+                };
+                // Commands are undone in reverse order, so the lane this added is still the last.
+                assert_eq!(id.0, map.lanes.len() - 1);
+                let lane = map.lanes.pop().unwrap();
+                map.intersections[lane.src_i.0]
+                    .outgoing_lanes
+                    .retain(|l| *l != id);
+                map.intersections[lane.dst_i.0]
+                    .incoming_lanes
+                    .retain(|l| *l != id);
+
+                restripe(*road, map, effects, timer);
+                true
+            }
+            EditCmd::DeleteLane { id, fwds, idx } => {
+                let lane = &mut map.lanes[id.0];
+                if !lane.deleted {
+                    return false;
+                }
+                lane.deleted = false;
+
+                let r = &mut map.roads[lane.parent.0];
+                if *fwds {
+                    r.children_forwards.insert(*idx, (*id, lane.lane_type));
+                } else {
+                    r.children_backwards.insert(*idx, (*id, lane.lane_type));
+                }
+                map.intersections[lane.src_i.0].outgoing_lanes.push(*id);
+                map.intersections[lane.dst_i.0].incoming_lanes.push(*id);
+
+                effects.changed_lanes.insert(*id);
+                let road = lane.parent;
+                restripe(road, map, effects, timer);
+                true
+            }
+            EditCmd::ChangeLaneWidth { id, new, old } => EditCmd::ChangeLaneWidth {
+                id: *id,
+                new: *old,
+                old: *new,
+            }
+            .apply(effects, map, timer),
+            EditCmd::ChangeSpeedLimit { road, new, old } => EditCmd::ChangeSpeedLimit {
+                road: *road,
+                new: *old,
+                old: *new,
+            }
+            .apply(effects, map, timer),
+            EditCmd::BanTurns(ban) => set_ban(*ban, false, effects, map, timer),
+            // This ban passed the check when it was first applied
+            EditCmd::AllowTurns(ban) => set_ban(*ban, true, effects, map, timer),
+        }
+    }
+}
+
+// True if it actually did anything.
+fn set_ban(
+    ban: TurnBan,
+    banned: bool,
+    effects: &mut EditEffects,
+    map: &mut Map,
+    timer: &mut Timer,
+) -> bool {
+    let id = ban.parent();
+    let bans = &mut map.intersections[id.0].banned_turns;
+    let changed = if banned {
+        bans.insert(ban)
+    } else {
+        bans.remove(&ban)
+    };
+    if !changed {
+        return false;
+    }
+    effects.changed_intersections.insert(id);
+    recalculate_turns(id, map, effects, timer);
+    true
+}
+
+// After adding, deleting, or resizing a lane, the road gets wider or narrower. Trim back every road
+// at both ends again and redo the intersection polygons, just like when the map is first made.
+// Then the lanes of all of those roads shift over, and the turns at both ends change.
+fn restripe(r: RoadID, map: &mut Map, effects: &mut EditEffects, timer: &mut Timer) {
+    let ends = vec![map.roads[r.0].src_i, map.roads[r.0].dst_i];
+    let mut roads: BTreeSet<RoadID> = BTreeSet::new();
+    for i in &ends {
+        roads.extend(map.intersections[i.0].roads.iter().cloned());
+    }
+
+    let mut initial_roads: BTreeMap<OriginalRoad, make::initial::Road> = BTreeMap::new();
+    for id in &roads {
+        let road = &map.roads[id.0];
+        initial_roads.insert(
+            road.orig_id,
+            make::initial::Road {
+                id: road.orig_id,
+                src_i: map.intersections[road.src_i.0].orig_id,
+                dst_i: map.intersections[road.dst_i.0].orig_id,
+                original_center_pts: road.original_center_pts.clone(),
+                trimmed_center_pts: untrimmed_center_pts(road, &ends, timer),
+                fwd_width: road.width(true, map),
+                back_width: road.width(false, map),
+                lane_specs: Vec::new(),
+            },
+        );
+    }
+    for id in &ends {
+        let i = &map.intersections[id.0];
+        let polygon = make::initial::intersection_polygon(
+            &make::initial::Intersection {
+                id: i.orig_id,
+                polygon: Vec::new(),
+                roads: i.roads.iter().map(|r| map.roads[r.0].orig_id).collect(),
+                intersection_type: i.intersection_type,
+                elevation: i.elevation,
+            },
+            &mut initial_roads,
+            timer,
+        )
+        .0;
+        map.intersections[id.0].polygon = Polygon::new(&polygon);
+        effects.changed_intersections.insert(*id);
+    }
+
+    for id in roads {
+        let road = &mut map.roads[id.0];
+        road.center_pts = initial_roads[&road.orig_id].trimmed_center_pts.clone();
+        effects.changed_roads.insert(id);
+        restripe_lanes(id, map, effects, timer);
+    }
+    for id in ends {
+        recalculate_turns(id, map, effects, timer);
+    }
+}
+
+// The center line of a road before it was trimmed back at these intersections, but still trimmed
+// at any other end.
+fn untrimmed_center_pts(road: &Road, ends: &Vec<IntersectionID>, timer: &mut Timer) -> PolyLine {
+    let orig = &road.original_center_pts;
+    let untrimmed = match (ends.contains(&road.src_i), ends.contains(&road.dst_i)) {
+        (true, true) => Some((orig.clone(), Distance::ZERO)),
+        (true, false) => orig
+            .dist_along_of_point(road.center_pts.last_pt())
+            .and_then(|(dist, _)| orig.slice(Distance::ZERO, dist.min(orig.length()))),
+        (false, true) => orig
+            .dist_along_of_point(road.center_pts.first_pt())
+            .and_then(|(dist, _)| orig.slice(dist.min(orig.length()), orig.length())),
+        (false, false) => unreachable!(),
+    };
+    untrimmed.map(|(pl, _)| pl).unwrap_or_else(|| {
+        timer.warn(format!(
+            "{} doesn't line up with its original center line; trimming it back more",
+            road.id
+        ));
+        road.center_pts.clone()
+    })
+}
+
+// Shift every lane of the road over from its center line.
+fn restripe_lanes(r: RoadID, map: &mut Map, effects: &mut EditEffects, timer: &mut Timer) {
+    let road = &map.roads[r.0];
+    let mut geometry = Vec::new();
+    for (fwds, children) in vec![
+        (true, &road.children_forwards),
+        (false, &road.children_backwards),
+    ] {
+        let mut inner_width = Distance::ZERO;
+        for (l, _) in children {
+            let width = map.lanes[l.0].width;
+            geometry.push((
+                *l,
+                lane_center_pts(road, fwds, inner_width, width)
+                    .with_context(timer, format!("shift for {}", l)),
+            ));
+            inner_width += width;
+        }
+    }
+
+    for (l, pts) in geometry {
+        let lane = &mut map.lanes[l.0];
+        lane.grade = grade(
+            map.intersections[lane.src_i.0].elevation,
+            map.intersections[lane.dst_i.0].elevation,
+            pts.length(),
+        );
+        lane.lane_center_pts = pts;
+        effects.changed_lanes.insert(l);
+
+        // Sidewalks might've changed length a bit.
+        let lane = &map.lanes[l.0];
+        let len = lane.lane_center_pts.length();
+        for b in &lane.building_paths {
+            let front_path = &mut map.buildings[b.0].front_path;
+            front_path.sidewalk = Position::new(l, front_path.sidewalk.dist_along().min(len));
+            front_path.line = Line::new(
+                front_path.line.pt1(),
+                lane.dist_along(front_path.sidewalk.dist_along()).0,
+            );
+        }
+        for bs in &lane.bus_stops {
+            let stop = map.bus_stops.get_mut(bs).unwrap();
+            stop.sidewalk_pos = Position::new(l, stop.sidewalk_pos.dist_along().min(len));
         }
     }
 }
@@ -1180,3 +1580,12 @@ fn recalculate_turns(
         IntersectionType::Border | IntersectionType::Construction => unreachable!(),
     }
 }
+
+// Rise over run. Lanes missing elevation data on either end, and degenerate lanes with no length,
+// are treated as flat.
+fn grade(src: Option<Distance>, dst: Option<Distance>, length: Distance) -> f64 {
+    match (src, dst) {
+        (Some(src), Some(dst)) if length > Distance::ZERO => (dst - src) / length,
+        _ => 0.0,
+    }
+}
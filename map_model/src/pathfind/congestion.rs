@@ -0,0 +1,122 @@
+use crate::pathfind::driving::VehiclePathfinder;
+use crate::{LaneID, Map, Path, PathRequest};
+use abstutil::Timer;
+use geom::{Duration, Time};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Travel times observed while driving, binned by hour of the day.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TravelTimes {
+    // For each lane and hour, the average time to cross the lane and the turn after it
+    per_lane: BTreeMap<LaneID, BTreeMap<usize, Duration>>,
+}
+
+impl TravelTimes {
+    // Each observation is (when the car entered the lane, the lane, how long until the car
+    // entered the next lane).
+    pub fn new(observations: &Vec<(Time, LaneID, Duration)>) -> TravelTimes {
+        let mut sums: BTreeMap<LaneID, BTreeMap<usize, (Duration, usize)>> = BTreeMap::new();
+        for (t, l, dt) in observations {
+            let entry = sums
+                .entry(*l)
+                .or_insert_with(BTreeMap::new)
+                .entry(hour(*t))
+                .or_insert((Duration::ZERO, 0));
+            entry.0 += *dt;
+            entry.1 += 1;
+        }
+
+        TravelTimes {
+            per_lane: sums
+                .into_iter()
+                .map(|(l, per_hour)| {
+                    (
+                        l,
+                        per_hour
+                            .into_iter()
+                            .map(|(h, (sum, count))| (h, sum / (count as f64)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, l: LaneID, hour: usize) -> Option<Duration> {
+        self.per_lane.get(&l)?.get(&hour).cloned()
+    }
+
+    fn all_hours(&self) -> Vec<usize> {
+        let mut hours: Vec<usize> = self
+            .per_lane
+            .values()
+            .flat_map(|per_hour| per_hour.keys().cloned())
+            .collect();
+        hours.sort();
+        hours.dedup();
+        hours
+    }
+}
+
+fn hour(t: Time) -> usize {
+    (t.inner_seconds() / 3600.0) as usize
+}
+
+// One car graph per hour with observations, with edge costs from the travel times.
+pub struct CongestedPathfinder {
+    travel_times: TravelTimes,
+    per_hour: BTreeMap<usize, VehiclePathfinder>,
+}
+
+impl CongestedPathfinder {
+    pub fn new(
+        map: &Map,
+        travel_times: TravelTimes,
+        seed: &VehiclePathfinder,
+        timer: &mut Timer,
+    ) -> CongestedPathfinder {
+        let mut c = CongestedPathfinder {
+            travel_times,
+            per_hour: BTreeMap::new(),
+        };
+        c.apply_edits(map, seed, timer);
+        c
+    }
+
+    // Each graph costs every lane using one hour, so a long trip is costed as if the whole thing
+    // happened at once. To soften that, route using the hour the car is expected to be halfway
+    // through the trip, not the hour it departs.
+    pub fn pathfind(&self, req: &PathRequest, depart: Time, map: &Map) -> Option<Path> {
+        // If we didn't observe anything during this hour, assume free-flow.
+        let (path, seconds) = self.per_hour.get(&hour(depart))?.pathfind(req, map)?;
+        let midway = hour(depart + Duration::seconds((seconds as f64) / 2.0));
+        if midway == hour(depart) {
+            return Some(path);
+        }
+        match self.per_hour.get(&midway) {
+            Some(graph) => graph.pathfind(req, map).map(|(p, _)| p).or(Some(path)),
+            None => Some(path),
+        }
+    }
+
+    // The observed travel times to use for a car departing at some time
+    pub fn costs_at(&self, depart: Time) -> (&TravelTimes, usize) {
+        (&self.travel_times, hour(depart))
+    }
+
+    // Lane types and turns might've changed, so rebuild everything. The node ordering from the
+    // free-flow graph keeps this fast.
+    pub fn apply_edits(&mut self, map: &Map, seed: &VehiclePathfinder, timer: &mut Timer) {
+        let hours = self.travel_times.all_hours();
+        timer.start_iter("prepare congestion-aware pathfinding", hours.len());
+        self.per_hour.clear();
+        for h in hours {
+            timer.next();
+            self.per_hour.insert(
+                h,
+                VehiclePathfinder::new_congested(map, &self.travel_times, h, seed),
+            );
+        }
+    }
+}
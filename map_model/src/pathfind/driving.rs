@@ -1,8 +1,13 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
-use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
+use crate::{
+    Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TravelTimes, Turn, TurnID,
+};
 use fast_paths::{FastGraph, InputGraph, PathCalculator};
+use geom::Duration;
 use serde_derive::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use thread_local::ThreadLocal;
 
 #[derive(Serialize, Deserialize)]
@@ -26,10 +31,10 @@ impl VehiclePathfinder {
         // and we want the node in the graph. Do this first, so the IDs of all the nodes doesn't
         // depend on lane types and turns and such.
         let mut nodes = NodeMap::new();
-        for l in map.all_lanes() {
+        for l in map.all_lanes_including_deleted() {
             nodes.get_or_insert(l.id);
         }
-        let input_graph = make_input_graph(map, &nodes, constraints);
+        let input_graph = make_input_graph(map, &nodes, constraints, None);
 
         // All VehiclePathfinders have the same nodes (lanes), so if we're not the first being
         // built, seed from the node ordering.
@@ -48,6 +53,29 @@ impl VehiclePathfinder {
         }
     }
 
+    // Costs come from observed travel times during some hour, when available.
+    pub fn new_congested(
+        map: &Map,
+        travel_times: &TravelTimes,
+        hour: usize,
+        seed: &VehiclePathfinder,
+    ) -> VehiclePathfinder {
+        assert_eq!(seed.constraints, PathConstraints::Car);
+        let input_graph = make_input_graph(
+            map,
+            &seed.nodes,
+            PathConstraints::Car,
+            Some((travel_times, hour)),
+        );
+        let node_ordering = seed.graph.get_node_ordering();
+        VehiclePathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: seed.nodes.clone(),
+            constraints: PathConstraints::Car,
+            path_calc: ThreadLocal::new(),
+        }
+    }
+
     pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<(Path, usize)> {
         assert!(!map.get_l(req.start.lane()).is_sidewalk());
         let mut calc = self
@@ -77,11 +105,17 @@ impl VehiclePathfinder {
     }
 
     pub fn apply_edits(&mut self, map: &Map) {
-        // The NodeMap is just all lanes -- it won't change. So we can also reuse the node
-        // ordering.
+        // Lanes added by map edits need new nodes, so start over.
+        if self.graph.get_num_nodes() != map.all_lanes_including_deleted().len() {
+            *self = VehiclePathfinder::new(map, self.constraints, None);
+            return;
+        }
+
+        // Otherwise the NodeMap is just all lanes -- it won't change. So we can also reuse the
+        // node ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, self.constraints);
+        let input_graph = make_input_graph(map, &self.nodes, self.constraints, None);
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
     }
@@ -91,10 +125,11 @@ fn make_input_graph(
     map: &Map,
     nodes: &NodeMap<LaneID>,
     constraints: PathConstraints,
+    travel_times: Option<(&TravelTimes, usize)>,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
-    let num_lanes = map.all_lanes().len();
-    for l in map.all_lanes() {
+    let num_lanes = map.all_lanes_including_deleted().len();
+    for l in map.all_lanes_including_deleted() {
         let from = nodes.get(l.id);
         let mut any = false;
         if constraints.can_use(l, map) {
@@ -103,7 +138,7 @@ fn make_input_graph(
                 input_graph.add_edge(
                     from,
                     nodes.get(turn.id.dst),
-                    cost(l, turn, constraints, map),
+                    edge_cost(l, turn, constraints, travel_times, map),
                 );
             }
         }
@@ -120,6 +155,76 @@ fn make_input_graph(
     input_graph
 }
 
+fn edge_cost(
+    lane: &Lane,
+    turn: &Turn,
+    constraints: PathConstraints,
+    travel_times: Option<(&TravelTimes, usize)>,
+    map: &Map,
+) -> usize {
+    // Observed travel times are in seconds, just like the free-flow cost for cars.
+    match travel_times.and_then(|(times, hour)| times.get(lane.id, hour)) {
+        Some(dt) => dt.inner_seconds().round() as usize,
+        None => cost(lane, turn, constraints, map),
+    }
+}
+
+// Live delays change constantly, so it's not worth preparing a contraction hierarchy for them.
+// Instead, just run Dijkstra over the lanes, adding the extra delay to the usual cost of leaving
+// each lane.
+pub fn pathfind_with_delays(
+    req: &PathRequest,
+    map: &Map,
+    travel_times: Option<(&TravelTimes, usize)>,
+    delays: &BTreeMap<LaneID, Duration>,
+) -> Option<Path> {
+    let start = req.start.lane();
+    let end = req.end.lane();
+    let mut best: HashMap<LaneID, usize> = HashMap::new();
+    let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+    let mut queue: BinaryHeap<Reverse<(usize, LaneID)>> = BinaryHeap::new();
+    best.insert(start, 0);
+    queue.push(Reverse((0, start)));
+
+    while let Some(Reverse((cost_so_far, current))) = queue.pop() {
+        if current == end {
+            let mut steps = vec![PathStep::Lane(end)];
+            let mut l = end;
+            while l != start {
+                let turn = backrefs[&l];
+                steps.push(PathStep::Turn(turn));
+                steps.push(PathStep::Lane(turn.src));
+                l = turn.src;
+            }
+            steps.reverse();
+            return Some(Path::new(map, steps, req.end.dist_along()));
+        }
+        if cost_so_far > best[&current] {
+            continue;
+        }
+
+        let lane = map.get_l(current);
+        let delay = delays
+            .get(&current)
+            .map(|dt| dt.inner_seconds().round() as usize)
+            .unwrap_or(0);
+        for turn in map.get_turns_for(current, req.constraints) {
+            let next_cost =
+                cost_so_far + delay + edge_cost(lane, turn, req.constraints, travel_times, map);
+            if best
+                .get(&turn.id.dst)
+                .map(|c| next_cost < *c)
+                .unwrap_or(true)
+            {
+                best.insert(turn.id.dst, next_cost);
+                backrefs.insert(turn.id.dst, turn.id);
+                queue.push(Reverse((next_cost, turn.id.dst)));
+            }
+        }
+    }
+    None
+}
+
 pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -> usize {
     // TODO Could cost turns differently.
 
@@ -132,8 +237,8 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
         }
         PathConstraints::Bike => {
             // Speed limits don't matter, bikes are usually constrained by their own speed limit.
-            let dist = lane.length() + turn.geom.length();
-            // TODO Elevation gain is bad, loss is good.
+            // Climbing is slow, so stretch uphill lanes by how much they slow down bikes.
+            let dist = lane.length() / lane.bike_speed_factor() + turn.geom.length();
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
 
@@ -163,6 +268,11 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
             };
             (lt_penalty * (t1 + t2)).inner_seconds().round() as usize
         }
+        PathConstraints::Train => {
+            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
+            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            (t1 + t2).inner_seconds().round() as usize
+        }
         PathConstraints::Pedestrian => unreachable!(),
     }
 }
@@ -1,7 +1,10 @@
+mod congestion;
 mod driving;
 mod node_map;
 mod walking;
 
+use self::congestion::CongestedPathfinder;
+pub use self::congestion::TravelTimes;
 pub use self::driving::cost;
 use self::driving::VehiclePathfinder;
 use self::walking::SidewalkPathfinder;
@@ -9,9 +12,9 @@ use crate::{
     osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID,
 };
 use abstutil::Timer;
-use geom::{Distance, PolyLine};
+use geom::{Distance, Duration, PolyLine, Time};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -187,6 +190,19 @@ impl Path {
         }
     }
 
+    // Swap out everything from the current step onwards. Trusting the caller that the other path
+    // starts with the same step.
+    pub fn replace_remaining(&mut self, other: Path) {
+        assert_eq!(self.steps[0], other.steps[0]);
+        self.total_length = self.crossed_so_far + other.total_length;
+        self.steps = other.steps;
+        self.end_dist = other.end_dist;
+    }
+
+    pub fn end_dist(&self) -> Distance {
+        self.end_dist
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -306,6 +322,7 @@ pub enum PathConstraints {
     Car,
     Bike,
     Bus,
+    Train,
 }
 
 impl PathConstraints {
@@ -316,11 +333,15 @@ impl PathConstraints {
             LaneType::Driving => PathConstraints::Car,
             LaneType::Biking => PathConstraints::Bike,
             LaneType::Bus => PathConstraints::Bus,
+            LaneType::LightRail => PathConstraints::Train,
             _ => panic!("PathConstraints::from_lt({:?}) doesn't make sense", lt),
         }
     }
 
     pub fn can_use(self, l: &Lane, map: &Map) -> bool {
+        if l.deleted {
+            return false;
+        }
         match self {
             PathConstraints::Pedestrian => l.is_sidewalk(),
             PathConstraints::Car => l.is_driving(),
@@ -338,6 +359,7 @@ impl PathConstraints {
                 }
             }
             PathConstraints::Bus => l.is_driving() || l.is_bus(),
+            PathConstraints::Train => l.is_light_rail(),
         }
     }
 }
@@ -347,6 +369,9 @@ pub struct PathRequest {
     pub start: Position,
     pub end: Position,
     pub constraints: PathConstraints,
+    // If set and the map has observed travel times, route cars around the congestion expected
+    // when they depart. Otherwise, assume free-flow.
+    pub departure_time: Option<Time>,
 }
 
 impl fmt::Display for PathRequest {
@@ -411,9 +436,13 @@ pub struct Pathfinder {
     car_graph: VehiclePathfinder,
     bike_graph: VehiclePathfinder,
     bus_graph: VehiclePathfinder,
+    train_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     // TODO Option just during initialization! Ewww.
     walking_with_transit_graph: Option<SidewalkPathfinder>,
+    // Only exists after travel times are loaded; not saved with the map.
+    #[serde(skip_serializing, skip_deserializing)]
+    congested_car_graph: Option<CongestedPathfinder>,
 }
 
 impl Pathfinder {
@@ -432,32 +461,78 @@ impl Pathfinder {
         let bus_graph = VehiclePathfinder::new(map, PathConstraints::Bus, Some(&car_graph));
         timer.stop("prepare pathfinding for buses");
 
+        timer.start("prepare pathfinding for trains");
+        let train_graph = VehiclePathfinder::new(map, PathConstraints::Train, Some(&car_graph));
+        timer.stop("prepare pathfinding for trains");
+
         timer.start("prepare pathfinding for pedestrians");
-        let walking_graph = SidewalkPathfinder::new(map, false, &bus_graph);
+        let walking_graph = SidewalkPathfinder::new(map, false, &bus_graph, &train_graph);
         timer.stop("prepare pathfinding for pedestrians");
 
         Pathfinder {
             car_graph,
             bike_graph,
             bus_graph,
+            train_graph,
             walking_graph,
             walking_with_transit_graph: None,
+            congested_car_graph: None,
         }
     }
 
     pub fn setup_walking_with_transit(&mut self, map: &Map) {
-        self.walking_with_transit_graph = Some(SidewalkPathfinder::new(map, true, &self.bus_graph));
+        self.walking_with_transit_graph = Some(SidewalkPathfinder::new(
+            map,
+            true,
+            &self.bus_graph,
+            &self.train_graph,
+        ));
     }
 
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<Path> {
         match req.constraints {
             PathConstraints::Pedestrian => self.walking_graph.pathfind(&req, map),
-            PathConstraints::Car => self.car_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Car => {
+                if let (Some(depart), Some(ref congested)) =
+                    (req.departure_time, &self.congested_car_graph)
+                {
+                    if let Some(path) = congested.pathfind(&req, depart, map) {
+                        return Some(path);
+                    }
+                }
+                self.car_graph.pathfind(&req, map).map(|(p, _)| p)
+            }
             PathConstraints::Bike => self.bike_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Train => self.train_graph.pathfind(&req, map).map(|(p, _)| p),
         }
     }
 
+    // Only for cars. Starts from any observed travel times, like pathfind does, then adds delays
+    // happening right now.
+    pub fn pathfind_with_delays(
+        &self,
+        req: PathRequest,
+        map: &Map,
+        delays: &BTreeMap<LaneID, Duration>,
+    ) -> Option<Path> {
+        assert_eq!(req.constraints, PathConstraints::Car);
+        let travel_times = match (req.departure_time, &self.congested_car_graph) {
+            (Some(depart), Some(ref congested)) => Some(congested.costs_at(depart)),
+            _ => None,
+        };
+        driving::pathfind_with_delays(&req, map, travel_times, delays)
+    }
+
+    pub fn set_travel_times(&mut self, map: &Map, travel_times: TravelTimes, timer: &mut Timer) {
+        self.congested_car_graph = Some(CongestedPathfinder::new(
+            map,
+            travel_times,
+            &self.car_graph,
+            timer,
+        ));
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -475,6 +550,12 @@ impl Pathfinder {
         self.car_graph.apply_edits(map);
         timer.stop("apply edits to car pathfinding");
 
+        if let Some(ref mut congested) = self.congested_car_graph {
+            timer.start("apply edits to congestion-aware car pathfinding");
+            congested.apply_edits(map, &self.car_graph, timer);
+            timer.stop("apply edits to congestion-aware car pathfinding");
+        }
+
         timer.start("apply edits to bike pathfinding");
         self.bike_graph.apply_edits(map);
         timer.stop("apply edits to bike pathfinding");
@@ -483,15 +564,20 @@ impl Pathfinder {
         self.bus_graph.apply_edits(map);
         timer.stop("apply edits to bus pathfinding");
 
+        timer.start("apply edits to train pathfinding");
+        self.train_graph.apply_edits(map);
+        timer.stop("apply edits to train pathfinding");
+
         timer.start("apply edits to pedestrian pathfinding");
-        self.walking_graph.apply_edits(map, &self.bus_graph);
+        self.walking_graph
+            .apply_edits(map, &self.bus_graph, &self.train_graph);
         timer.stop("apply edits to pedestrian pathfinding");
 
         timer.start("apply edits to pedestrian using transit pathfinding");
         self.walking_with_transit_graph
             .as_mut()
             .unwrap()
-            .apply_edits(map, &self.bus_graph);
+            .apply_edits(map, &self.bus_graph, &self.train_graph);
         timer.stop("apply edits to pedestrian using transit pathfinding");
     }
 }
@@ -4,7 +4,7 @@ use std::collections::BTreeMap;
 use std::fmt::Debug;
 
 // TODO Upstream this in fast_paths when this is more solid.
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct NodeMap<T: Copy + Ord + Debug + Serialize> {
     #[serde(skip_serializing)]
     node_to_id: BTreeMap<T, NodeId>,
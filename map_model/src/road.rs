@@ -1,7 +1,5 @@
 use crate::raw::{OriginalRoad, RestrictionType};
-use crate::{
-    osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints, LANE_THICKNESS,
-};
+use crate::{osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints};
 use abstutil::{Error, Warn};
 use geom::{Distance, PolyLine, Polygon, Speed};
 use serde_derive::{Deserialize, Serialize};
@@ -113,8 +111,14 @@ pub struct Road {
     // Unshifted original center points. Order implies road orientation. Reversing lanes doesn't
     // change this.
     pub center_pts: PolyLine,
+    // Before trimming back at the intersections. Map edits that change the width of the road
+    // trim again from this.
+    pub original_center_pts: PolyLine,
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
+
+    // From the OSM tags, unless map edits changed it
+    pub speed_limit: Speed,
 }
 
 impl Road {
@@ -212,22 +216,7 @@ impl Road {
     }
 
     pub fn get_speed_limit(&self) -> Speed {
-        // TODO Should probably cache this
-        if let Some(limit) = self.osm_tags.get(osm::MAXSPEED) {
-            // TODO handle other units
-            if limit.ends_with(" mph") {
-                if let Ok(mph) = limit[0..limit.len() - 4].parse::<f64>() {
-                    return Speed::miles_per_hour(mph);
-                }
-            }
-        }
-
-        if self.osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
-            || self.osm_tags.get(osm::HIGHWAY) == Some(&"secondary".to_string())
-        {
-            return Speed::miles_per_hour(40.0);
-        }
-        Speed::miles_per_hour(20.0)
+        self.speed_limit
     }
 
     pub fn get_zorder(&self) -> isize {
@@ -316,9 +305,21 @@ impl Road {
         search.iter().find(|(_, t)| lt == *t).map(|(id, _)| *id)
     }
 
-    pub fn get_thick_polyline(&self) -> Warn<(PolyLine, Distance)> {
-        let width_right = (self.children_forwards.len() as f64) * LANE_THICKNESS;
-        let width_left = (self.children_backwards.len() as f64) * LANE_THICKNESS;
+    // The total width of the lanes on one side of the road
+    pub fn width(&self, fwds: bool, map: &Map) -> Distance {
+        let children = if fwds {
+            &self.children_forwards
+        } else {
+            &self.children_backwards
+        };
+        children
+            .iter()
+            .fold(Distance::ZERO, |sum, (l, _)| sum + map.get_l(*l).width)
+    }
+
+    pub fn get_thick_polyline(&self, map: &Map) -> Warn<(PolyLine, Distance)> {
+        let width_right = self.width(true, map);
+        let width_left = self.width(false, map);
         let total_width = width_right + width_left;
         if width_right >= width_left {
             self.center_pts
@@ -331,8 +332,8 @@ impl Road {
         }
     }
 
-    pub fn get_thick_polygon(&self) -> Warn<Polygon> {
-        self.get_thick_polyline()
+    pub fn get_thick_polygon(&self, map: &Map) -> Warn<Polygon> {
+        self.get_thick_polyline(map)
             .map(|(pl, width)| pl.make_polygons(width))
     }
 
@@ -407,3 +408,26 @@ impl Road {
         stops
     }
 }
+
+pub fn speed_limit_from_osm(osm_tags: &BTreeMap<String, String>) -> Speed {
+    if let Some(limit) = osm_tags.get(osm::MAXSPEED) {
+        // TODO handle other units
+        if limit.ends_with(" mph") {
+            if let Ok(mph) = limit[0..limit.len() - 4].parse::<f64>() {
+                return Speed::miles_per_hour(mph);
+            }
+        }
+    }
+
+    // Light rail usually has its own right-of-way, so it can go faster than most streets. Streets
+    // that happen to carry railway tags are still streets, though.
+    if osm_tags.contains_key(osm::RAILWAY) && !osm_tags.contains_key(osm::HIGHWAY) {
+        return Speed::miles_per_hour(35.0);
+    }
+    if osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
+        || osm_tags.get(osm::HIGHWAY) == Some(&"secondary".to_string())
+    {
+        return Speed::miles_per_hour(40.0);
+    }
+    Speed::miles_per_hour(20.0)
+}
@@ -21,7 +21,33 @@ pub struct ControlTrafficSignal {
 pub struct Phase {
     pub protected_groups: BTreeSet<TurnGroupID>,
     pub yield_groups: BTreeSet<TurnGroupID>,
+    // For actuated phases, this is only used for drawing and the nominal cycle length.
     pub duration: Duration,
+    // If set, the phase responds to agents waiting at the signal, instead of always lasting
+    // duration. Actuated phases with nobody waiting for them are skipped.
+    #[serde(default)]
+    pub actuated: Option<ActuatedTiming>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ActuatedTiming {
+    // Once the phase starts, it lasts at least this long.
+    pub min_green: Duration,
+    // After that, the phase keeps going while somebody arrives or waits at least this often.
+    pub extension: Duration,
+    // No matter what, the phase ends after this long.
+    pub max_green: Duration,
+}
+
+impl ActuatedTiming {
+    // A reasonable starting point for a phase that used to last a fixed time.
+    pub fn new(duration: Duration) -> ActuatedTiming {
+        ActuatedTiming {
+            min_green: duration * 0.5,
+            extension: Duration::seconds(3.0),
+            max_green: duration * 1.5,
+        }
+    }
 }
 
 impl ControlTrafficSignal {
@@ -75,6 +101,11 @@ impl ControlTrafficSignal {
         cycle_length
     }
 
+    pub fn is_actuated(&self) -> bool {
+        self.phases.iter().any(|p| p.actuated.is_some())
+    }
+
+    // Only meaningful for fixed-time signals. The simulation tracks the state of actuated signals.
     pub fn current_phase_and_remaining_time(&self, now: Time) -> (usize, &Phase, Duration) {
         let mut now_offset = ((now + self.offset) - Time::START_OF_DAY) % self.cycle_length();
         for (idx, p) in self.phases.iter().enumerate() {
@@ -124,6 +155,15 @@ impl ControlTrafficSignal {
                 }
             }
 
+            if let Some(timing) = phase.actuated {
+                if timing.min_green > timing.max_green || timing.extension <= Duration::ZERO {
+                    return Err(format!(
+                        "Traffic signal {} has a phase with bad actuated timing: {:?}",
+                        self.id, timing
+                    ));
+                }
+            }
+
             // Do any of the crosswalks yield?
             for g in phase.yield_groups.iter().map(|g| &self.turn_groups[g]) {
                 assert!(g.turn_type != TurnType::Crosswalk);
@@ -488,6 +528,15 @@ impl Phase {
             protected_groups: BTreeSet::new(),
             yield_groups: BTreeSet::new(),
             duration: Duration::seconds(30.0),
+            actuated: None,
+        }
+    }
+
+    // The longest this phase could last
+    pub fn max_duration(&self) -> Duration {
+        match self.actuated {
+            Some(timing) => timing.max_green,
+            None => self.duration,
         }
     }
 
@@ -53,7 +53,7 @@ impl TurnType {
 // turns should be treated as less important.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, PartialOrd)]
 pub enum TurnPriority {
-    // For stop signs: Can't currently specify this!
+    // For stop signs: Can't currently specify this! Ban the turn through map edits instead.
     // For traffic signals: Can't do this turn right now.
     Banned,
     // For stop signs: cars have to stop before doing this turn, and are accepted with the lowest
@@ -1,8 +1,12 @@
+mod od;
 pub mod psrc;
+mod synthetic;
 mod trips;
 
+pub use od::{ODEntry, ODMatrix, Zone};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+pub use synthetic::{synthetic_scenario, DemandOptions, Gravity, LandUse};
 pub use trips::{clip_trips, trips_to_scenario, Trip, TripEndpt};
 
 #[derive(Serialize, Deserialize)]
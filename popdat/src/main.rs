@@ -1,5 +1,72 @@
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use popdat::{ODMatrix, Zone};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::Scenario;
+
 fn main() {
-    let mut timer = abstutil::Timer::new("creating popdat");
+    let mut args = CmdArgs::new();
+    // Instead of importing PSRC data, turn an origin-destination matrix between these GeoJSON
+    // zones into a scenario, or a scenario back into a matrix.
+    let zones_path = args.optional("--zones");
+    let import_od = args.optional("--import_od");
+    let export_od = args.optional("--export_od");
+    // For --import_od
+    let map_path = args.optional("--map");
+    let scenario_name = args
+        .optional("--scenario_name")
+        .unwrap_or_else(|| "od".to_string());
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse::<u8>())
+        .unwrap_or(42);
+    // For --export_od
+    let scenario_path = args.optional("--scenario");
+    let slice_minutes = args
+        .optional_parse("--slice_minutes", |s| s.parse::<usize>())
+        .unwrap_or(60);
+    args.done();
+
+    if let Some(path) = import_od {
+        let mut timer = Timer::new("import OD matrix");
+        let map = Map::new(
+            map_path.expect("--import_od needs --map"),
+            false,
+            &mut timer,
+        );
+        let zones = Zone::load_all(&zones_path.expect("--import_od needs --zones"), &map).unwrap();
+        let matrix = ODMatrix::load(&path).unwrap();
+        let mut rng = XorShiftRng::from_seed([rng_seed; 16]);
+        let scenario = matrix.to_scenario(&scenario_name, &zones, &map, &mut rng, &mut timer);
+        scenario.save();
+        println!(
+            "Saved {}",
+            abstutil::path_scenario(&scenario.map_name, &scenario.scenario_name)
+        );
+        return;
+    }
+    if let Some(path) = export_od {
+        let mut timer = Timer::new("export OD matrix");
+        let scenario: Scenario = abstutil::read_binary(
+            scenario_path.expect("--export_od needs --scenario"),
+            &mut timer,
+        );
+        let map = Map::new(abstutil::path_map(&scenario.map_name), false, &mut timer);
+        let zones = Zone::load_all(&zones_path.expect("--export_od needs --zones"), &map).unwrap();
+        ODMatrix::from_scenario(
+            &scenario,
+            &zones,
+            &map,
+            geom::Duration::minutes(slice_minutes),
+            &mut timer,
+        )
+        .save(&path)
+        .unwrap();
+        println!("Saved {}", path);
+        return;
+    }
+
+    let mut timer = Timer::new("creating popdat");
     let (trips, parcels) = popdat::psrc::import_trips(
         "../data/input/parcels_urbansim.txt",
         "../data/input/trips_2014.csv",
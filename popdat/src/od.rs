@@ -0,0 +1,577 @@
+use crate::psrc::Mode;
+use crate::trips::{parked_cars_needed, spawn_trip, TripEndpt};
+use abstutil::{prettyprint_usize, Timer};
+use geojson::{GeoJson, PolygonType, Value};
+use geom::{Duration, LonLat, Polygon, Pt2D, Time};
+use map_model::{BuildingID, Intersection, IntersectionID, Map, PathConstraints};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use sim::{DrivingGoal, ModeChoice, Scenario, SidewalkPOI, SidewalkSpot, SpawnTrip};
+use std::collections::BTreeMap;
+
+// Zones without any borders use the borders within this many degrees of their direction from the
+// map's center.
+const MAX_BORDER_ANGLE: f64 = 45.0;
+
+// An area that trips in an origin-destination matrix start or end in. A zone can extend past the
+// map boundary, or be entirely outside of it. Trips starting or ending outside the map use the
+// map's borders; a zone without any border uses the ones in its direction from the map.
+pub struct Zone {
+    pub name: String,
+    // MultiPolygons become multiple polygons
+    pub polygons: Vec<Polygon>,
+}
+
+impl Zone {
+    // Reads a FeatureCollection of Polygons and MultiPolygons with a "name" property, just like
+    // convert_osm does for neighborhoods.
+    pub fn load_all(path: &str, map: &Map) -> Result<Vec<Zone>, failure::Error> {
+        let document: GeoJson = abstutil::read_json(path.to_string(), &mut Timer::throwaway());
+        let collection = match document {
+            GeoJson::FeatureCollection(c) => c,
+            _ => {
+                return Err(failure::err_msg(format!(
+                    "{} isn't a GeoJSON FeatureCollection",
+                    path
+                )))
+            }
+        };
+
+        let mut zones = Vec::new();
+        for f in collection.features {
+            let name = match f
+                .properties
+                .as_ref()
+                .and_then(|props| props.get("name"))
+                .and_then(|x| x.as_str())
+            {
+                Some(name) => name.to_string(),
+                None => {
+                    return Err(failure::err_msg(format!(
+                        "A zone in {} doesn't have a name",
+                        path
+                    )))
+                }
+            };
+            let polygons = match f.geometry.map(|g| g.value) {
+                Some(Value::Polygon(p)) => vec![to_polygon(p, map)],
+                Some(Value::MultiPolygon(polygons)) => {
+                    polygons.into_iter().map(|p| to_polygon(p, map)).collect()
+                }
+                x => {
+                    return Err(failure::err_msg(format!(
+                        "Zone {} has unexpected geometry {:?}",
+                        name, x
+                    )))
+                }
+            };
+            zones.push(Zone { name, polygons });
+        }
+        Ok(zones)
+    }
+
+    fn contains_pt(&self, pt: Pt2D) -> bool {
+        self.polygons.iter().any(|p| p.contains_pt(pt))
+    }
+
+    fn buildings(&self, map: &Map) -> Vec<BuildingID> {
+        map.all_buildings()
+            .iter()
+            .filter(|b| self.contains_pt(b.polygon.center()))
+            .map(|b| b.id)
+            .collect()
+    }
+
+    fn center(&self) -> Pt2D {
+        Pt2D::center(&self.polygons.iter().map(|p| p.center()).collect())
+    }
+
+    // From 0 to 1
+    fn pct_inside_map(&self, map: &Map) -> f64 {
+        let total: f64 = self.polygons.iter().map(|p| p.area()).sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        let inside: f64 = self
+            .polygons
+            .iter()
+            .flat_map(|p| p.intersection(map.get_boundary_polygon()))
+            .map(|p| p.area())
+            .sum();
+        let pct = inside / total;
+        // Don't let slivers from clipping against the boundary count as outside.
+        if pct > 0.99 {
+            1.0
+        } else {
+            pct
+        }
+    }
+
+    // Where trips using some mode can enter or leave the map in this zone. If the zone doesn't
+    // contain any borders, use the ones pointing from the map's center towards the zone.
+    fn borders(&self, mode: Mode, incoming: bool, map: &Map) -> Vec<IntersectionID> {
+        let constraints = constraints(mode);
+        let borders: Vec<&Intersection> = if incoming {
+            map.all_incoming_borders()
+        } else {
+            map.all_outgoing_borders()
+        }
+        .into_iter()
+        .filter(|i| {
+            if incoming {
+                !i.get_outgoing_lanes(map, constraints).is_empty()
+            } else {
+                !i.get_incoming_lanes(map, constraints).is_empty()
+            }
+        })
+        .collect();
+
+        let inside: Vec<IntersectionID> = borders
+            .iter()
+            .filter(|i| self.contains_pt(i.polygon.center()))
+            .map(|i| i.id)
+            .collect();
+        if !inside.is_empty() {
+            return inside;
+        }
+
+        let map_center = map.get_boundary_polygon().center();
+        let dir = map_center.angle_to(self.center());
+        // How many degrees a border is off from the zone's direction
+        let mut by_angle: Vec<(f64, IntersectionID)> = borders
+            .into_iter()
+            .map(|i| {
+                let rot = dir
+                    .shortest_rotation_towards(map_center.angle_to(i.polygon.center()))
+                    .normalized_degrees();
+                (rot.min(360.0 - rot), i.id)
+            })
+            .collect();
+        by_angle.sort_by(|(a1, _), (a2, _)| a1.partial_cmp(a2).unwrap());
+        let mut result: Vec<IntersectionID> = by_angle
+            .iter()
+            .filter(|(off, _)| *off < MAX_BORDER_ANGLE)
+            .map(|(_, i)| *i)
+            .collect();
+        if result.is_empty() {
+            result.extend(by_angle.first().map(|(_, i)| *i));
+        }
+        result
+    }
+}
+
+// Where trips start or end in one zone
+struct Endpoints {
+    bldgs: Vec<TripEndpt>,
+    borders: Vec<TripEndpt>,
+    // Trips use a building this often, and a border otherwise.
+    pct_bldgs: f64,
+}
+
+impl Endpoints {
+    fn new(
+        zone: &Zone,
+        bldgs: &Vec<BuildingID>,
+        pct_inside: f64,
+        mode: Mode,
+        incoming: bool,
+        map: &Map,
+    ) -> Endpoints {
+        // Zones entirely inside the map only need borders if they don't have any buildings.
+        let borders = if bldgs.is_empty() || pct_inside < 1.0 {
+            zone.borders(mode, incoming, map)
+                .into_iter()
+                .map(|i| TripEndpt::Border(i, map.get_i(i).polygon.center()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Endpoints {
+            bldgs: bldgs.iter().map(|b| TripEndpt::Building(*b)).collect(),
+            borders,
+            pct_bldgs: pct_inside,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bldgs.is_empty() && self.borders.is_empty()
+    }
+
+    fn choose(&self, rng: &mut XorShiftRng) -> TripEndpt {
+        let use_bldg = if self.bldgs.is_empty() {
+            false
+        } else if self.borders.is_empty() {
+            true
+        } else {
+            rng.gen_bool(self.pct_bldgs)
+        };
+        if use_bldg {
+            self.bldgs.choose(rng).unwrap().clone()
+        } else {
+            self.borders.choose(rng).unwrap().clone()
+        }
+    }
+
+    // Picks uniformly from everything besides one endpoint
+    fn choose_except(&self, except: &TripEndpt, rng: &mut XorShiftRng) -> Option<TripEndpt> {
+        let choices: Vec<&TripEndpt> = self
+            .bldgs
+            .iter()
+            .chain(self.borders.iter())
+            .filter(|e| !same_endpt(e, except))
+            .collect();
+        choices.choose(rng).map(|e| (*e).clone())
+    }
+}
+
+// Inner rings are ignored.
+fn to_polygon(input: PolygonType, map: &Map) -> Polygon {
+    Polygon::new(
+        &input[0]
+            .iter()
+            .map(|raw_pt| {
+                Pt2D::forcibly_from_gps(LonLat::new(raw_pt[0], raw_pt[1]), map.get_gps_bounds())
+            })
+            .collect(),
+    )
+}
+
+// How many trips using one mode go from one zone to another, departing sometime in [start, end)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ODEntry {
+    pub origin: String,
+    pub destination: String,
+    pub mode: Mode,
+    pub start: Time,
+    pub end: Time,
+    pub trips: usize,
+}
+
+pub struct ODMatrix {
+    pub entries: Vec<ODEntry>,
+}
+
+impl ODMatrix {
+    // The CSV has a header, then lines like "downtown,uw,drive,07:00:00.0,08:00:00.0,120". Modes
+    // are walk, bike, drive, or transit.
+    pub fn load(path: &str) -> Result<ODMatrix, failure::Error> {
+        let mut entries = Vec::new();
+        for rec in csv::Reader::from_path(path)?.records() {
+            let rec = rec?;
+            let mode = match parse_mode(&rec[2]) {
+                Some(mode) => mode,
+                None => {
+                    return Err(failure::err_msg(format!(
+                        "{} has unknown mode {}",
+                        path, &rec[2]
+                    )))
+                }
+            };
+            let start = Time::parse(&rec[3])?;
+            let end = Time::parse(&rec[4])?;
+            if end <= start {
+                return Err(failure::err_msg(format!(
+                    "{} has a time slice from {} to {}",
+                    path, start, end
+                )));
+            }
+            entries.push(ODEntry {
+                origin: rec[0].to_string(),
+                destination: rec[1].to_string(),
+                mode,
+                start,
+                end,
+                trips: rec[5].parse::<usize>()?,
+            });
+        }
+        Ok(ODMatrix { entries })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), failure::Error> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(&[
+            "origin",
+            "destination",
+            "mode",
+            "start_time",
+            "end_time",
+            "trips",
+        ])?;
+        for e in &self.entries {
+            writer.write_record(&[
+                e.origin.clone(),
+                e.destination.clone(),
+                mode_name(e.mode).to_string(),
+                e.start.to_string(),
+                e.end.to_string(),
+                e.trips.to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Makes individual trips between random buildings in each pair of zones, departing at random
+    // times during each slice. Zones without buildings use the map's borders instead, and zones
+    // partly outside the map use the borders for the share of trips outside.
+    pub fn to_scenario(
+        &self,
+        scenario_name: &str,
+        zones: &Vec<Zone>,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Scenario {
+        let zones: BTreeMap<&str, &Zone> = zones.iter().map(|z| (z.name.as_str(), z)).collect();
+        let mut bldgs_per_zone: BTreeMap<&str, Vec<BuildingID>> = BTreeMap::new();
+        let mut pct_inside_per_zone: BTreeMap<&str, f64> = BTreeMap::new();
+        timer.start_iter("find buildings in zones", zones.len());
+        for (name, zone) in &zones {
+            timer.next();
+            bldgs_per_zone.insert(*name, zone.buildings(map));
+            pct_inside_per_zone.insert(*name, zone.pct_inside_map(map));
+        }
+
+        // (departure, from, to, mode)
+        let mut trips: Vec<(Time, TripEndpt, TripEndpt, Mode)> = Vec::new();
+        let mut same_start_and_end = 0;
+        timer.start_iter("distribute OD trips", self.entries.len());
+        for e in &self.entries {
+            timer.next();
+            if e.trips == 0 {
+                continue;
+            }
+            let (origin, destination) = match (
+                zones.get(e.origin.as_str()),
+                zones.get(e.destination.as_str()),
+            ) {
+                (Some(o), Some(d)) => (o, d),
+                _ => {
+                    timer.warn(format!(
+                        "Skipping {} trips from {} to {}; one of the zones isn't defined",
+                        e.trips, e.origin, e.destination
+                    ));
+                    continue;
+                }
+            };
+            let starts = Endpoints::new(
+                origin,
+                &bldgs_per_zone[origin.name.as_str()],
+                pct_inside_per_zone[origin.name.as_str()],
+                e.mode,
+                true,
+                map,
+            );
+            let ends = Endpoints::new(
+                destination,
+                &bldgs_per_zone[destination.name.as_str()],
+                pct_inside_per_zone[destination.name.as_str()],
+                e.mode,
+                false,
+                map,
+            );
+            if starts.is_empty() || ends.is_empty() {
+                timer.warn(format!(
+                    "Skipping {} {} trips from {} to {}; nowhere for them to start or end",
+                    e.trips,
+                    mode_name(e.mode),
+                    e.origin,
+                    e.destination
+                ));
+                continue;
+            }
+
+            for _ in 0..e.trips {
+                let depart = e.start + (e.end - e.start) * rng.gen_range(0.0, 1.0);
+                let from = starts.choose(rng);
+                let mut to = ends.choose(rng);
+                if same_endpt(&from, &to) {
+                    if let Some(other) = ends.choose_except(&from, rng) {
+                        to = other;
+                    } else {
+                        // A zone with one building or border can't have trips within itself.
+                        same_start_and_end += 1;
+                        continue;
+                    }
+                }
+                trips.push((depart, from, to, e.mode));
+            }
+        }
+        trips.sort_by_key(|(depart, _, _, _)| *depart);
+        if same_start_and_end > 0 {
+            timer.warn(format!(
+                "Skipping {} trips that would start and end at the same place",
+                prettyprint_usize(same_start_and_end)
+            ));
+        }
+
+        let mut individ_trips = Vec::new();
+        let mut impossible = 0;
+        for (depart, from, to, mode) in &trips {
+            if let Some(trip) = spawn_trip(from, to, *mode, *depart, map) {
+                individ_trips.push(trip);
+            } else {
+                impossible += 1;
+            }
+        }
+        if impossible > 0 {
+            timer.warn(format!(
+                "{} OD trips couldn't be spawned",
+                prettyprint_usize(impossible)
+            ));
+        }
+        timer.note(format!(
+            "Distributed {} trips from the OD matrix",
+            prettyprint_usize(individ_trips.len())
+        ));
+
+        let individ_parked_cars = parked_cars_needed(
+            map,
+            trips
+                .iter()
+                .filter(|(_, _, _, mode)| *mode == Mode::Drive)
+                .map(|(_, from, to, _)| (from, to)),
+        );
+
+        Scenario {
+            scenario_name: scenario_name.to_string(),
+            map_name: map.get_name().to_string(),
+            seed_buses: true,
+            seed_parked_cars: Vec::new(),
+            spawn_over_time: Vec::new(),
+            border_spawn_over_time: Vec::new(),
+            mode_choice: ModeChoice::FixedPercentages,
+            individ_trips,
+            individ_parked_cars,
+            people: Vec::new(),
+        }
+    }
+
+    // Counts a scenario's individual trips (including the ones people take) between zones, using
+    // time slices of a fixed length starting at midnight. SpawnOverTime and BorderSpawnOverTime
+    // aren't included. Trips starting or ending outside of every zone are skipped.
+    pub fn from_scenario(
+        scenario: &Scenario,
+        zones: &Vec<Zone>,
+        map: &Map,
+        slice: Duration,
+        timer: &mut Timer,
+    ) -> ODMatrix {
+        // (origin, destination, mode, slice) -> trips
+        let mut counts: BTreeMap<(&str, &str, Mode, usize), usize> = BTreeMap::new();
+        let mut skipped = 0;
+        let trips = scenario.all_trips();
+        timer.start_iter("find zones of trips", trips.len());
+        for trip in trips {
+            timer.next();
+            let (depart, from, to, mode) = trip_endpoints(trip, map);
+            let origin = zones.iter().find(|z| z.contains_pt(from));
+            let destination = zones.iter().find(|z| z.contains_pt(to));
+            if let (Some(o), Some(d)) = (origin, destination) {
+                let idx = ((depart - Time::START_OF_DAY) / slice).floor() as usize;
+                *counts
+                    .entry((o.name.as_str(), d.name.as_str(), mode, idx))
+                    .or_insert(0) += 1;
+            } else {
+                skipped += 1;
+            }
+        }
+        if skipped > 0 {
+            timer.warn(format!(
+                "{} trips start or end outside of every zone",
+                prettyprint_usize(skipped)
+            ));
+        }
+
+        ODMatrix {
+            entries: counts
+                .into_iter()
+                .map(|((origin, destination, mode, idx), trips)| ODEntry {
+                    origin: origin.to_string(),
+                    destination: destination.to_string(),
+                    mode,
+                    start: Time::START_OF_DAY + slice * (idx as f64),
+                    end: Time::START_OF_DAY + slice * ((idx + 1) as f64),
+                    trips,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn same_endpt(e1: &TripEndpt, e2: &TripEndpt) -> bool {
+    match (e1, e2) {
+        (TripEndpt::Building(b1), TripEndpt::Building(b2)) => b1 == b2,
+        (TripEndpt::Border(i1, _), TripEndpt::Border(i2, _)) => i1 == i2,
+        _ => false,
+    }
+}
+
+// (departure, origin, destination, mode)
+fn trip_endpoints(trip: &SpawnTrip, map: &Map) -> (Time, Pt2D, Pt2D, Mode) {
+    let goal_pt = |goal: &DrivingGoal| match goal {
+        DrivingGoal::ParkNear(b) => map.get_b(*b).polygon.center(),
+        DrivingGoal::Border(i, _) => map.get_i(*i).polygon.center(),
+    };
+    let spot_pt = |spot: &SidewalkSpot| match spot.connection {
+        SidewalkPOI::Building(b) => map.get_b(b).polygon.center(),
+        SidewalkPOI::Border(i) => map.get_i(i).polygon.center(),
+        _ => spot.sidewalk_pos.pt(map),
+    };
+    match trip {
+        SpawnTrip::CarAppearing {
+            depart,
+            start,
+            goal,
+            is_bike,
+        } => (
+            *depart,
+            start.pt(map),
+            goal_pt(goal),
+            if *is_bike { Mode::Bike } else { Mode::Drive },
+        ),
+        SpawnTrip::MaybeUsingParkedCar(depart, b, goal) => (
+            *depart,
+            map.get_b(*b).polygon.center(),
+            goal_pt(goal),
+            Mode::Drive,
+        ),
+        SpawnTrip::UsingBike(depart, start, goal) => {
+            (*depart, spot_pt(start), goal_pt(goal), Mode::Bike)
+        }
+        SpawnTrip::JustWalking(depart, start, goal) => {
+            (*depart, spot_pt(start), spot_pt(goal), Mode::Walk)
+        }
+        SpawnTrip::UsingTransit(depart, start, goal, _, _, _) => {
+            (*depart, spot_pt(start), spot_pt(goal), Mode::Transit)
+        }
+    }
+}
+
+fn constraints(mode: Mode) -> PathConstraints {
+    match mode {
+        Mode::Walk | Mode::Transit => PathConstraints::Pedestrian,
+        Mode::Bike => PathConstraints::Bike,
+        Mode::Drive => PathConstraints::Car,
+    }
+}
+
+fn parse_mode(x: &str) -> Option<Mode> {
+    match x {
+        "walk" => Some(Mode::Walk),
+        "bike" => Some(Mode::Bike),
+        "drive" => Some(Mode::Drive),
+        "transit" => Some(Mode::Transit),
+        _ => None,
+    }
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Walk => "walk",
+        Mode::Bike => "bike",
+        Mode::Drive => "drive",
+        Mode::Transit => "transit",
+    }
+}
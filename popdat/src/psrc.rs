@@ -16,6 +16,8 @@ pub struct Trip {
     pub purpose: (Purpose, Purpose),
     pub trip_time: Duration,
     pub trip_dist: Distance,
+    // (household, person within the household)
+    pub person: (usize, usize),
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -31,7 +33,7 @@ pub struct Parcel {
     pub offstreet_parking_spaces: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Mode {
     Walk,
     Bike,
@@ -97,6 +99,12 @@ pub fn import_trips(
         // travdist
         let trip_dist = Distance::miles(rec[24].parse::<f64>()?);
 
+        // hhno and pno
+        let person = (
+            rec[11].trim_end_matches(".0").parse::<usize>()?,
+            rec[20].trim_end_matches(".0").parse::<usize>()?,
+        );
+
         trips.push(Trip {
             from,
             to,
@@ -105,6 +113,7 @@ pub fn import_trips(
             mode,
             trip_time,
             trip_dist,
+            person,
         });
     }
     done(timer);
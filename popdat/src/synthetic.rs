@@ -0,0 +1,359 @@
+use crate::psrc::{Mode, Parcel};
+use crate::trips::{spawn_trip, TripEndpt};
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Distance, Duration, Pt2D, Time};
+use map_model::{Building, BuildingID, Map};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use sim::{IndividTrip, ModeChoice, PersonSpec, Scenario};
+use std::collections::{BTreeMap, HashMap};
+
+// Destinations are grouped into square cells this wide, so the gravity model only has to weigh
+// cells, not every building.
+const CELL_SIZE: f64 = 500.0;
+
+pub struct DemandOptions {
+    pub rng_seed: u8,
+
+    // Only used for buildings without parcel data
+    pub sq_meters_per_resident: f64,
+    pub sq_meters_per_office_job: f64,
+    pub sq_meters_per_retail_job: f64,
+    // Only used for buildings with parcel data
+    pub people_per_household: f64,
+
+    pub pct_working: f64,
+    // The chance of somebody running an errand, after work or sometime midday
+    pub pct_errand: f64,
+    // Attraction to a destination falls off like exp(-distance / decay)
+    pub commute_decay: Distance,
+    pub errand_decay: Distance,
+
+    pub leave_home: (Time, Time),
+    pub workday: Duration,
+    pub errand_window: (Time, Time),
+    pub errand_duration: Duration,
+
+    // Everybody walks trips shorter than this
+    pub max_walk: Distance,
+    // Nobody bikes trips longer than this
+    pub max_bike: Distance,
+    pub pct_bike: f64,
+    pub pct_transit: f64,
+}
+
+impl Default for DemandOptions {
+    fn default() -> DemandOptions {
+        DemandOptions {
+            rng_seed: 42,
+            sq_meters_per_resident: 60.0,
+            sq_meters_per_office_job: 30.0,
+            sq_meters_per_retail_job: 50.0,
+            people_per_household: 2.2,
+            pct_working: 0.6,
+            pct_errand: 0.3,
+            commute_decay: Distance::meters(3000.0),
+            errand_decay: Distance::meters(1000.0),
+            leave_home: (
+                Time::START_OF_DAY + Duration::hours(7),
+                Time::START_OF_DAY + Duration::hours(9),
+            ),
+            workday: Duration::hours(8) + Duration::minutes(30),
+            errand_window: (
+                Time::START_OF_DAY + Duration::hours(10),
+                Time::START_OF_DAY + Duration::hours(16),
+            ),
+            errand_duration: Duration::minutes(30),
+            max_walk: Distance::meters(1000.0),
+            max_bike: Distance::meters(8000.0),
+            pct_bike: 0.05,
+            pct_transit: 0.15,
+        }
+    }
+}
+
+// Builds demand for any map. Residents and jobs are assigned to buildings using PSRC parcels
+// where they're available (pass in an empty map otherwise) and building size and OSM tags
+// elsewhere. Then everybody picks a workplace and errands with a gravity model and gets a
+// daily schedule.
+pub fn synthetic_scenario(
+    map: &Map,
+    parcels: &HashMap<BuildingID, Parcel>,
+    opts: DemandOptions,
+    timer: &mut Timer,
+) -> Scenario {
+    let mut rng = XorShiftRng::from_seed([opts.rng_seed; 16]);
+
+    let mut residents: Vec<(BuildingID, usize)> = Vec::new();
+    let mut jobs: Vec<(BuildingID, f64)> = Vec::new();
+    let mut shops: Vec<(BuildingID, f64)> = Vec::new();
+    for b in map.all_buildings() {
+        let land_use = LandUse::new(b, parcels.get(&b.id), &opts);
+        if land_use.residents > 0 {
+            residents.push((b.id, land_use.residents));
+        }
+        if land_use.jobs > 0 {
+            jobs.push((b.id, land_use.jobs as f64));
+        }
+        if land_use.errands > 0.0 {
+            shops.push((b.id, land_use.errands));
+        }
+    }
+    timer.note(format!(
+        "{} residents and {} jobs in {} buildings",
+        prettyprint_usize(residents.iter().map(|(_, n)| n).sum()),
+        prettyprint_usize(jobs.iter().map(|(_, n)| *n as usize).sum()),
+        prettyprint_usize(map.all_buildings().len())
+    ));
+    let workplaces = Gravity::new(jobs, map);
+    let errands = Gravity::new(shops, map);
+
+    let mut people = Vec::new();
+    let mut individ_parked_cars = BTreeMap::new();
+    timer.start_iter("make schedules", residents.len());
+    for (home, num_residents) in residents {
+        timer.next();
+        let home_pt = map.get_b(home).polygon.center();
+        let commute_dest = workplaces.destinations_from(home_pt, opts.commute_decay);
+        let errand_dest = errands.destinations_from(home_pt, opts.errand_decay);
+
+        for _ in 0..num_residents {
+            // (destination, departure, dwell). The last trip goes home.
+            let mut stops: Vec<(BuildingID, Time, Duration)> = Vec::new();
+            let work = if rng.gen_bool(opts.pct_working) {
+                commute_dest
+                    .as_ref()
+                    .map(|d| workplaces.pick(d, &mut rng))
+                    .filter(|b| *b != home)
+            } else {
+                None
+            };
+            let errand = if rng.gen_bool(opts.pct_errand) {
+                errand_dest
+                    .as_ref()
+                    .map(|d| errands.pick(d, &mut rng))
+                    .filter(|b| *b != home)
+            } else {
+                None
+            };
+
+            if let Some(work) = work {
+                let leave = rand_time(&mut rng, opts.leave_home);
+                // Stay at least half the workday, even if the commute takes forever.
+                stops.push((work, leave, opts.workday / 2.0));
+                let leave_work = leave + opts.workday;
+                if let Some(errand) = errand.filter(|b| *b != work) {
+                    stops.push((errand, leave_work, opts.errand_duration));
+                    stops.push((home, leave_work + opts.errand_duration, Duration::ZERO));
+                } else {
+                    stops.push((home, leave_work, Duration::ZERO));
+                }
+            } else if let Some(errand) = errand {
+                let leave = rand_time(&mut rng, opts.errand_window);
+                stops.push((errand, leave, opts.errand_duration));
+                stops.push((home, leave + opts.errand_duration, Duration::ZERO));
+            } else {
+                continue;
+            }
+
+            // Use the same mode all day, so anybody driving keeps their car with them. Decide
+            // based on the farthest stop.
+            let farthest = stops
+                .iter()
+                .map(|(b, _, _)| map.get_b(*b).polygon.center().dist_to(home_pt))
+                .fold(Distance::ZERO, |a, b| if a > b { a } else { b });
+            let mode = pick_mode(farthest, &opts, &mut rng);
+
+            let num_stops = stops.len();
+            let mut trips = Vec::new();
+            let mut from = home;
+            for (to, depart, dwell) in stops {
+                if let Some(trip) = spawn_trip(
+                    &TripEndpt::Building(from),
+                    &TripEndpt::Building(to),
+                    mode,
+                    depart,
+                    map,
+                ) {
+                    trips.push(IndividTrip { trip, dwell });
+                } else {
+                    break;
+                }
+                from = to;
+            }
+            // Don't strand anybody partway through their day.
+            if trips.len() != num_stops {
+                continue;
+            }
+            if mode == Mode::Drive {
+                *individ_parked_cars.entry(home).or_insert(0) += 1;
+            }
+            people.push(PersonSpec { trips });
+        }
+    }
+    timer.note(format!(
+        "{} people making {} trips",
+        prettyprint_usize(people.len()),
+        prettyprint_usize(people.iter().map(|p| p.trips.len()).sum())
+    ));
+
+    Scenario {
+        scenario_name: "synthetic".to_string(),
+        map_name: map.get_name().to_string(),
+        seed_buses: true,
+        seed_parked_cars: Vec::new(),
+        spawn_over_time: Vec::new(),
+        border_spawn_over_time: Vec::new(),
+        mode_choice: ModeChoice::FixedPercentages,
+        individ_trips: Vec::new(),
+        individ_parked_cars,
+        people,
+    }
+}
+
+// What's in one building
+pub struct LandUse {
+    pub residents: usize,
+    pub jobs: usize,
+    // How attractive the building is for errands
+    pub errands: f64,
+}
+
+impl LandUse {
+    pub fn new(b: &Building, parcel: Option<&Parcel>, opts: &DemandOptions) -> LandUse {
+        let building = b.osm_tags.get("building").map(|x| x.as_str());
+        let retail = b.osm_tags.contains_key("shop")
+            || b.osm_tags.contains_key("amenity")
+            || match building {
+                Some("retail") | Some("commercial") | Some("supermarket") | Some("kiosk") => true,
+                _ => false,
+            };
+        let office = b.osm_tags.contains_key("office")
+            || match building {
+                Some("office") | Some("industrial") | Some("warehouse") | Some("school")
+                | Some("university") | Some("hospital") | Some("civic") | Some("public")
+                | Some("government") => true,
+                _ => false,
+            };
+        let residential = match building {
+            Some("house")
+            | Some("detached")
+            | Some("residential")
+            | Some("apartments")
+            | Some("terrace")
+            | Some("semidetached_house")
+            | Some("dormitory") => true,
+            // Most buildings in OSM aren't tagged with anything more specific.
+            Some("yes") | None => !retail && !office,
+            _ => false,
+        };
+
+        let levels = b
+            .osm_tags
+            .get("building:levels")
+            .and_then(|x| x.parse::<f64>().ok())
+            .unwrap_or(1.0)
+            .max(1.0);
+        let floor_area = b.polygon.area() * levels;
+        let errands = if retail { floor_area } else { 0.0 };
+
+        if let Some(p) = parcel {
+            return LandUse {
+                residents: ((p.num_households as f64) * opts.people_per_household).round() as usize,
+                jobs: p.num_employees,
+                errands,
+            };
+        }
+
+        let residents = if residential {
+            ((floor_area / opts.sq_meters_per_resident).round() as usize).max(1)
+        } else {
+            0
+        };
+        let jobs = if retail {
+            (floor_area / opts.sq_meters_per_retail_job).round() as usize
+        } else if office {
+            (floor_area / opts.sq_meters_per_office_job).round() as usize
+        } else {
+            0
+        };
+        LandUse {
+            residents,
+            jobs,
+            errands,
+        }
+    }
+}
+
+// Picks destinations with probability proportional to their weight, falling off with distance
+pub struct Gravity {
+    cells: Vec<Cell>,
+}
+
+struct Cell {
+    center: Pt2D,
+    total: f64,
+    bldgs: Vec<BuildingID>,
+    choose_bldg: WeightedIndex<f64>,
+}
+
+impl Gravity {
+    pub fn new(weights: Vec<(BuildingID, f64)>, map: &Map) -> Gravity {
+        let mut per_cell: BTreeMap<(i64, i64), Vec<(BuildingID, f64)>> = BTreeMap::new();
+        for (b, weight) in weights {
+            let pt = map.get_b(b).polygon.center();
+            per_cell
+                .entry((
+                    (pt.x() / CELL_SIZE).floor() as i64,
+                    (pt.y() / CELL_SIZE).floor() as i64,
+                ))
+                .or_insert_with(Vec::new)
+                .push((b, weight));
+        }
+        Gravity {
+            cells: per_cell
+                .into_iter()
+                .map(|((x, y), bldgs)| Cell {
+                    center: Pt2D::new((x as f64 + 0.5) * CELL_SIZE, (y as f64 + 0.5) * CELL_SIZE),
+                    total: bldgs.iter().map(|(_, w)| w).sum(),
+                    choose_bldg: WeightedIndex::new(bldgs.iter().map(|(_, w)| *w)).unwrap(),
+                    bldgs: bldgs.into_iter().map(|(b, _)| b).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    // None if there's nowhere to go
+    pub fn destinations_from(&self, from: Pt2D, decay: Distance) -> Option<WeightedIndex<f64>> {
+        WeightedIndex::new(
+            self.cells
+                .iter()
+                .map(|c| c.total * (-(c.center.dist_to(from) / decay)).exp()),
+        )
+        .ok()
+    }
+
+    pub fn pick(&self, destinations: &WeightedIndex<f64>, rng: &mut XorShiftRng) -> BuildingID {
+        let cell = &self.cells[destinations.sample(rng)];
+        cell.bldgs[cell.choose_bldg.sample(rng)]
+    }
+}
+
+fn pick_mode(dist: Distance, opts: &DemandOptions, rng: &mut XorShiftRng) -> Mode {
+    if dist <= opts.max_walk {
+        return Mode::Walk;
+    }
+    let x = rng.gen_range(0.0, 1.0);
+    if x < opts.pct_bike && dist <= opts.max_bike {
+        Mode::Bike
+    } else if x < opts.pct_bike + opts.pct_transit {
+        Mode::Transit
+    } else {
+        Mode::Drive
+    }
+}
+
+fn rand_time(rng: &mut XorShiftRng, (low, high): (Time, Time)) -> Time {
+    low + (high - low) * rng.gen_range(0.0, 1.0)
+}
@@ -3,7 +3,9 @@ use crate::PopDat;
 use abstutil::Timer;
 use geom::{Distance, Duration, LonLat, Polygon, Pt2D, Time};
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, Position};
-use sim::{DrivingGoal, Scenario, SidewalkSpot, SpawnTrip, TripSpec};
+use sim::{
+    DrivingGoal, IndividTrip, ModeChoice, PersonSpec, Scenario, SidewalkSpot, SpawnTrip, TripSpec,
+};
 use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Debug)]
@@ -16,6 +18,8 @@ pub struct Trip {
     // These are an upper bound when TripEndpt::Border is involved.
     pub trip_time: Duration,
     pub trip_dist: Distance,
+    // (household, person within the household)
+    pub person: (usize, usize),
 }
 
 #[derive(Clone, Debug)]
@@ -32,86 +36,7 @@ impl Trip {
     }
 
     pub fn to_spawn_trip(&self, map: &Map) -> Option<SpawnTrip> {
-        match self.mode {
-            Mode::Drive => match self.from {
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Car)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            depart: self.depart_at,
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Car, map),
-                            is_bike: false,
-                        })
-                    } else {
-                        // TODO need to be able to emit warnings from parallelize
-                        //timer.warn(format!("No room for car to appear at {:?}", self.from));
-                        None
-                    }
-                }
-                TripEndpt::Building(b) => Some(SpawnTrip::MaybeUsingParkedCar(
-                    self.depart_at,
-                    b,
-                    self.to.driving_goal(PathConstraints::Car, map),
-                )),
-            },
-            Mode::Bike => match self.from {
-                TripEndpt::Building(b) => Some(SpawnTrip::UsingBike(
-                    self.depart_at,
-                    SidewalkSpot::building(b, map),
-                    self.to.driving_goal(PathConstraints::Bike, map),
-                )),
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Bike)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            depart: self.depart_at,
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Bike, map),
-                            is_bike: true,
-                        })
-                    } else {
-                        //timer.warn(format!("No room for bike to appear at {:?}", self.from));
-                        None
-                    }
-                }
-            },
-            Mode::Walk => Some(SpawnTrip::JustWalking(
-                self.depart_at,
-                self.from.start_sidewalk_spot(map),
-                self.to.end_sidewalk_spot(map),
-            )),
-            Mode::Transit => {
-                let start = self.from.start_sidewalk_spot(map);
-                let goal = self.to.end_sidewalk_spot(map);
-                if let Some((stop1, stop2, route)) =
-                    map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
-                {
-                    Some(SpawnTrip::UsingTransit(
-                        self.depart_at,
-                        start,
-                        goal,
-                        route,
-                        stop1,
-                        stop2,
-                    ))
-                } else {
-                    //timer.warn(format!("{:?} not actually using transit, because pathfinding
-                    // didn't find any useful route", trip));
-                    Some(SpawnTrip::JustWalking(self.depart_at, start, goal))
-                }
-            }
-        }
+        spawn_trip(&self.from, &self.to, self.mode, self.depart_at, map)
     }
 }
 
@@ -168,6 +93,90 @@ impl TripEndpt {
     }
 }
 
+pub(crate) fn spawn_trip(
+    from: &TripEndpt,
+    to: &TripEndpt,
+    mode: Mode,
+    depart_at: Time,
+    map: &Map,
+) -> Option<SpawnTrip> {
+    match mode {
+        Mode::Drive => match *from {
+            TripEndpt::Border(i, _) => {
+                if let Some(start) = TripSpec::spawn_car_at(
+                    Position::new(
+                        map.get_i(i).get_outgoing_lanes(map, PathConstraints::Car)[0],
+                        Distance::ZERO,
+                    ),
+                    map,
+                ) {
+                    Some(SpawnTrip::CarAppearing {
+                        depart: depart_at,
+                        start,
+                        goal: to.driving_goal(PathConstraints::Car, map),
+                        is_bike: false,
+                    })
+                } else {
+                    // TODO need to be able to emit warnings from parallelize
+                    //timer.warn(format!("No room for car to appear at {:?}", from));
+                    None
+                }
+            }
+            TripEndpt::Building(b) => Some(SpawnTrip::MaybeUsingParkedCar(
+                depart_at,
+                b,
+                to.driving_goal(PathConstraints::Car, map),
+            )),
+        },
+        Mode::Bike => match *from {
+            TripEndpt::Building(b) => Some(SpawnTrip::UsingBike(
+                depart_at,
+                SidewalkSpot::building(b, map),
+                to.driving_goal(PathConstraints::Bike, map),
+            )),
+            TripEndpt::Border(i, _) => {
+                if let Some(start) = TripSpec::spawn_car_at(
+                    Position::new(
+                        map.get_i(i).get_outgoing_lanes(map, PathConstraints::Bike)[0],
+                        Distance::ZERO,
+                    ),
+                    map,
+                ) {
+                    Some(SpawnTrip::CarAppearing {
+                        depart: depart_at,
+                        start,
+                        goal: to.driving_goal(PathConstraints::Bike, map),
+                        is_bike: true,
+                    })
+                } else {
+                    //timer.warn(format!("No room for bike to appear at {:?}", from));
+                    None
+                }
+            }
+        },
+        Mode::Walk => Some(SpawnTrip::JustWalking(
+            depart_at,
+            from.start_sidewalk_spot(map),
+            to.end_sidewalk_spot(map),
+        )),
+        Mode::Transit => {
+            let start = from.start_sidewalk_spot(map);
+            let goal = to.end_sidewalk_spot(map);
+            if let Some((stop1, stop2, route)) =
+                map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
+            {
+                Some(SpawnTrip::UsingTransit(
+                    depart_at, start, goal, route, stop1, stop2,
+                ))
+            } else {
+                //timer.warn(format!("{:?} not actually using transit, because pathfinding
+                // didn't find any useful route", trip));
+                Some(SpawnTrip::JustWalking(depart_at, start, goal))
+            }
+        }
+    }
+}
+
 pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingID, Parcel>) {
     let popdat: PopDat = abstutil::read_binary(abstutil::path_popdat(), timer);
 
@@ -250,6 +259,7 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
             mode: trip.mode,
             trip_time: trip.trip_time,
             trip_dist: trip.trip_dist,
+            person: trip.person,
         };
 
         match (&trip.from, &trip.to) {
@@ -278,50 +288,89 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
     (trips, bldgs)
 }
 
-pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
-    let (trips, _) = clip_trips(map, timer);
-    // TODO Don't clone trips for parallelize
-    let individ_trips = timer
-        .parallelize("turn PSRC trips into SpawnTrips", trips.clone(), |trip| {
-            trip.to_spawn_trip(map)
-        })
+// Takes the trips from clip_trips.
+pub fn trips_to_scenario(map: &Map, trips: Vec<Trip>, timer: &mut Timer) -> Scenario {
+    // Chain together everybody's trips in order. Somebody might have a trip that starts or ends
+    // outside the map clipped out, so they'll seem to teleport; that's fine.
+    let mut trips_per_person: BTreeMap<(usize, usize), Vec<Trip>> = BTreeMap::new();
+    for trip in &trips {
+        trips_per_person
+            .entry(trip.person)
+            .or_insert_with(Vec::new)
+            .push(trip.clone());
+    }
+    let people = timer
+        .parallelize(
+            "turn PSRC trips into people",
+            trips_per_person.into_iter().map(|(_, t)| t).collect(),
+            |mut trips| {
+                trips.sort_by_key(|t| t.depart_at);
+                let mut individ_trips = Vec::new();
+                for (idx, trip) in trips.iter().enumerate() {
+                    // How long until the next trip, assuming this one takes as long as PSRC says
+                    let dwell = trips
+                        .get(idx + 1)
+                        .map(|next| (next.depart_at - trip.end_time()).max(Duration::ZERO))
+                        .unwrap_or(Duration::ZERO);
+                    if let Some(spawn) = trip.to_spawn_trip(map) {
+                        individ_trips.push(IndividTrip { trip: spawn, dwell });
+                    }
+                }
+                PersonSpec {
+                    trips: individ_trips,
+                }
+            },
+        )
         .into_iter()
-        .flatten()
+        .filter(|p| !p.trips.is_empty())
         .collect();
 
-    // How many parked cars do we need to spawn near each building?
-    // TODO This assumes trips are instantaneous. At runtime, somebody might try to use a parked
-    // car from a building, but one hasn't been delivered yet.
+    let individ_parked_cars = parked_cars_needed(
+        map,
+        trips
+            .iter()
+            .filter(|t| t.mode == Mode::Drive)
+            .map(|t| (&t.from, &t.to)),
+    );
+
+    Scenario {
+        scenario_name: "weekday".to_string(),
+        map_name: map.get_name().to_string(),
+        seed_buses: true,
+        seed_parked_cars: Vec::new(),
+        spawn_over_time: Vec::new(),
+        border_spawn_over_time: Vec::new(),
+        mode_choice: ModeChoice::FixedPercentages,
+        individ_trips: Vec::new(),
+        individ_parked_cars,
+        people,
+    }
+}
+
+// How many parked cars do we need to spawn near each building, given some driving trips?
+// TODO This assumes trips are instantaneous. At runtime, somebody might try to use a parked
+// car from a building, but one hasn't been delivered yet.
+pub(crate) fn parked_cars_needed<'a, I: Iterator<Item = (&'a TripEndpt, &'a TripEndpt)>>(
+    map: &Map,
+    driving_trips: I,
+) -> BTreeMap<BuildingID, usize> {
     let mut individ_parked_cars = BTreeMap::new();
     let mut avail_per_bldg = BTreeMap::new();
     for b in map.all_buildings() {
         individ_parked_cars.insert(b.id, 0);
         avail_per_bldg.insert(b.id, 0);
     }
-    for trip in trips {
-        if trip.mode != Mode::Drive {
-            continue;
-        }
-        if let TripEndpt::Building(b) = trip.from {
-            if avail_per_bldg[&b] > 0 {
-                *avail_per_bldg.get_mut(&b).unwrap() -= 1;
+    for (from, to) in driving_trips {
+        if let TripEndpt::Building(b) = from {
+            if avail_per_bldg[b] > 0 {
+                *avail_per_bldg.get_mut(b).unwrap() -= 1;
             } else {
-                *individ_parked_cars.get_mut(&b).unwrap() += 1;
+                *individ_parked_cars.get_mut(b).unwrap() += 1;
             }
         }
-        if let TripEndpt::Building(b) = trip.to {
-            *avail_per_bldg.get_mut(&b).unwrap() += 1;
+        if let TripEndpt::Building(b) = to {
+            *avail_per_bldg.get_mut(b).unwrap() += 1;
         }
     }
-
-    Scenario {
-        scenario_name: "weekday".to_string(),
-        map_name: map.get_name().to_string(),
-        seed_buses: true,
-        seed_parked_cars: Vec::new(),
-        spawn_over_time: Vec::new(),
-        border_spawn_over_time: Vec::new(),
-        individ_trips,
-        individ_parked_cars,
-    }
+    individ_parked_cars
 }
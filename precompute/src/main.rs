@@ -1,12 +1,16 @@
 use abstutil::{CmdArgs, Timer};
 use map_model::Map;
-use popdat::trips_to_scenario;
+use popdat::{clip_trips, synthetic_scenario, trips_to_scenario, DemandOptions};
+use std::collections::HashMap;
 
 fn main() {
     let mut args = CmdArgs::new();
     let load = args.required_free();
     let disable_psrc_scenarios = args.enabled("--disable_psrc_scenarios");
     let use_fixes = !args.enabled("--nofixes");
+    // Also generate demand from building size, land use, and PSRC parcels (unless disabled), for
+    // maps without PSRC trips.
+    let synthetic_demand = args.enabled("--synthetic_demand");
     args.done();
 
     let mut timer = Timer::new(format!("precompute {}", load));
@@ -16,7 +20,14 @@ fn main() {
     map.save();
     timer.stop("save map");
 
-    if !disable_psrc_scenarios {
-        trips_to_scenario(&map, &mut timer).save();
+    let parcels = if disable_psrc_scenarios {
+        HashMap::new()
+    } else {
+        let (trips, parcels) = clip_trips(&map, &mut timer);
+        trips_to_scenario(&map, trips, &mut timer).save();
+        parcels
+    };
+    if synthetic_demand {
+        synthetic_scenario(&map, &parcels, DemandOptions::default(), &mut timer).save();
     }
 }
@@ -1,26 +1,47 @@
-use crate::{AgentID, CarID, Event, TripID, TripMode, VehicleType};
+use crate::{AgentID, CarID, Emissions, Event, TripID, TripMode, VehicleType};
 use abstutil::Counter;
 use derivative::Derivative;
 use geom::{Distance, Duration, DurationHistogram, PercentageHistogram, Time};
 use map_model::{
-    BusRouteID, BusStopID, IntersectionID, Map, Path, PathRequest, RoadID, Traversable, TurnGroupID,
+    BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, RoadID, TravelTimes,
+    Traversable, TurnGroupID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 
+// Transit agencies commonly count arriving between 1 minute early and 5 minutes late as on time.
+const ON_TIME_EARLY: Duration = Duration::const_seconds(60.0);
+const ON_TIME_LATE: Duration = Duration::const_seconds(300.0);
+// Two buses are bunched if they arrive at a stop closer together than this fraction of their
+// scheduled headway.
+const BUNCHING_FRACTION: f64 = 0.25;
+
 #[derive(Clone, Serialize, Deserialize, Derivative)]
 pub struct Analytics {
     pub thruput_stats: ThruputStats,
     #[serde(skip_serializing, skip_deserializing)]
     pub(crate) test_expectations: VecDeque<Event>,
     pub bus_arrivals: Vec<(Time, CarID, BusRouteID, BusStopID)>,
+    // Only for buses following a timetable.
+    // Actual arrival, bus, route, run (indexing BusRoute::departures), stop, scheduled arrival
+    pub bus_schedule: Vec<(Time, CarID, BusRouteID, usize, BusStopID, Time)>,
     pub bus_passengers_waiting: Vec<(Time, BusStopID, BusRouteID)>,
+    // Every time somebody couldn't board a full bus
+    pub bus_boardings_denied: Vec<(Time, BusStopID, BusRouteID)>,
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
     pub finished_trips: Vec<(Time, TripID, Option<TripMode>, Duration)>,
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, String)>,
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(Time, Duration)>>,
+    // When a vehicle left the Traversable, and everything it emitted there
+    pub emissions: Vec<(Time, TripID, Traversable, Emissions)>,
+    // Only for cars. When they entered a lane, and how long until they entered the next one.
+    pub lane_travel_times: Vec<(Time, LaneID, Duration)>,
+    // Every time a driver switched to a better route while driving
+    pub reroutes: Vec<(Time, TripID)>,
+    #[serde(skip_serializing, skip_deserializing)]
+    lane_entered: BTreeMap<CarID, (Time, LaneID)>,
 
     // After we restore from a savestate, don't record anything. This is only going to make sense
     // if savestates are only used for quickly previewing against prebaked results, where we have
@@ -35,8 +56,9 @@ pub struct ThruputStats {
     #[serde(skip_serializing, skip_deserializing)]
     pub count_per_intersection: Counter<IntersectionID>,
 
-    raw_per_road: Vec<(Time, TripMode, RoadID)>,
-    raw_per_intersection: Vec<(Time, TripMode, IntersectionID)>,
+    // Every time something entered a road or intersection
+    pub raw_per_road: Vec<(Time, TripMode, RoadID)>,
+    pub raw_per_intersection: Vec<(Time, TripMode, IntersectionID)>,
 
     // Unlike everything else in Analytics, this is just for a moment in time.
     pub demand: BTreeMap<TurnGroupID, usize>,
@@ -54,10 +76,16 @@ impl Analytics {
             },
             test_expectations: VecDeque::new(),
             bus_arrivals: Vec::new(),
+            bus_schedule: Vec::new(),
             bus_passengers_waiting: Vec::new(),
+            bus_boardings_denied: Vec::new(),
             finished_trips: Vec::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            emissions: Vec::new(),
+            lane_travel_times: Vec::new(),
+            reroutes: Vec::new(),
+            lane_entered: BTreeMap::new(),
             record_anything: true,
         }
     }
@@ -77,7 +105,7 @@ impl Analytics {
                 AgentID::Car(c) => match c.1 {
                     VehicleType::Car => TripMode::Drive,
                     VehicleType::Bike => TripMode::Bike,
-                    VehicleType::Bus => TripMode::Transit,
+                    VehicleType::Bus | VehicleType::Train => TripMode::Transit,
                 },
             };
 
@@ -104,6 +132,21 @@ impl Analytics {
             };
         }
 
+        // Travel times
+        if let Event::AgentEntersTraversable(AgentID::Car(car), Traversable::Lane(l)) = ev {
+            if car.1 == VehicleType::Car {
+                if let Some((t, prev)) = self.lane_entered.insert(car, (time, l)) {
+                    self.lane_travel_times.push((t, prev, time - t));
+                }
+            }
+        }
+        match ev {
+            Event::CarReachedParkingSpot(car, _) | Event::CarOrBikeReachedBorder(car, _) => {
+                self.lane_entered.remove(&car);
+            }
+            _ => {}
+        }
+
         // Test expectations
         if !self.test_expectations.is_empty() && &ev == self.test_expectations.front().unwrap() {
             println!("At {}, met expectation {:?}", time, ev);
@@ -115,10 +158,19 @@ impl Analytics {
             self.bus_arrivals.push((time, bus, route, stop));
         }
 
+        // Schedule adherence
+        if let Event::ScheduledBusArrival(bus, route, run, stop, scheduled) = ev {
+            self.bus_schedule
+                .push((time, bus, route, run, stop, scheduled));
+        }
+
         // Bus passengers
         if let Event::PedReachedBusStop(_, stop, route) = ev {
             self.bus_passengers_waiting.push((time, stop, route));
         }
+        if let Event::PedDeniedBoarding(_, _, route, stop) = ev {
+            self.bus_boardings_denied.push((time, stop, route));
+        }
 
         // Finished trips
         if let Event::TripFinished(id, mode, dt) = ev {
@@ -135,6 +187,16 @@ impl Analytics {
                 .push((time, delay));
         }
 
+        // Rerouting
+        if let Event::CarRerouted(_, trip) = ev {
+            self.reroutes.push((time, trip));
+        }
+
+        // Emissions
+        if let Event::VehicleEmissions(_, trip, on, amount) = ev {
+            self.emissions.push((time, trip, on, amount));
+        }
+
         // TODO Kinda hacky, but these all consume the event, so kinda bundle em.
         match ev {
             Event::TripPhaseStarting(id, maybe_req, metadata) => {
@@ -297,6 +359,120 @@ impl Analytics {
         delays_to_stop
     }
 
+    // For each run of the route, how late (positive) or early (negative) did the bus arrive at
+    // each stop, compared to the timetable? Returns (stop, scheduled arrival, delta) for each run.
+    pub fn bus_schedule_deltas(
+        &self,
+        now: Time,
+        r: BusRouteID,
+    ) -> BTreeMap<usize, Vec<(BusStopID, Time, Duration)>> {
+        let mut per_run: BTreeMap<usize, Vec<(BusStopID, Time, Duration)>> = BTreeMap::new();
+        for (t, _, route, run, stop, scheduled) in &self.bus_schedule {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_run.entry(*run).or_insert_with(Vec::new).push((
+                    *stop,
+                    *scheduled,
+                    *t - *scheduled,
+                ));
+            }
+        }
+        per_run
+    }
+
+    // What percentage of arrivals at each stop were on time? Also returns the percentage over the
+    // whole route first. None if no bus following a timetable has arrived anywhere yet.
+    pub fn bus_on_time_performance(
+        &self,
+        now: Time,
+        r: BusRouteID,
+    ) -> Option<(f64, BTreeMap<BusStopID, f64>)> {
+        // (on time, total)
+        let mut per_stop: BTreeMap<BusStopID, (usize, usize)> = BTreeMap::new();
+        for (t, _, route, _, stop, scheduled) in &self.bus_schedule {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                let delta = *t - *scheduled;
+                let cnt = per_stop.entry(*stop).or_insert((0, 0));
+                if delta >= Duration::ZERO - ON_TIME_EARLY && delta <= ON_TIME_LATE {
+                    cnt.0 += 1;
+                }
+                cnt.1 += 1;
+            }
+        }
+        if per_stop.is_empty() {
+            return None;
+        }
+
+        let on_time: usize = per_stop.values().map(|(x, _)| *x).sum();
+        let total: usize = per_stop.values().map(|(_, x)| *x).sum();
+        Some((
+            (on_time as f64) / (total as f64),
+            per_stop
+                .into_iter()
+                .map(|(stop, (x, y))| (stop, (x as f64) / (y as f64)))
+                .collect(),
+        ))
+    }
+
+    // Find consecutive buses that arrived at a stop much closer together than scheduled. Buses
+    // overtaking each other count too. Returns (time, stop, leading bus, following bus, actual
+    // headway, scheduled headway).
+    pub fn bus_bunching(
+        &self,
+        now: Time,
+        r: BusRouteID,
+    ) -> Vec<(Time, BusStopID, CarID, CarID, Duration, Duration)> {
+        let mut per_stop: BTreeMap<BusStopID, Vec<(Time, Time, CarID)>> = BTreeMap::new();
+        for (t, car, route, _, stop, scheduled) in &self.bus_schedule {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                per_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push((*t, *scheduled, *car));
+            }
+        }
+
+        let mut results = Vec::new();
+        for (stop, arrivals) in per_stop {
+            // bus_schedule is already sorted by actual arrival time
+            for pair in arrivals.windows(2) {
+                let (t1, scheduled1, bus1) = pair[0];
+                let (t2, scheduled2, bus2) = pair[1];
+                let actual_headway = t2 - t1;
+                let scheduled_headway = scheduled2 - scheduled1;
+                if scheduled_headway <= Duration::ZERO
+                    || actual_headway < scheduled_headway * BUNCHING_FRACTION
+                {
+                    results.push((t2, stop, bus1, bus2, actual_headway, scheduled_headway));
+                }
+            }
+        }
+        results.sort_by_key(|(t, _, _, _, _, _)| *t);
+        results
+    }
+
+    // How many times did people fail to board a full bus at each stop along the route?
+    pub fn bus_boardings_denied(&self, now: Time, r: BusRouteID) -> BTreeMap<BusStopID, usize> {
+        let mut per_stop = BTreeMap::new();
+        for (t, stop, route) in &self.bus_boardings_denied {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                *per_stop.entry(*stop).or_insert(0) += 1;
+            }
+        }
+        per_stop
+    }
+
     // At some moment in time, what's the distribution of passengers waiting for a route like?
     pub fn bus_passenger_delays(
         &self,
@@ -316,13 +492,34 @@ impl Analytics {
             }
         }
 
+        // When a bus arrives, people board in the order they started waiting, until it fills up.
+        let mut denied: BTreeMap<(Time, BusStopID), usize> = BTreeMap::new();
+        for (t, stop, route) in &self.bus_boardings_denied {
+            if *t > now {
+                break;
+            }
+            if *route == r {
+                *denied.entry((*t, *stop)).or_insert(0) += 1;
+            }
+        }
+
         for (t, _, route, stop) in &self.bus_arrivals {
             if *t > now {
                 break;
             }
             if *route == r {
                 if let Some(ref mut times) = waiting_per_stop.get_mut(stop) {
-                    times.retain(|time| *time > *t);
+                    let left_behind = denied.get(&(*t, *stop)).cloned().unwrap_or(0);
+                    let num_before = times.iter().filter(|time| **time <= *t).count();
+                    let mut skip = num_before.saturating_sub(left_behind);
+                    times.retain(|time| {
+                        if *time <= *t && skip > 0 {
+                            skip -= 1;
+                            false
+                        } else {
+                            true
+                        }
+                    });
                 }
             }
         }
@@ -403,6 +600,50 @@ impl Analytics {
         per_mode
     }
 
+    // How many agents of each mode entered a road during [start, end)
+    pub fn throughput_road_between(
+        &self,
+        road: RoadID,
+        start: Time,
+        end: Time,
+    ) -> BTreeMap<TripMode, usize> {
+        let mut per_mode = TripMode::all()
+            .into_iter()
+            .map(|m| (m, 0))
+            .collect::<BTreeMap<_, _>>();
+        for (t, m, r) in &self.thruput_stats.raw_per_road {
+            if *t >= end {
+                break;
+            }
+            if *r == road && *t >= start {
+                *per_mode.get_mut(m).unwrap() += 1;
+            }
+        }
+        per_mode
+    }
+
+    // How many agents of each mode made a turn through an intersection during [start, end)
+    pub fn throughput_intersection_between(
+        &self,
+        intersection: IntersectionID,
+        start: Time,
+        end: Time,
+    ) -> BTreeMap<TripMode, usize> {
+        let mut per_mode = TripMode::all()
+            .into_iter()
+            .map(|m| (m, 0))
+            .collect::<BTreeMap<_, _>>();
+        for (t, m, i) in &self.thruput_stats.raw_per_intersection {
+            if *t >= end {
+                break;
+            }
+            if *i == intersection && *t >= start {
+                *per_mode.get_mut(m).unwrap() += 1;
+            }
+        }
+        per_mode
+    }
+
     pub fn get_trip_phases(&self, trip: TripID, map: &Map) -> Vec<TripPhase> {
         let mut phases: Vec<TripPhase> = Vec::new();
         for (t, id, maybe_req, md) in &self.trip_log {
@@ -500,6 +741,51 @@ impl Analytics {
         ]
     }
 
+    // Emissions on roads and in intersections, up until now
+    pub fn emissions_per_location(
+        &self,
+        now: Time,
+        map: &Map,
+    ) -> (
+        BTreeMap<RoadID, Emissions>,
+        BTreeMap<IntersectionID, Emissions>,
+    ) {
+        let mut per_road = BTreeMap::new();
+        let mut per_intersection = BTreeMap::new();
+        for (t, _, on, amount) in &self.emissions {
+            if *t > now {
+                break;
+            }
+            match on {
+                Traversable::Lane(l) => {
+                    *per_road
+                        .entry(map.get_l(*l).parent)
+                        .or_insert(Emissions::ZERO) += *amount;
+                }
+                Traversable::Turn(t) => {
+                    *per_intersection.entry(t.parent).or_insert(Emissions::ZERO) += *amount;
+                }
+            }
+        }
+        (per_road, per_intersection)
+    }
+
+    // Average travel times per lane and hour, for congestion-aware pathfinding
+    pub fn travel_times(&self) -> TravelTimes {
+        TravelTimes::new(&self.lane_travel_times)
+    }
+
+    pub fn emissions_per_trip(&self, now: Time) -> BTreeMap<TripID, Emissions> {
+        let mut per_trip = BTreeMap::new();
+        for (t, trip, _, amount) in &self.emissions {
+            if *t > now {
+                break;
+            }
+            *per_trip.entry(*trip).or_insert(Emissions::ZERO) += *amount;
+        }
+        per_trip
+    }
+
     pub fn intersection_delays(&self, i: IntersectionID, t1: Time, t2: Time) -> DurationHistogram {
         let mut delays = DurationHistogram::new();
         // TODO Binary search
@@ -0,0 +1,168 @@
+use crate::VehicleType;
+use geom::{Duration, Speed, Time};
+use map_model::Traversable;
+use serde_derive::{Deserialize, Serialize};
+use std::ops;
+
+// The sim changes speed instantly, so acceleration is spread over the next constant-speed
+// interval. Clamp it to something physically reasonable when that interval is very short.
+const MAX_ACCEL: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    // Both in grams
+    pub co2: f64,
+    pub nox: f64,
+    // In liters
+    pub fuel: f64,
+}
+
+impl Emissions {
+    pub const ZERO: Emissions = Emissions {
+        co2: 0.0,
+        nox: 0.0,
+        fuel: 0.0,
+    };
+
+    pub fn is_zero(self) -> bool {
+        self == Emissions::ZERO
+    }
+}
+
+impl ops::Add for Emissions {
+    type Output = Emissions;
+
+    fn add(self, other: Emissions) -> Emissions {
+        Emissions {
+            co2: self.co2 + other.co2,
+            nox: self.nox + other.nox,
+            fuel: self.fuel + other.fuel,
+        }
+    }
+}
+
+impl ops::AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        *self = *self + other;
+    }
+}
+
+// From Int Panis et al, "Modelling instantaneous traffic emission and the influence of traffic
+// speed limits" (2006). Each pollutant is max(0, f1 + f2*v + f3*v^2 + f4*a + f5*a^2 + f6*v*a)
+// grams per second, with v in m/s and a in m/s^2.
+type Coefficients = [f64; 6];
+
+// Petrol cars
+const CAR_CO2: Coefficients = [5.53e-01, 1.61e-01, -2.89e-03, 2.66e-01, 5.11e-01, 1.83e-01];
+const CAR_NOX: Coefficients = [6.19e-04, 8.00e-05, -4.03e-06, -4.13e-04, 3.80e-04, 1.77e-04];
+// When decelerating harder than -0.5 m/s^2
+const CAR_NOX_DECEL: Coefficients = [2.17e-04, 0.0, 0.0, 0.0, 0.0, 0.0];
+// Diesel buses
+const BUS_CO2: Coefficients = [1.52e+00, 1.88e+00, -6.95e-02, 4.71e+00, 5.88e+00, 2.09e+00];
+const BUS_NOX: Coefficients = [3.56e-02, 9.71e-03, -4.34e-04, 3.75e-03, 2.77e-03, 8.88e-03];
+
+// Burning fuel produces CO2 in a fixed ratio, so work backwards to how much was burned. Grams of
+// CO2 per liter, from the EPA's "Greenhouse Gas Emissions from a Typical Passenger Vehicle".
+const PETROL_CO2_PER_LITER: f64 = 2347.0;
+const DIESEL_CO2_PER_LITER: f64 = 2689.0;
+
+fn rate(f: Coefficients, v: f64, a: f64) -> f64 {
+    (f[0] + f[1] * v + f[2] * v * v + f[3] * a + f[4] * a * a + f[5] * v * a).max(0.0)
+}
+
+// Emitted by one vehicle holding a constant speed and acceleration for some time.
+pub fn emissions(vehicle_type: VehicleType, speed: Speed, accel: f64, dt: Duration) -> Emissions {
+    let v = speed.inner_meters_per_second();
+    let a = accel.max(-MAX_ACCEL).min(MAX_ACCEL);
+    let (co2, nox, co2_per_liter) = match vehicle_type {
+        VehicleType::Car => (
+            rate(CAR_CO2, v, a),
+            rate(if a < -0.5 { CAR_NOX_DECEL } else { CAR_NOX }, v, a),
+            PETROL_CO2_PER_LITER,
+        ),
+        VehicleType::Bus => (
+            rate(BUS_CO2, v, a),
+            rate(BUS_NOX, v, a),
+            DIESEL_CO2_PER_LITER,
+        ),
+        // Light rail is electric
+        VehicleType::Bike | VehicleType::Train => {
+            return Emissions::ZERO;
+        }
+    };
+    Emissions {
+        co2: co2 * dt.inner_seconds(),
+        nox: nox * dt.inner_seconds(),
+        fuel: co2 * dt.inner_seconds() / co2_per_liter,
+    }
+}
+
+// Follows one vehicle's speed profile, totalling up what it emits on each Traversable.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct EmissionsTracker {
+    on: Traversable,
+    since: Time,
+    speed: Speed,
+    // The speed before the current one, to figure out acceleration
+    prev_speed: Speed,
+    // So far on the current Traversable
+    total: Emissions,
+}
+
+impl EmissionsTracker {
+    pub fn new(on: Traversable, now: Time) -> EmissionsTracker {
+        EmissionsTracker {
+            on,
+            since: now,
+            speed: Speed::ZERO,
+            prev_speed: Speed::ZERO,
+            total: Emissions::ZERO,
+        }
+    }
+
+    // The vehicle is now moving at some new speed on some Traversable. If it left the previous
+    // Traversable, returns everything emitted there.
+    pub fn change_speed(
+        &mut self,
+        vehicle_type: VehicleType,
+        now: Time,
+        speed: Speed,
+        on: Traversable,
+    ) -> Option<(Traversable, Emissions)> {
+        let dt = now - self.since;
+        if dt > Duration::ZERO {
+            let accel =
+                (self.speed - self.prev_speed).inner_meters_per_second() / dt.inner_seconds();
+            self.total += emissions(vehicle_type, self.speed, accel, dt);
+            self.prev_speed = self.speed;
+        }
+        self.since = now;
+        self.speed = speed;
+
+        if on == self.on {
+            return None;
+        }
+        let result = (self.on, std::mem::replace(&mut self.total, Emissions::ZERO));
+        self.on = on;
+        if result.1.is_zero() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    // The vehicle is vanishing. Returns everything emitted on the last Traversable.
+    pub fn finish(
+        &mut self,
+        vehicle_type: VehicleType,
+        now: Time,
+    ) -> Option<(Traversable, Emissions)> {
+        self.change_speed(vehicle_type, now, Speed::ZERO, self.on);
+        let result = (self.on, std::mem::replace(&mut self.total, Emissions::ZERO));
+        if result.1.is_zero() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+}
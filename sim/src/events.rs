@@ -1,5 +1,5 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TripID, TripMode};
-use geom::Duration;
+use crate::{AgentID, CarID, Emissions, OutputFile, ParkingSpot, PedestrianID, TripID, TripMode};
+use geom::{Duration, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Path, PathRequest, Traversable,
 };
@@ -12,6 +12,9 @@ pub enum Event {
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
+    // Just for buses following a timetable. The run indexes BusRoute::departures, and the time is
+    // when the bus was scheduled to arrive.
+    ScheduledBusArrival(CarID, BusRouteID, usize, BusStopID, Time),
 
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
     PedReachedBuilding(PedestrianID, BuildingID),
@@ -19,8 +22,12 @@ pub enum Event {
     PedReachedBusStop(PedestrianID, BusStopID, BusRouteID),
     PedEntersBus(PedestrianID, CarID, BusRouteID),
     PedLeavesBus(PedestrianID, CarID, BusRouteID),
+    // The bus was too full. The pedestrian keeps waiting at the stop.
+    PedDeniedBoarding(PedestrianID, CarID, BusRouteID, BusStopID),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    // Everything one vehicle emitted while on one Traversable
+    VehicleEmissions(CarID, TripID, Traversable, Emissions),
 
     AgentEntersTraversable(AgentID, Traversable),
     IntersectionDelayMeasured(IntersectionID, Duration),
@@ -29,7 +36,69 @@ pub enum Event {
     TripAborted(TripID),
     TripPhaseStarting(TripID, Option<PathRequest>, String),
 
-    // Just use for parking replanning. Not happy about copying the full path in here, but the way
-    // to plumb info into Analytics is Event.
+    // A driver found a better route while en route. A PathAmended event with the new path comes
+    // first.
+    CarRerouted(CarID, TripID),
+
+    // Used for parking replanning and rerouting. Not happy about copying the full path in here,
+    // but the way to plumb info into Analytics is Event.
     PathAmended(Path),
 }
+
+// Streams every event to a file, so other tools can analyze a simulation without rerunning it.
+// Each event has the time (seconds since midnight), the type (like "TripFinished"), and the
+// event's fields as JSON (an array if there's more than one). If the path ends in .csv, the file
+// looks like:
+//
+//   time,type,data
+//   3600.5,TripFinished,"[123,""Drive"",600.0]"
+//
+// Otherwise, each line is a JSON object:
+//
+//   {"time":3600.5,"type":"TripFinished","data":[123,"Drive",600.0]}
+#[derive(Clone)]
+pub(crate) struct EventLog {
+    file: OutputFile,
+    csv: bool,
+    // Written at the end of every step
+    pending: Vec<String>,
+}
+
+impl EventLog {
+    pub fn new(path: String) -> EventLog {
+        let csv = path.ends_with(".csv");
+        EventLog {
+            file: OutputFile::create(path, if csv { Some("time,type,data") } else { None }),
+            csv,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn event(&mut self, ev: &Event, time: Time) {
+        let (event_type, data) = match serde_json::to_value(ev).unwrap() {
+            serde_json::Value::Object(obj) => obj.into_iter().next().unwrap(),
+            x => panic!("{:?} serialized unexpectedly as {}", ev, x),
+        };
+        if self.csv {
+            self.pending.push(format!(
+                "{},{},\"{}\"",
+                time.inner_seconds(),
+                event_type,
+                data.to_string().replace("\"", "\"\"")
+            ));
+        } else {
+            self.pending.push(
+                serde_json::json!({
+                    "time": time.inner_seconds(),
+                    "type": event_type,
+                    "data": data,
+                })
+                .to_string(),
+            );
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.file.write_lines(self.pending.drain(..).collect());
+    }
+}
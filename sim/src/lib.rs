@@ -1,26 +1,36 @@
 mod analytics;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
+mod output;
 mod render;
 mod router;
 mod scheduler;
 mod sim;
+mod trajectories;
 mod transit;
 mod trips;
 
 pub use self::analytics::{Analytics, TripPhase};
+pub use self::emissions::Emissions;
+pub(crate) use self::emissions::EmissionsTracker;
 pub use self::events::Event;
+pub(crate) use self::events::EventLog;
 pub use self::make::{
-    ABTest, BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SimFlags,
-    SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    calibrate_scenario, geh, optimize_signals, ABTest, BorderSpawnOverTime, CalibrationOptions,
+    CountLocation, IndividTrip, LogitParams, ModeChoice, ObservedCount, OriginDestination,
+    PersonSpec, Scenario, SeedParkedCars, SignalOptimizerOptions, SimFlags, SpawnOverTime,
+    SpawnTrip, TripSpawner, TripSpec,
 };
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
+pub(crate) use self::output::OutputFile;
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{Sim, SimOptions};
+pub(crate) use self::trajectories::{TrajectoryLog, TrajectorySample};
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{TripCount, TripResult};
 pub use self::trips::{TripEnd, TripMode, TripStart};
@@ -46,6 +56,8 @@ pub const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
+// Two coupled light rail cars, each about 29m long
+pub const TRAIN_LENGTH: Distance = Distance::const_meters(58.0);
 
 // At all speeds (including at rest), cars must be at least this far apart, measured from front of
 // one car to the back of the other.
@@ -105,11 +117,21 @@ impl fmt::Display for TripID {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PersonID(pub usize);
+
+impl fmt::Display for PersonID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PersonID({0})", self.0)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
 pub enum VehicleType {
     Car,
     Bus,
     Bike,
+    Train,
 }
 
 impl fmt::Display for VehicleType {
@@ -117,6 +139,7 @@ impl fmt::Display for VehicleType {
         match self {
             VehicleType::Car => write!(f, "car"),
             VehicleType::Bus => write!(f, "bus"),
+            VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
         }
     }
@@ -127,9 +150,15 @@ impl VehicleType {
         match self {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
+            VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
         }
     }
+
+    // Buses and trains serve a route
+    pub fn is_transit(self) -> bool {
+        self == VehicleType::Bus || self == VehicleType::Train
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -217,7 +246,9 @@ impl DrivingGoal {
                     let l = map.find_biking_lane_near_building(*b);
                     Position::new(l, map.get_l(l).length() / 2.0)
                 }
-                PathConstraints::Bus | PathConstraints::Pedestrian => unreachable!(),
+                PathConstraints::Bus | PathConstraints::Train | PathConstraints::Pedestrian => {
+                    unreachable!()
+                }
             },
             DrivingGoal::Border(_, l) => Position::new(*l, map.get_l(*l).length()),
         }
@@ -525,6 +556,7 @@ impl TripPositions {
 // kind of an ezgui concept.
 impl Cloneable for ABTest {}
 impl Cloneable for CarID {}
+impl Cloneable for ModeChoice {}
 impl Cloneable for Scenario {}
 impl Cloneable for TripID {}
 impl Cloneable for TripMode {}
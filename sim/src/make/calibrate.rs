@@ -0,0 +1,390 @@
+use crate::{Scenario, Sim, SimOptions, TripID, TripMode, TripStart};
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{
+    BuildingID, FullNeighborhoodInfo, IntersectionID, Map, PathConstraints, PathRequest, PathStep,
+    RoadID,
+};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Demand for one source can't change by more than this factor per round, since the estimates of
+// which sources pass through which counts are rough.
+const MAX_CHANGE_PER_ROUND: f64 = 2.0;
+// Don't scale any source below this fraction of its original demand. Along with keeping at least
+// one agent, this lets a source that got scaled down too far recover in later rounds.
+const MIN_FACTOR: f64 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountLocation {
+    Road(RoadID),
+    Intersection(IntersectionID),
+}
+
+// How many vehicles (cars, bikes, and buses) were observed passing somewhere during [start, end)
+#[derive(Clone, Debug)]
+pub struct ObservedCount {
+    pub location: CountLocation,
+    pub start: Time,
+    pub end: Time,
+    pub count: usize,
+}
+
+impl ObservedCount {
+    // The CSV has a header, then lines like "road,123,7:00:00,8:00:00,540". The first column is
+    // "road" or "intersection".
+    pub fn load(path: &str) -> Result<Vec<ObservedCount>, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        let mut counts = Vec::new();
+        for (idx, line) in contents.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            if parts.len() != 5 {
+                return Err(format!(
+                    "Line {} of {} doesn't have 5 columns: {}",
+                    idx + 1,
+                    path,
+                    line
+                ));
+            }
+            let id = parts[1]
+                .parse::<usize>()
+                .map_err(|_| format!("Line {} of {} has a bad ID", idx + 1, path))?;
+            let location = match parts[0] {
+                "road" => CountLocation::Road(RoadID(id)),
+                "intersection" => CountLocation::Intersection(IntersectionID(id)),
+                x => {
+                    return Err(format!(
+                        "Line {} of {} counts at a {}, not a road or intersection",
+                        idx + 1,
+                        path,
+                        x
+                    ))
+                }
+            };
+            let start = Time::parse(parts[2])
+                .map_err(|_| format!("Line {} of {} has a bad start time", idx + 1, path))?;
+            let end = Time::parse(parts[3])
+                .map_err(|_| format!("Line {} of {} has a bad end time", idx + 1, path))?;
+            if end <= start {
+                return Err(format!(
+                    "Line {} of {} ends before it starts",
+                    idx + 1,
+                    path
+                ));
+            }
+            let count = parts[4]
+                .parse::<usize>()
+                .map_err(|_| format!("Line {} of {} has a bad count", idx + 1, path))?;
+            counts.push(ObservedCount {
+                location,
+                start,
+                end,
+                count,
+            });
+        }
+        if counts.is_empty() {
+            return Err(format!("{} has no counts", path));
+        }
+        Ok(counts)
+    }
+
+    fn describe(&self) -> String {
+        let location = match self.location {
+            CountLocation::Road(r) => r.to_string(),
+            CountLocation::Intersection(i) => i.to_string(),
+        };
+        format!("{} from {} to {}", location, self.start, self.end)
+    }
+
+    fn simulated(&self, sim: &Sim) -> usize {
+        let analytics = sim.get_analytics();
+        let per_mode = match self.location {
+            CountLocation::Road(r) => analytics.throughput_road_between(r, self.start, self.end),
+            CountLocation::Intersection(i) => {
+                analytics.throughput_intersection_between(i, self.start, self.end)
+            }
+        };
+        per_mode
+            .into_iter()
+            .filter(|(mode, _)| *mode != TripMode::Walk)
+            .map(|(_, cnt)| cnt)
+            .sum()
+    }
+}
+
+// The GEH statistic compares hourly flows. Under 5 is conventionally a good match.
+pub fn geh(simulated: usize, observed: usize, window: Duration) -> f64 {
+    let per_hour = Duration::hours(1) / window;
+    let m = (simulated as f64) * per_hour;
+    let c = (observed as f64) * per_hour;
+    if m + c == 0.0 {
+        return 0.0;
+    }
+    (2.0 * (m - c).powi(2) / (m + c)).sqrt()
+}
+
+pub struct CalibrationOptions {
+    pub rounds: usize,
+    pub rng_seed: u8,
+}
+
+// Iteratively scales the number of agents from each SpawnOverTime and BorderSpawnOverTime to match
+// observed counts. Each round simulates the scenario, estimates which sources send vehicles past
+// each count by re-tracing their paths, and nudges every source by how much the counts it passes
+// through are over or under. Returns the scenario with the lowest mean GEH seen.
+pub fn calibrate_scenario(
+    map: &Map,
+    scenario: &Scenario,
+    counts: &Vec<ObservedCount>,
+    opts: CalibrationOptions,
+    timer: &mut Timer,
+) -> Scenario {
+    let end_time = counts.iter().map(|c| c.end).max().unwrap();
+    let sources = Source::all(scenario, map);
+
+    let mut factors = vec![1.0; sources.len()];
+    let mut current = scenario.clone();
+    let mut best: Option<(f64, Scenario, Vec<usize>)> = None;
+    for round in 0..=opts.rounds {
+        timer.start(format!("calibration round {}", round));
+        let sim = simulate(map, &current, end_time, opts.rng_seed, timer);
+        let simulated: Vec<usize> = counts.iter().map(|c| c.simulated(&sim)).collect();
+        let gehs: Vec<f64> = counts
+            .iter()
+            .zip(simulated.iter())
+            .map(|(c, s)| geh(*s, c.count, c.end - c.start))
+            .collect();
+        let mean_geh = gehs.iter().sum::<f64>() / (gehs.len() as f64);
+        timer.note(format!(
+            "Round {}: mean GEH {:.2}, {}/{} counts under 5",
+            round,
+            mean_geh,
+            gehs.iter().filter(|x| **x < 5.0).count(),
+            gehs.len()
+        ));
+        if best
+            .as_ref()
+            .map(|(geh, _, _)| mean_geh < *geh)
+            .unwrap_or(true)
+        {
+            best = Some((mean_geh, current.clone(), simulated.clone()));
+        }
+
+        if round == opts.rounds {
+            timer.stop(format!("calibration round {}", round));
+            break;
+        }
+
+        let uses = estimate_uses(&sim, map, &sources, counts, end_time, timer);
+        for (idx, source_uses) in uses.into_iter().enumerate() {
+            let total: f64 = source_uses.values().sum();
+            if total == 0.0 {
+                continue;
+            }
+            let ratio = source_uses
+                .into_iter()
+                .map(|(c, weight)| weight * (counts[c].count as f64) / (simulated[c].max(1) as f64))
+                .sum::<f64>()
+                / total;
+            factors[idx] = (factors[idx]
+                * ratio
+                    .min(MAX_CHANGE_PER_ROUND)
+                    .max(1.0 / MAX_CHANGE_PER_ROUND))
+            .max(MIN_FACTOR);
+        }
+        current = scale(scenario, &sources, &factors);
+        timer.stop(format!("calibration round {}", round));
+    }
+
+    let (_, result, simulated) = best.unwrap();
+    for (c, s) in counts.iter().zip(simulated) {
+        timer.note(format!(
+            "{}: observed {}, simulated {}, GEH {:.2}",
+            c.describe(),
+            c.count,
+            s,
+            geh(s, c.count, c.end - c.start)
+        ));
+    }
+    result
+}
+
+fn simulate(
+    map: &Map,
+    scenario: &Scenario,
+    end_time: Time,
+    rng_seed: u8,
+    timer: &mut Timer,
+) -> Sim {
+    let mut sim = Sim::new(map, SimOptions::new("calibration"), &mut Timer::throwaway());
+    // Use the same seed every time, so only the amount of demand differs between runs.
+    let mut rng = XorShiftRng::from_seed([rng_seed; 16]);
+    scenario.instantiate(&mut sim, map, &mut rng, &mut Timer::throwaway());
+    sim.timed_step(map, end_time - Time::START_OF_DAY, timer);
+    sim
+}
+
+// Somewhere demand in the scenario comes from, matched to trips by where and when they start.
+enum Source {
+    // Index into spawn_over_time
+    Spawn(usize, BTreeSet<BuildingID>, Time, Time),
+    // Index into border_spawn_over_time
+    Border(usize, IntersectionID, Time, Time),
+}
+
+impl Source {
+    fn all(scenario: &Scenario, map: &Map) -> Vec<Source> {
+        let neighborhoods = FullNeighborhoodInfo::load_all(map);
+        let mut sources = Vec::new();
+        for (idx, s) in scenario.spawn_over_time.iter().enumerate() {
+            sources.push(Source::Spawn(
+                idx,
+                neighborhoods[&s.start_from_neighborhood]
+                    .buildings
+                    .iter()
+                    .cloned()
+                    .collect(),
+                s.start_time,
+                s.stop_time,
+            ));
+        }
+        for (idx, s) in scenario.border_spawn_over_time.iter().enumerate() {
+            sources.push(Source::Border(
+                idx,
+                s.start_from_border.src_i(map),
+                s.start_time,
+                s.stop_time,
+            ));
+        }
+        sources
+    }
+
+    fn matches(&self, start: &TripStart, time: Time) -> bool {
+        match (self, start) {
+            (Source::Spawn(_, bldgs, t1, t2), TripStart::Bldg(b)) => {
+                bldgs.contains(b) && time >= *t1 && time <= *t2
+            }
+            (Source::Border(_, i1, t1, t2), TripStart::Border(i2)) => {
+                i1 == i2 && time >= *t1 && time <= *t2
+            }
+            _ => false,
+        }
+    }
+}
+
+// For every source, how many of its vehicles passed each count (indexed into counts). Trips
+// matching several sources are split evenly between them. Trips from individ_trips or people
+// might be mistaken for a source that starts at the same place and time; that's fine.
+fn estimate_uses(
+    sim: &Sim,
+    map: &Map,
+    sources: &Vec<Source>,
+    counts: &Vec<ObservedCount>,
+    end_time: Time,
+    timer: &mut Timer,
+) -> Vec<BTreeMap<usize, f64>> {
+    // Each phase of each trip, with the time it happened
+    let mut phases_per_trip: BTreeMap<TripID, Vec<(Time, Time, Option<PathRequest>)>> =
+        BTreeMap::new();
+    for (t, id, maybe_req, _) in &sim.get_analytics().trip_log {
+        let phases = phases_per_trip.entry(*id).or_insert_with(Vec::new);
+        if let Some(last) = phases.last_mut() {
+            last.1 = *t;
+        }
+        phases.push((*t, end_time, maybe_req.clone()));
+    }
+
+    let requests: Vec<(TripID, Vec<(Time, Time, Option<PathRequest>)>)> =
+        phases_per_trip.into_iter().collect();
+    let passed_counts = timer.parallelize("trace trips past counts", requests, |(id, phases)| {
+        let spawned_at = phases[0].0;
+        let mut passed = BTreeSet::new();
+        for (start, end, maybe_req) in phases {
+            let req = match maybe_req {
+                Some(req) => req,
+                None => continue,
+            };
+            if req.constraints == PathConstraints::Pedestrian {
+                continue;
+            }
+            let path = match map.pathfind(req) {
+                Some(path) => path,
+                None => continue,
+            };
+            for (idx, c) in counts.iter().enumerate() {
+                if end < c.start || start >= c.end {
+                    continue;
+                }
+                if path
+                    .get_steps()
+                    .iter()
+                    .any(|step| match (step, c.location) {
+                        (PathStep::Lane(l), CountLocation::Road(r))
+                        | (PathStep::ContraflowLane(l), CountLocation::Road(r)) => {
+                            map.get_l(*l).parent == r
+                        }
+                        (PathStep::Turn(t), CountLocation::Intersection(i)) => t.parent == i,
+                        _ => false,
+                    })
+                {
+                    passed.insert(idx);
+                }
+            }
+        }
+        (id, spawned_at, passed)
+    });
+
+    let mut uses = vec![BTreeMap::new(); sources.len()];
+    for (id, spawned_at, passed) in passed_counts {
+        if passed.is_empty() {
+            continue;
+        }
+        let (trip_start, _) = sim.trip_endpoints(id);
+        let matches: Vec<usize> = sources
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.matches(&trip_start, spawned_at))
+            .map(|(idx, _)| idx)
+            .collect();
+        for source in &matches {
+            for c in &passed {
+                *uses[*source].entry(*c).or_insert(0.0) += 1.0 / (matches.len() as f64);
+            }
+        }
+    }
+    uses
+}
+
+// Factors always apply to the original scenario, so rounding doesn't accumulate between rounds.
+fn scale(scenario: &Scenario, sources: &Vec<Source>, factors: &Vec<f64>) -> Scenario {
+    let mut result = scenario.clone();
+    for (source, factor) in sources.iter().zip(factors.iter()) {
+        match source {
+            Source::Spawn(idx, _, _, _) => {
+                let s = &mut result.spawn_over_time[*idx];
+                s.num_agents = scale_count(s.num_agents, *factor);
+            }
+            // Pedestrians don't show up in vehicle counts.
+            Source::Border(idx, _, _, _) => {
+                let s = &mut result.border_spawn_over_time[*idx];
+                s.num_cars = scale_count(s.num_cars, *factor);
+                s.num_bikes = scale_count(s.num_bikes, *factor);
+            }
+        }
+    }
+    result
+}
+
+// A source that had any demand keeps at least one agent, so its trips can still be traced past
+// the counts.
+fn scale_count(orig: usize, factor: f64) -> usize {
+    if orig == 0 {
+        return 0;
+    }
+    (((orig as f64) * factor).round() as usize).max(1)
+}
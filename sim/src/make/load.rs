@@ -10,17 +10,26 @@ pub struct SimFlags {
     pub load: String,
     pub use_map_fixes: bool,
     pub rng_seed: Option<u8>,
+    // Route cars around congestion observed in some previous run
+    pub travel_times: Option<String>,
     pub opts: SimOptions,
 }
 
 impl SimFlags {
     pub fn from_args(args: &mut CmdArgs) -> SimFlags {
+        let trajectory_log = args.optional("--trajectory_log").map(|path| {
+            let every = args
+                .optional_parse("--trajectory_every", Duration::parse)
+                .unwrap_or_else(|| Duration::seconds(1.0));
+            (path, every)
+        });
         SimFlags {
             load: args
                 .optional_free()
                 .unwrap_or_else(|| "../data/system/maps/montlake.bin".to_string()),
             use_map_fixes: !args.enabled("--nofixes"),
             rng_seed: args.optional_parse("--rng_seed", |s| s.parse()),
+            travel_times: args.optional("--travel_times"),
             opts: SimOptions {
                 run_name: args
                     .optional("--run_name")
@@ -29,6 +38,14 @@ impl SimFlags {
                 use_freeform_policy_everywhere: args.enabled("--freeform_policy"),
                 disable_block_the_box: args.enabled("--disable_block_the_box"),
                 recalc_lanechanging: !args.enabled("--dont_recalc_lc"),
+                event_log: args.optional("--event_log"),
+                trajectory_log,
+                reroute_compliance: args
+                    .optional_parse("--reroute_compliance", |s| match s.parse::<f64>() {
+                        Ok(x) if (0.0..=1.0).contains(&x) => Ok(x),
+                        _ => Err(()),
+                    })
+                    .unwrap_or(0.0),
             },
         }
     }
@@ -43,6 +60,7 @@ impl SimFlags {
             load: abstutil::path_map(map),
             use_map_fixes: true,
             rng_seed: Some(42),
+            travel_times: None,
             opts: SimOptions::new(run_name),
         }
     }
@@ -68,13 +86,12 @@ impl SimFlags {
 
             let mut map = Map::new(abstutil::path_map(&sim.map_name), false, timer);
             if sim.edits_name != "no_edits" {
-                map.apply_edits(
-                    MapEdits::load(map.get_name(), &sim.edits_name, timer),
-                    timer,
-                );
+                let (edits, _) = MapEdits::load(&map, &sim.edits_name, timer).unwrap();
+                map.apply_edits(edits, timer);
                 map.mark_edits_fresh();
                 map.recalculate_pathfinding_after_edits(timer);
             }
+            self.load_travel_times(&mut map, timer);
             sim.restore_paths(&map, timer);
 
             (map, sim, rng)
@@ -86,7 +103,9 @@ impl SimFlags {
 
             let scenario: Scenario = abstutil::read_binary(self.load.clone(), timer);
 
-            let map = Map::new(abstutil::path_map(&scenario.map_name), false, timer);
+            let mut map = Map::new(abstutil::path_map(&scenario.map_name), false, timer);
+            // Before instantiating, so the initial paths avoid the congestion too
+            self.load_travel_times(&mut map, timer);
 
             if opts.run_name == "unnamed" {
                 opts.run_name = scenario.scenario_name.clone();
@@ -100,7 +119,8 @@ impl SimFlags {
         {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(self.load.clone(), self.use_map_fixes, timer);
+            let mut map = Map::new(self.load.clone(), self.use_map_fixes, timer);
+            self.load_travel_times(&mut map, timer);
 
             timer.start("create sim");
             let sim = Sim::new(&map, opts, timer);
@@ -110,7 +130,8 @@ impl SimFlags {
         } else if self.load.starts_with(&abstutil::path_all_maps()) {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(self.load.clone(), false, timer);
+            let mut map = Map::new(self.load.clone(), false, timer);
+            self.load_travel_times(&mut map, timer);
 
             timer.start("create sim");
             let sim = Sim::new(&map, opts, timer);
@@ -121,4 +142,10 @@ impl SimFlags {
             panic!("Don't know how to load {}", self.load);
         }
     }
+
+    fn load_travel_times(&self, map: &mut Map, timer: &mut abstutil::Timer) {
+        if let Some(ref path) = self.travel_times {
+            map.set_travel_times(abstutil::read_binary(path.clone(), timer), timer);
+        }
+    }
 }
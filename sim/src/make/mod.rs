@@ -1,11 +1,20 @@
 mod a_b_test;
+mod calibrate;
 mod load;
+mod mode_choice;
 mod scenario;
+mod signal_optimizer;
 mod spawner;
 
 pub use self::a_b_test::ABTest;
+pub use self::calibrate::{
+    calibrate_scenario, geh, CalibrationOptions, CountLocation, ObservedCount,
+};
 pub use self::load::SimFlags;
+pub use self::mode_choice::{LogitParams, ModeChoice};
 pub use self::scenario::{
-    BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime, SpawnTrip,
+    BorderSpawnOverTime, IndividTrip, OriginDestination, PersonSpec, Scenario, SeedParkedCars,
+    SpawnOverTime, SpawnTrip,
 };
+pub use self::signal_optimizer::{optimize_signals, SignalOptimizerOptions};
 pub use self::spawner::{TripSpawner, TripSpec};
@@ -0,0 +1,249 @@
+use crate::{SidewalkPOI, SidewalkSpot, TripMode, TripSpec};
+use geom::{Distance, Duration, Speed};
+use map_model::{Map, Path, PathConstraints, PathRequest, PathStep, Position};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+
+// How agents from SpawnOverTime decide whether to walk, bike, drive, or take transit
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum ModeChoice {
+    // Anybody with a parked car available drives. Otherwise, flip coins using each
+    // SpawnOverTime's percent_biking and percent_use_transit.
+    FixedPercentages,
+    // Estimate the time and cost of every possible mode from the pathfinders, then pick one with
+    // a multinomial logit. Map edits change the estimates, and so the mode share.
+    Logit(LogitParams),
+}
+
+impl ModeChoice {
+    pub fn describe(&self) -> String {
+        match self {
+            ModeChoice::FixedPercentages => "fixed percentages".to_string(),
+            ModeChoice::Logit(_) => "multinomial logit".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct LogitParams {
+    // How much utility each minute of (perceived) travel time costs
+    pub per_minute: f64,
+    // Dollars somebody would pay to save an hour of travel, for weighing costs against time
+    pub value_of_time_per_hour: f64,
+    pub driving_cost_per_mile: f64,
+    pub transit_fare: f64,
+    // Finding a spot and walking from it
+    pub parking_overhead: Duration,
+    pub transit_wait: Duration,
+    // Biking alongside traffic feels this many times longer than biking in a bike lane
+    pub bike_traffic_penalty: f64,
+
+    // Preferences for each mode that time and cost don't capture
+    pub walk_constant: f64,
+    pub bike_constant: f64,
+    pub drive_constant: f64,
+    pub transit_constant: f64,
+}
+
+impl Default for LogitParams {
+    // TODO Not calibrated against anything yet.
+    fn default() -> LogitParams {
+        LogitParams {
+            per_minute: 0.05,
+            value_of_time_per_hour: 15.0,
+            driving_cost_per_mile: 0.3,
+            transit_fare: 2.75,
+            parking_overhead: Duration::minutes(5),
+            transit_wait: Duration::minutes(8),
+            bike_traffic_penalty: 1.5,
+            walk_constant: 0.0,
+            bike_constant: -1.0,
+            drive_constant: 0.5,
+            transit_constant: -0.5,
+        }
+    }
+}
+
+impl LogitParams {
+    // The candidates should be different ways of making the same trip. Returns None if none of
+    // them are possible.
+    pub fn choose(
+        &self,
+        candidates: Vec<TripSpec>,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<TripSpec> {
+        let mut options: Vec<(TripSpec, f64)> = candidates
+            .into_iter()
+            .filter_map(|spec| {
+                let utility = self.utility(&spec, map)?;
+                Some((spec, utility))
+            })
+            .collect();
+        if options.is_empty() {
+            return None;
+        }
+
+        // Shift everything by the best utility, so exp() doesn't underflow for long trips.
+        let best = options
+            .iter()
+            .map(|(_, u)| *u)
+            .fold(std::f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = options.iter().map(|(_, u)| (u - best).exp()).collect();
+        let mut pick = rng.gen_range(0.0, weights.iter().sum::<f64>());
+        for (idx, w) in weights.iter().enumerate() {
+            if pick < *w {
+                return Some(options.remove(idx).0);
+            }
+            pick -= w;
+        }
+        // Roundoff
+        options.pop().map(|(spec, _)| spec)
+    }
+
+    fn utility(&self, spec: &TripSpec, map: &Map) -> Option<f64> {
+        let (mode, time, dollars) = self.estimate(spec, map)?;
+        let constant = match mode {
+            TripMode::Walk => self.walk_constant,
+            TripMode::Bike => self.bike_constant,
+            TripMode::Drive => self.drive_constant,
+            TripMode::Transit => self.transit_constant,
+        };
+        let minutes = time.inner_seconds() / 60.0 + 60.0 * dollars / self.value_of_time_per_hour;
+        Some(constant - self.per_minute * minutes)
+    }
+
+    // (mode, perceived time, dollars spent), or None if the trip isn't possible
+    fn estimate(&self, spec: &TripSpec, map: &Map) -> Option<(TripMode, Duration, f64)> {
+        match spec {
+            TripSpec::JustWalking {
+                start,
+                goal,
+                ped_speed,
+            } => Some((
+                TripMode::Walk,
+                walking_time(start.sidewalk_pos, goal.sidewalk_pos, *ped_speed, map)?,
+                0.0,
+            )),
+            TripSpec::UsingBike {
+                start,
+                goal,
+                vehicle,
+                ped_speed,
+            } => {
+                let rack = SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map)?;
+                let bike_start = match rack.connection {
+                    SidewalkPOI::BikeRack(pos) => pos,
+                    _ => unreachable!(),
+                };
+                let walk = walking_time(start.sidewalk_pos, rack.sidewalk_pos, *ped_speed, map)?;
+                let path = map.pathfind(PathRequest {
+                    start: bike_start,
+                    end: goal.goal_pos(PathConstraints::Bike, map),
+                    constraints: PathConstraints::Bike,
+                    departure_time: None,
+                })?;
+                let speed = vehicle
+                    .max_speed
+                    .unwrap_or_else(|| Speed::miles_per_hour(9.0));
+                let mut bike = Duration::ZERO;
+                for step in path.get_steps() {
+                    bike += match step {
+                        PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                            let lane = map.get_l(*l);
+                            let time = lane.length() / lane.bike_speed_factor() / speed;
+                            if lane.is_biking() {
+                                time
+                            } else {
+                                time * self.bike_traffic_penalty
+                            }
+                        }
+                        PathStep::Turn(t) => map.get_t(*t).geom.length() / speed,
+                    };
+                }
+                Some((TripMode::Bike, walk + bike, 0.0))
+            }
+            TripSpec::UsingParkedCar { start, goal, .. } => {
+                // Without a building to start from, there's no telling where the car is.
+                let b = match start.connection {
+                    SidewalkPOI::Building(b) => b,
+                    _ => return None,
+                };
+                // Assume the car is parked close by.
+                let start = Position::new(map.find_driving_lane_near_building(b), Distance::ZERO);
+                let path = map.pathfind(PathRequest {
+                    start,
+                    end: goal.goal_pos(PathConstraints::Car, map),
+                    constraints: PathConstraints::Car,
+                    departure_time: None,
+                })?;
+                Some((
+                    TripMode::Drive,
+                    free_flow_time(&path, map) + self.parking_overhead,
+                    self.driving_cost_per_mile * to_miles(path.total_length()),
+                ))
+            }
+            TripSpec::UsingTransit {
+                start,
+                goal,
+                stop1,
+                stop2,
+                ped_speed,
+                ..
+            } => {
+                let stop1 = map.get_bs(*stop1);
+                let stop2 = map.get_bs(*stop2);
+                let walk1 = walking_time(start.sidewalk_pos, stop1.sidewalk_pos, *ped_speed, map)?;
+                let ride = map.pathfind(PathRequest {
+                    start: stop1.driving_pos,
+                    end: stop2.driving_pos,
+                    constraints: PathConstraints::Bus,
+                    departure_time: None,
+                })?;
+                let walk2 = walking_time(stop2.sidewalk_pos, goal.sidewalk_pos, *ped_speed, map)?;
+                Some((
+                    TripMode::Transit,
+                    walk1 + self.transit_wait + free_flow_time(&ride, map) + walk2,
+                    self.transit_fare,
+                ))
+            }
+            // SpawnOverTime doesn't offer these.
+            TripSpec::CarAppearing { .. } | TripSpec::MaybeUsingParkedCar { .. } => None,
+        }
+    }
+}
+
+fn walking_time(start: Position, end: Position, speed: Speed, map: &Map) -> Option<Duration> {
+    if start == end {
+        return Some(Duration::ZERO);
+    }
+    let path = map.pathfind(PathRequest {
+        start,
+        end,
+        constraints: PathConstraints::Pedestrian,
+        departure_time: None,
+    })?;
+    Some(path.total_length() / speed)
+}
+
+// Driving every lane at the speed limit
+fn free_flow_time(path: &Path, map: &Map) -> Duration {
+    let mut time = Duration::ZERO;
+    for step in path.get_steps() {
+        time += match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                let lane = map.get_l(*l);
+                lane.length() / map.get_r(lane.parent).get_speed_limit()
+            }
+            PathStep::Turn(t) => {
+                map.get_t(*t).geom.length() / map.get_parent(t.dst).get_speed_limit()
+            }
+        };
+    }
+    time
+}
+
+fn to_miles(dist: Distance) -> f64 {
+    dist.inner_meters() / 1609.34
+}
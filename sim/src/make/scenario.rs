@@ -1,6 +1,6 @@
 use crate::{
-    CarID, DrivingGoal, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec, VehicleType,
-    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    CarID, DrivingGoal, ModeChoice, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec,
+    VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
 };
 use abstutil::{fork_rng, prettyprint_usize, Timer, WeightedUsizeChoice};
 use geom::{Distance, Duration, Speed, Time};
@@ -24,10 +24,28 @@ pub struct Scenario {
     pub seed_parked_cars: Vec<SeedParkedCars>,
     pub spawn_over_time: Vec<SpawnOverTime>,
     pub border_spawn_over_time: Vec<BorderSpawnOverTime>,
+    // Only used for spawn_over_time
+    pub mode_choice: ModeChoice,
 
     // Much more detailed
     pub individ_trips: Vec<SpawnTrip>,
     pub individ_parked_cars: BTreeMap<BuildingID, usize>,
+    pub people: Vec<PersonSpec>,
+}
+
+// Somebody's trips throughout the day, in order. Each trip starts at its departure time or once
+// the previous trip is done and they've stayed at the destination for its dwell time, whichever
+// is later. Cars and bikes stick with the person between trips.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PersonSpec {
+    pub trips: Vec<IndividTrip>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct IndividTrip {
+    pub trip: SpawnTrip,
+    // How long to stay at the destination before starting the next trip
+    pub dwell: Duration,
 }
 
 // SpawnOverTime and BorderSpawnOverTime should be kept separate. Agents in SpawnOverTime pick
@@ -80,10 +98,28 @@ impl Scenario {
                 "{} BorderSpawnOverTime",
                 prettyprint_usize(self.border_spawn_over_time.len())
             ),
+            format!("Mode choice: {}", self.mode_choice.describe()),
             format!("{} SpawnTrip", prettyprint_usize(self.individ_trips.len())),
+            format!(
+                "{} people making {} trips",
+                prettyprint_usize(self.people.len()),
+                prettyprint_usize(self.people.iter().map(|p| p.trips.len()).sum())
+            ),
         ]
     }
 
+    // The independent trips, then everybody's trips in order
+    pub fn all_trips(&self) -> Vec<&SpawnTrip> {
+        self.individ_trips
+            .iter()
+            .chain(
+                self.people
+                    .iter()
+                    .flat_map(|p| p.trips.iter().map(|t| &t.trip)),
+            )
+            .collect()
+    }
+
     // TODO may need to fork the RNG a bit more
     pub fn instantiate(&self, sim: &mut Sim, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
         sim.set_name(self.scenario_name.clone());
@@ -127,7 +163,15 @@ impl Scenario {
             timer.start_iter("SpawnOverTime each agent", s.num_agents);
             for _ in 0..s.num_agents {
                 timer.next();
-                s.spawn_agent(rng, sim, &mut reserved_cars, &neighborhoods, map, timer);
+                s.spawn_agent(
+                    rng,
+                    sim,
+                    &mut reserved_cars,
+                    &neighborhoods,
+                    &self.mode_choice,
+                    map,
+                    timer,
+                );
             }
         }
 
@@ -155,6 +199,20 @@ impl Scenario {
             sim.schedule_trip(depart, spec, map);
         }
 
+        timer.start_iter("PersonSpec", self.people.len());
+        for p in &self.people {
+            timer.next();
+            let trips = p
+                .trips
+                .iter()
+                .map(|t| {
+                    let (depart, spec) = t.trip.clone().to_trip_spec(rng);
+                    (depart, t.dwell, spec)
+                })
+                .collect();
+            sim.schedule_person(trips, map);
+        }
+
         sim.spawn_all_trips(map, timer, true);
         timer.stop(format!("Instantiating {}", self.scenario_name));
     }
@@ -202,8 +260,10 @@ impl Scenario {
                     percent_use_transit: 0.5,
                 })
                 .collect(),
+            mode_choice: ModeChoice::FixedPercentages,
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
         };
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
@@ -227,8 +287,10 @@ impl Scenario {
             seed_parked_cars: Vec::new(),
             spawn_over_time: Vec::new(),
             border_spawn_over_time: Vec::new(),
+            mode_choice: ModeChoice::FixedPercentages,
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
         }
     }
 
@@ -254,8 +316,10 @@ impl Scenario {
                 percent_use_transit: 0.5,
             }],
             border_spawn_over_time: Vec::new(),
+            mode_choice: ModeChoice::FixedPercentages,
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
         }
     }
 
@@ -311,6 +375,7 @@ impl SpawnOverTime {
         sim: &mut Sim,
         reserved_cars: &mut HashSet<CarID>,
         neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
+        mode_choice: &ModeChoice,
         map: &Map,
         timer: &mut Timer,
     ) {
@@ -322,6 +387,83 @@ impl SpawnOverTime {
             .choose(rng)
             .unwrap();
 
+        if let ModeChoice::Logit(ref params) = mode_choice {
+            // Pick the destination first, then offer every mode that can get there.
+            let start = SidewalkSpot::building(from_bldg, map);
+            let ped_speed = Scenario::rand_ped_speed(rng);
+            let (walk_goal, car_goal, bike_goal) = match self.goal {
+                OriginDestination::Neighborhood(ref n) => {
+                    let b = *neighborhoods[n].buildings.choose(rng).unwrap();
+                    (
+                        Some(SidewalkSpot::building(b, map)),
+                        Some(DrivingGoal::ParkNear(b)),
+                        Some(DrivingGoal::ParkNear(b)),
+                    )
+                }
+                OriginDestination::EndOfRoad(dr) => (
+                    SidewalkSpot::end_at_border(dr.dst_i(map), map),
+                    DrivingGoal::end_at_border(dr, PathConstraints::Car, map),
+                    DrivingGoal::end_at_border(dr, PathConstraints::Bike, map),
+                ),
+            };
+
+            let mut candidates = Vec::new();
+            let parked_car = sim
+                .get_parked_cars_by_owner(from_bldg)
+                .into_iter()
+                .find(|p| !reserved_cars.contains(&p.vehicle.id))
+                .map(|p| (p.vehicle.id, p.spot));
+            if let (Some(goal), Some((_, spot))) = (car_goal, parked_car) {
+                candidates.push(TripSpec::UsingParkedCar {
+                    start: start.clone(),
+                    spot,
+                    goal,
+                    ped_speed,
+                });
+            }
+            if let Some(goal) = bike_goal {
+                if can_bike(from_bldg, &goal, map) {
+                    candidates.push(TripSpec::UsingBike {
+                        start: start.clone(),
+                        vehicle: Scenario::rand_bike(rng),
+                        goal,
+                        ped_speed,
+                    });
+                }
+            }
+            if let Some(goal) = walk_goal {
+                if goal != start {
+                    if let Some((stop1, stop2, route)) =
+                        map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
+                    {
+                        candidates.push(TripSpec::UsingTransit {
+                            start: start.clone(),
+                            route,
+                            stop1,
+                            stop2,
+                            goal: goal.clone(),
+                            ped_speed,
+                        });
+                    }
+                    candidates.push(TripSpec::JustWalking {
+                        start,
+                        goal,
+                        ped_speed,
+                    });
+                }
+            }
+
+            if let Some(spec) = params.choose(candidates, map, rng) {
+                if let TripSpec::UsingParkedCar { .. } = spec {
+                    reserved_cars.insert(parked_car.unwrap().0);
+                }
+                sim.schedule_trip(spawn_time, spec, map);
+            } else {
+                timer.warn(format!("Couldn't fulfill {:?} at all", self));
+            }
+            return;
+        }
+
         // What mode?
         if let Some(parked_car) = sim
             .get_parked_cars_by_owner(from_bldg)
@@ -353,34 +495,18 @@ impl SpawnOverTime {
                 self.goal
                     .pick_driving_goal(PathConstraints::Bike, map, &neighborhoods, rng, timer)
             {
-                let start_at = map.get_b(from_bldg).sidewalk();
-                // TODO Just start biking on the other side of the street if the sidewalk
-                // is on a one-way. Or at least warn.
-                if map
-                    .get_parent(start_at)
-                    .sidewalk_to_bike(start_at)
-                    .is_some()
-                {
-                    let ok = if let DrivingGoal::ParkNear(to_bldg) = goal {
-                        let end_at = map.get_b(to_bldg).sidewalk();
-                        map.get_parent(end_at).sidewalk_to_bike(end_at).is_some()
-                            && start_at != end_at
-                    } else {
-                        true
-                    };
-                    if ok {
-                        sim.schedule_trip(
-                            spawn_time,
-                            TripSpec::UsingBike {
-                                start: SidewalkSpot::building(from_bldg, map),
-                                vehicle: Scenario::rand_bike(rng),
-                                goal,
-                                ped_speed: Scenario::rand_ped_speed(rng),
-                            },
-                            map,
-                        );
-                        return;
-                    }
+                if can_bike(from_bldg, &goal, map) {
+                    sim.schedule_trip(
+                        spawn_time,
+                        TripSpec::UsingBike {
+                            start: SidewalkSpot::building(from_bldg, map),
+                            vehicle: Scenario::rand_bike(rng),
+                            goal,
+                            ped_speed: Scenario::rand_ped_speed(rng),
+                        },
+                        map,
+                    );
+                    return;
                 }
             }
         }
@@ -807,6 +933,26 @@ fn find_spot_near_building(
     }
 }
 
+// Can somebody bike from a building to somewhere, without just riding along one sidewalk?
+fn can_bike(from_bldg: BuildingID, goal: &DrivingGoal, map: &Map) -> bool {
+    let start_at = map.get_b(from_bldg).sidewalk();
+    // TODO Just start biking on the other side of the street if the sidewalk is on a one-way. Or
+    // at least warn.
+    if map
+        .get_parent(start_at)
+        .sidewalk_to_bike(start_at)
+        .is_none()
+    {
+        return false;
+    }
+    if let DrivingGoal::ParkNear(to_bldg) = goal {
+        let end_at = map.get_b(*to_bldg).sidewalk();
+        map.get_parent(end_at).sidewalk_to_bike(end_at).is_some() && start_at != end_at
+    } else {
+        true
+    }
+}
+
 fn rand_time(rng: &mut XorShiftRng, low: Time, high: Time) -> Time {
     assert!(high > low);
     Time::START_OF_DAY + Duration::seconds(rng.gen_range(low.inner_seconds(), high.inner_seconds()))
@@ -0,0 +1,192 @@
+use crate::{Scenario, Sim, SimOptions};
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{ControlTrafficSignal, EditCmd, IntersectionID, Map, MapEdits};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+const MIN_PHASE_DURATION: Duration = Duration::const_seconds(5.0);
+
+pub struct SignalOptimizerOptions {
+    // Simulate from midnight until this time
+    pub end_time: Time,
+    pub rounds: usize,
+    // How many variations of the best plan so far to simulate in parallel each round
+    pub batch_size: usize,
+    pub rng_seed: u8,
+    // Every simulation runs with these, except for anything that'd make the simulations running
+    // at once write over each other's files
+    pub sim_options: SimOptions,
+}
+
+// Hill-climbing search for signal timing that minimizes delay at some intersections. Each round
+// randomly changes phase durations or swaps phases in the best plan so far, simulates every
+// variation, and keeps the best one. Actuated phases can be reordered, but their timing is left
+// alone. Returns the map's current edits plus the best plan found.
+pub fn optimize_signals(
+    map: &Map,
+    scenario: &Scenario,
+    intersections: Vec<IntersectionID>,
+    opts: SignalOptimizerOptions,
+    timer: &mut Timer,
+) -> Result<MapEdits, String> {
+    if intersections.is_empty() {
+        return Err("No traffic signals to optimize".to_string());
+    }
+    for i in &intersections {
+        if map.maybe_get_traffic_signal(*i).is_none() {
+            return Err(format!("{} isn't a traffic signal", i));
+        }
+    }
+    if opts.batch_size == 0 {
+        return Err("The batch size must be at least 1".to_string());
+    }
+    let mut rng = XorShiftRng::from_seed([opts.rng_seed; 16]);
+    let mut sim_opts = opts.sim_options.clone();
+    sim_opts.savestate_every = None;
+    sim_opts.event_log = None;
+    sim_opts.trajectory_log = None;
+
+    // Each simulation running at once edits its own copy of the map.
+    timer.start_iter("copy the map", opts.batch_size);
+    let mut maps = Vec::new();
+    for _ in 0..opts.batch_size {
+        timer.next();
+        maps.push(map.deep_clone());
+    }
+
+    let mut best: Vec<ControlTrafficSignal> = intersections
+        .iter()
+        .map(|i| map.get_traffic_signal(*i).clone())
+        .collect();
+    let mut best_delay = evaluate(
+        &mut maps,
+        vec![best.clone()],
+        scenario,
+        &intersections,
+        opts.end_time,
+        &sim_opts,
+        timer,
+    )[0];
+    timer.note(format!("Original signals: {} total delay", best_delay));
+
+    for round in 0..opts.rounds {
+        let candidates: Vec<Vec<ControlTrafficSignal>> = (0..opts.batch_size)
+            .map(|_| mutate(&best, &mut rng))
+            .collect();
+        let delays = evaluate(
+            &mut maps,
+            candidates.clone(),
+            scenario,
+            &intersections,
+            opts.end_time,
+            &sim_opts,
+            timer,
+        );
+        let (idx, delay) = delays
+            .into_iter()
+            .enumerate()
+            .min_by_key(|(_, delay)| *delay)
+            .unwrap();
+        if delay < best_delay {
+            timer.note(format!(
+                "Round {}: improved from {} to {} total delay",
+                round + 1,
+                best_delay,
+                delay
+            ));
+            best_delay = delay;
+            best = candidates[idx].clone();
+        } else {
+            timer.note(format!("Round {}: no improvement", round + 1));
+        }
+    }
+
+    let mut edits = map.get_edits().clone();
+    for signal in best {
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+    }
+    Ok(edits)
+}
+
+// Returns the total delay for each candidate plan. The maps are handed out to the simulations and
+// returned afterwards.
+fn evaluate(
+    maps: &mut Vec<Map>,
+    candidates: Vec<Vec<ControlTrafficSignal>>,
+    scenario: &Scenario,
+    intersections: &Vec<IntersectionID>,
+    end_time: Time,
+    sim_opts: &SimOptions,
+    timer: &mut Timer,
+) -> Vec<Duration> {
+    let requests: Vec<(Map, Vec<ControlTrafficSignal>)> = maps
+        .drain(0..candidates.len())
+        .zip(candidates.into_iter())
+        .collect();
+    let results = timer.parallelize("simulate signal timing", requests, |(mut map, signals)| {
+        for signal in signals {
+            map.incremental_edit_traffic_signal(signal);
+        }
+        let delay = total_delay(&map, scenario, intersections, end_time, sim_opts);
+        (map, delay)
+    });
+
+    let mut delays = Vec::new();
+    for (map, delay) in results {
+        maps.push(map);
+        delays.push(delay);
+    }
+    delays
+}
+
+fn total_delay(
+    map: &Map,
+    scenario: &Scenario,
+    intersections: &Vec<IntersectionID>,
+    end_time: Time,
+    sim_opts: &SimOptions,
+) -> Duration {
+    let mut timer = Timer::throwaway();
+    let mut sim = Sim::new(map, sim_opts.clone(), &mut timer);
+    // Use the same seed every time, so only the signal timing differs between runs.
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    scenario.instantiate(&mut sim, map, &mut rng, &mut timer);
+    sim.step(map, end_time - Time::START_OF_DAY);
+
+    let analytics = sim.get_analytics();
+    let mut total = Duration::ZERO;
+    for i in intersections {
+        if let Some(delays) = analytics.intersection_delays.get(i) {
+            for (_, delay) in delays {
+                total += *delay;
+            }
+        }
+        // Otherwise gridlock would look great, since nobody gets through to measure any delay.
+        total += sim.total_waiting_delay(*i);
+    }
+    total
+}
+
+fn mutate(signals: &Vec<ControlTrafficSignal>, rng: &mut XorShiftRng) -> Vec<ControlTrafficSignal> {
+    let mut signals = signals.clone();
+    let num_signals = signals.len();
+    let signal = &mut signals[rng.gen_range(0, num_signals)];
+    let num_phases = signal.phases.len();
+    let idx = rng.gen_range(0, num_phases);
+
+    if num_phases > 1 && (signal.phases[idx].actuated.is_some() || rng.gen_bool(0.2)) {
+        signal.phases.swap(idx, (idx + 1) % num_phases);
+    } else if signal.phases[idx].actuated.is_none() {
+        let delta = Duration::seconds(rng.gen_range(2.0, 15.0));
+        let phase = &mut signal.phases[idx];
+        if rng.gen_bool(0.5) {
+            phase.duration += delta;
+        } else if phase.duration - delta >= MIN_PHASE_DURATION {
+            phase.duration -= delta;
+        } else {
+            phase.duration = MIN_PHASE_DURATION;
+        }
+    }
+    signals
+}
@@ -1,6 +1,6 @@
 use crate::{
     CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
+    PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
     VehicleSpec, MAX_CAR_LENGTH,
 };
 use abstutil::Timer;
@@ -53,7 +53,13 @@ pub enum TripSpec {
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct TripSpawner {
     parked_cars_claimed: BTreeSet<CarID>,
-    trips: Vec<(Time, Option<PedestrianID>, Option<CarID>, TripSpec)>,
+    trips: Vec<(
+        Time,
+        Option<PedestrianID>,
+        Option<CarID>,
+        Option<PersonID>,
+        TripSpec,
+    )>,
 }
 
 impl TripSpawner {
@@ -69,9 +75,11 @@ impl TripSpawner {
         start_time: Time,
         ped_id: Option<PedestrianID>,
         car_id: Option<CarID>,
+        person: Option<PersonID>,
         spec: TripSpec,
         map: &Map,
         parking: &ParkingSimState,
+        trips: &TripManager,
     ) {
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
         match &spec {
@@ -107,7 +115,9 @@ impl TripSpawner {
             }
             TripSpec::UsingParkedCar { spot, .. } => {
                 let car_id = parking.get_car_at_spot(*spot).unwrap().vehicle.id;
-                if self.parked_cars_claimed.contains(&car_id) {
+                // People return to the same car for later trips.
+                let returning = person.and_then(|p| trips.get_person_car(p)) == Some(car_id);
+                if !returning && self.parked_cars_claimed.contains(&car_id) {
                     panic!(
                         "A TripSpec wants to use {}, which is already claimed",
                         car_id
@@ -163,6 +173,7 @@ impl TripSpawner {
                             start_time,
                             ped_id,
                             None,
+                            person,
                             TripSpec::JustWalking {
                                 start: start.clone(),
                                 goal: SidewalkSpot::building(*b, map),
@@ -176,7 +187,7 @@ impl TripSpawner {
             TripSpec::UsingTransit { .. } => {}
         };
 
-        self.trips.push((start_time, ped_id, car_id, spec));
+        self.trips.push((start_time, ped_id, car_id, person, spec));
     }
 
     pub fn spawn_all(
@@ -192,13 +203,13 @@ impl TripSpawner {
             "calculate paths",
             std::mem::replace(&mut self.trips, Vec::new()),
             |tuple| {
-                let req = tuple.3.get_pathfinding_request(map, parking);
+                let req = tuple.4.get_pathfinding_request(tuple.0, map, parking);
                 (tuple, req.clone(), map.pathfind(req))
             },
         );
 
         timer.start_iter("spawn trips", paths.len());
-        for ((start_time, ped_id, car_id, spec), req, maybe_path) in paths {
+        for ((start_time, ped_id, car_id, person, spec), req, maybe_path) in paths {
             timer.next();
             match spec {
                 TripSpec::CarAppearing {
@@ -225,7 +236,7 @@ impl TripSpawner {
                         ));
                     }
                     let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
-                    let trip = trips.new_trip(start_time, trip_start, legs);
+                    let trip = trips.new_trip(start_time, person, trip_start, legs);
                     if let Some(path) = maybe_path {
                         let router = goal.make_router(path, map, vehicle.vehicle_type);
                         scheduler.quick_push(
@@ -250,10 +261,14 @@ impl TripSpawner {
                     ped_speed,
                 } => {
                     let vehicle = &parking.get_car_at_spot(spot).unwrap().vehicle;
-                    match start.connection {
-                        SidewalkPOI::Building(b) => assert_eq!(vehicle.owner, Some(b)),
+                    let start_bldg = match start.connection {
+                        SidewalkPOI::Building(b) => b,
                         _ => unreachable!(),
                     };
+                    // People might return to their car from somewhere besides home.
+                    if person.and_then(|p| trips.get_person_car(p)) != Some(vehicle.id) {
+                        assert_eq!(vehicle.owner, Some(start_bldg));
+                    }
 
                     let parking_spot = SidewalkSpot::parking_spot(spot, map, parking);
 
@@ -272,7 +287,7 @@ impl TripSpawner {
                         DrivingGoal::Border(_, _) => {}
                     }
                     let trip =
-                        trips.new_trip(start_time, TripStart::Bldg(vehicle.owner.unwrap()), legs);
+                        trips.new_trip(start_time, person, TripStart::Bldg(start_bldg), legs);
 
                     if let Some(path) = maybe_path {
                         scheduler.quick_push(
@@ -304,7 +319,8 @@ impl TripSpawner {
                     // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
                     // the DrivingGoal, so we can expand the trip later.
                     let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
-                    let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs);
+                    let trip =
+                        trips.new_trip(start_time, person, TripStart::Bldg(start_bldg), legs);
 
                     scheduler.quick_push(
                         start_time,
@@ -327,6 +343,7 @@ impl TripSpawner {
                 } => {
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -383,6 +400,7 @@ impl TripSpawner {
                     };
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -426,6 +444,7 @@ impl TripSpawner {
                     let walk_to = SidewalkSpot::bus_stop(stop1, map);
                     let trip = trips.new_trip(
                         start_time,
+                        person,
                         match start.connection {
                             SidewalkPOI::Building(b) => TripStart::Bldg(b),
                             SidewalkPOI::SuddenlyAppear => {
@@ -495,6 +514,7 @@ impl TripSpec {
 
     pub(crate) fn get_pathfinding_request(
         &self,
+        start_time: Time,
         map: &Map,
         parking: &ParkingSimState,
     ) -> PathRequest {
@@ -510,12 +530,14 @@ impl TripSpec {
                     start: *start_pos,
                     end: goal.goal_pos(constraints, map),
                     constraints,
+                    departure_time: Some(start_time),
                 }
             }
             TripSpec::UsingParkedCar { start, spot, .. } => PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::parking_spot(*spot, map, parking).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
+                departure_time: None,
             },
             // Don't know where the parked car will be, so just make a dummy path that'll never
             // fail.
@@ -525,12 +547,14 @@ impl TripSpec {
                     start: pos,
                     end: pos,
                     constraints: PathConstraints::Pedestrian,
+                    departure_time: None,
                 }
             }
             TripSpec::JustWalking { start, goal, .. } => PathRequest {
                 start: start.sidewalk_pos,
                 end: goal.sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
+                departure_time: None,
             },
             TripSpec::UsingBike { start, .. } => PathRequest {
                 start: start.sidewalk_pos,
@@ -538,11 +562,13 @@ impl TripSpec {
                     .unwrap()
                     .sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
+                departure_time: None,
             },
             TripSpec::UsingTransit { start, stop1, .. } => PathRequest {
                 start: start.sidewalk_pos,
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
+                departure_time: None,
             },
         }
     }
@@ -1,11 +1,14 @@
 use crate::{
-    AgentMetadata, CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, Router, TimeInterval,
-    TransitSimState, TripID, Vehicle, VehicleType,
+    AgentMetadata, CarStatus, DistanceInterval, DrawCarInput, EmissionsTracker, Event, ParkingSpot,
+    Router, TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
 };
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{Map, Traversable, LANE_THICKNESS};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{LaneID, Map, Traversable, LANE_THICKNESS};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+
+const REROUTE_PERIOD: Duration = Duration::const_seconds(300.0);
+pub(crate) const BLOCKED_BEFORE_REROUTE: Duration = Duration::const_seconds(60.0);
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Car {
@@ -15,6 +18,9 @@ pub struct Car {
     pub trip: TripID,
     pub blocked_since: Option<Time>,
     pub started_at: Time,
+    pub emissions: EmissionsTracker,
+    // The last time this driver considered a different route. None if they never reroute.
+    pub last_reroute: Option<Time>,
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
@@ -22,6 +28,72 @@ pub struct Car {
 }
 
 impl Car {
+    // All state changes should go through here, so the emissions model sees every change in
+    // speed.
+    pub fn set_state(&mut self, state: CarState, now: Time, events: &mut Vec<Event>) {
+        let speed = state.speed();
+        self.state = state;
+        if let Some((on, amount)) =
+            self.emissions
+                .change_speed(self.vehicle.vehicle_type, now, speed, self.router.head())
+        {
+            events.push(Event::VehicleEmissions(
+                self.vehicle.id,
+                self.trip,
+                on,
+                amount,
+            ));
+        }
+    }
+
+    // Call right before the car vanishes.
+    pub fn finish_emissions(&mut self, now: Time, events: &mut Vec<Event>) {
+        if let Some((on, amount)) = self.emissions.finish(self.vehicle.vehicle_type, now) {
+            events.push(Event::VehicleEmissions(
+                self.vehicle.id,
+                self.trip,
+                on,
+                amount,
+            ));
+        }
+    }
+
+    // Drivers who reroute check for a better path every so often, or sooner if they've been
+    // stuck.
+    pub fn should_reroute(&self, now: Time) -> bool {
+        let last = match self.last_reroute {
+            Some(t) => t,
+            None => {
+                return false;
+            }
+        };
+        let since = now - last;
+        let stuck = self
+            .blocked_since
+            .map(|t| now - t >= BLOCKED_BEFORE_REROUTE)
+            .unwrap_or(false);
+        since >= REROUTE_PERIOD || (stuck && since >= BLOCKED_BEFORE_REROUTE)
+    }
+
+    // Call when the car is at the end of a lane and about to ask for a turn, and should_reroute is
+    // true. Delays are how long traffic has been stuck on each lane right now. Returns true if the
+    // path changed.
+    pub fn reroute(
+        &mut self,
+        now: Time,
+        map: &Map,
+        delays: &BTreeMap<LaneID, Duration>,
+        events: &mut Vec<Event>,
+    ) -> bool {
+        self.last_reroute = Some(now);
+        if self.router.reroute(&self.vehicle, now, map, delays) {
+            events.push(Event::PathAmended(self.router.get_path().clone()));
+            events.push(Event::CarRerouted(self.vehicle.id, self.trip));
+            return true;
+        }
+        false
+    }
+
     // Assumes the current head of the path is the thing to cross.
     pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
         let dist_int = DistanceInterval::new_driving(
@@ -44,6 +116,10 @@ impl Car {
         let on = self.router.head();
         let mut speed = on.speed_limit(map);
         if let Some(s) = self.vehicle.max_speed {
+            let s = match (self.vehicle.vehicle_type, on) {
+                (VehicleType::Bike, Traversable::Lane(l)) => s * map.get_l(l).bike_speed_factor(),
+                _ => s,
+            };
             speed = speed.min(s);
         }
         let dt = (dist_int.end - dist_int.start) / speed;
@@ -159,7 +235,7 @@ impl Car {
                 CarState::Idling(_, _) => CarStatus::Parked,
             },
             on: self.router.head(),
-            label: if self.vehicle.vehicle_type == VehicleType::Bus {
+            label: if self.vehicle.vehicle_type.is_transit() {
                 Some(
                     map.get_br(transit.bus_route(self.vehicle.id))
                         .name
@@ -207,4 +283,25 @@ impl CarState {
             CarState::Idling(_, ref time_int) => time_int.end,
         }
     }
+
+    // Cars only move while Crossing; the engine is idling otherwise.
+    pub fn speed(&self) -> Speed {
+        match self {
+            CarState::Crossing(ref time_int, ref dist_int) => {
+                if time_int.start == time_int.end {
+                    Speed::ZERO
+                } else {
+                    Speed::from_dist_time(
+                        dist_int.end - dist_int.start,
+                        time_int.end - time_int.start,
+                    )
+                }
+            }
+            CarState::Queued
+            | CarState::WaitingToAdvance
+            | CarState::Unparking(_, _, _)
+            | CarState::Parking(_, _, _)
+            | CarState::Idling(_, _) => Speed::ZERO,
+        }
+    }
 }
@@ -1,10 +1,10 @@
-use crate::mechanics::car::{Car, CarState};
+use crate::mechanics::car::{Car, CarState, BLOCKED_BEFORE_REROUTE};
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
-    Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
-    TransitSimState, TripManager, TripPositions, UnzoomedAgent, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    EmissionsTracker, Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler,
+    TimeInterval, TrajectorySample, TransitSimState, TripManager, TripPositions, UnzoomedAgent,
+    VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine, Time};
@@ -14,7 +14,6 @@ use std::collections::{BTreeMap, HashSet, VecDeque};
 
 const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
-const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -35,15 +34,17 @@ pub struct DrivingSimState {
     events: Vec<Event>,
 
     recalc_lanechanging: bool,
+    reroute_compliance: f64,
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(map: &Map, recalc_lanechanging: bool, reroute_compliance: f64) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
             recalc_lanechanging,
+            reroute_compliance,
         };
 
         for l in map.all_lanes() {
@@ -89,16 +90,28 @@ impl DrivingSimState {
                 router: params.router,
                 // Temporary
                 state: CarState::Queued,
+                emissions: EmissionsTracker::new(Traversable::Lane(first_lane), now),
                 last_steps: VecDeque::new(),
                 blocked_since: None,
                 started_at: now,
+                last_reroute: if params.vehicle.vehicle_type == VehicleType::Car
+                    && follows_reroutes(params.vehicle.id, self.reroute_compliance)
+                {
+                    Some(now)
+                } else {
+                    None
+                },
                 trip: params.trip,
             };
             if let Some(p) = params.maybe_parked_car {
-                car.state = CarState::Unparking(
-                    params.start_dist,
-                    p.spot,
-                    TimeInterval::new(now, now + TIME_TO_UNPARK),
+                car.set_state(
+                    CarState::Unparking(
+                        params.start_dist,
+                        p.spot,
+                        TimeInterval::new(now, now + TIME_TO_UNPARK),
+                    ),
+                    now,
+                    &mut self.events,
                 );
             } else {
                 // Have to do this early
@@ -129,7 +142,11 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(params.start_dist, now, map);
+                car.set_state(
+                    car.crossing_state(params.start_dist, now, map),
+                    now,
+                    &mut self.events,
+                );
             }
             scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             {
@@ -247,7 +264,7 @@ impl DrivingSimState {
     ) -> bool {
         match car.state {
             CarState::Crossing(_, _) => {
-                car.state = CarState::Queued;
+                car.set_state(CarState::Queued, now, &mut self.events);
                 car.blocked_since = Some(now);
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
@@ -256,7 +273,7 @@ impl DrivingSimState {
                 let queue = &self.queues[&car.router.head()];
                 if queue.cars[0] == car.vehicle.id && queue.laggy_head.is_none() {
                     // Want to re-run, but no urgency about it happening immediately.
-                    car.state = CarState::WaitingToAdvance;
+                    car.set_state(CarState::WaitingToAdvance, now, &mut self.events);
                     if self.recalc_lanechanging {
                         car.router.opportunistically_lanechange(&self.queues, map);
                     }
@@ -278,14 +295,20 @@ impl DrivingSimState {
                         &mut self.events,
                     );
                 }
-                car.state = car.crossing_state(front, now, map);
+                car.set_state(car.crossing_state(front, now, map), now, &mut self.events);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
             CarState::Idling(dist, _) => {
+                // Passengers who boarded while the bus was already waiting hold it up longer.
+                if let Some(extra) = transit.bus_still_boarding(car.vehicle.id) {
+                    car.state = CarState::Idling(dist, TimeInterval::new(now, now + extra));
+                    scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                    return false;
+                }
                 car.router = transit.bus_departed_from_stop(car.vehicle.id);
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
-                car.state = car.crossing_state(dist, now, map);
+                car.set_state(car.crossing_state(dist, now, map), now, &mut self.events);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
 
                 // Update our follower, so they know we stopped idling.
@@ -302,11 +325,16 @@ impl DrivingSimState {
                             // If they're on their last step, they might be ending early and not
                             // right behind us.
                             if !follower.router.last_step() {
-                                follower.state = follower.crossing_state(
-                                    // Since the follower was Queued, this must be where they are.
-                                    dist - car.vehicle.length - FOLLOWING_DISTANCE,
+                                follower.set_state(
+                                    follower.crossing_state(
+                                        // Since the follower was Queued, this must be where
+                                        // they are.
+                                        dist - car.vehicle.length - FOLLOWING_DISTANCE,
+                                        now,
+                                        map,
+                                    ),
                                     now,
-                                    map,
+                                    &mut self.events,
                                 );
                                 follower.blocked_since = None;
                                 scheduler.update(
@@ -329,6 +357,22 @@ impl DrivingSimState {
             CarState::Queued => unreachable!(),
             CarState::WaitingToAdvance => {
                 // 'car' is the leader.
+                if car.should_reroute(now) {
+                    let old_next = car.router.next();
+                    let delays = self.live_delays(now);
+                    if car.reroute(now, map, &delays, &mut self.events) {
+                        if self.recalc_lanechanging {
+                            car.router.opportunistically_lanechange(&self.queues, map);
+                        }
+                        // We might've already asked for a turn we no longer want.
+                        if let Traversable::Turn(t) = old_next {
+                            if car.router.next() != old_next {
+                                intersections.cancel_request(AgentID::Car(car.vehicle.id), t);
+                            }
+                        }
+                    }
+                }
+
                 let from = car.router.head();
                 let goto = car.router.next();
                 assert!(from != goto);
@@ -350,7 +394,15 @@ impl DrivingSimState {
                             &car,
                         )),
                     ) {
-                        // Don't schedule a retry here.
+                        // Don't schedule a retry here; the intersection wakes us up. But drivers
+                        // who reroute also check back once they've been stuck long enough to look
+                        // for another way around.
+                        if car.last_reroute.is_some() {
+                            scheduler.update(
+                                now + BLOCKED_BEFORE_REROUTE,
+                                Command::UpdateCar(car.vehicle.id),
+                            );
+                        }
                         return false;
                     }
                 }
@@ -367,7 +419,11 @@ impl DrivingSimState {
                 let last_step =
                     car.router
                         .advance(&car.vehicle, parking, map, car.trip, &mut self.events);
-                car.state = car.crossing_state(Distance::ZERO, now, map);
+                car.set_state(
+                    car.crossing_state(Distance::ZERO, now, map),
+                    now,
+                    &mut self.events,
+                );
                 car.blocked_since = None;
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
@@ -453,10 +509,14 @@ impl DrivingSimState {
                         trips.abort_trip_impossible_parking(car.vehicle.id);
                     }
                     Some(ActionAtEnd::StartParking(spot)) => {
-                        car.state = CarState::Parking(
-                            our_dist,
-                            spot,
-                            TimeInterval::new(now, now + TIME_TO_PARK),
+                        car.set_state(
+                            CarState::Parking(
+                                our_dist,
+                                spot,
+                                TimeInterval::new(now, now + TIME_TO_PARK),
+                            ),
+                            now,
+                            &mut self.events,
                         );
                         // If we don't do this, then we might have another car creep up
                         // behind, see the spot free, and start parking too. This can
@@ -467,7 +527,11 @@ impl DrivingSimState {
                         return true;
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
-                        car.state = car.crossing_state(our_dist, now, map);
+                        car.set_state(
+                            car.crossing_state(our_dist, now, map),
+                            now,
+                            &mut self.events,
+                        );
                         scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         return true;
@@ -476,21 +540,21 @@ impl DrivingSimState {
                         trips.bike_reached_end(now, car.vehicle.id, bike_rack, map, scheduler);
                     }
                     Some(ActionAtEnd::BusAtStop) => {
-                        transit.bus_arrived_at_stop(
+                        // If the bus finished its run, fall through and vanish.
+                        if let Some(dwell) = transit.bus_arrived_at_stop(
                             now,
                             car.vehicle.id,
                             trips,
                             walking,
                             scheduler,
                             map,
-                        );
-                        car.state = CarState::Idling(
-                            our_dist,
-                            TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
-                        );
-                        scheduler
-                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                        return true;
+                        ) {
+                            car.state =
+                                CarState::Idling(our_dist, TimeInterval::new(now, now + dwell));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            return true;
+                        }
                     }
                     None => {
                         scheduler.push(
@@ -575,6 +639,7 @@ impl DrivingSimState {
         scheduler: &mut Scheduler,
         intersections: &mut IntersectionSimState,
     ) {
+        car.finish_emissions(now, &mut self.events);
         {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
             assert_eq!(queue.cars.remove(idx).unwrap(), car.vehicle.id);
@@ -606,7 +671,11 @@ impl DrivingSimState {
                     // by leader yet. In that case, recalculating their Crossing state is a
                     // no-op. But if they were blocked, then this will prevent them from
                     // jumping forwards.
-                    follower.state = follower.crossing_state(follower_dist, now, map);
+                    follower.set_state(
+                        follower.crossing_state(follower_dist, now, map),
+                        now,
+                        &mut self.events,
+                    );
                     follower.blocked_since = None;
                     scheduler.update(
                         follower.state.get_end_time(),
@@ -727,7 +796,11 @@ impl DrivingSimState {
                                 // The follower has been smoothly following while the laggy head
                                 // gets out of the way. So
                                 // immediately promote them to WaitingToAdvance.
-                                follower.state = CarState::WaitingToAdvance;
+                                follower.set_state(
+                                    CarState::WaitingToAdvance,
+                                    now,
+                                    &mut self.events,
+                                );
                                 if self.recalc_lanechanging {
                                     follower
                                         .router
@@ -797,6 +870,24 @@ impl DrivingSimState {
         }
     }
 
+    pub fn populate_trajectories(&self, now: Time, map: &Map, samples: &mut Vec<TrajectorySample>) {
+        for queue in self.queues.values() {
+            if queue.cars.is_empty() {
+                continue;
+            }
+
+            for (car, dist) in queue.get_car_positions(now, &self.cars, &self.queues) {
+                samples.push(TrajectorySample {
+                    agent: AgentID::Car(car),
+                    trip: self.cars[&car].trip,
+                    on: queue.id,
+                    dist_along: dist,
+                    pt: queue.id.dist_along(dist, map).0,
+                });
+            }
+        }
+    }
+
     pub fn get_all_draw_cars(
         &self,
         now: Time,
@@ -980,4 +1071,35 @@ impl DrivingSimState {
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
+
+    // For every lane with somebody stuck on it right now, how long the car that's been stuck the
+    // longest has been waiting.
+    fn live_delays(&self, now: Time) -> BTreeMap<LaneID, Duration> {
+        let mut delays = BTreeMap::new();
+        for queue in self.queues.values() {
+            if let Traversable::Lane(l) = queue.id {
+                // The car being updated is temporarily missing from self.cars.
+                if let Some(delay) = queue
+                    .cars
+                    .iter()
+                    .filter_map(|c| self.cars.get(c)?.blocked_since)
+                    .map(|t| now - t)
+                    .max()
+                {
+                    delays.insert(l, delay);
+                }
+            }
+        }
+        delays
+    }
+}
+
+// Decide once per car whether the driver follows rerouting advice. This is deterministic, so runs
+// with the same options match.
+fn follows_reroutes(id: CarID, compliance: f64) -> bool {
+    if compliance <= 0.0 {
+        return false;
+    }
+    let bucket = (id.0 as u64).wrapping_mul(2_654_435_761) % 1000;
+    (bucket as f64) < compliance * 1000.0
 }
@@ -5,8 +5,8 @@ use abstutil::{deserialize_btreemap, serialize_btreemap};
 use derivative::Derivative;
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, TurnID, TurnPriority,
-    TurnType,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, Phase, TurnID,
+    TurnPriority, TurnType,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
@@ -33,6 +33,22 @@ struct State {
         deserialize_with = "deserialize_btreemap"
     )]
     waiting: BTreeMap<Request, Time>,
+    // Only used for traffic signals with actuated phases. Fixed-time signals are purely a function
+    // of the clock.
+    signal: Option<SignalState>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+struct SignalState {
+    current_phase: usize,
+    phase_started: Time,
+    // The last time somebody asked for a turn allowed in the current phase
+    last_demand: Time,
+    // When the phase is set to end, unless more demand extends it
+    phase_ends: Time,
+    // Nobody wants any phase, so the signal rests in the current one without checking back until
+    // somebody shows up.
+    resting: bool,
 }
 
 impl IntersectionSimState {
@@ -55,6 +71,7 @@ impl IntersectionSimState {
                     id: i.id,
                     accepted: BTreeSet::new(),
                     waiting: BTreeMap::new(),
+                    signal: None,
                 },
             );
             if i.is_traffic_signal() && !use_freeform_policy_everywhere {
@@ -125,7 +142,7 @@ impl IntersectionSimState {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let (_, phase, _) = signal.current_phase_and_remaining_time(now);
+            let (_, phase, _) = self.state[&i].current_phase(signal, now);
             for (req, _) in all {
                 match phase.get_priority_of_turn(req.turn, signal) {
                     TurnPriority::Protected => {
@@ -168,16 +185,27 @@ impl IntersectionSimState {
 
     // This is only triggered for traffic signals.
     pub fn update_intersection(
-        &self,
+        &mut self,
         now: Time,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
+        let signal = map.get_traffic_signal(id);
+        if signal.is_actuated() {
+            let state = self.state.get_mut(&id).unwrap();
+            state.update_actuated_signal(signal, now);
+            let s = state.signal.as_ref().unwrap();
+            let next_update = if s.resting { None } else { Some(s.phase_ends) };
+            self.wakeup_waiting(now, id, scheduler, map);
+            if let Some(t) = next_update {
+                scheduler.push(t, Command::UpdateIntersection(id));
+            }
+            return;
+        }
+
         self.wakeup_waiting(now, id, scheduler, map);
-        let (_, _, remaining) = map
-            .get_traffic_signal(id)
-            .current_phase_and_remaining_time(now);
+        let (_, _, remaining) = signal.current_phase_and_remaining_time(now);
         scheduler.push(now + remaining, Command::UpdateIntersection(id));
     }
 
@@ -202,6 +230,13 @@ impl IntersectionSimState {
         let req = Request { agent, turn };
         let state = self.state.get_mut(&turn.parent).unwrap();
         state.waiting.entry(req.clone()).or_insert(now);
+        if !self.use_freeform_policy_everywhere {
+            if let Some(ref signal) = map.maybe_get_traffic_signal(state.id) {
+                if state.note_demand(signal, &req, now) {
+                    scheduler.push(now, Command::UpdateIntersection(state.id));
+                }
+            }
+        }
 
         let allowed = if self.use_freeform_policy_everywhere {
             state.freeform_policy(&req, map)
@@ -245,6 +280,26 @@ impl IntersectionSimState {
         true
     }
 
+    // Only for traffic signals. The index of the current phase and how long until it's scheduled to
+    // end. Actuated phases might be extended.
+    pub fn current_phase_and_remaining_time(
+        &self,
+        now: Time,
+        id: IntersectionID,
+        map: &Map,
+    ) -> (usize, Duration) {
+        let (idx, _, remaining) = self.state[&id].current_phase(map.get_traffic_signal(id), now);
+        (idx, remaining)
+    }
+
+    // How long everybody currently waiting here has been waiting
+    pub fn total_waiting_delay(&self, now: Time, id: IntersectionID) -> Duration {
+        self.state[&id]
+            .waiting
+            .values()
+            .fold(Duration::ZERO, |sum, t| sum + (now - *t))
+    }
+
     pub fn debug(&self, id: IntersectionID, map: &Map) {
         println!("{}", abstutil::to_json(&self.state[&id]));
         if let Some(ref sign) = map.maybe_get_stop_sign(id) {
@@ -270,6 +325,105 @@ impl IntersectionSimState {
 }
 
 impl State {
+    // Like ControlTrafficSignal::current_phase_and_remaining_time, but also handles actuated
+    // signals.
+    fn current_phase<'a>(
+        &self,
+        signal: &'a ControlTrafficSignal,
+        now: Time,
+    ) -> (usize, &'a Phase, Duration) {
+        if signal.is_actuated() {
+            // The signal might've been edited live, so double check the phase still exists.
+            if let Some(ref s) = self.signal {
+                if s.current_phase < signal.phases.len() {
+                    let remaining = if s.phase_ends > now {
+                        s.phase_ends - now
+                    } else {
+                        Duration::ZERO
+                    };
+                    return (s.current_phase, &signal.phases[s.current_phase], remaining);
+                }
+            }
+        }
+        signal.current_phase_and_remaining_time(now)
+    }
+
+    // Is anybody waiting at the signal for a turn allowed during this phase? This acts like the
+    // queue detectors on incoming lanes.
+    fn has_demand(&self, phase: &Phase, signal: &ControlTrafficSignal) -> bool {
+        self.waiting
+            .keys()
+            .any(|req| phase.get_priority_of_turn(req.turn, signal) != TurnPriority::Banned)
+    }
+
+    // Returns true if the signal was resting and needs to be updated right away.
+    fn note_demand(&mut self, signal: &ControlTrafficSignal, req: &Request, now: Time) -> bool {
+        if let Some(ref mut s) = self.signal {
+            if s.current_phase < signal.phases.len()
+                && signal.phases[s.current_phase].get_priority_of_turn(req.turn, signal)
+                    != TurnPriority::Banned
+            {
+                s.last_demand = now;
+            }
+            if s.resting {
+                s.resting = false;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Decide whether the current phase keeps going, and if not, which phase is next. Afterwards,
+    // phase_ends is when to check again.
+    fn update_actuated_signal(&mut self, signal: &ControlTrafficSignal, now: Time) {
+        let mut s = match self.signal.take() {
+            Some(s) if s.current_phase < signal.phases.len() => s,
+            _ => SignalState {
+                current_phase: 0,
+                phase_started: now,
+                last_demand: now,
+                phase_ends: now,
+                resting: false,
+            },
+        };
+        s.resting = false;
+
+        let phase = &signal.phases[s.current_phase];
+        if self.has_demand(phase, signal) {
+            s.last_demand = now;
+        }
+        let end = phase_end(phase, &s);
+        if now < end {
+            s.phase_ends = end;
+            self.signal = Some(s);
+            return;
+        }
+
+        // Find the next phase that somebody wants. Fixed-time phases always happen.
+        let num_phases = signal.phases.len();
+        let next = (1..=num_phases)
+            .map(|offset| (s.current_phase + offset) % num_phases)
+            .find(|idx| {
+                let p = &signal.phases[*idx];
+                p.actuated.is_none() || self.has_demand(p, signal)
+            });
+        match next {
+            Some(idx) => {
+                s.current_phase = idx;
+                s.phase_started = now;
+                s.last_demand = now;
+                s.phase_ends = phase_end(&signal.phases[idx], &s);
+            }
+            None => {
+                // Nobody's waiting anywhere, so rest in the current phase until somebody asks for
+                // a turn.
+                s.phase_ends = now;
+                s.resting = true;
+            }
+        }
+        self.signal = Some(s);
+    }
+
     fn any_accepted_conflict_with(&self, t: TurnID, map: &Map) -> bool {
         let turn = map.get_t(t);
         self.accepted
@@ -298,6 +452,24 @@ impl State {
             return false;
         }
 
+        // Vehicles yield to pedestrians waiting at the one crosswalk of a degenerate intersection,
+        // like the ones splitting roads at mid-block crossings. Everywhere else, the usual stop
+        // sign rules decide.
+        let turn = map.get_t(req.turn);
+        if !turn.between_sidewalks()
+            && map.get_i(self.id).roads.len() == 2
+            && self.waiting.keys().any(|other| {
+                let other_turn = map.get_t(other.turn);
+                other_turn.turn_type == TurnType::Crosswalk && other_turn.conflicts_with(turn)
+            })
+        {
+            // The pedestrian wakes us up after crossing, but nothing does if they don't start
+            // crossing soon, so try again later too. Something else might've already woken us
+            // up, so update.
+            scheduler.update(now + WAIT_AT_STOP_SIGN, Command::update_agent(req.agent));
+            return false;
+        }
+
         let our_priority = sign.get_priority(req.turn, map);
         assert!(our_priority != TurnPriority::Banned);
         let our_time = self.waiting[req];
@@ -347,7 +519,7 @@ impl State {
             return true;
         }
 
-        let (_, phase, remaining_phase_time) = signal.current_phase_and_remaining_time(now);
+        let (_, phase, mut remaining_phase_time) = self.current_phase(signal, now);
 
         // Can't go at all this phase.
         let our_priority = phase.get_priority_of_turn(req.turn, signal);
@@ -381,17 +553,29 @@ impl State {
         // TODO Make sure we can optimistically finish this turn before an approaching
         // higher-priority vehicle wants to begin.
 
+        // Our own demand keeps an actuated phase going until it gaps out, but nothing guarantees
+        // anybody else will extend it further, so plan on the earliest possible end.
+        if let (Some(timing), Some(s)) = (phase.actuated, &self.signal) {
+            let earliest_end = (s.phase_started + timing.min_green)
+                .max(s.last_demand.max(now) + timing.extension)
+                .min(s.phase_started + timing.max_green);
+            if earliest_end > now {
+                remaining_phase_time = remaining_phase_time.max(earliest_end - now);
+            }
+        }
+
         // Optimistically if nobody else is in the way, this is how long it'll take to finish the
         // turn. Don't start the turn if we won't finish by the time the light changes. If we get
         // it wrong, that's fine -- block the box a bit.
         let time_to_cross = turn.geom.length() / speed;
         if time_to_cross > remaining_phase_time {
             // Actually, we might have bigger problems...
-            if time_to_cross > phase.duration {
+            if time_to_cross > phase.max_duration() {
                 println!(
                     "OYYY! {:?} is impossible to fit into phase duration of {}. Allowing, but fix \
                      the policy!",
-                    req, phase.duration
+                    req,
+                    phase.max_duration()
                 );
             } else {
                 return false;
@@ -402,6 +586,16 @@ impl State {
     }
 }
 
+// When the current phase should end, given the demand seen so far
+fn phase_end(phase: &Phase, s: &SignalState) -> Time {
+    match phase.actuated {
+        Some(timing) => (s.phase_started + timing.min_green)
+            .max(s.last_demand + timing.extension)
+            .min(s.phase_started + timing.max_green),
+        None => s.phase_started + phase.duration,
+    }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
 struct Request {
     agent: AgentID,
@@ -145,6 +145,15 @@ impl ParkingSimState {
         None
     }
 
+    // Returns None if the car isn't parked or somebody else already reserved it.
+    pub fn dynamically_reserve_specific_car(&mut self, id: CarID) -> Option<ParkedCar> {
+        let p = self.parked_cars.get(&id)?;
+        if !self.dynamically_reserved_cars.insert(id) {
+            return None;
+        }
+        Some(p.clone())
+    }
+
     pub fn dynamically_return_car(&mut self, p: ParkedCar) {
         self.dynamically_reserved_cars.remove(&p.vehicle.id);
     }
@@ -2,7 +2,7 @@ use crate::{
     AgentID, AgentMetadata, Command, CreatePedestrian, DistanceInterval, DrawPedCrowdInput,
     DrawPedestrianInput, Event, IntersectionSimState, ParkingSimState, ParkingSpot,
     PedCrowdLocation, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval,
-    TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
+    TrajectorySample, TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
 };
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed, Time};
@@ -325,6 +325,18 @@ impl WalkingSimState {
         }
     }
 
+    pub fn populate_trajectories(&self, now: Time, map: &Map, samples: &mut Vec<TrajectorySample>) {
+        for ped in self.peds.values() {
+            samples.push(TrajectorySample {
+                agent: AgentID::Pedestrian(ped.id),
+                trip: ped.trip,
+                on: ped.path.current_step().as_traversable(),
+                dist_along: ped.get_dist_along(now, map),
+                pt: ped.get_draw_ped(now, map).pos,
+            });
+        }
+    }
+
     pub fn get_draw_peds_on(
         &self,
         now: Time,
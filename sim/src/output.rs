@@ -0,0 +1,74 @@
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// A file that the simulation streams output to, kept open while it runs. When the simulation is
+// cloned (to preview edits, for example), the copy continues in its own file, starting with
+// everything written so far. events.csv becomes events_copy1.csv, then events_copy2.csv, and so
+// on.
+pub(crate) struct OutputFile {
+    path: String,
+    file: File,
+    num_copies: Cell<usize>,
+}
+
+impl OutputFile {
+    // Also creates any missing parent directories.
+    pub fn create(path: String, header: Option<&str>) -> OutputFile {
+        if let Some(dir) = Path::new(&path).parent() {
+            std::fs::create_dir_all(dir).expect("Creating parent dir failed");
+        }
+        let mut file =
+            File::create(&path).unwrap_or_else(|err| panic!("Can't create {}: {}", path, err));
+        if let Some(header) = header {
+            writeln!(file, "{}", header).unwrap();
+        }
+        OutputFile {
+            path,
+            file,
+            num_copies: Cell::new(0),
+        }
+    }
+
+    // Each line is terminated with a newline.
+    pub fn write_lines(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        let mut chunk = lines.join("\n");
+        chunk.push('\n');
+        self.file
+            .write_all(chunk.as_bytes())
+            .unwrap_or_else(|err| panic!("Can't write to {}: {}", self.path, err));
+    }
+}
+
+impl Clone for OutputFile {
+    fn clone(&self) -> OutputFile {
+        self.num_copies.set(self.num_copies.get() + 1);
+        let path = copy_path(&self.path, self.num_copies.get());
+        std::fs::copy(&self.path, &path)
+            .unwrap_or_else(|err| panic!("Can't copy {} to {}: {}", self.path, path, err));
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|err| panic!("Can't append to {}: {}", path, err));
+        OutputFile {
+            path,
+            file,
+            num_copies: Cell::new(0),
+        }
+    }
+}
+
+fn copy_path(path: &str, copy: usize) -> String {
+    let p = Path::new(path);
+    let stem = p.file_stem().unwrap().to_string_lossy();
+    let name = if let Some(ext) = p.extension() {
+        format!("{}_copy{}.{}", stem, copy, ext.to_string_lossy())
+    } else {
+        format!("{}_copy{}", stem, copy)
+    };
+    p.with_file_name(name).to_string_lossy().to_string()
+}
@@ -1,9 +1,9 @@
 use crate::mechanics::Queue;
-use crate::{Event, ParkingSimState, ParkingSpot, SidewalkSpot, TripID, Vehicle};
-use geom::Distance;
+use crate::{Event, ParkingSimState, ParkingSpot, SidewalkSpot, TripID, Vehicle, VehicleType};
+use geom::{Distance, Duration, Time};
 use map_model::{
     BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, TurnID,
+    Position, RoadID, Traversable, TurnID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, VecDeque};
@@ -198,6 +198,7 @@ impl Router {
                                 start: Position::new(current_lane, front),
                                 end: new_pos,
                                 constraints: PathConstraints::Car,
+                                departure_time: None,
                             }),
                             format!("parking on the current lane"),
                         ));
@@ -218,6 +219,7 @@ impl Router {
                                     start: Position::new(current_lane, front),
                                     end: new_pos,
                                     constraints: PathConstraints::Car,
+                                    departure_time: None,
                                 }),
                                 format!("parking somewhere else"),
                             ));
@@ -334,11 +336,77 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
 
+    // Look for a better path from the end of the current lane to the same destination, using
+    // whatever the pathfinder knows about travel times, plus delays happening right now. Buses
+    // and bikes stick to their plans. Returns true if the path changed.
+    pub fn reroute(
+        &mut self,
+        vehicle: &Vehicle,
+        now: Time,
+        map: &Map,
+        delays: &BTreeMap<LaneID, Duration>,
+    ) -> bool {
+        if vehicle.vehicle_type != VehicleType::Car {
+            return false;
+        }
+        let end_dist = match self.goal {
+            Goal::EndAtBorder { end_dist, .. } => end_dist,
+            Goal::ParkNearBuilding { spot, .. } => {
+                spot.map(|(_, dist)| dist).unwrap_or(self.path.end_dist())
+            }
+            Goal::BikeThenStop { .. } | Goal::FollowBusRoute { .. } => {
+                return false;
+            }
+        };
+        // Nothing to decide if the next turn leads to the last lane.
+        if self.path.get_steps().len() < 4 {
+            return false;
+        }
+        let current = match self.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return false;
+            }
+        };
+        let goal = self.path.last_step().as_lane();
+        if current == goal {
+            return false;
+        }
+
+        if let Some(path) = map.pathfind_with_delays(
+            PathRequest {
+                start: Position::new(current, map.get_l(current).length()),
+                end: Position::new(goal, end_dist),
+                constraints: PathConstraints::Car,
+                departure_time: Some(now),
+            },
+            delays,
+        ) {
+            // Picking a different lane on the same roads isn't a new route; lane-changing
+            // handles that.
+            if roads(&path, map) != roads(&self.path, map) {
+                self.path.replace_remaining(path);
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn replace_path_for_serialization(&mut self, path: Path) -> Path {
         std::mem::replace(&mut self.path, path)
     }
 }
 
+fn roads(path: &Path, map: &Map) -> Vec<RoadID> {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => Some(map.get_l(*l).parent),
+            PathStep::Turn(_) => None,
+        })
+        .collect()
+}
+
 // Unrealistically assumes the driver has knowledge of currently free parking spots, even if
 // they're far away. Since they don't reserve the spot in advance, somebody else can still beat
 // them there, producing some nice, realistic churn if there's too much contention.
@@ -1,7 +1,7 @@
-use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID};
+use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, PersonID};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram, Time};
-use map_model::{IntersectionID, Path, PathRequest};
+use map_model::{BusRouteID, IntersectionID, Path, PathRequest};
 use serde_derive::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BinaryHeap};
@@ -16,7 +16,12 @@ pub enum Command {
     UpdateLaggyHead(CarID),
     UpdatePed(PedestrianID),
     UpdateIntersection(IntersectionID),
+    // Dispatch the bus for some index into BusRoute::departures
+    StartBus(BusRouteID, usize),
+    // Start the next trip for somebody, once they're done with the previous one
+    StartTrip(PersonID),
     Savestate(Duration),
+    SampleTrajectories(Duration),
 }
 
 impl Command {
@@ -35,7 +40,10 @@ impl Command {
             Command::UpdateLaggyHead(id) => CommandType::CarLaggyHead(*id),
             Command::UpdatePed(id) => CommandType::Ped(*id),
             Command::UpdateIntersection(id) => CommandType::Intersection(*id),
+            Command::StartBus(route, _) => CommandType::BusRoute(*route),
+            Command::StartTrip(person) => CommandType::Person(*person),
             Command::Savestate(_) => CommandType::Savestate,
+            Command::SampleTrajectories(_) => CommandType::SampleTrajectories,
         }
     }
 }
@@ -48,7 +56,12 @@ pub enum CommandType {
     CarLaggyHead(CarID),
     Ped(PedestrianID),
     Intersection(IntersectionID),
+    // Only the next departure for each route is scheduled at a time.
+    BusRoute(BusRouteID),
+    // Only the next trip for each person is scheduled at a time.
+    Person(PersonID),
     Savestate,
+    SampleTrajectories,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -1,10 +1,10 @@
 use crate::{
     AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, GetDrawAgents, IntersectionSimState,
-    ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Router, Scheduler, SidewalkPOI,
-    SidewalkSpot, TransitSimState, TripCount, TripEnd, TripID, TripLeg, TripManager, TripMode,
-    TripPositions, TripResult, TripSpawner, TripSpec, TripStart, UnzoomedAgent, VehicleSpec,
-    VehicleType, WalkingSimState, BUS_LENGTH,
+    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, EventLog, GetDrawAgents,
+    IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, PersonID, Router,
+    Scheduler, SidewalkPOI, SidewalkSpot, TrajectoryLog, TransitSimState, TripCount, TripEnd,
+    TripID, TripLeg, TripManager, TripMode, TripPositions, TripResult, TripSpawner, TripSpec,
+    TripStart, UnzoomedAgent, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, TRAIN_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
@@ -55,6 +55,13 @@ pub struct Sim {
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     analytics: Analytics,
+    // Not restored from savestates.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_log: Option<EventLog>,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    trajectory_log: Option<TrajectoryLog>,
 }
 
 #[derive(Clone)]
@@ -64,6 +71,13 @@ pub struct SimOptions {
     pub use_freeform_policy_everywhere: bool,
     pub disable_block_the_box: bool,
     pub recalc_lanechanging: bool,
+    // Write every event to this file. See EventLog for the format.
+    pub event_log: Option<String>,
+    // Write the position of every agent to this CSV file at some interval. See TrajectoryLog for
+    // the format.
+    pub trajectory_log: Option<(String, Duration)>,
+    // The fraction of drivers (0 to 1) who look for a better route while driving
+    pub reroute_compliance: f64,
 }
 
 impl SimOptions {
@@ -74,6 +88,9 @@ impl SimOptions {
             use_freeform_policy_everywhere: false,
             disable_block_the_box: false,
             recalc_lanechanging: true,
+            event_log: None,
+            trajectory_log: None,
+            reroute_compliance: 0.0,
         }
     }
 }
@@ -85,8 +102,11 @@ impl Sim {
         if let Some(d) = opts.savestate_every {
             scheduler.push(Time::START_OF_DAY + d, Command::Savestate(d));
         }
+        if let Some((_, d)) = opts.trajectory_log {
+            scheduler.push(Time::START_OF_DAY, Command::SampleTrajectories(d));
+        }
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(map, opts.recalc_lanechanging, opts.reroute_compliance),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
@@ -104,13 +124,16 @@ impl Sim {
             ped_id_counter: 0,
 
             map_name: map.get_name().to_string(),
-            // TODO
-            edits_name: "no_edits".to_string(),
+            edits_name: map.get_edits().edits_name.clone(),
             run_name: opts.run_name,
             step_count: 0,
             trip_positions: None,
 
             analytics: Analytics::new(),
+            event_log: opts.event_log.map(EventLog::new),
+            trajectory_log: opts
+                .trajectory_log
+                .map(|(path, _)| TrajectoryLog::new(path)),
         }
     }
 
@@ -119,6 +142,33 @@ impl Sim {
         start_time: Time,
         spec: TripSpec,
         map: &Map,
+    ) -> (Option<PedestrianID>, Option<CarID>) {
+        self.schedule_trip_for_person(start_time, spec, None, map)
+    }
+
+    // Each trip is (earliest departure time, how long to stay at the destination afterwards,
+    // spec). Only the first trip is scheduled now; the rest start once the previous one is done.
+    pub fn schedule_person(
+        &mut self,
+        trips: Vec<(Time, Duration, TripSpec)>,
+        map: &Map,
+    ) -> PersonID {
+        let person = self.trips.new_person(trips);
+        if let Some((start_time, spec)) =
+            self.trips
+                .next_trip_for_person(person, map, &mut self.parking)
+        {
+            self.schedule_trip_for_person(start_time, spec, Some(person), map);
+        }
+        person
+    }
+
+    fn schedule_trip_for_person(
+        &mut self,
+        start_time: Time,
+        spec: TripSpec,
+        person: Option<PersonID>,
+        map: &Map,
     ) -> (Option<PedestrianID>, Option<CarID>) {
         let (ped_id, car_id) = match spec {
             TripSpec::CarAppearing {
@@ -155,8 +205,16 @@ impl Sim {
             }
         };
 
-        self.spawner
-            .schedule_trip(start_time, ped_id, car_id, spec, map, &self.parking);
+        self.spawner.schedule_trip(
+            start_time,
+            ped_id,
+            car_id,
+            person,
+            spec,
+            map,
+            &self.parking,
+            &self.trips,
+        );
         (ped_id, car_id)
     }
 
@@ -209,59 +267,84 @@ impl Sim {
         self.parking.get_offstreet_parked_cars(bldg)
     }
 
+    // Buses following a timetable are dispatched later, so this only returns the buses created
+    // immediately.
     pub fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) -> Vec<CarID> {
         let mut results: Vec<CarID> = Vec::new();
 
+        let legs = self.transit.create_empty_route(route, map);
+        if !route.departures.is_empty() {
+            self.scheduler.push(
+                route.departures[0].max(self.time),
+                Command::StartBus(route.id, 0),
+            );
+            return results;
+        }
+
         // Try to spawn just ONE bus anywhere.
         // TODO Be more realistic. One bus per stop is too much, one is too little.
-        for (next_stop_idx, req, mut path, end_dist) in
-            self.transit.create_empty_route(route, map).into_iter()
-        {
-            let id = CarID(self.car_id_counter, VehicleType::Bus);
-            self.car_id_counter += 1;
-
-            // For now, no desire for randomness. Caller can pass in list of specs if that ever
-            // changes.
-            let vehicle = VehicleSpec {
-                vehicle_type: VehicleType::Bus,
-                length: BUS_LENGTH,
-                max_speed: None,
+        for (next_stop_idx, req, path, end_dist) in legs.into_iter() {
+            if let Some(id) = self.start_bus(route, next_stop_idx, req, path, end_dist, None, map) {
+                results.push(id);
+                return results;
             }
-            .make(id, None);
-
-            // TODO The path analytics (total dist, dist crossed so far) will be wrong for the
-            // first round of buses.
-            // Same for this TripStart, though it doesn't matter too much.
-            let trip = self.trips.new_trip(
-                self.time,
-                TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
-                vec![TripLeg::ServeBusRoute(id, route.id)],
-            );
+            timer.warn(format!(
+                "Giving up on seeding a bus headed towards stop {} of {} ({})",
+                next_stop_idx, route.name, route.id
+            ));
+        }
+        if results.is_empty() {
+            // TODO Bigger failure
+            timer.warn(format!("Failed to make ANY buses for {}!", route.name));
+        }
+        results
+    }
 
-            loop {
-                if path.is_last_step() {
-                    timer.warn(format!(
-                        "Giving up on seeding a bus headed towards stop {} of {} ({})",
-                        next_stop_idx, route.name, route.id
-                    ));
-                    self.trips.abort_trip_failed_start(trip);
-                    break;
-                }
-                let start_lane = if let PathStep::Lane(l) = path.current_step() {
-                    l
-                } else {
-                    path.shift(map);
-                    // TODO Technically should update request, but it shouldn't matter
-                    continue;
-                };
-                if map.get_l(start_lane).length() < vehicle.length {
-                    path.shift(map);
-                    // TODO Technically should update request, but it shouldn't matter
-                    continue;
-                }
+    // Returns None if there's no room anywhere along the path.
+    fn start_bus(
+        &mut self,
+        route: &BusRoute,
+        next_stop_idx: usize,
+        req: PathRequest,
+        mut path: Path,
+        end_dist: Distance,
+        run: Option<usize>,
+        map: &Map,
+    ) -> Option<CarID> {
+        // For now, no desire for randomness. Caller can pass in list of specs if that ever
+        // changes.
+        let spec = transit_vehicle(route);
+        let id = CarID(self.car_id_counter, spec.vehicle_type);
+        self.car_id_counter += 1;
+        let vehicle = spec.make(id, None);
+
+        // TODO The path analytics (total dist, dist crossed so far) will be wrong for the
+        // first round of buses.
+        // Same for this TripStart, though it doesn't matter too much.
+        let trip = self.trips.new_trip(
+            self.time,
+            None,
+            TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
+            vec![TripLeg::ServeBusRoute(id, route.id)],
+        );
 
-                // Bypass some layers of abstraction that don't make sense for buses.
-                if self.driving.start_car_on_lane(
+        loop {
+            // Timetabled buses start on a one-step path towards the first stop.
+            if path.is_last_step() && run.is_none() {
+                self.trips.abort_trip_failed_start(trip);
+                return None;
+            }
+            let start_lane = if let PathStep::Lane(l) = path.current_step() {
+                l
+            } else {
+                path.shift(map);
+                // TODO Technically should update request, but it shouldn't matter
+                continue;
+            };
+
+            // Bypass some layers of abstraction that don't make sense for buses.
+            if map.get_l(start_lane).length() >= vehicle.length
+                && self.driving.start_car_on_lane(
                     self.time,
                     CreateCar {
                         start_dist: vehicle.length,
@@ -275,22 +358,27 @@ impl Sim {
                     &self.intersections,
                     &self.parking,
                     &mut self.scheduler,
-                ) {
-                    self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
-                    self.transit.bus_created(id, route.id, next_stop_idx);
-                    self.analytics.record_demand(&path, map);
-                    results.push(id);
-                    return results;
-                } else {
-                    path.shift(map);
-                }
+                )
+            {
+                self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
+                self.transit.bus_created(
+                    id,
+                    route.id,
+                    next_stop_idx,
+                    run,
+                    route.seated_capacity,
+                    route.standing_capacity,
+                );
+                self.analytics.record_demand(&path, map);
+                return Some(id);
             }
+            if path.is_last_step() {
+                self.trips.abort_trip_failed_start(trip);
+                return None;
+            }
+            path.shift(map);
+            // TODO Technically should update request, but it shouldn't matter
         }
-        if results.is_empty() {
-            // TODO Bigger failure
-            timer.warn(format!("Failed to make ANY buses for {}!", route.name));
-        }
-        results
     }
 
     pub fn set_name(&mut self, name: String) {
@@ -428,6 +516,7 @@ impl Sim {
                                 start: create_ped.start.sidewalk_pos,
                                 end: create_ped.goal.sidewalk_pos,
                                 constraints: PathConstraints::Pedestrian,
+                                departure_time: None,
                             };
                             if let Some(path) = map.pathfind(create_ped.req.clone()) {
                                 create_ped.path = path;
@@ -557,12 +646,55 @@ impl Sim {
                     self.intersections
                         .update_intersection(self.time, i, map, &mut self.scheduler);
                 }
+                Command::StartBus(r, idx) => {
+                    let route = map.get_br(r);
+                    let (next_stop_idx, req, path, end_dist) =
+                        self.transit
+                            .start_of_run(r, transit_vehicle(route).length, map);
+                    if self
+                        .start_bus(route, next_stop_idx, req, path, end_dist, Some(idx), map)
+                        .is_some()
+                    {
+                        if let Some(t) = route.departures.get(idx + 1) {
+                            self.scheduler
+                                .push((*t).max(self.time), Command::StartBus(r, idx + 1));
+                        }
+                    } else {
+                        // Keep trying. Later departures get delayed if this takes a while.
+                        self.scheduler
+                            .push(self.time + BLIND_RETRY_TO_SPAWN, Command::StartBus(r, idx));
+                    }
+                }
+                Command::StartTrip(person) => {
+                    if let Some((_, spec)) =
+                        self.trips
+                            .next_trip_for_person(person, map, &mut self.parking)
+                    {
+                        self.schedule_trip_for_person(self.time, spec, Some(person), map);
+                        self.spawn_all_trips(map, &mut Timer::throwaway(), true);
+                    }
+                }
                 Command::Savestate(frequency) => {
                     self.scheduler
                         .push(self.time + frequency, Command::Savestate(frequency));
                     assert_eq!(savestate_at, None);
                     savestate_at = Some(self.time);
                 }
+                Command::SampleTrajectories(frequency) => {
+                    // Savestates don't keep the log, so stop sampling after loading one.
+                    if let Some(ref mut log) = self.trajectory_log {
+                        self.scheduler.push(
+                            self.time + frequency,
+                            Command::SampleTrajectories(frequency),
+                        );
+                        let mut samples = Vec::new();
+                        self.driving
+                            .populate_trajectories(self.time, map, &mut samples);
+                        self.walking
+                            .populate_trajectories(self.time, map, &mut samples);
+                        log.write(self.time, samples, map.get_gps_bounds());
+                    }
+                }
             }
 
             // Record events at precisely the time they occur.
@@ -572,9 +704,25 @@ impl Sim {
             events.extend(self.walking.collect_events());
             events.extend(self.intersections.collect_events());
             for ev in events {
+                match ev {
+                    Event::TripFinished(trip, _, _) | Event::TripAborted(trip) => {
+                        if let Some((person, start_at)) =
+                            self.trips.person_ready_for_next_trip(trip, self.time)
+                        {
+                            self.scheduler.push(start_at, Command::StartTrip(person));
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(ref mut log) = self.event_log {
+                    log.event(&ev, self.time);
+                }
                 self.analytics.event(ev, self.time, map);
             }
         }
+        if let Some(ref mut log) = self.event_log {
+            log.flush();
+        }
         if let Some(t) = savestate_at {
             self.time = t;
             self.save();
@@ -864,9 +1012,15 @@ impl Sim {
 
     pub fn car_tooltip(&self, car: CarID) -> Vec<String> {
         if let Some(mut lines) = self.driving.tooltip_lines(car, self.time) {
-            if car.1 == VehicleType::Bus {
+            if car.1.is_transit() {
                 let passengers = self.transit.get_passengers(car);
-                lines.push(format!("{} passengers riding", passengers.len()));
+                let (_, standing, capacity) = self.transit.get_load(car);
+                lines.push(format!(
+                    "{} passengers riding ({} standing), capacity {}",
+                    passengers.len(),
+                    standing,
+                    capacity
+                ));
                 for (id, stop) in passengers {
                     lines.push(format!("- {} till {:?}", id, stop));
                 }
@@ -889,7 +1043,7 @@ impl Sim {
     }
 
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<BusRouteID> {
-        if maybe_bus.1 == VehicleType::Bus {
+        if maybe_bus.1.is_transit() {
             Some(self.transit.bus_route(maybe_bus))
         } else {
             None
@@ -913,7 +1067,12 @@ impl Sim {
     }
 
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
-        for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
+        for vt in &[
+            VehicleType::Car,
+            VehicleType::Bike,
+            VehicleType::Bus,
+            VehicleType::Train,
+        ] {
             let id = CarID(idx, *vt);
             if self.driving.tooltip_lines(id, self.time).is_some() {
                 return Some(id);
@@ -999,6 +1158,20 @@ impl Sim {
         }
     }
 
+    // Only for traffic signals
+    pub fn current_phase_and_remaining_time(
+        &self,
+        id: IntersectionID,
+        map: &Map,
+    ) -> (usize, Duration) {
+        self.intersections
+            .current_phase_and_remaining_time(self.time, id, map)
+    }
+
+    pub fn total_waiting_delay(&self, id: IntersectionID) -> Duration {
+        self.intersections.total_waiting_delay(self.time, id)
+    }
+
     pub fn get_accepted_agents(&self, id: IntersectionID) -> HashSet<AgentID> {
         self.intersections.get_accepted_agents(id)
     }
@@ -1040,7 +1213,7 @@ impl Sim {
     }
 
     pub fn trip_spec_to_path_req(&self, spec: &TripSpec, map: &Map) -> PathRequest {
-        spec.get_pathfinding_request(map, &self.parking)
+        spec.get_pathfinding_request(self.time, map, &self.parking)
     }
 }
 
@@ -1062,3 +1235,20 @@ impl Sim {
         }
     }
 }
+
+// What kind of vehicle serves a route?
+fn transit_vehicle(route: &BusRoute) -> VehicleSpec {
+    if route.route_type == PathConstraints::Train {
+        VehicleSpec {
+            vehicle_type: VehicleType::Train,
+            length: TRAIN_LENGTH,
+            max_speed: None,
+        }
+    } else {
+        VehicleSpec {
+            vehicle_type: VehicleType::Bus,
+            length: BUS_LENGTH,
+            max_speed: None,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{AgentID, OutputFile, TripID};
+use geom::{Distance, GPSBounds, Pt2D, Time};
+use map_model::Traversable;
+
+// Where one agent is at some moment.
+pub(crate) struct TrajectorySample {
+    pub agent: AgentID,
+    pub trip: TripID,
+    pub on: Traversable,
+    // For vehicles, the front
+    pub dist_along: Distance,
+    pub pt: Pt2D,
+}
+
+// Periodically writes the position of every moving car, bike, bus, train, and pedestrian, like
+// floating car data. The CSV file looks like:
+//
+//   time,id,type,trip,lane,intersection,dist_along,x,y,lon,lat
+//   3600,car12,car,34,567,,20.5,1024.3,899.1,-122.3021,47.6489
+//   3600,ped8,pedestrian,40,,102,3.1,1200.0,850.2,-122.3003,47.6491
+//
+// Time is in seconds since midnight, and distances in meters. Agents crossing an intersection
+// have the intersection filled out instead of the lane. x and y are in map-space; lon and lat are
+// WGS84. Parked cars and people riding transit aren't included.
+#[derive(Clone)]
+pub(crate) struct TrajectoryLog {
+    file: OutputFile,
+}
+
+impl TrajectoryLog {
+    pub fn new(path: String) -> TrajectoryLog {
+        TrajectoryLog {
+            file: OutputFile::create(
+                path,
+                Some("time,id,type,trip,lane,intersection,dist_along,x,y,lon,lat"),
+            ),
+        }
+    }
+
+    pub fn write(&mut self, time: Time, samples: Vec<TrajectorySample>, gps_bounds: &GPSBounds) {
+        let mut lines = Vec::new();
+        for s in samples {
+            let (id, agent_type) = match s.agent {
+                AgentID::Car(c) => (format!("{}{}", c.1, c.0), c.1.to_string()),
+                AgentID::Pedestrian(p) => (format!("ped{}", p.0), "pedestrian".to_string()),
+            };
+            let (lane, intersection) = match s.on {
+                Traversable::Lane(l) => (l.0.to_string(), String::new()),
+                Traversable::Turn(t) => (String::new(), t.parent.0.to_string()),
+            };
+            let gps = s.pt.forcibly_to_gps(gps_bounds);
+            lines.push(format!(
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                time.inner_seconds(),
+                id,
+                agent_type,
+                s.trip.0,
+                lane,
+                intersection,
+                s.dist_along.inner_meters(),
+                s.pt.x(),
+                s.pt.y(),
+                gps.longitude,
+                gps.latitude
+            ));
+        }
+        self.file.write_lines(lines);
+    }
+}
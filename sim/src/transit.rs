@@ -1,15 +1,19 @@
 use crate::{CarID, Event, PedestrianID, Router, Scheduler, TripManager, WalkingSimState};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Distance, Time};
-use map_model::{
-    BusRoute, BusRouteID, BusStopID, Map, Path, PathConstraints, PathRequest, Position,
-};
+use geom::{Distance, Duration, Time};
+use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+// Opening and closing the doors, plus time for each passenger getting off or on. Boarding is
+// slower, since people pay then.
+const DWELL_TIME_BASE: Duration = Duration::const_seconds(10.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(2.0);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(3.0);
+
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 struct StopForRoute {
     id: BusStopID,
@@ -31,7 +35,30 @@ struct Bus {
     route: BusRouteID,
     // Where does each passenger want to deboard?
     passengers: Vec<(PedestrianID, BusStopID)>,
+    seated_capacity: usize,
+    standing_capacity: usize,
     state: BusState,
+    // Passengers who boarded after the bus arrived at its current stop make it dwell this much
+    // longer.
+    extra_dwell: Duration,
+    // Buses dispatched from a timetable serve one run (indexing BusRoute::departures), finishing
+    // at the last stop. Otherwise, buses loop around the route forever.
+    run: Option<usize>,
+}
+
+impl Bus {
+    // Starting from some stop, will this bus still reach another stop before its run ends?
+    fn still_serves(&self, route: &Route, from: StopIdx, stop: BusStopID) -> bool {
+        if self.run.is_some() {
+            route.stops[from + 1..].iter().any(|s| s.id == stop)
+        } else {
+            true
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.passengers.len() >= self.seated_capacity + self.standing_capacity
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
@@ -98,7 +125,8 @@ impl TransitSimState {
                     let req = PathRequest {
                         start: stop1.driving_pos,
                         end: map.get_bs(bus_route.stops[stop2_idx]).driving_pos,
-                        constraints: PathConstraints::Bus,
+                        constraints: bus_route.route_type,
+                        departure_time: None,
                     };
                     let path = map.pathfind(req.clone()).expect(&format!(
                         "No route between bus stops {:?} and {:?}",
@@ -131,7 +159,15 @@ impl TransitSimState {
         stops
     }
 
-    pub fn bus_created(&mut self, bus: CarID, route: BusRouteID, next_stop_idx: StopIdx) {
+    pub fn bus_created(
+        &mut self,
+        bus: CarID,
+        route: BusRouteID,
+        next_stop_idx: StopIdx,
+        run: Option<usize>,
+        seated_capacity: usize,
+        standing_capacity: usize,
+    ) {
         self.routes.get_mut(&route).unwrap().buses.push(bus);
         self.buses.insert(
             bus,
@@ -139,11 +175,48 @@ impl TransitSimState {
                 car: bus,
                 route,
                 passengers: Vec::new(),
+                seated_capacity,
+                standing_capacity,
                 state: BusState::DrivingToStop(next_stop_idx),
+                extra_dwell: Duration::ZERO,
+                run,
             },
         );
     }
 
+    // Where should a bus following the timetable start? Returns the stop it's first headed
+    // towards, and the path to it, which begins just upstream of the first stop if possible.
+    pub fn start_of_run(
+        &self,
+        route: BusRouteID,
+        vehicle_length: Distance,
+        map: &Map,
+    ) -> (StopIdx, PathRequest, Path, Distance) {
+        let r = &self.routes[&route];
+        let first_stop = r.stops[0].driving_pos;
+        if first_stop.dist_along() > vehicle_length {
+            let req = PathRequest {
+                start: Position::new(first_stop.lane(), vehicle_length),
+                end: first_stop,
+                constraints: map.get_br(route).route_type,
+                departure_time: None,
+            };
+            if let Some(path) = map.pathfind(req.clone()) {
+                return (0, req, path, first_stop.dist_along());
+            }
+        }
+        // Not enough room before the first stop, so just skip it.
+        let stop = &r.stops[0];
+        (
+            stop.next_stop_idx,
+            stop.req.clone(),
+            stop.path_to_next_stop.clone(),
+            r.stops[stop.next_stop_idx].driving_pos.dist_along(),
+        )
+    }
+
+    // Returns how long the bus should dwell at the stop, or None if the bus just finished its run
+    // and should vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Time,
@@ -152,19 +225,30 @@ impl TransitSimState {
         walking: &mut WalkingSimState,
         scheduler: &mut Scheduler,
         map: &Map,
-    ) {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
-                let stop1 = self.routes[&bus.route].stops[stop_idx].id;
+                let r = &self.routes[&bus.route];
+                let stop1 = r.stops[stop_idx].id;
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop1));
+                if let Some(run) = bus.run {
+                    let times = &map.get_br(bus.route).scheduled_arrivals[run];
+                    if let Some(scheduled) = times[stop_idx] {
+                        self.events.push(Event::ScheduledBusArrival(
+                            id, bus.route, run, stop1, scheduled,
+                        ));
+                    }
+                }
 
                 // Deboard existing passengers.
+                let mut alighted = 0;
                 let mut still_riding = Vec::new();
                 for (ped, stop2) in bus.passengers.drain(..) {
                     if stop1 == stop2 {
+                        alighted += 1;
                         self.events.push(Event::PedLeavesBus(ped, id, bus.route));
                         trips.ped_left_bus(now, ped, map, scheduler);
                     } else {
@@ -173,12 +257,20 @@ impl TransitSimState {
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // Board new passengers, first come first serve.
+                let mut boarded = 0;
                 let mut still_waiting = Vec::new();
                 for (ped, route, stop2, started_waiting) in
                     self.peds_waiting.remove(&stop1).unwrap_or_else(Vec::new)
                 {
-                    if bus.route == route {
+                    if bus.route == route && bus.still_serves(r, stop_idx, stop2) {
+                        if bus.is_full() {
+                            self.events
+                                .push(Event::PedDeniedBoarding(ped, id, route, stop1));
+                            still_waiting.push((ped, route, stop2, started_waiting));
+                            continue;
+                        }
+                        boarded += 1;
                         bus.passengers.push((ped, stop2));
                         self.events.push(Event::PedEntersBus(ped, id, route));
                         let trip = trips.ped_boarded_bus(ped, walking);
@@ -187,7 +279,8 @@ impl TransitSimState {
                             Some(PathRequest {
                                 start: map.get_bs(stop1).driving_pos,
                                 end: map.get_bs(stop2).driving_pos,
-                                constraints: PathConstraints::Bus,
+                                constraints: map.get_br(route).route_type,
+                                departure_time: None,
                             }),
                             format!("{} riding {}", ped, route),
                         ));
@@ -196,9 +289,38 @@ impl TransitSimState {
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
+
+                if bus.run.is_none() || stop_idx != r.stops.len() - 1 {
+                    return Some(
+                        DWELL_TIME_BASE
+                            + TIME_PER_ALIGHTING * (alighted as f64)
+                            + TIME_PER_BOARDING * (boarded as f64),
+                    );
+                }
+                // Nobody boards for stops the bus won't reach, so everyone's off by now.
+                assert!(bus.passengers.is_empty());
+                let route = bus.route;
+                self.buses.remove(&id);
+                self.routes
+                    .get_mut(&route)
+                    .unwrap()
+                    .buses
+                    .retain(|b| *b != id);
+                trips.bus_finished_run(now, id);
+                None
             }
             BusState::AtStop(_) => unreachable!(),
-        };
+        }
+    }
+
+    // If passengers boarded while the bus was dwelling, returns how much longer it has to wait
+    // before departing.
+    pub fn bus_still_boarding(&mut self, id: CarID) -> Option<Duration> {
+        let bus = self.buses.get_mut(&id).unwrap();
+        if bus.extra_dwell == Duration::ZERO {
+            return None;
+        }
+        Some(std::mem::replace(&mut bus.extra_dwell, Duration::ZERO))
     }
 
     pub fn bus_departed_from_stop(&mut self, id: CarID) -> Router {
@@ -233,14 +355,20 @@ impl TransitSimState {
         if let Some(route) = self.routes.get(&route_id) {
             for bus in &route.buses {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
-                    if route.stops[idx].id == stop1 {
-                        self.buses
-                            .get_mut(bus)
-                            .unwrap()
-                            .passengers
-                            .push((ped, stop2));
+                    if route.stops[idx].id == stop1
+                        && self.buses[bus].still_serves(route, idx, stop2)
+                    {
+                        if self.buses[bus].is_full() {
+                            self.events
+                                .push(Event::PedDeniedBoarding(ped, *bus, route_id, stop1));
+                            continue;
+                        }
+                        let bus = self.buses.get_mut(bus).unwrap();
+                        bus.passengers.push((ped, stop2));
+                        bus.extra_dwell += TIME_PER_BOARDING;
+                        let bus = bus.car;
                         // TODO shift trips
-                        self.events.push(Event::PedEntersBus(ped, *bus, route_id));
+                        self.events.push(Event::PedEntersBus(ped, bus, route_id));
                         return true;
                     }
                 }
@@ -267,6 +395,17 @@ impl TransitSimState {
         &self.buses[&bus].passengers
     }
 
+    // (seated, standing, total capacity)
+    pub fn get_load(&self, bus: CarID) -> (usize, usize, usize) {
+        let bus = &self.buses[&bus];
+        let riding = bus.passengers.len();
+        (
+            riding.min(bus.seated_capacity),
+            riding.saturating_sub(bus.seated_capacity),
+            bus.seated_capacity + bus.standing_capacity,
+        )
+    }
+
     pub fn bus_route(&self, bus: CarID) -> BusRouteID {
         self.buses[&bus].route
     }
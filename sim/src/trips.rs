@@ -1,10 +1,10 @@
 use crate::{
     AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    Vehicle, VehicleType, WalkingSimState,
+    ParkingSpot, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState,
+    TripID, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
-use geom::{Speed, Time};
+use geom::{Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
 };
@@ -14,6 +14,7 @@ use std::collections::{BTreeMap, VecDeque};
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct TripManager {
     trips: Vec<Trip>,
+    people: Vec<Person>,
     // For quick lookup of active agents
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -30,6 +31,7 @@ impl TripManager {
     pub fn new() -> TripManager {
         TripManager {
             trips: Vec::new(),
+            people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             num_bus_trips: 0,
             unfinished_trips: 0,
@@ -37,7 +39,13 @@ impl TripManager {
         }
     }
 
-    pub fn new_trip(&mut self, spawned_at: Time, start: TripStart, legs: Vec<TripLeg>) -> TripID {
+    pub fn new_trip(
+        &mut self,
+        spawned_at: Time,
+        person: Option<PersonID>,
+        start: TripStart,
+        legs: Vec<TripLeg>,
+    ) -> TripID {
         assert!(!legs.is_empty());
         // TODO Make sure the legs constitute a valid state machine.
 
@@ -81,6 +89,7 @@ impl TripManager {
         };
         let trip = Trip {
             id,
+            person,
             spawned_at,
             finished_at: None,
             aborted: false,
@@ -111,6 +120,102 @@ impl TripManager {
         if self.trips[trip.0].is_bus_trip() {
             self.num_bus_trips += 1;
         }
+        // Remember the car somebody's using, so they can return to it for their next trip.
+        if let AgentID::Car(car) = agent {
+            if car.1 == VehicleType::Car {
+                if let Some(person) = self.trips[trip.0].person {
+                    self.people[person.0].car = Some(car);
+                }
+            }
+        }
+    }
+
+    // Each trip is (earliest departure time, how long to stay at the destination afterwards,
+    // spec)
+    pub fn new_person(&mut self, trips: Vec<(Time, Duration, TripSpec)>) -> PersonID {
+        let id = PersonID(self.people.len());
+        self.people.push(Person {
+            id,
+            remaining_trips: VecDeque::from(trips),
+            dwell: Duration::ZERO,
+            car: None,
+            bike: None,
+        });
+        id
+    }
+
+    // Pops the person's next trip, adjusting it to use the vehicles they already have. If the
+    // trip uses their car, it's reserved until they get to it.
+    pub fn next_trip_for_person(
+        &mut self,
+        id: PersonID,
+        map: &Map,
+        parking: &mut ParkingSimState,
+    ) -> Option<(Time, TripSpec)> {
+        let person = &mut self.people[id.0];
+        let (depart, dwell, spec) = person.remaining_trips.pop_front()?;
+        person.dwell = dwell;
+
+        let spec = match spec {
+            TripSpec::MaybeUsingParkedCar {
+                start_bldg,
+                goal,
+                ped_speed,
+            } => {
+                // The car might've left the map, or somebody else might've claimed it. Then just
+                // look for any car at the start.
+                match person
+                    .car
+                    .and_then(|car| parking.dynamically_reserve_specific_car(car))
+                {
+                    Some(parked_car) => TripSpec::UsingParkedCar {
+                        start: SidewalkSpot::building(start_bldg, map),
+                        spot: parked_car.spot,
+                        goal,
+                        ped_speed,
+                    },
+                    None => {
+                        person.car = None;
+                        TripSpec::MaybeUsingParkedCar {
+                            start_bldg,
+                            goal,
+                            ped_speed,
+                        }
+                    }
+                }
+            }
+            TripSpec::UsingBike {
+                start,
+                goal,
+                vehicle,
+                ped_speed,
+            } => {
+                if person.bike.is_none() {
+                    person.bike = Some(vehicle);
+                }
+                TripSpec::UsingBike {
+                    start,
+                    goal,
+                    vehicle: person.bike.clone().unwrap(),
+                    ped_speed,
+                }
+            }
+            spec => spec,
+        };
+        Some((depart, spec))
+    }
+
+    // The car somebody last used, if they're still returning to it
+    pub fn get_person_car(&self, id: PersonID) -> Option<CarID> {
+        self.people[id.0].car
+    }
+
+    // When a person's trip finishes (or aborts) at some time, figure out when their next trip
+    // should start.
+    pub fn person_ready_for_next_trip(&self, trip: TripID, now: Time) -> Option<(PersonID, Time)> {
+        let person = &self.people[self.trips[trip.0].person?.0];
+        let (depart, _, _) = person.remaining_trips.front()?;
+        Some((person.id, (now + person.dwell).max(*depart)))
     }
 
     pub fn car_reached_parking_spot(
@@ -157,6 +262,8 @@ impl TripManager {
             scheduler,
         ) {
             self.unfinished_trips -= 1;
+            trip.aborted = true;
+            self.events.push(Event::TripAborted(trip.id));
         }
     }
 
@@ -194,6 +301,7 @@ impl TripManager {
             start,
             end,
             constraints: PathConstraints::Car,
+            departure_time: Some(now),
         };
         let path = if let Some(p) = map.pathfind(req.clone()) {
             p
@@ -253,6 +361,7 @@ impl TripManager {
             start: driving_pos,
             end,
             constraints: PathConstraints::Bike,
+            departure_time: None,
         };
         let path = if let Some(p) = map.pathfind(req.clone()) {
             p
@@ -298,6 +407,8 @@ impl TripManager {
 
         if !trip.spawn_ped(now, bike_rack, map, scheduler) {
             self.unfinished_trips -= 1;
+            trip.aborted = true;
+            self.events.push(Event::TripAborted(trip.id));
         }
     }
 
@@ -389,6 +500,8 @@ impl TripManager {
 
         if !trip.spawn_ped(now, start, map, scheduler) {
             self.unfinished_trips -= 1;
+            trip.aborted = true;
+            self.events.push(Event::TripAborted(trip.id));
         }
     }
 
@@ -435,6 +548,14 @@ impl TripManager {
         ));
     }
 
+    pub fn bus_finished_run(&mut self, now: Time, bus: CarID) {
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bus)).unwrap().0];
+        assert!(trip.is_bus_trip());
+        assert!(!trip.finished_at.is_some());
+        trip.finished_at = Some(now);
+        self.num_bus_trips -= 1;
+    }
+
     pub fn abort_trip_failed_start(&mut self, id: TripID) {
         self.trips[id.0].aborted = true;
         if !self.trips[id.0].is_bus_trip() {
@@ -510,7 +631,7 @@ impl TripManager {
     }
 
     pub fn is_done(&self) -> bool {
-        self.unfinished_trips == 0
+        self.unfinished_trips == 0 && self.people.iter().all(|p| p.remaining_trips.is_empty())
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
@@ -578,6 +699,7 @@ impl TripManager {
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 struct Trip {
     id: TripID,
+    person: Option<PersonID>,
     spawned_at: Time,
     finished_at: Option<Time>,
     aborted: bool,
@@ -626,6 +748,7 @@ impl Trip {
             start: start.sidewalk_pos,
             end: walk_to.sidewalk_pos,
             constraints: PathConstraints::Pedestrian,
+            departure_time: None,
         };
         let path = if let Some(p) = map.pathfind(req.clone()) {
             p
@@ -663,6 +786,19 @@ impl Trip {
     }
 }
 
+// Somebody making a sequence of trips throughout the day. Only the next trip is scheduled at a
+// time, once the previous one is done.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct Person {
+    id: PersonID,
+    remaining_trips: VecDeque<(Time, Duration, TripSpec)>,
+    // How long to stay after the current trip
+    dwell: Duration,
+    // The car last used, which stays parked wherever they left it
+    car: Option<CarID>,
+    bike: Option<VehicleSpec>,
+}
+
 // These don't specify where the leg starts, since it might be unknown -- like when we drive and
 // don't know where we'll wind up parking.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -697,7 +833,7 @@ impl TripMode {
             AgentID::Car(id) => match id.1 {
                 VehicleType::Car => TripMode::Drive,
                 VehicleType::Bike => TripMode::Bike,
-                VehicleType::Bus => TripMode::Transit,
+                VehicleType::Bus | VehicleType::Train => TripMode::Transit,
             },
         }
     }
@@ -0,0 +1,48 @@
+use crate::runner::TestRunner;
+use headless::api::{handle_request, Session};
+use sim::SimFlags;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("analytics_endpoint_returns_json", |_| {
+        let flags = SimFlags::for_test("analytics_endpoint_returns_json");
+        let mut session: Option<Session> = None;
+        handle_request(
+            "POST",
+            "/sim/load",
+            r#"{"map": "montlake", "scenario": null, "num_agents": null, "rng_seed": 42}"#,
+            &mut session,
+            &flags,
+        )
+        .unwrap();
+        handle_request(
+            "POST",
+            "/sim/step",
+            r#"{"seconds": 600.0}"#,
+            &mut session,
+            &flags,
+        )
+        .unwrap();
+
+        let response = handle_request("GET", "/sim/analytics", "", &mut session, &flags).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert!(json["finished_trips"].is_array());
+        assert!(json["trip_log"]
+            .as_array()
+            .map(|x| !x.is_empty())
+            .unwrap_or(false));
+        assert!(json["turn_group_demand"].is_array());
+        assert!(json["intersection_delays"].is_array());
+        // After 10 minutes, somebody has gone somewhere.
+        for key in &[
+            "throughput_per_road",
+            "throughput_per_intersection",
+            "raw_throughput_per_road",
+            "raw_throughput_per_intersection",
+        ] {
+            assert!(json[*key]
+                .as_array()
+                .map(|x| !x.is_empty())
+                .unwrap_or(false));
+        }
+    });
+}
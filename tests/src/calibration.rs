@@ -0,0 +1,18 @@
+use crate::runner::TestRunner;
+use geom::Duration;
+use sim::geh;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_fast("geh", |_| {
+        let close = |x: f64, y: f64| (x - y).abs() < 0.001;
+        assert!(close(geh(0, 0, Duration::hours(1)), 0.0));
+        assert!(close(geh(100, 100, Duration::hours(1)), 0.0));
+        // sqrt(2 * 50^2 / 250)
+        assert!(close(geh(150, 100, Duration::hours(1)), 20.0_f64.sqrt()));
+        // It's symmetric
+        assert!(close(geh(100, 150, Duration::hours(1)), 20.0_f64.sqrt()));
+        // Counts over half an hour are compared as hourly flows.
+        assert!(close(geh(75, 50, Duration::minutes(30)), 20.0_f64.sqrt()));
+        assert!(close(geh(0, 50, Duration::hours(1)), 10.0));
+    });
+}
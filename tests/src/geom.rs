@@ -1,5 +1,5 @@
 use crate::runner::TestRunner;
-use geom::{Duration, Line, PolyLine, Pt2D};
+use geom::{Angle, Duration, Line, PolyLine, Polygon, Pt2D};
 
 #[allow(clippy::unreadable_literal)]
 pub fn run(t: &mut TestRunner) {
@@ -48,6 +48,36 @@ pub fn run(t: &mut TestRunner) {
         assert_eq!(Duration::parse("00:02:03.5"), Ok(Duration::seconds(123.5)));
         assert_eq!(Duration::parse("01:02:03.5"), Ok(Duration::seconds(3723.5)));
     });
+
+    t.run_fast("polygon_area", |_| {
+        let close = |x: f64, y: f64| (x - y).abs() < 0.01;
+        let rect = Polygon::rectangle(10.0, 20.0);
+        assert!(close(rect.area(), 200.0));
+        // Moving or rotating doesn't change the area.
+        assert!(close(rect.translate(50.0, 70.0).area(), 200.0));
+        assert!(close(rect.rotate(Angle::new_degs(30.0)).area(), 200.0));
+        assert!(close(rect.scale(2.0).area(), 800.0));
+
+        let tri = Polygon::new(&vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 0.0),
+            Pt2D::new(0.0, 10.0),
+        ]);
+        assert!(close(tri.area(), 50.0));
+
+        // Concave, and the orientation of the points doesn't matter
+        let mut l_shape = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(20.0, 0.0),
+            Pt2D::new(20.0, 10.0),
+            Pt2D::new(10.0, 10.0),
+            Pt2D::new(10.0, 30.0),
+            Pt2D::new(0.0, 30.0),
+        ];
+        assert!(close(Polygon::new(&l_shape).area(), 400.0));
+        l_shape.reverse();
+        assert!(close(Polygon::new(&l_shape).area(), 400.0));
+    });
 }
 
 // TODO test that shifting lines and polylines is a reversible operation
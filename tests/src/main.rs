@@ -1,9 +1,13 @@
+mod api;
+mod calibration;
 mod geom;
 mod map_conversion;
 mod parking;
+mod popdat;
 mod runner;
 mod sim_completion;
 mod sim_determinism;
+mod traffic_signals;
 mod transit;
 mod trips;
 
@@ -29,11 +33,15 @@ fn main() {
 
     let mut t = runner::TestRunner::new(flags);
 
+    api::run(t.suite("api"));
+    calibration::run(t.suite("calibration"));
     geom::run(t.suite("geom"));
     map_conversion::run(t.suite("map_conversion"));
     parking::run(t.suite("parking"));
+    popdat::run(t.suite("popdat"));
     sim_completion::run(t.suite("sim_completion"));
     sim_determinism::run(t.suite("sim_determinism"));
+    traffic_signals::run(t.suite("traffic_signals"));
     transit::run(t.suite("transit"));
     trips::run(t.suite("trips"));
 
@@ -9,6 +9,7 @@ pub fn run(t: &mut TestRunner) {
             sidewalks: Some("../data/input/sidewalks.bin".to_string()),
             gtfs: Some("../data/input/google_transit_2018_18_08".to_string()),
             neighborhoods: Some("../data/input/neighborhoods.geojson".to_string()),
+            elevation: None,
             clip: Some(abstutil::path_polygon("montlake")),
             output: "convert_osm_twice.bin".to_string(),
         };
@@ -24,6 +25,103 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_fast("mid_block_crossings_split_roads", |_| {
+        // One road with crossings 10m from the start, then at 30m and 35m, then at 60m
+        let pts: Vec<geom::Pt2D> = vec![0.0, 10.0, 30.0, 35.0, 60.0, 100.0]
+            .into_iter()
+            .map(|x| geom::Pt2D::new(x, 0.0))
+            .collect();
+        let osm_node_ids = pts
+            .iter()
+            .enumerate()
+            .map(|(idx, pt)| (pt.to_hashable(), idx as i64))
+            .collect();
+        let crossings = pts[1..5].iter().map(|pt| pt.to_hashable()).collect();
+        let road = map_model::raw::RawRoad {
+            center_points: pts,
+            osm_tags: std::collections::BTreeMap::new(),
+            turn_restrictions: Vec::new(),
+        };
+        let map = convert_osm::split_up_roads(
+            (
+                map_model::raw::RawMap::blank("crossings".to_string()),
+                vec![(1, road)],
+                std::collections::HashSet::new(),
+                crossings,
+                osm_node_ids,
+                Vec::new(),
+            ),
+            &mut abstutil::Timer::throwaway(),
+        );
+        // The crossing by the start is part of that intersection, and only one of the two close
+        // crossings splits the road.
+        let nodes: Vec<i64> = map.intersections.keys().map(|i| i.osm_node_id).collect();
+        assert_eq!(nodes, vec![0, 2, 4, 5]);
+        assert_eq!(map.roads.len(), 3);
+    });
+
+    t.run_fast("speed_limit_defaults", |_| {
+        let speed = |tags: Vec<(&str, &str)>| {
+            map_model::speed_limit_from_osm(
+                &tags
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            )
+        };
+        let mph = geom::Speed::miles_per_hour;
+        assert_eq!(speed(vec![("highway", "residential")]), mph(20.0));
+        assert_eq!(speed(vec![("highway", "primary")]), mph(40.0));
+        assert_eq!(speed(vec![("railway", "light_rail")]), mph(35.0));
+        // A street that also has tracks running down it
+        assert_eq!(
+            speed(vec![("highway", "residential"), ("railway", "tram")]),
+            mph(20.0)
+        );
+        assert_eq!(
+            speed(vec![("highway", "primary"), ("maxspeed", "30 mph")]),
+            mph(30.0)
+        );
+    });
+
+    t.run_slow("partial_elevation_data", |h| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut raw: map_model::raw::RawMap =
+            abstutil::read_binary(abstutil::path_raw_map("montlake"), &mut timer);
+        raw.name = "partial_elevation_data".to_string();
+        // Only every other intersection has data.
+        let mut csv = vec!["osm_node_id,elevation_meters".to_string()];
+        for (idx, id) in raw.intersections.keys().enumerate() {
+            if idx % 2 == 0 {
+                csv.push(format!("{},{}", id.osm_node_id, (idx % 7) * 5));
+            }
+        }
+        let csv_path = h.temp_path("elevation.csv");
+        std::fs::write(&csv_path, csv.join("\n")).unwrap();
+        convert_osm::use_elevation(&mut raw, &csv_path, &mut timer);
+        for (idx, i) in raw.intersections.values().enumerate() {
+            assert_eq!(i.elevation.is_some(), idx % 2 == 0);
+        }
+
+        let raw_path = h.temp_path("raw.json");
+        abstutil::write_json(raw_path.clone(), &raw);
+        let map = map_model::Map::new(raw_path, false, &mut timer);
+        let mut sloped = 0;
+        for l in map.all_lanes() {
+            if map.get_i(l.src_i).elevation.is_none() || map.get_i(l.dst_i).elevation.is_none() {
+                assert!(
+                    l.grade.abs() < 1e-9,
+                    "{} has a fake grade {}",
+                    l.id,
+                    l.grade
+                );
+            } else if l.grade.abs() > 1e-9 {
+                sloped += 1;
+            }
+        }
+        assert!(sloped > 0);
+    });
+
     t.run_slow("raw_to_map_twice", |_| {
         let map1 = map_model::Map::new(
             abstutil::path_raw_map("montlake"),
@@ -59,4 +157,364 @@ pub fn run(t: &mut TestRunner) {
             &mut abstutil::Timer::throwaway(),
         );
     });
+
+    t.run_slow("edits_survive_rebuild", |h| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map1 = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let mut raw: map_model::raw::RawMap =
+            abstutil::read_binary(abstutil::path_raw_map("montlake"), &mut timer);
+        // Late in the map, so removing an earlier road shifts its ID
+        let l = map1
+            .all_lanes()
+            .filter(|l| l.is_driving())
+            .last()
+            .unwrap()
+            .id;
+        let edited_road = map1.get_parent(l).orig_id;
+        // Some road early in the map and away from the edit that the rebuilt map won't have
+        let removed_road = map1
+            .all_roads()
+            .iter()
+            .find(|r| {
+                r.orig_id != edited_road
+                    && raw.roads.contains_key(&r.orig_id)
+                    && raw.roads_per_intersection(r.orig_id.i1).len() > 2
+                    && raw.roads_per_intersection(r.orig_id.i2).len() > 2
+            })
+            .unwrap()
+            .id;
+        let remove = map1.get_r(removed_road).orig_id;
+
+        let mut edits = map1.get_edits().clone();
+        edits.edits_name = "test_edits_survive_rebuild".to_string();
+        edits.dirty = true;
+        edits.commands.push(map_model::EditCmd::ChangeLaneType {
+            id: l,
+            lt: map_model::LaneType::Bus,
+            orig_lt: map_model::LaneType::Driving,
+        });
+        let old_speed = map1.get_r(removed_road).speed_limit;
+        edits.commands.push(map_model::EditCmd::ChangeSpeedLimit {
+            road: removed_road,
+            new: old_speed + geom::Speed::miles_per_hour(5.0),
+            old: old_speed,
+        });
+        map1.apply_edits(edits, &mut timer);
+        h.cleanup_later(abstutil::path_edits(
+            map1.get_name(),
+            "test_edits_survive_rebuild",
+        ));
+        map1.save_edits();
+
+        // Rebuild the map without that road, so the IDs change.
+        raw.delete_road(remove);
+        let shifted_path = abstutil::path_raw_map("test_edits_survive_rebuild");
+        h.cleanup_later(shifted_path.clone());
+        abstutil::write_binary(shifted_path.clone(), &raw);
+        let map2 = map_model::Map::new(shifted_path, true, &mut timer);
+
+        let (edits2, skipped) =
+            map_model::MapEdits::load(&map2, "test_edits_survive_rebuild", &mut timer).unwrap();
+        match edits2.commands.as_slice() {
+            [map_model::EditCmd::ChangeLaneType { id, lt, .. }] => {
+                if *id == l {
+                    panic!("Removing {} didn't change the IDs", remove);
+                }
+                let (r1, r2) = (map1.get_parent(l), map2.get_parent(*id));
+                if r1.orig_id != r2.orig_id
+                    || r1.dir_and_offset(l) != r2.dir_and_offset(*id)
+                    || *lt != map_model::LaneType::Bus
+                {
+                    panic!("Edit to {} loaded as an edit to {}", l, id);
+                }
+            }
+            cmds => panic!("Loaded {:?} against the rebuilt map", cmds),
+        }
+        // The edit to the missing road is reported, not just dropped.
+        if skipped.len() != 1 || !skipped[0].contains(&remove.to_string()) {
+            panic!("Skipped {:?}", skipped);
+        }
+
+        // Legacy edits name IDs directly. Ones that don't match the map are skipped.
+        let mut legacy = map1.get_edits().clone();
+        legacy.edits_name = "test_legacy_edits".to_string();
+        legacy.commands.push(map_model::EditCmd::ChangeLaneType {
+            id: map_model::LaneID(map1.all_lanes_including_deleted().len() + 10),
+            lt: map_model::LaneType::Bus,
+            orig_lt: map_model::LaneType::Driving,
+        });
+        let legacy_path = abstutil::path_edits(map1.get_name(), "test_legacy_edits");
+        h.cleanup_later(legacy_path.clone());
+        abstutil::write_json(legacy_path, &legacy);
+        let (loaded, _) =
+            map_model::MapEdits::load(&map1, "test_legacy_edits", &mut timer).unwrap();
+        if abstutil::to_json(&loaded.commands) != abstutil::to_json(&map1.get_edits().commands) {
+            panic!("Legacy edits loaded as {:?}", loaded.commands);
+        }
+    });
+
+    t.run_slow("undo_redo_and_merge_edits", |_| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let driving: Vec<map_model::LaneID> = map
+            .all_lanes()
+            .filter(|l| l.is_driving())
+            .map(|l| l.id)
+            .take(2)
+            .collect();
+        let change = |id, lt| map_model::EditCmd::ChangeLaneType {
+            id,
+            lt,
+            orig_lt: map_model::LaneType::Driving,
+        };
+
+        let mut edits = map.get_edits().clone();
+        edits
+            .commands
+            .push(change(driving[0], map_model::LaneType::Bus));
+        map.apply_edits(edits, &mut timer);
+
+        let mut edits = map.get_edits().clone();
+        edits.undo().unwrap();
+        map.apply_edits(edits, &mut timer);
+        assert!(map.get_edits().commands.is_empty());
+        assert!(map.get_edits().can_redo());
+
+        let mut edits = map.get_edits().clone();
+        edits.redo().unwrap();
+        map.apply_edits(edits, &mut timer);
+        assert_eq!(map.get_edits().commands.len(), 1);
+        assert!(!map.get_edits().can_redo());
+
+        let mut other = map_model::MapEdits::new(map.get_name().to_string());
+        other.edits_name = "other".to_string();
+        other
+            .commands
+            .push(change(driving[1], map_model::LaneType::Bike));
+        let diff = map.get_edits().diff(&other, &map);
+        assert_eq!(diff.lanes, driving.iter().cloned().collect());
+        let merged = map.get_edits().merge(&other, &map).unwrap();
+        assert_eq!(merged.commands.len(), 2);
+
+        let mut conflict = other.clone();
+        conflict
+            .commands
+            .push(change(driving[0], map_model::LaneType::Parking));
+        assert!(map.get_edits().merge(&conflict, &map).is_err());
+
+        // Restriping the same road in different ways conflicts, even when the lanes differ.
+        let road = map
+            .all_roads()
+            .iter()
+            .find(|r| r.children_forwards.len() >= 2)
+            .unwrap();
+        let mut reverse = map_model::MapEdits::new(map.get_name().to_string());
+        reverse.edits_name = "reverse".to_string();
+        reverse.commands.push(map_model::EditCmd::ReverseLane {
+            l: road.children_forwards[0].0,
+            dst_i: road.src_i,
+        });
+        let mut delete = map_model::MapEdits::new(map.get_name().to_string());
+        delete.edits_name = "delete".to_string();
+        delete.commands.push(map_model::EditCmd::DeleteLane {
+            id: road.children_forwards[1].0,
+            fwds: true,
+            idx: 1,
+        });
+        assert!(reverse.merge(&delete, &map).is_err());
+    });
+
+    t.run_slow("turn_bans", |_| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let uses = |map: &map_model::Map, t: map_model::TurnID| {
+            map.pathfind(map_model::PathRequest {
+                start: map_model::Position::new(t.src, map.get_l(t.src).length()),
+                end: map_model::Position::new(t.dst, map.get_l(t.dst).length()),
+                constraints: map_model::PathConstraints::Car,
+                departure_time: None,
+            })
+            .map(|path| path.get_steps().contains(&map_model::PathStep::Turn(t)))
+            .unwrap_or(false)
+        };
+
+        // A vehicle turn that can be banned, and that's the way to go between its lanes
+        let t = map
+            .all_turns()
+            .values()
+            .filter(|t| map.get_l(t.id.src).is_driving() && map.get_l(t.id.dst).is_driving())
+            .map(|t| t.id)
+            .find(|t| map_model::TurnBan::Turn(*t).check(&map).is_ok() && uses(&map, *t))
+            .unwrap();
+        let ban = map_model::TurnBan::Turn(t);
+
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(map_model::EditCmd::BanTurns(ban));
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        assert!(map.maybe_get_t(t).is_none());
+        assert!(!uses(&map, t));
+        assert_eq!(map.get_edits().banned_turns.len(), 1);
+
+        // Allowing the ban restores the turn
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(map_model::EditCmd::AllowTurns(ban));
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        assert!(map.maybe_get_t(t).is_some());
+        assert!(uses(&map, t));
+        assert!(map.get_edits().banned_turns.is_empty());
+
+        // A ban that'd strand a lane is skipped, even when it doesn't come from the UI
+        let only = map
+            .all_lanes()
+            .filter(|l| l.is_driving())
+            .find_map(|l| {
+                let turns = map.get_turns_from_lane(l.id);
+                if turns.len() == 1 {
+                    Some(turns[0].id)
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        let bad = map_model::TurnBan::Turn(only);
+        assert!(bad.check(&map).is_err());
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(map_model::EditCmd::BanTurns(bad));
+        map.apply_edits(edits, &mut timer);
+        assert!(map.maybe_get_t(only).is_some());
+        assert!(map.get_edits().banned_turns.is_empty());
+    });
+
+    t.run_slow("add_delete_and_widen_lanes", |h| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let r = map
+            .all_roads()
+            .iter()
+            .find(|r| {
+                !r.children_forwards.is_empty()
+                    && !r.children_backwards.is_empty()
+                    && r.all_bus_stops(&map).is_empty()
+                    && !map.get_i(r.src_i).is_border()
+                    && !map.get_i(r.dst_i).is_border()
+            })
+            .unwrap()
+            .id;
+        let num_lanes = map.all_lanes_including_deleted().len();
+        let (src_i, dst_i) = (map.get_r(r).src_i, map.get_r(r).dst_i);
+        let polygons = |map: &map_model::Map| {
+            vec![
+                map.get_i(src_i).polygon.points().clone(),
+                map.get_i(dst_i).polygon.points().clone(),
+            ]
+        };
+        let same_pts = |pts1: &Vec<geom::Pt2D>, pts2: &Vec<geom::Pt2D>| {
+            pts1.len() == pts2.len()
+                && pts1
+                    .iter()
+                    .zip(pts2.iter())
+                    .all(|(a, b)| a.approx_eq(*b, geom::Distance::meters(0.01)))
+        };
+        let orig_polygons = polygons(&map);
+        let orig_center = map.get_r(r).center_pts.points().clone();
+        let orig_width = map.get_r(r).width(true, &map);
+        let deleted = map.get_r(r).children_backwards[0].0;
+
+        // A bike lane next to the outermost lane, widened twice, and the innermost lane going the
+        // other way removed
+        let added = map_model::LaneID(num_lanes);
+        let idx = map.get_r(r).children_forwards.len() - 1;
+        let mut edits = map.get_edits().clone();
+        edits.edits_name = "test_add_delete_and_widen_lanes".to_string();
+        edits.dirty = true;
+        edits.commands.push(map_model::EditCmd::AddLane {
+            road: r,
+            fwds: true,
+            idx,
+            lt: map_model::LaneType::Biking,
+        });
+        edits.commands.push(map_model::EditCmd::ChangeLaneWidth {
+            id: added,
+            new: geom::Distance::meters(2.0),
+            old: map_model::LANE_THICKNESS,
+        });
+        edits.commands.push(map_model::EditCmd::ChangeLaneWidth {
+            id: added,
+            new: geom::Distance::meters(3.5),
+            old: geom::Distance::meters(2.0),
+        });
+        edits.commands.push(map_model::EditCmd::DeleteLane {
+            id: deleted,
+            fwds: false,
+            idx: 0,
+        });
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+
+        assert_eq!(map.get_r(r).children_forwards[idx].0, added);
+        assert_eq!(map.get_l(added).lane_type, map_model::LaneType::Biking);
+        assert_eq!(map.get_l(added).parent, r);
+        let widened = map.get_r(r).width(true, &map) - orig_width;
+        if (widened - geom::Distance::meters(3.5)).abs() > geom::Distance::meters(0.01) {
+            panic!("Adding a 3.5m lane to {} widened it by {}", r, widened);
+        }
+        // The new lane is shifted over from the center line, past the lanes closer to it.
+        let expected_shift = map_model::LANE_THICKNESS * (idx as f64) + geom::Distance::meters(1.75);
+        let shift = map
+            .get_r(r)
+            .center_pts
+            .first_pt()
+            .dist_to(map.get_l(added).lane_center_pts.first_pt());
+        if (shift - expected_shift).abs() > geom::Distance::meters(0.01) {
+            panic!("{} is {} from the center of {}", added, shift, r);
+        }
+        let edited_polygons = polygons(&map);
+        if same_pts(&edited_polygons[0], &orig_polygons[0])
+            && same_pts(&edited_polygons[1], &orig_polygons[1])
+        {
+            panic!("Restriping {} didn't change the intersections at its ends", r);
+        }
+
+        assert!(map.get_l(deleted).deleted);
+        assert!(map.all_lanes().all(|l| l.id != deleted));
+        assert!(!map.get_r(r).all_lanes().contains(&deleted));
+        assert!(!map.get_i(src_i).incoming_lanes.contains(&deleted)
+            && !map.get_i(src_i).outgoing_lanes.contains(&deleted)
+            && !map.get_i(dst_i).incoming_lanes.contains(&deleted)
+            && !map.get_i(dst_i).outgoing_lanes.contains(&deleted));
+        assert!(map.all_turns().keys().all(|t| t.src != deleted && t.dst != deleted));
+
+        // Saving drops the redundant width change, but keeps the lane changes in order.
+        h.cleanup_later(abstutil::path_edits(
+            map.get_name(),
+            "test_add_delete_and_widen_lanes",
+        ));
+        map.save_edits();
+        match map.get_edits().commands.as_slice() {
+            [map_model::EditCmd::AddLane { .. }, map_model::EditCmd::DeleteLane { .. }, map_model::EditCmd::ChangeLaneWidth { id, new, old }] =>
+            {
+                assert_eq!(*id, added);
+                assert_eq!(*new, geom::Distance::meters(3.5));
+                assert_eq!(*old, map_model::LANE_THICKNESS);
+            }
+            cmds => panic!("Compressed to {:?}", cmds),
+        }
+
+        // Undoing the compressed edits puts everything back.
+        map.apply_edits(
+            map_model::MapEdits::new(map.get_name().to_string()),
+            &mut timer,
+        );
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        assert_eq!(map.all_lanes_including_deleted().len(), num_lanes);
+        assert!(!map.get_l(deleted).deleted);
+        assert_eq!(map.get_r(r).children_backwards[0].0, deleted);
+        assert!(same_pts(map.get_r(r).center_pts.points(), &orig_center));
+        let restored = polygons(&map);
+        if !same_pts(&restored[0], &orig_polygons[0]) || !same_pts(&restored[1], &orig_polygons[1]) {
+            panic!("Undoing the edits to {} didn't restore its intersections", r);
+        }
+    });
 }
@@ -0,0 +1,183 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, Line, Polygon, Pt2D, Time};
+use map_model::{Building, BuildingID, FrontPath, LaneID, Position};
+use popdat::psrc::{Mode, Parcel};
+use popdat::{synthetic_scenario, DemandOptions, Gravity, LandUse, ODEntry, ODMatrix, Zone};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{SidewalkPOI, SpawnTrip};
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("od_zone_outside_map", |_| {
+        let mut timer = Timer::throwaway();
+        let map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let bounds = map.get_bounds();
+        let center_x = (bounds.min_x + bounds.max_x) / 2.0;
+        let center_y = (bounds.min_y + bounds.max_y) / 2.0;
+        // The whole map, and a zone a bit east of it that contains no borders
+        let zones = vec![
+            Zone {
+                name: "map".to_string(),
+                polygons: vec![map.get_boundary_polygon().clone()],
+            },
+            Zone {
+                name: "east".to_string(),
+                polygons: vec![Polygon::rectangle(1000.0, 1000.0)
+                    .translate(bounds.max_x + 500.0, center_y - 500.0)],
+            },
+        ];
+        let entry = |origin: &str, destination: &str, mode| ODEntry {
+            origin: origin.to_string(),
+            destination: destination.to_string(),
+            mode,
+            start: Time::START_OF_DAY,
+            end: Time::START_OF_DAY + Duration::hours(1),
+            trips: 50,
+        };
+        let matrix = ODMatrix {
+            entries: vec![
+                entry("east", "map", Mode::Drive),
+                entry("map", "east", Mode::Walk),
+            ],
+        };
+        let scenario = matrix.to_scenario(
+            "od_zone_outside_map",
+            &zones,
+            &map,
+            &mut XorShiftRng::from_seed([42; 16]),
+            &mut timer,
+        );
+
+        let mut drives = 0;
+        let mut walks = 0;
+        for trip in &scenario.individ_trips {
+            match trip {
+                SpawnTrip::CarAppearing { start, .. } => {
+                    let i = map.get_l(start.lane()).src_i;
+                    assert!(map.get_i(i).is_border());
+                    assert!(map.get_i(i).polygon.center().x() > center_x);
+                    drives += 1;
+                }
+                SpawnTrip::JustWalking(_, _, goal) => match goal.connection {
+                    SidewalkPOI::Border(i) => {
+                        assert!(map.get_i(i).polygon.center().x() > center_x);
+                        walks += 1;
+                    }
+                    ref x => panic!("Walking trip to the east zone ends at {:?}", x),
+                },
+                x => panic!("Unexpected trip {:?}", x),
+            }
+        }
+        assert!(drives > 0);
+        assert!(walks > 0);
+    });
+
+    t.run_fast("land_use_per_building", |_| {
+        let opts = DemandOptions::default();
+        // A 10x20m building
+        let bldg = |tags: Vec<(&str, &str)>| Building {
+            id: BuildingID(0),
+            polygon: Polygon::rectangle(10.0, 20.0),
+            osm_tags: tags
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            osm_way_id: 0,
+            label_center: Pt2D::new(5.0, 10.0),
+            front_path: FrontPath {
+                sidewalk: Position::new(LaneID(0), Distance::ZERO),
+                line: Line::new(Pt2D::new(5.0, 20.0), Pt2D::new(5.0, 25.0)),
+            },
+            parking: None,
+        };
+        let check = |tags, parcel: Option<&Parcel>, residents, jobs, errands: f64| {
+            let land_use = LandUse::new(&bldg(tags), parcel, &opts);
+            assert_eq!(land_use.residents, residents);
+            assert_eq!(land_use.jobs, jobs);
+            assert!((land_use.errands - errands).abs() < 0.01);
+        };
+
+        // 200 square meters at 60 per resident
+        check(vec![("building", "house")], None, 3, 0, 0.0);
+        // Untagged buildings are homes.
+        check(vec![], None, 3, 0, 0.0);
+        check(
+            vec![("building", "apartments"), ("building:levels", "3")],
+            None,
+            10,
+            0,
+            0.0,
+        );
+        // 200 square meters at 50 per retail job
+        check(vec![("shop", "bakery")], None, 0, 4, 200.0);
+        // 200 square meters at 30 per office job
+        check(vec![("building", "office")], None, 0, 7, 0.0);
+        // Parcels override the building's size, but not what errands it attracts.
+        let parcel = Parcel {
+            num_households: 10,
+            num_employees: 5,
+            offstreet_parking_spaces: 0,
+        };
+        check(vec![("shop", "bakery")], Some(&parcel), 22, 5, 200.0);
+    });
+
+    t.run_slow("gravity_prefers_closer_destinations", |_| {
+        let map = map_model::Map::new(
+            abstutil::path_raw_map("montlake"),
+            true,
+            &mut Timer::throwaway(),
+        );
+        let center = |b: BuildingID| map.get_b(b).polygon.center();
+        let home = map.all_buildings()[0].id;
+        let (near, far) = {
+            let mut bldgs: Vec<BuildingID> = map
+                .all_buildings()
+                .iter()
+                .map(|b| b.id)
+                .filter(|b| *b != home)
+                .collect();
+            bldgs.sort_by_key(|b| center(*b).dist_to(center(home)));
+            (bldgs[0], *bldgs.last().unwrap())
+        };
+
+        let gravity = Gravity::new(vec![(near, 1.0), (far, 1.0)], &map);
+        let dests = gravity
+            .destinations_from(center(home), Distance::meters(200.0))
+            .unwrap();
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let mut picked_near = 0;
+        for _ in 0..100 {
+            if gravity.pick(&dests, &mut rng) == near {
+                picked_near += 1;
+            }
+        }
+        assert!(picked_near > 90);
+
+        // Nothing to pick from
+        assert!(Gravity::new(Vec::new(), &map)
+            .destinations_from(center(home), Distance::meters(200.0))
+            .is_none());
+    });
+
+    t.run_slow("synthetic_scenario_is_deterministic", |_| {
+        let mut timer = Timer::throwaway();
+        let map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let parcels = std::collections::HashMap::new();
+        let scenario = |seed| {
+            let opts = DemandOptions {
+                rng_seed: seed,
+                ..DemandOptions::default()
+            };
+            abstutil::to_json(&synthetic_scenario(
+                &map,
+                &parcels,
+                opts,
+                &mut Timer::throwaway(),
+            ))
+        };
+        let first = scenario(42);
+        assert_eq!(first, scenario(42));
+        assert_ne!(first, scenario(43));
+    });
+}
@@ -144,6 +144,8 @@ impl TestRunner {
         let start = std::time::Instant::now();
         let mut helper = TestHelper {
             debug_with_savestate: None,
+            temp_dir: format!("{}/{}_tmp", self.output_dir, test_name),
+            temp_paths: Vec::new(),
         };
         let output_path = format!("{}/{}.log", self.output_dir, test_name);
         std::fs::create_dir_all(std::path::Path::new(&output_path).parent().unwrap())
@@ -193,6 +195,8 @@ impl TestRunner {
             }))
             .is_ok()
         };
+        // Even if the test failed partway through
+        helper.remove_temp_paths();
 
         if pass && !self.flags.keep_output {
             std::fs::remove_file(&output_path).expect(&format!(
@@ -245,9 +249,36 @@ impl TestRunner {
 
 pub struct TestHelper {
     debug_with_savestate: Option<String>,
+    temp_dir: String,
+    temp_paths: Vec<String>,
 }
 
 impl TestHelper {
+    // A file or directory for the test to use, deleted afterwards whether or not the test passes.
+    pub fn temp_path(&mut self, name: &str) -> String {
+        std::fs::create_dir_all(&self.temp_dir).expect("Creating temp dir failed");
+        let path = format!("{}/{}", self.temp_dir, name);
+        self.temp_paths.push(path.clone());
+        path
+    }
+
+    // Like temp_path, for files that have to live somewhere else, like data/.
+    pub fn cleanup_later(&mut self, path: String) {
+        self.temp_paths.push(path);
+    }
+
+    fn remove_temp_paths(&mut self) {
+        for path in self.temp_paths.drain(..) {
+            let p = std::path::Path::new(&path);
+            if p.is_dir() {
+                let _ = std::fs::remove_dir_all(p);
+            } else if p.exists() {
+                let _ = std::fs::remove_file(p);
+            }
+        }
+        let _ = std::fs::remove_dir(&self.temp_dir);
+    }
+
     pub fn setup_done(&mut self, sim: &mut Sim) {
         if self.debug_with_savestate.is_some() {
             panic!("Can't call setup_done twice in one test");
@@ -1,7 +1,7 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::Duration;
-use sim::{Scenario, SimFlags};
+use sim::{LogitParams, ModeChoice, Scenario, SimFlags};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -12,4 +12,32 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&mut sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_slow("small_spawn_with_mode_choice_completes", |h| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("small_spawn_with_mode_choice_completes")
+            .load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        scenario.mode_choice = ModeChoice::Logit(LogitParams::default());
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_slow("cloned_sim_logs_events_separately", |h| {
+        let path = h.temp_path("events.csv");
+        let copy_path = h.temp_path("events_copy1.csv");
+        let mut flags = SimFlags::for_test("cloned_sim_logs_events_separately");
+        flags.opts.event_log = Some(path.clone());
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.step(&map, Duration::minutes(5));
+
+        let mut copy = sim.clone();
+        sim.step(&map, Duration::minutes(5));
+        copy.step(&map, Duration::minutes(5));
+        // Both simulations did the same thing, and neither wrote into the other's file.
+        let events = std::fs::read_to_string(&path).unwrap();
+        assert!(events.lines().count() > 1);
+        assert_eq!(events, std::fs::read_to_string(&copy_path).unwrap());
+    });
 }
@@ -0,0 +1,286 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration, Time};
+use map_model::{
+    ActuatedTiming, ControlTrafficSignal, Corridor, EditCmd, IntersectionID, LaneID, LaneType, Map,
+    PathConstraints, PathRequest, PathStep, Position, Road, TurnPriority,
+};
+use rand_xorshift::XorShiftRng;
+use sim::{
+    optimize_signals, DrivingGoal, Scenario, SignalOptimizerOptions, Sim, SimFlags, TripSpec,
+    FOLLOWING_DISTANCE, MIN_CAR_LENGTH,
+};
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("actuated_signal_gaps_out_and_maxes_out", |_| {
+        let mut timer = Timer::throwaway();
+        let flags = SimFlags::for_test("actuated_signal_gaps_out_and_maxes_out");
+        let (mut map, _, mut rng) = flags.load(&mut timer);
+
+        // Find a signal with some phase besides the first, a lane whose cars only get to go
+        // starting in that phase, and a lane whose cars can't go in that phase.
+        let mut found = None;
+        for i in map.all_intersections() {
+            if !i.is_traffic_signal() {
+                continue;
+            }
+            let signal = map.get_traffic_signal(i.id);
+            for phase in 1..signal.phases.len() {
+                if let (Some(go), Some(other)) = (
+                    approach(&map, signal, |p| {
+                        p.iter().take(phase).all(|x| *x == TurnPriority::Banned)
+                            && p[phase] != TurnPriority::Banned
+                    }),
+                    approach(&map, signal, |p| p[phase] == TurnPriority::Banned),
+                ) {
+                    found = Some((i.id, phase, go, other));
+                    break;
+                }
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+        let (i, phase, (go_lane, go_goal), (other_lane, other_goal)) =
+            found.expect("no suitable traffic signal in montlake");
+
+        let timing = ActuatedTiming {
+            min_green: Duration::seconds(3.0),
+            extension: Duration::seconds(5.0),
+            max_green: Duration::seconds(10.0),
+        };
+        let mut signal = map.get_traffic_signal(i).clone();
+        for p in signal.phases.iter_mut() {
+            p.actuated = Some(timing);
+        }
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+
+        // Nobody's around, so the signal rests in the first phase, instead of checking back
+        // every extension.
+        let t1 = Time::START_OF_DAY + Duration::seconds(60.0);
+        sim.step(&map, Duration::seconds(57.3));
+        if sim.current_phase_and_remaining_time(i, &map) != (0, Duration::ZERO) {
+            panic!(
+                "Idle signal {} isn't resting: {:?}",
+                i,
+                sim.current_phase_and_remaining_time(i, &map)
+            );
+        }
+
+        // One car shows up. The signal switches to let them through, then gaps out long before the
+        // max green, resting again.
+        let car = |lane: LaneID, goal: &DrivingGoal, front: Distance, rng: &mut XorShiftRng| {
+            TripSpec::CarAppearing {
+                start_pos: Position::new(lane, front),
+                goal: goal.clone(),
+                vehicle_spec: {
+                    let mut spec = Scenario::rand_car(rng);
+                    spec.length = MIN_CAR_LENGTH;
+                    spec
+                },
+                ped_speed: Scenario::rand_ped_speed(rng),
+            }
+        };
+        let go_end = map.get_l(go_lane).length() - Distance::meters(1.0);
+        sim.schedule_trip(t1, car(go_lane, &go_goal, go_end, &mut rng), &map);
+        sim.spawn_all_trips(&map, &mut timer, false);
+        let started = step_until(&mut sim, &map, i, t1, |(idx, _)| idx == phase);
+        let gapped_out = step_until(&mut sim, &map, i, t1, |(idx, remaining)| {
+            idx == phase && remaining == Duration::ZERO
+        });
+        if gapped_out - started > timing.max_green - Duration::seconds(2.0) {
+            panic!(
+                "Phase {} at {} started {} and rested {} after the car arrived; it should've \
+                 gapped out",
+                phase, i, started, gapped_out
+            );
+        }
+
+        // Now a steady stream of cars keeps the phase going, while somebody else waits. The phase
+        // has to end at the max green anyway.
+        let t2 = Time::START_OF_DAY + Duration::seconds(120.0);
+        sim.step(&map, t2 - sim.time());
+        let mut front = go_end;
+        while front >= MIN_CAR_LENGTH {
+            sim.schedule_trip(t2, car(go_lane, &go_goal, front, &mut rng), &map);
+            front = front - MIN_CAR_LENGTH - FOLLOWING_DISTANCE - Distance::meters(0.1);
+        }
+        let other_end = map.get_l(other_lane).length() - Distance::meters(1.0);
+        sim.schedule_trip(
+            t2 + Duration::seconds(1.0),
+            car(other_lane, &other_goal, other_end, &mut rng),
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut timer, false);
+        let maxed_out = step_until(&mut sim, &map, i, t2, |(idx, _)| idx != phase);
+        if maxed_out < timing.max_green - Duration::seconds(1.0)
+            || maxed_out > timing.max_green + Duration::seconds(1.0)
+        {
+            panic!(
+                "Phase {} at {} ended {} after a steady stream started; the max green is {}",
+                phase, i, maxed_out, timing.max_green
+            );
+        }
+    });
+
+    t.run_slow("corridor_follows_one_way_roads", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("corridor_follows_one_way_roads").load(&mut Timer::throwaway());
+        let signals: Vec<IntersectionID> = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| i.id)
+            .collect();
+        let one_way = |r: &Road| {
+            r.children_forwards
+                .iter()
+                .all(|(_, lt)| *lt != LaneType::Driving)
+                || r.children_backwards
+                    .iter()
+                    .all(|(_, lt)| *lt != LaneType::Driving)
+        };
+
+        // Every corridor between two signals has to be drivable in order. Make sure some of them
+        // go along one-way roads, so the check means something.
+        let mut used_one_way = false;
+        for from in &signals {
+            for to in &signals {
+                if from == to {
+                    continue;
+                }
+                let corridor = match Corridor::new(&map, vec![*from, *to]) {
+                    Ok(c) => c,
+                    Err(_) => continue,
+                };
+                let mut at = *from;
+                for r in corridor.roads.iter().flatten() {
+                    let road = map.get_r(*r);
+                    if one_way(road) {
+                        used_one_way = true;
+                    }
+                    if road
+                        .outgoing_lanes(at)
+                        .iter()
+                        .all(|(_, lt)| *lt != LaneType::Driving)
+                    {
+                        panic!(
+                            "Corridor from {} to {} drives the wrong way down {}",
+                            from, to, r
+                        );
+                    }
+                    at = if road.src_i == at {
+                        road.dst_i
+                    } else {
+                        road.src_i
+                    };
+                }
+                if at != *to {
+                    panic!("Corridor from {} to {} ends at {}", from, to, at);
+                }
+            }
+        }
+        if !used_one_way {
+            panic!("No corridor in montlake uses a one-way road");
+        }
+    });
+
+    t.run_slow("signal_optimizer", |_| {
+        let mut timer = Timer::throwaway();
+        let flags = SimFlags::for_test("signal_optimizer");
+        let (map, _, _) = flags.load(&mut timer);
+        let scenario = Scenario::small_run(&map);
+        let i = map
+            .all_intersections()
+            .iter()
+            .find(|i| i.is_traffic_signal() && map.get_traffic_signal(i.id).phases.len() > 1)
+            .unwrap()
+            .id;
+        let opts = |batch_size| SignalOptimizerOptions {
+            end_time: Time::START_OF_DAY + Duration::minutes(10),
+            rounds: 2,
+            batch_size,
+            rng_seed: 42,
+            sim_options: flags.opts.clone(),
+        };
+
+        assert!(optimize_signals(&map, &scenario, vec![i], opts(0), &mut timer).is_err());
+        let border = map.all_incoming_borders()[0].id;
+        assert!(optimize_signals(&map, &scenario, vec![border], opts(2), &mut timer).is_err());
+
+        // The result is the map's edits plus the new plan, and the same seed finds the same plan.
+        let edits = optimize_signals(&map, &scenario, vec![i], opts(2), &mut timer).unwrap();
+        assert_eq!(edits.commands.len(), map.get_edits().commands.len() + 1);
+        match edits.commands.last() {
+            Some(EditCmd::ChangeTrafficSignal(ts)) if ts.id == i => {}
+            cmd => panic!("Optimizing {} produced {:?}", i, cmd),
+        }
+        let again = optimize_signals(&map, &scenario, vec![i], opts(2), &mut timer).unwrap();
+        assert_eq!(edits.commands, again.commands);
+    });
+}
+
+// A driving lane at least 60m long into the signal, and a goal for cars starting there, such that
+// the priority of their turn in each phase passes the check.
+fn approach<F: Fn(&[TurnPriority]) -> bool>(
+    map: &Map,
+    signal: &ControlTrafficSignal,
+    check: F,
+) -> Option<(LaneID, DrivingGoal)> {
+    for l in &map.get_i(signal.id).incoming_lanes {
+        let lane = map.get_l(*l);
+        if !lane.is_driving() || lane.length() < Distance::meters(60.0) {
+            continue;
+        }
+        for border in map.all_outgoing_borders() {
+            for goal in &border.incoming_lanes {
+                if !map.get_l(*goal).is_driving() {
+                    continue;
+                }
+                let path = match map.pathfind(PathRequest {
+                    start: Position::new(*l, lane.length()),
+                    end: Position::new(*goal, map.get_l(*goal).length()),
+                    constraints: PathConstraints::Car,
+                    departure_time: None,
+                }) {
+                    Some(path) => path,
+                    None => continue,
+                };
+                if let Some(PathStep::Turn(t)) = path.get_steps().get(1) {
+                    let priorities: Vec<TurnPriority> = signal
+                        .phases
+                        .iter()
+                        .map(|p| p.get_priority_of_turn(*t, signal))
+                        .collect();
+                    if check(&priorities) {
+                        return Some((*l, DrivingGoal::Border(border.id, *goal)));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// Step the simulation until the signal's (current phase, remaining time) passes the check, and
+// return how long after `since` that happened. Gives up after 5 minutes.
+fn step_until<F: Fn((usize, Duration)) -> bool>(
+    sim: &mut Sim,
+    map: &Map,
+    i: IntersectionID,
+    since: Time,
+    check: F,
+) -> Duration {
+    let deadline = sim.time() + Duration::minutes(5);
+    while sim.time() < deadline {
+        if check(sim.current_phase_and_remaining_time(i, map)) {
+            return sim.time() - since;
+        }
+        sim.step(map, Duration::seconds(0.5));
+    }
+    panic!("{} never reached the expected state by {}", i, sim.time());
+}
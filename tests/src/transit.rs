@@ -1,7 +1,8 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
 use geom::{Duration, Time};
-use sim::{Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use map_model::{BusRouteID, BusStopID, LaneID};
+use sim::{Analytics, CarID, Event, Scenario, SidewalkSpot, SimFlags, TripSpec, VehicleType};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bus_reaches_stops", |h| {
@@ -79,4 +80,189 @@ pub fn run(t: &mut TestRunner) {
             Duration::minutes(9),
         );
     });
+
+    t.run_fast("gtfs_short_turn_trip", |h| {
+        let dir = h.temp_path("gtfs");
+        write_gtfs(
+            &dir,
+            vec!["r1,a,1,One,,3,,,"],
+            vec![
+                "s1,,A,,47.60,-122.30,,,0,,,0",
+                "s2,,B,,47.61,-122.30,,,0,,,0",
+                "s3,,C,,47.62,-122.30,,,0,,,0",
+                "s4,,D,,47.605,-122.31,,,0,,,0",
+            ],
+            vec![
+                "r1,weekday,full,,,0,,,0,0",
+                "r1,weekday,short,,,0,,,0,0",
+                "r1,weekday,extra,,,0,,,0,0",
+            ],
+            vec![
+                "full,08:00:00,08:00:00,s1,1,,0,0,,1",
+                "full,08:05:00,08:05:00,s2,2,,0,0,,1",
+                "full,08:10:00,08:10:00,s3,3,,0,0,,1",
+                // Turns around early
+                "short,09:00:00,09:00:00,s1,1,,0,0,,1",
+                "short,09:05:00,09:05:00,s2,2,,0,0,,1",
+                // Makes a detour
+                "extra,10:00:00,10:00:00,s1,1,,0,0,,1",
+                "extra,10:02:00,10:02:00,s4,2,,0,0,,1",
+                "extra,10:05:00,10:05:00,s2,3,,0,0,,1",
+                "extra,10:10:00,10:10:00,s3,4,,0,0,,1",
+            ],
+        );
+
+        let routes = gtfs::load(&dir);
+        assert_eq!(routes.len(), 1);
+        let route = &routes[0];
+        assert_eq!(route.stops.len(), 3);
+        let hour = |h: f64| Time::START_OF_DAY + Duration::hours(1) * h;
+        assert_eq!(route.departures, vec![hour(8.0), hour(9.0), hour(10.0)]);
+        // Every run lines up with the route's stops, even if the timetable doesn't say when it
+        // gets to them.
+        assert_eq!(
+            route.scheduled_arrivals,
+            vec![
+                vec![
+                    Some(hour(8.0)),
+                    Some(hour(8.0) + Duration::minutes(5)),
+                    Some(hour(8.0) + Duration::minutes(10))
+                ],
+                vec![None, None, None],
+                vec![None, None, None],
+            ]
+        );
+    });
+
+    t.run_fast("gtfs_tram_routes", |h| {
+        let dir = h.temp_path("gtfs");
+        // Route types 0 and 3 are streetcars and buses.
+        write_gtfs(
+            &dir,
+            vec!["streetcar,a,S,Streetcar,,0,,,", "bus,a,1,Bus,,3,,,"],
+            vec![
+                "s1,,A,,47.60,-122.30,,,0,,,0",
+                "s2,,B,,47.61,-122.30,,,0,,,0",
+            ],
+            vec!["streetcar,weekday,t1,,,0,,,0,0", "bus,weekday,t2,,,0,,,0,0"],
+            vec![
+                "t1,08:00:00,08:00:00,s1,1,,0,0,,1",
+                "t1,08:05:00,08:05:00,s2,2,,0,0,,1",
+                "t2,08:00:00,08:00:00,s1,1,,0,0,,1",
+                "t2,08:05:00,08:05:00,s2,2,,0,0,,1",
+            ],
+        );
+
+        let routes = gtfs::load(&dir);
+        let is_train = |name: &str| routes.iter().find(|r| r.name == name).unwrap().is_train;
+        assert!(is_train("S"));
+        assert!(!is_train("1"));
+    });
+
+    t.run_fast("bus_schedule_adherence", |_| {
+        let close = |x: f64, y: f64| (x - y).abs() < 0.001;
+        let at = |mins: f64| Time::START_OF_DAY + Duration::hours(8) + Duration::minutes(1) * mins;
+        let route = BusRouteID(0);
+        let stop1 = BusStopID {
+            sidewalk: LaneID(0),
+            idx: 0,
+        };
+        let stop2 = BusStopID {
+            sidewalk: LaneID(1),
+            idx: 1,
+        };
+        let bus1 = CarID(0, VehicleType::Bus);
+        let bus2 = CarID(1, VehicleType::Bus);
+
+        // Two runs scheduled 10 minutes apart. The first falls behind, and the second catches up
+        // to it.
+        let mut analytics = Analytics::new();
+        analytics.bus_schedule = vec![
+            (at(0.0), bus1, route, 0, stop1, at(0.0)),
+            (at(11.0), bus2, route, 1, stop1, at(10.0)),
+            (at(12.0), bus1, route, 0, stop2, at(5.0)),
+            (at(13.0), bus2, route, 1, stop2, at(15.0)),
+            // Another route doesn't count
+            (at(14.0), bus1, BusRouteID(1), 0, stop1, at(0.0)),
+        ];
+        let now = at(60.0);
+
+        let deltas = analytics.bus_schedule_deltas(now, route);
+        assert_eq!(
+            deltas[&0],
+            vec![
+                (stop1, at(0.0), Duration::ZERO),
+                (stop2, at(5.0), Duration::minutes(7))
+            ]
+        );
+        assert_eq!(
+            deltas[&1],
+            vec![
+                (stop1, at(10.0), Duration::minutes(1)),
+                (stop2, at(15.0), Duration::ZERO - Duration::minutes(2))
+            ]
+        );
+
+        // 7 minutes late and 2 minutes early are both off schedule.
+        let (overall, per_stop) = analytics.bus_on_time_performance(now, route).unwrap();
+        assert!(close(overall, 0.5));
+        assert!(close(per_stop[&stop1], 1.0));
+        assert!(close(per_stop[&stop2], 0.0));
+        assert!(analytics.bus_on_time_performance(at(-1.0), route).is_none());
+
+        assert_eq!(
+            analytics.bus_bunching(now, route),
+            vec![(
+                at(13.0),
+                stop2,
+                bus1,
+                bus2,
+                Duration::minutes(1),
+                Duration::minutes(10)
+            )]
+        );
+        // Before the second bus catches up
+        assert!(analytics.bus_bunching(at(12.0), route).is_empty());
+    });
+}
+
+// Writes a minimal GTFS feed. Each list is the rows of one file, without the header.
+fn write_gtfs(
+    dir: &str,
+    routes: Vec<&str>,
+    stops: Vec<&str>,
+    trips: Vec<&str>,
+    stop_times: Vec<&str>,
+) {
+    std::fs::create_dir_all(dir).unwrap();
+    for (file, header, rows) in vec![
+        (
+            "routes.txt",
+            "route_id,agency_id,route_short_name,route_long_name,route_desc,route_type,route_url,\
+             route_color,route_text_color",
+            routes,
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_code,stop_name,stop_desc,stop_lat,stop_lon,zone_id,stop_url,\
+             location_type,parent_station,stop_timezone,wheelchair_boarding",
+            stops,
+        ),
+        (
+            "trips.txt",
+            "route_id,service_id,trip_id,trip_headsign,trip_short_name,direction_id,block_id,\
+             shape_id,wheelchair_accessible,bikes_allowed",
+            trips,
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence,stop_headsign,pickup_type,\
+             drop_off_type,shape_dist_traveled,timepoint",
+            stop_times,
+        ),
+    ] {
+        let mut lines = vec![header];
+        lines.extend(rows);
+        std::fs::write(format!("{}/{}", dir, file), lines.join("\n")).unwrap();
+    }
 }
@@ -1,8 +1,18 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::{Duration, Time};
-use map_model::{BuildingID, IntersectionID};
-use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use geom::{Distance, Duration, Time};
+use map_model::{
+    Building, BuildingID, EditCmd, IntersectionID, LaneType, Map, PathConstraints, PathRequest,
+    PathStep, Phase, Position,
+};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{
+    AgentID, DrivingGoal, Event, IndividTrip, LogitParams, ParkingSpot, PersonSpec, Scenario,
+    SidewalkPOI, SidewalkSpot, Sim, SimFlags, SpawnTrip, TripSpec, FOLLOWING_DISTANCE,
+    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+};
+use std::collections::BTreeMap;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bike_from_border", |h| {
@@ -37,4 +47,403 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+
+    t.run_slow("person_makes_trips_in_order", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("person_makes_trips_in_order").load(&mut Timer::throwaway());
+        let (home, others) = home_and_others(&map);
+        // Close enough that the trip there is over well before the person's done shopping
+        let shop = others[0];
+        let walk = |from, to, ped_speed| TripSpec::JustWalking {
+            start: SidewalkSpot::building(from, &map),
+            goal: SidewalkSpot::building(to, &map),
+            ped_speed,
+        };
+        // The second trip can't start until the first is done and the person has shopped for a
+        // while, even though it's supposed to depart right away.
+        sim.schedule_person(
+            vec![
+                (
+                    Time::START_OF_DAY,
+                    Duration::minutes(5),
+                    walk(home, shop, Scenario::rand_ped_speed(&mut rng)),
+                ),
+                (
+                    Time::START_OF_DAY,
+                    Duration::ZERO,
+                    walk(shop, home, Scenario::rand_ped_speed(&mut rng)),
+                ),
+            ],
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.timed_step(&map, Duration::minutes(4), &mut Timer::throwaway());
+        assert!(!sim.is_done());
+        sim.just_run_until_done(&map, Some(Duration::minutes(10)));
+    });
+
+    t.run_slow("person_reuses_car", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("person_reuses_car").load(&mut Timer::throwaway());
+        let (home, others) = home_and_others(&map);
+        // Far enough away that they won't just walk back
+        let work = *others.last().unwrap();
+
+        let mut scenario = Scenario::empty(&map, "person_reuses_car");
+        scenario.individ_parked_cars.insert(home, 1);
+        scenario.people.push(PersonSpec {
+            trips: vec![
+                IndividTrip {
+                    trip: SpawnTrip::MaybeUsingParkedCar(
+                        Time::START_OF_DAY,
+                        home,
+                        DrivingGoal::ParkNear(work),
+                    ),
+                    dwell: Duration::minutes(5),
+                },
+                IndividTrip {
+                    trip: SpawnTrip::MaybeUsingParkedCar(
+                        Time::START_OF_DAY,
+                        work,
+                        DrivingGoal::ParkNear(home),
+                    ),
+                    dwell: Duration::ZERO,
+                },
+            ],
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        let car = sim.get_parked_cars_by_owner(home)[0].vehicle.id;
+        h.setup_done(&mut sim);
+        sim.just_run_until_done(&map, Some(Duration::hours(1)));
+
+        // Every time a car starts driving, the trip log names it. The second trip has to find the
+        // car wherever the first trip parked it.
+        let trips_using_car: Vec<_> = sim
+            .get_analytics()
+            .trip_log
+            .iter()
+            .filter(|(_, _, _, msg)| *msg == car.to_string())
+            .map(|(_, trip, _, _)| *trip)
+            .collect();
+        if trips_using_car.len() != 2 || trips_using_car[0] == trips_using_car[1] {
+            panic!("{} was used by trips {:?}", car, trips_using_car);
+        }
+    });
+
+    t.run_slow("reroute_around_blocked_road", |_| {
+        let mut timer = Timer::throwaway();
+        let mut flags = SimFlags::for_test("reroute_around_blocked_road");
+        flags.opts.reroute_compliance = 1.0;
+        let (mut map, _, mut rng) = flags.load(&mut timer);
+
+        // Find a lane that's the only way into a fixed-time traffic signal from its road, another
+        // lane feeding into it, and a border that cars on the feeder would normally reach through
+        // the first lane, but could detour to if it was jammed.
+        let mut found = None;
+        'search: for blocked in map.all_lanes() {
+            if !blocked.is_driving()
+                || blocked.length() < Distance::meters(30.0)
+                || blocked.length() > Distance::meters(150.0)
+                || map
+                    .maybe_get_traffic_signal(blocked.dst_i)
+                    .map(|ts| ts.is_actuated())
+                    .unwrap_or(true)
+                || map
+                    .all_lanes()
+                    .filter(|l| {
+                        l.parent == blocked.parent && l.is_driving() && l.dst_i == blocked.dst_i
+                    })
+                    .count()
+                    != 1
+            {
+                continue;
+            }
+            let mut detour_delays = BTreeMap::new();
+            detour_delays.insert(blocked.id, Duration::hours(1));
+            for feeder in map.all_turns().values().filter_map(|t| {
+                let src = map.get_l(t.id.src);
+                if t.id.dst == blocked.id
+                    && src.is_driving()
+                    && src.parent != blocked.parent
+                    && src.length() > MAX_CAR_LENGTH * 3.0
+                {
+                    Some(src)
+                } else {
+                    None
+                }
+            }) {
+                for border in map.all_outgoing_borders() {
+                    for goal in &border.incoming_lanes {
+                        if !map.get_l(*goal).is_driving() {
+                            continue;
+                        }
+                        let req = PathRequest {
+                            start: Position::new(feeder.id, feeder.length()),
+                            end: Position::new(*goal, map.get_l(*goal).length()),
+                            constraints: PathConstraints::Car,
+                            departure_time: None,
+                        };
+                        let usual = match map.pathfind(req.clone()) {
+                            Some(path) => path,
+                            None => continue,
+                        };
+                        if !usual.get_steps().contains(&PathStep::Lane(blocked.id)) {
+                            continue;
+                        }
+                        if let Some(detour) = map.pathfind_with_delays(req, &detour_delays) {
+                            if detour.get_steps().iter().all(|step| match step {
+                                PathStep::Lane(l) => map.get_l(*l).parent != blocked.parent,
+                                _ => true,
+                            }) {
+                                found = Some((blocked.id, feeder.id, border.id, *goal));
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let (blocked, feeder, border, goal) = found.expect("no lane to block in montlake");
+        let blocked_road = map.get_l(blocked).parent;
+
+        // Hold the signal red in every direction for an hour.
+        let mut signal = map.get_traffic_signal(map.get_l(blocked).dst_i).clone();
+        let mut all_red = Phase::new();
+        all_red.duration = Duration::hours(1);
+        signal.phases.insert(0, all_red);
+        signal.offset = Duration::ZERO;
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+
+        // Pack the lane with cars stuck at the red, leaving no room for anybody else to enter.
+        let mut front = map.get_l(blocked).length() - Distance::meters(1.0);
+        while front >= MIN_CAR_LENGTH {
+            let mut vehicle_spec = Scenario::rand_car(&mut rng);
+            vehicle_spec.length = MIN_CAR_LENGTH;
+            sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::CarAppearing {
+                    start_pos: Position::new(blocked, front),
+                    goal: DrivingGoal::Border(border, goal),
+                    vehicle_spec,
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+            front = front - MIN_CAR_LENGTH - FOLLOWING_DISTANCE - Distance::meters(0.1);
+        }
+        let (_, car) = sim.schedule_trip(
+            Time::START_OF_DAY + Duration::minutes(1),
+            TripSpec::CarAppearing {
+                start_pos: Position::new(feeder, map.get_l(feeder).length() / 2.0),
+                goal: DrivingGoal::Border(border, goal),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        let car = car.unwrap();
+        sim.spawn_all_trips(&map, &mut timer, false);
+        sim.timed_step(&map, Duration::minutes(2), &mut timer);
+        let trip = sim.agent_to_trip(AgentID::Car(car)).unwrap();
+        if sim
+            .get_path(AgentID::Car(car))
+            .unwrap()
+            .get_steps()
+            .iter()
+            .all(|step| *step != PathStep::Lane(blocked))
+        {
+            panic!("{} was never headed for {}", car, blocked);
+        }
+
+        // Stuck behind the full lane, the driver should eventually notice the jam and go around.
+        sim.timed_step(&map, Duration::minutes(20), &mut timer);
+        if !sim.get_analytics().reroutes.iter().any(|(_, t)| *t == trip) {
+            panic!("{} never rerouted around {}", car, blocked);
+        }
+        if let Some(path) = sim.get_path(AgentID::Car(car)) {
+            for step in path.get_steps() {
+                if let PathStep::Lane(l) = step {
+                    if map.get_l(*l).parent == blocked_road {
+                        panic!("{} rerouted, but still plans to use {}", car, blocked_road);
+                    }
+                }
+            }
+        }
+    });
+
+    t.run_slow("mode_share_follows_time_and_cost", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("mode_share_follows_time_and_cost").load(&mut Timer::throwaway());
+        let (home, others) = home_and_others(&map);
+        // Far enough that walking and driving both have a real chance
+        let work = *others.last().unwrap();
+        let parking_lane = map
+            .find_closest_lane(map.get_b(home).sidewalk(), vec![LaneType::Parking])
+            .unwrap();
+        let ped_speed = Scenario::rand_ped_speed(&mut XorShiftRng::from_seed([42; 16]));
+        let candidates = || {
+            vec![
+                TripSpec::JustWalking {
+                    start: SidewalkSpot::building(home, &map),
+                    goal: SidewalkSpot::building(work, &map),
+                    ped_speed,
+                },
+                TripSpec::UsingParkedCar {
+                    start: SidewalkSpot::building(home, &map),
+                    // The estimate assumes the car is close to the start, wherever it really is.
+                    spot: ParkingSpot::Onstreet(parking_lane, 0),
+                    goal: DrivingGoal::ParkNear(work),
+                    ped_speed,
+                },
+            ]
+        };
+        let num_driving = |params: &LogitParams| {
+            let mut rng = XorShiftRng::from_seed([42; 16]);
+            (0..500)
+                .filter(|_| match params.choose(candidates(), &map, &mut rng) {
+                    Some(TripSpec::UsingParkedCar { .. }) => true,
+                    _ => false,
+                })
+                .count()
+        };
+
+        let baseline = num_driving(&LogitParams::default());
+        let expensive = num_driving(&LogitParams {
+            driving_cost_per_mile: 10.0,
+            ..LogitParams::default()
+        });
+        let slow = num_driving(&LogitParams {
+            parking_overhead: Duration::minutes(60),
+            ..LogitParams::default()
+        });
+        if expensive + 50 > baseline || slow + 50 > baseline {
+            panic!(
+                "Out of 500 trips, {} drive normally, {} when driving costs more, and {} when \
+                 parking takes longer",
+                baseline, expensive, slow
+            );
+        }
+    });
+
+    t.run_slow("bike_lanes_raise_bike_share", |_| {
+        let mut timer = Timer::throwaway();
+        let (mut map, _, _) = SimFlags::for_test("bike_lanes_raise_bike_share").load(&mut timer);
+        let (home, others) = home_and_others(&map);
+        let work = *others.last().unwrap();
+        let mut rng = XorShiftRng::from_seed([42; 16]);
+        let vehicle = Scenario::rand_bike(&mut rng);
+        let ped_speed = Scenario::rand_ped_speed(&mut rng);
+        let num_biking = |map: &Map| {
+            let mut rng = XorShiftRng::from_seed([42; 16]);
+            (0..500)
+                .filter(|_| {
+                    let candidates = vec![
+                        TripSpec::JustWalking {
+                            start: SidewalkSpot::building(home, map),
+                            goal: SidewalkSpot::building(work, map),
+                            ped_speed,
+                        },
+                        TripSpec::UsingBike {
+                            start: SidewalkSpot::building(home, map),
+                            goal: DrivingGoal::ParkNear(work),
+                            vehicle: vehicle.clone(),
+                            ped_speed,
+                        },
+                    ];
+                    match LogitParams::default().choose(candidates, map, &mut rng) {
+                        Some(TripSpec::UsingBike { .. }) => true,
+                        _ => false,
+                    }
+                })
+                .count()
+        };
+        let before = num_biking(&map);
+
+        // Paint bike lanes over the parking alongside the usual route.
+        let rack = SidewalkSpot::bike_from_bike_rack(map.get_b(home).sidewalk(), &map).unwrap();
+        let start = match rack.connection {
+            SidewalkPOI::BikeRack(pos) => pos,
+            _ => unreachable!(),
+        };
+        let path = map
+            .pathfind(PathRequest {
+                start,
+                end: DrivingGoal::ParkNear(work).goal_pos(PathConstraints::Bike, &map),
+                constraints: PathConstraints::Bike,
+                departure_time: None,
+            })
+            .unwrap();
+        let mut edits = map.get_edits().clone();
+        for step in path.get_steps() {
+            if let PathStep::Lane(l) = step {
+                let r = map.get_parent(*l);
+                let side = if r.is_forwards(*l) {
+                    &r.children_forwards
+                } else {
+                    &r.children_backwards
+                };
+                for (id, lt) in side {
+                    if *lt == LaneType::Parking {
+                        edits.commands.push(EditCmd::ChangeLaneType {
+                            id: *id,
+                            lt: LaneType::Biking,
+                            orig_lt: LaneType::Parking,
+                        });
+                    }
+                }
+            }
+        }
+        if edits.commands.is_empty() {
+            panic!(
+                "No parking to replace along the route from {} to {}",
+                home, work
+            );
+        }
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+
+        // The same random draws pick biking whenever they did before, plus some more once biking
+        // gets faster.
+        let after = num_biking(&map);
+        if after <= before {
+            panic!(
+                "Out of 500 trips, {} bike before adding bike lanes and {} after",
+                before, after
+            );
+        }
+    });
+}
+
+// A building with on-street parking along its road, so a car can be seeded for it, and all the
+// other buildings, from nearest to farthest.
+fn home_and_others(map: &Map) -> (BuildingID, Vec<BuildingID>) {
+    let home = map
+        .all_buildings()
+        .iter()
+        .find(|b| {
+            map.find_closest_lane(b.sidewalk(), vec![LaneType::Parking])
+                .is_ok()
+        })
+        .expect("no building with on-street parking")
+        .id;
+    let home_pt = map.get_b(home).polygon.center();
+    let mut others: Vec<&Building> = map
+        .all_buildings()
+        .iter()
+        .filter(|b| b.id != home)
+        .collect();
+    others.sort_by(|b1, b2| {
+        b1.polygon
+            .center()
+            .dist_to(home_pt)
+            .partial_cmp(&b2.polygon.center().dist_to(home_pt))
+            .unwrap()
+    });
+    (home, others.into_iter().map(|b| b.id).collect())
 }